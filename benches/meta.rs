@@ -0,0 +1,92 @@
+//! Measures the time and the peak memory of the meta output by the number of
+//! the graphs. Run by `cargo bench --bench meta`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use mackerel_plugin::{graph, Graph, Plugin, Value};
+
+struct CountingAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+/// Returns the peak of the memory allocated while running the closure.
+fn peak_memory<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let base = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let ret = f();
+    (ret, PEAK.load(Ordering::Relaxed) - base)
+}
+
+struct ManyGraphs(Vec<Graph>);
+
+impl ManyGraphs {
+    fn new(count: usize) -> ManyGraphs {
+        ManyGraphs(
+            (0..count)
+                .map(|i| {
+                    graph! {
+                        name: &format!("graph{}", i),
+                        label: &format!("Graph {}", i),
+                        unit: "integer",
+                        metrics: [
+                            { name: "foo", label: "Foo" },
+                            { name: "bar", label: "Bar", stacked: true },
+                            { name: "baz", label: "Baz" },
+                        ],
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Plugin for ManyGraphs {
+    fn fetch_values(&self) -> Result<HashMap<String, Value>, String> {
+        Ok(HashMap::new())
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        self.0.clone()
+    }
+
+    fn graph_definition_ref(&self) -> Cow<'_, [Graph]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn metric_key_prefix(&self) -> String {
+        "bench".to_owned()
+    }
+}
+
+fn main() {
+    println!("graphs\ttime\tmemory");
+    for count in [10, 100, 1000, 10000] {
+        let plugin = ManyGraphs::new(count);
+        let started = Instant::now();
+        let (result, memory) = peak_memory(|| plugin.output_definitions(&mut std::io::sink()));
+        let elapsed = started.elapsed();
+        result.unwrap();
+        println!("{}\t{:?}\t{}", count, elapsed, memory);
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io::Write;
+use std::process::Command;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::json;
+use crate::output::{parse_meta_output, parse_values, META_HEADER};
+use crate::plugin::join_name;
+use crate::sink::{MetricSink, TsvSink};
+
+/// An aggregator which executes the external plugin commands and merges the
+/// outputs into a single output, so that an entry of mackerel-agent fans out
+/// to many plugins.
+///
+/// The commands are executed in parallel until the deadline of the context
+/// (`MACKEREL_PLUGIN_TIMEOUT`). A failure of a command is logged to the
+/// standard error and drops only the output of the command, and the
+/// aggregator fails only when all the commands fail.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::Aggregator;
+///
+/// Aggregator::new()
+///     .command(["mackerel-plugin-linux", "-type", "users"])
+///     .command_with_prefix(["./collect-orders.sh"], "shop")
+///     .dedup()
+///     .run()
+///     .unwrap();
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct Aggregator {
+    commands: Vec<AggregatedCommand>,
+    dedup: bool,
+}
+
+#[derive(Clone, Debug)]
+struct AggregatedCommand {
+    args: Vec<OsString>,
+    prefix: String,
+}
+
+impl AggregatedCommand {
+    fn name(&self) -> String {
+        self.args
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn execute(&self, ctx: &Context, meta: bool) -> Result<String, Error> {
+        let mut command = Command::new(&self.args[0]);
+        command.args(&self.args[1..]);
+        if meta {
+            command.env("MACKEREL_AGENT_PLUGIN_META", "1");
+        } else {
+            command.env_remove("MACKEREL_AGENT_PLUGIN_META");
+        }
+        let output = ctx
+            .output(command.env_remove("MACKEREL_PLUGIN_OUTPUT_FORMAT"))
+            .map_err(|e| format!("{} failed: {}", self.name(), e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} failed: {}: {}",
+                self.name(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl Aggregator {
+    pub fn new() -> Aggregator {
+        Aggregator::default()
+    }
+
+    /// Adds the plugin command of the program and the arguments.
+    pub fn command(self, args: impl IntoIterator<Item = impl Into<OsString>>) -> Aggregator {
+        self.command_with_prefix(args, "")
+    }
+
+    /// Adds the plugin command, whose graph and metric names are prefixed.
+    pub fn command_with_prefix(
+        mut self,
+        args: impl IntoIterator<Item = impl Into<OsString>>,
+        prefix: impl Into<String>,
+    ) -> Aggregator {
+        let args = args.into_iter().map(Into::into).collect::<Vec<_>>();
+        if !args.is_empty() {
+            self.commands.push(AggregatedCommand {
+                args,
+                prefix: prefix.into(),
+            });
+        }
+        self
+    }
+
+    /// Emits only the first value of the metric names output by the multiple
+    /// commands.
+    pub fn dedup(mut self) -> Aggregator {
+        self.dedup = true;
+        self
+    }
+
+    /// Executes the commands and returns the merged metric values.
+    pub fn fetch_values(&self, ctx: &Context) -> Result<Vec<(String, f64, i64)>, Error> {
+        let mut values = self
+            .execute(ctx, false, parse_values)?
+            .into_iter()
+            .flat_map(|(command, values)| {
+                values.into_iter().map(move |(name, value, time)| {
+                    (join_name(&command.prefix, &name), value, time)
+                })
+            })
+            .collect::<Vec<_>>();
+        if self.dedup {
+            let mut names = HashSet::new();
+            values.retain(|(name, _, _)| names.insert(name.clone()));
+        }
+        Ok(values)
+    }
+
+    /// Executes the commands for the graph definitions and returns the merged
+    /// definitions, where the first definition is used for the same graph.
+    pub fn fetch_definitions(&self, ctx: &Context) -> Result<Vec<Graph>, Error> {
+        let mut names = HashSet::new();
+        Ok(self
+            .execute(ctx, true, parse_meta_output)?
+            .into_iter()
+            .flat_map(|(command, graphs)| {
+                graphs.into_iter().map(move |graph| Graph {
+                    name: join_name(&command.prefix, &graph.name),
+                    ..graph
+                })
+            })
+            .filter(|graph| names.insert(graph.name.clone()))
+            .collect())
+    }
+
+    /// Runs the aggregator, which outputs the graph definitions when the
+    /// environment variable `MACKEREL_AGENT_PLUGIN_META` is set, or the metric
+    /// values otherwise.
+    pub fn run(&self) -> Result<(), Error> {
+        let stdout = std::io::stdout();
+        let mut out = std::io::BufWriter::new(stdout.lock());
+        let ctx = Context::from_env();
+        if std::env::var("MACKEREL_AGENT_PLUGIN_META").is_ok_and(|value| !value.is_empty()) {
+            self.output_definitions(&ctx, &mut out)?;
+        } else {
+            self.output_values(&ctx, &mut out)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Outputs the merged metric values for mackerel-agent.
+    pub fn output_values(&self, ctx: &Context, out: &mut dyn Write) -> Result<(), Error> {
+        let values = self.fetch_values(ctx)?;
+        let mut sink = TsvSink::new(out);
+        for (name, value, time) in values {
+            sink.write_value(&name, value, time)?;
+        }
+        sink.finish()
+    }
+
+    /// Outputs the merged graph definitions for mackerel-agent.
+    pub fn output_definitions(&self, ctx: &Context, out: &mut dyn Write) -> Result<(), Error> {
+        let graphs = self.fetch_definitions(ctx)?;
+        writeln!(out, "{}", META_HEADER)?;
+        json::write_graphs_json(out, "", &graphs)?;
+        Ok(())
+    }
+
+    /// Executes the commands in parallel and returns the parsed outputs of
+    /// the succeeded commands in order, logging the errors of the others.
+    fn execute<T: Send>(
+        &self,
+        ctx: &Context,
+        meta: bool,
+        parse: impl Fn(&str) -> Result<T, Error> + Sync,
+    ) -> Result<Vec<(&AggregatedCommand, T)>, Error> {
+        let results = std::thread::scope(|s| {
+            self.commands
+                .iter()
+                .map(|command| {
+                    s.spawn(|| {
+                        let output = command.execute(ctx, meta)?;
+                        parse(&output)
+                            .map_err(|e| Error::from(format!("{} failed: {}", command.name(), e)))
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err("command panicked".into()))
+                })
+                .collect::<Vec<_>>()
+        });
+        let mut outputs = Vec::new();
+        let mut first_error = None;
+        for (command, result) in self.commands.iter().zip(results) {
+            match result {
+                Ok(output) => outputs.push((command, output)),
+                Err(err) => {
+                    ctx.log(&err.to_string());
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        match first_error {
+            Some(err) if outputs.is_empty() => Err(err),
+            _ => Ok(outputs),
+        }
+    }
+}
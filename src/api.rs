@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use crate::error::Error;
+
+const MAX_RETRIES: u32 = 3;
+
+/// A client of the Mackerel API.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::Client;
+///
+/// let client = Client::from_env().unwrap();
+/// client
+///     .post_host_metrics("2pS7Kx8Wy1u", &[("custom.dice.d6".to_owned(), 3.0, 1700000000)])
+///     .unwrap();
+/// ```
+pub struct Client {
+    api_key: String,
+    base_url: String,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    pub fn new(api_key: impl Into<String>) -> Client {
+        Client {
+            api_key: api_key.into(),
+            base_url: "https://api.mackerelio.com".to_owned(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+        }
+    }
+
+    /// Creates a client with the API key of `MACKEREL_APIKEY`, and the base
+    /// URL of `MACKEREL_APIBASE` if set.
+    pub fn from_env() -> Result<Client, Error> {
+        let api_key = std::env::var("MACKEREL_APIKEY")
+            .ok()
+            .filter(|api_key| !api_key.is_empty())
+            .ok_or("MACKEREL_APIKEY is not set")?;
+        let client = Client::new(api_key);
+        Ok(match std::env::var("MACKEREL_APIBASE") {
+            Ok(base_url) if !base_url.is_empty() => client.base_url(base_url),
+            _ => client,
+        })
+    }
+
+    /// Sets the base URL of the API, which defaults to
+    /// `https://api.mackerelio.com`.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Client {
+        self.base_url = base_url.into().trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Returns the ID of the host of the custom identifier.
+    pub fn find_host_id(&self, custom_identifier: &str) -> Result<Option<String>, Error> {
+        let path = "/api/v0/hosts";
+        let response = self
+            .agent
+            .get(&(self.base_url.clone() + path))
+            .set("X-Api-Key", &self.api_key)
+            .query("customIdentifier", custom_identifier)
+            .call()
+            .map_err(|err| format!("GET {} failed: {}", path, err))?;
+        let hosts: serde_json::Value = serde_json::from_reader(response.into_reader())
+            .map_err(|err| format!("GET {} failed: {}", path, err))?;
+        Ok(hosts["hosts"][0]["id"].as_str().map(str::to_owned))
+    }
+
+    /// Posts the metric values of the host. The names of the custom metrics
+    /// should start with `custom.`.
+    pub fn post_host_metrics(
+        &self,
+        host_id: &str,
+        values: &[(String, f64, i64)],
+    ) -> Result<(), Error> {
+        let body = values
+            .iter()
+            .map(|(name, value, time)| {
+                serde_json::json!({ "hostId": host_id, "name": name, "time": time, "value": value })
+            })
+            .collect::<serde_json::Value>();
+        self.post("/api/v0/tsdb", &body)
+    }
+
+    /// Posts the metric values of the service.
+    pub fn post_service_metrics(
+        &self,
+        service: &str,
+        values: &[(String, f64, i64)],
+    ) -> Result<(), Error> {
+        let body = values
+            .iter()
+            .map(|(name, value, time)| {
+                serde_json::json!({ "name": name, "time": time, "value": value })
+            })
+            .collect::<serde_json::Value>();
+        self.post(&format!("/api/v0/services/{}/tsdb", service), &body)
+    }
+
+    /// Posts the JSON to the path, retrying on the rate limit and the server
+    /// errors.
+    pub(crate) fn post(&self, path: &str, body: &serde_json::Value) -> Result<(), Error> {
+        let url = self.base_url.clone() + path;
+        let mut retries = 0;
+        loop {
+            match self
+                .agent
+                .post(&url)
+                .set("X-Api-Key", &self.api_key)
+                .set("Content-Type", "application/json")
+                .send_string(&body.to_string())
+            {
+                Ok(_) => return Ok(()),
+                Err(ureq::Error::Status(status, response))
+                    if (status == 429 || status >= 500) && retries < MAX_RETRIES =>
+                {
+                    let wait = response
+                        .header("Retry-After")
+                        .and_then(|secs| secs.parse().ok())
+                        .map_or(Duration::from_secs(1 << retries), Duration::from_secs);
+                    std::thread::sleep(wait);
+                    retries += 1;
+                }
+                Err(ureq::Error::Status(status, response)) => {
+                    let message = response.into_string().unwrap_or_default();
+                    return Err(format!("POST {} failed: {} {}", path, status, message).into());
+                }
+                Err(err) => return Err(format!("POST {} failed: {}", path, err).into()),
+            }
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use ring::{digest, hmac};
+use std::time::{Duration, SystemTime};
+
+use crate::error::Error;
+use crate::rfc3339;
+
+const MAX_RETRIES: u32 = 3;
+
+/// The credentials of AWS.
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Returns the credentials of `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`,
+    /// and `AWS_SESSION_TOKEN`.
+    pub fn from_env() -> Result<Credentials, Error> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        Ok(Credentials {
+            access_key_id: var("AWS_ACCESS_KEY_ID").ok_or("AWS_ACCESS_KEY_ID is not set")?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY")
+                .ok_or("AWS_SECRET_ACCESS_KEY is not set")?,
+            session_token: var("AWS_SESSION_TOKEN"),
+        })
+    }
+}
+
+/// A client of the query APIs of AWS, which signs the requests and retries
+/// on the throttling errors.
+pub(crate) struct Client {
+    pub(crate) region: String,
+    pub(crate) service: &'static str,
+    pub(crate) credentials: Credentials,
+    pub(crate) endpoint: String,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    /// Creates a client of the service, whose endpoint defaults to the
+    /// regional one like `https://monitoring.us-east-1.amazonaws.com`.
+    pub(crate) fn new(region: String, service: &'static str, credentials: Credentials) -> Client {
+        Client {
+            endpoint: format!("https://{}.{}.amazonaws.com", service, region),
+            region,
+            service,
+            credentials,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+        }
+    }
+
+    /// Returns the region of `AWS_REGION` (or `AWS_DEFAULT_REGION`).
+    pub(crate) fn region_from_env() -> Result<String, Error> {
+        Ok(["AWS_REGION", "AWS_DEFAULT_REGION"]
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+            .ok_or("AWS_REGION is not set")?)
+    }
+
+    /// Requests the action with the parameters, and returns the root element
+    /// of the response.
+    pub(crate) fn request(
+        &self,
+        action: &str,
+        params: &[(String, String)],
+    ) -> Result<Element, Error> {
+        let body = params
+            .iter()
+            .map(|(key, value)| format!("{}={}", uri_encode(key), uri_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let host = self
+            .endpoint
+            .split_once("://")
+            .map_or(&self.endpoint[..], |(_, host)| host);
+        let mut retries = 0;
+        loop {
+            let headers = sign(
+                &self.credentials,
+                &self.region,
+                self.service,
+                host,
+                &body,
+                SystemTime::now(),
+            );
+            let mut request = self.agent.post(&(self.endpoint.clone() + "/"));
+            for (name, value) in &headers {
+                request = request.set(name, value);
+            }
+            let (status, text) = match request.send_string(&body) {
+                Ok(response) => (200, response.into_string()?),
+                Err(ureq::Error::Status(status, response)) => {
+                    (status, response.into_string().unwrap_or_default())
+                }
+                Err(err) => return Err(format!("{} failed: {}", action, err).into()),
+            };
+            let element = Element::parse(&text);
+            if status == 200 {
+                return element
+                    .ok_or_else(|| format!("{} failed: invalid response", action).into());
+            }
+            let code = element
+                .as_ref()
+                .and_then(|e| e.child("Error")?.child_text("Code").map(str::to_owned))
+                .unwrap_or_default();
+            if (status == 429 || status >= 500 || code == "Throttling") && retries < MAX_RETRIES {
+                std::thread::sleep(Duration::from_secs(1 << retries));
+                retries += 1;
+                continue;
+            }
+            return Err(format!("{} failed: {} {}", action, status, code).into());
+        }
+    }
+}
+
+/// Returns the headers of the POST request signed with the signature version
+/// 4, including `Authorization`.
+fn sign(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    host: &str,
+    body: &str,
+    time: SystemTime,
+) -> Vec<(String, String)> {
+    let amz_date = rfc3339::format(time).replace(['-', ':'], "");
+    let mut headers = vec![
+        (
+            "content-type".to_owned(),
+            "application/x-www-form-urlencoded".to_owned(),
+        ),
+        ("host".to_owned(), host.to_owned()),
+        ("x-amz-date".to_owned(), amz_date.clone()),
+    ];
+    if let Some(session_token) = &credentials.session_token {
+        headers.push(("x-amz-security-token".to_owned(), session_token.clone()));
+    }
+    let authorization = authorization(credentials, region, service, "POST", "", &headers, body);
+    headers.push(("authorization".to_owned(), authorization));
+    headers
+}
+
+fn authorization(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    method: &str,
+    query: &str,
+    headers: &[(String, String)],
+    body: &str,
+) -> String {
+    let amz_date = headers
+        .iter()
+        .find(|(name, _)| name == "x-amz-date")
+        .map_or("", |(_, value)| value);
+    let date = &amz_date[..amz_date.len().min(8)];
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| &name[..])
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n/\n{}\n{}\n{}\n{}",
+        method,
+        query,
+        headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect::<String>(),
+        signed_headers,
+        hex(digest::digest(&digest::SHA256, body.as_bytes()).as_ref()),
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+    );
+    let key = [date, region, service, "aws4_request"].iter().fold(
+        format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+        |key, data| hmac_sha256(&key, data),
+    );
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id,
+        scope,
+        signed_headers,
+        hex(&hmac_sha256(&key, &string_to_sign))
+    )
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data.as_bytes())
+        .as_ref()
+        .to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn uri_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// An element of the XML response, which ignores the attributes.
+#[derive(Debug, Default)]
+pub(crate) struct Element {
+    pub(crate) name: String,
+    pub(crate) children: Vec<Element>,
+    pub(crate) text: String,
+}
+
+impl Element {
+    pub(crate) fn parse(s: &str) -> Option<Element> {
+        let mut stack = vec![Element::default()];
+        let mut rest = s;
+        while let Some(start) = rest.find('<') {
+            let text = &rest[..start];
+            if !text.trim().is_empty() {
+                stack.last_mut()?.text.push_str(&unescape(text));
+            }
+            let end = rest[start..].find('>')? + start;
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                let element = stack.pop()?;
+                if element.name != name.trim() {
+                    return None;
+                }
+                stack.last_mut()?.children.push(element);
+            } else {
+                let self_closing = tag.ends_with('/');
+                let name = tag
+                    .trim_end_matches('/')
+                    .split_whitespace()
+                    .next()?
+                    .to_owned();
+                let element = Element {
+                    name,
+                    ..Element::default()
+                };
+                if self_closing {
+                    stack.last_mut()?.children.push(element);
+                } else {
+                    stack.push(element);
+                }
+            }
+        }
+        let mut root = stack.pop()?;
+        if !stack.is_empty() || root.children.len() != 1 {
+            return None;
+        }
+        root.children.pop()
+    }
+
+    pub(crate) fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub(crate) fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| &child.text[..])
+    }
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorization() {
+        // the example of the signature version 4 in the documentation of AWS
+        let credentials = Credentials {
+            access_key_id: "AKIDEXAMPLE".to_owned(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_owned(),
+            session_token: None,
+        };
+        let headers = [
+            (
+                "content-type".to_owned(),
+                "application/x-www-form-urlencoded; charset=utf-8".to_owned(),
+            ),
+            ("host".to_owned(), "iam.amazonaws.com".to_owned()),
+            ("x-amz-date".to_owned(), "20150830T123600Z".to_owned()),
+        ];
+        assert_eq!(
+            authorization(
+                &credentials,
+                "us-east-1",
+                "iam",
+                "GET",
+                "Action=ListUsers&Version=2010-05-08",
+                &headers,
+                ""
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, \
+             Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+
+    #[test]
+    fn test_element_parse() {
+        let element = Element::parse(
+            r#"<?xml version="1.0"?>
+            <Response xmlns="http://example.com/">
+              <Result><Id>m0</Id><Label>a &amp; b</Label><Empty/></Result>
+              <NextToken>token</NextToken>
+            </Response>"#,
+        )
+        .unwrap();
+        assert_eq!(element.name, "Response");
+        let result = element.child("Result").unwrap();
+        assert_eq!(result.child_text("Id"), Some("m0"));
+        assert_eq!(result.child_text("Label"), Some("a & b"));
+        assert_eq!(result.child_text("Empty"), Some(""));
+        assert_eq!(element.child_text("NextToken"), Some("token"));
+        assert!(Element::parse("<a><b></a>").is_none());
+    }
+}
@@ -0,0 +1,61 @@
+use mackerel_plugin::{Package, Scaffold, DEFAULT_TARGETS};
+
+const USAGE: &str = "usage: cargo mackerel-plugin new <name>
+       cargo mackerel-plugin package <owner>/<repo> [<target>...]";
+
+fn run(args: &[String]) -> Result<(), String> {
+    match args {
+        [command, name] if command == "new" => {
+            let scaffold = Scaffold::new(name)?;
+            let dir = scaffold.dir_name();
+            scaffold.write_to(std::path::Path::new(&dir))?;
+            println!("Created {}", dir);
+            Ok(())
+        }
+        [command, source, targets @ ..] if command == "package" => {
+            let package = current_package(source)?;
+            let targets = if targets.is_empty() {
+                DEFAULT_TARGETS.to_vec()
+            } else {
+                targets.iter().map(String::as_str).collect()
+            };
+            let json = package.metadata_json(&targets)?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json).map_err(|e| e.to_string())?
+            );
+            Ok(())
+        }
+        _ => Err(USAGE.to_owned()),
+    }
+}
+
+fn current_package(source: &str) -> Result<Package, String> {
+    let output = std::process::Command::new(std::env::var("CARGO").unwrap_or("cargo".to_owned()))
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()
+        .map_err(|e| format!("cargo metadata failed: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    let package = &metadata["packages"][0];
+    let field = |name: &str| package[name].as_str().unwrap_or_default().to_owned();
+    Ok(Package::new(field("name"), field("version"), source).description(field("description")))
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    // cargo passes the subcommand name as the first argument
+    if args.first().is_some_and(|arg| arg == "mackerel-plugin") {
+        args.remove(0);
+    }
+    if let Err(err) = run(&args) {
+        eprintln!("cargo-mackerel-plugin: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::metric_map::MetricMap;
+use crate::value::Value;
+
+/// The entries of a BPF map.
+type Entries<K, V> = Vec<(K, V)>;
+
+/// A map of the BPF programs, which is read periodically to collect the
+/// metrics.
+///
+/// The plugins built on aya or libbpf implement this trait for the wrappers
+/// of their maps, and convert the entries to the metric values by
+/// [`BpfMap::metrics`]. The values of the per-CPU maps are aggregated by
+/// [`PerCpu`].
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use mackerel_plugin::{BpfMap, Error, MetricMap, PerCpu, Value};
+///
+/// // the map of the syscall counts by the syscall number on each CPU
+/// struct SyscallCounts(HashMap<u32, Vec<u64>>);
+///
+/// impl BpfMap for SyscallCounts {
+///     type Key = u32;
+///     type Value = PerCpu<u64>;
+///
+///     fn entries(&self) -> Result<Vec<(u32, PerCpu<u64>)>, Error> {
+///         Ok(self.0.iter().map(|(&k, v)| (k, PerCpu::new(v.clone()))).collect())
+///     }
+/// }
+///
+/// let map = SyscallCounts(HashMap::from([(0, vec![10, 20]), (1, vec![5, 0])]));
+/// let metrics: MetricMap = map
+///     .metrics(|nr, counts| {
+///         Some((format!("syscall.count.{}", nr), Value::counter(counts.sum())))
+///     })
+///     .unwrap();
+/// assert_eq!(metrics.get("syscall.count.0"), Some(&Value::Counter(30)));
+/// assert_eq!(metrics.get("syscall.count.1"), Some(&Value::Counter(5)));
+/// ```
+pub trait BpfMap {
+    type Key;
+    type Value;
+
+    /// Returns the entries of the map.
+    fn entries(&self) -> Result<Entries<Self::Key, Self::Value>, Error>;
+
+    /// Returns the metric values converted from the entries. The entries are
+    /// skipped when the conversion returns `None`, and the values of the same
+    /// metric names are overwritten by the latter ones.
+    fn metrics<F, V>(&self, mut f: F) -> Result<MetricMap, Error>
+    where
+        F: FnMut(&Self::Key, &Self::Value) -> Option<(String, V)>,
+        V: Into<Value>,
+    {
+        let mut metrics = MetricMap::new();
+        for (key, value) in self.entries()? {
+            if let Some((name, value)) = f(&key, &value) {
+                metrics.insert(name, value);
+            }
+        }
+        Ok(metrics)
+    }
+}
+
+impl<K: Clone, V: Clone> BpfMap for HashMap<K, V> {
+    type Key = K;
+    type Value = V;
+
+    fn entries(&self) -> Result<Vec<(K, V)>, Error> {
+        Ok(self
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+/// The values of a per-CPU map entry, indexed by the CPU.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PerCpu<T> {
+    values: Vec<T>,
+}
+
+impl<T> PerCpu<T> {
+    pub fn new(values: Vec<T>) -> PerCpu<T> {
+        PerCpu { values }
+    }
+
+    /// Returns the value of the CPU.
+    pub fn get(&self, cpu: usize) -> Option<&T> {
+        self.values.get(cpu)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.values.iter()
+    }
+
+    /// Aggregates the values of the CPUs by the function, which is useful for
+    /// the values of the structs.
+    pub fn fold<U>(&self, init: U, f: impl FnMut(U, &T) -> U) -> U {
+        self.values.iter().fold(init, f)
+    }
+}
+
+impl<T: Copy + std::iter::Sum<T>> PerCpu<T> {
+    /// Returns the sum of the values of the CPUs, such as the counters.
+    pub fn sum(&self) -> T {
+        self.values.iter().copied().sum()
+    }
+}
+
+impl<T: Copy + Ord> PerCpu<T> {
+    /// Returns the maximum of the values of the CPUs, such as the gauges.
+    pub fn max(&self) -> Option<T> {
+        self.values.iter().copied().max()
+    }
+}
+
+impl<T> From<Vec<T>> for PerCpu<T> {
+    fn from(values: Vec<T>) -> PerCpu<T> {
+        PerCpu::new(values)
+    }
+}
@@ -0,0 +1,147 @@
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::Error;
+use crate::state::StateStore;
+use crate::value::Value;
+
+/// A cache of expensive results persisted between runs, such as the list of
+/// databases or containers discovered by the plugin.
+///
+/// The entries are saved to the state store next to the state for calculating
+/// the difference of metric values, and refreshed after the time to live.
+///
+/// ```rust
+/// use mackerel_plugin::{Cache, MemoryStateStore};
+/// use std::time::Duration;
+///
+/// let state = MemoryStateStore::new();
+/// let cache = Cache::new(&state, "mackerel-plugin-dice.cache");
+/// let dice = cache
+///     .get_or_refresh("dice", Duration::from_secs(3600), || Ok(vec![6, 20]))
+///     .unwrap();
+/// assert_eq!(dice, vec![6, 20]);
+/// ```
+pub struct Cache<'a> {
+    state: &'a (dyn StateStore + Sync),
+    path: String,
+    clock: &'a (dyn Clock + Sync),
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CacheEntry<T = serde_json::Value> {
+    pub(crate) stored_at: i64,
+    pub(crate) value: T,
+}
+
+impl<T> CacheEntry<T> {
+    /// Returns whether the entry is stored within the time to live.
+    pub(crate) fn is_fresh(&self, now: i64, ttl: Duration) -> bool {
+        self.stored_at <= now
+            && now.saturating_sub(self.stored_at) < ttl.as_secs().try_into().unwrap_or(i64::MAX)
+    }
+}
+
+impl<'a> Cache<'a> {
+    pub fn new(state: &'a (dyn StateStore + Sync), path: impl Into<String>) -> Cache<'a> {
+        Cache {
+            state,
+            path: path.into(),
+            clock: &SystemClock,
+        }
+    }
+
+    /// Sets the clock for checking the time to live.
+    pub fn clock(mut self, clock: &'a (dyn Clock + Sync)) -> Cache<'a> {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the cached value of the key, or refreshes the value if it is
+    /// missing or older than the time to live.
+    pub fn get_or_refresh<T: serde::Serialize + DeserializeOwned>(
+        &self,
+        key: &str,
+        ttl: Duration,
+        refresh: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, Error> {
+        let now = self
+            .clock
+            .now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs() as i64;
+        let mut entries = self.load();
+        if let Some(entry) = entries.get(key) {
+            if entry.is_fresh(now, ttl) {
+                if let Ok(value) = serde_json::from_value(entry.value.clone()) {
+                    return Ok(value);
+                }
+            }
+        }
+        let value = refresh()?;
+        entries.insert(
+            key.to_owned(),
+            CacheEntry {
+                stored_at: now,
+                value: serde_json::to_value(&value).map_err(|e| e.to_string())?,
+            },
+        );
+        let bytes = serde_json::to_vec(&entries).map_err(|e| e.to_string())?;
+        self.state.save(&self.path, &bytes)?;
+        Ok(value)
+    }
+
+    /// Removes the cached value of the key.
+    pub fn invalidate(&self, key: &str) -> Result<(), Error> {
+        let mut entries = self.load();
+        if entries.remove(key).is_some() {
+            let bytes = serde_json::to_vec(&entries).map_err(|e| e.to_string())?;
+            self.state.save(&self.path, &bytes)?;
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> HashMap<String, CacheEntry> {
+        self.state
+            .load(&self.path)
+            .ok()
+            .flatten()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Loads the fetched metric values cached within the time to live.
+pub(crate) fn load_fetched_values(
+    state: &dyn StateStore,
+    path: &str,
+    now: i64,
+    ttl: Duration,
+) -> Option<HashMap<String, Value>> {
+    state
+        .load(&(path.to_owned() + ".values"))
+        .ok()
+        .flatten()
+        .and_then(|bytes| serde_json::from_slice::<CacheEntry<_>>(&bytes).ok())
+        .filter(|entry| entry.is_fresh(now, ttl))
+        .map(|entry| entry.value)
+}
+
+/// Saves the fetched metric values to reuse them within the time to live.
+pub(crate) fn save_fetched_values(
+    state: &dyn StateStore,
+    path: &str,
+    now: i64,
+    values: &HashMap<String, Value>,
+) -> Result<(), String> {
+    let entry = CacheEntry {
+        stored_at: now,
+        value: values,
+    };
+    let bytes = serde_json::to_vec(&entry).map_err(|e| e.to_string())?;
+    state.save(&(path.to_owned() + ".values"), &bytes)
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme};
+
+use crate::check::{CheckResult, CheckStatus};
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::rfc3339;
+use crate::value::Value;
+
+enum Source {
+    Address(String),
+    File(PathBuf),
+}
+
+/// A checker of the expiry of the TLS certificate, which connects to the
+/// server or reads the PEM file.
+///
+/// The certificate is not verified, so that the expired or self-signed
+/// certificates can be checked. The expiry of the leaf certificate (the first
+/// one in the PEM file) is checked.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::CertificateExpiry;
+///
+/// let expiry = CertificateExpiry::connect("example", "example.com:443");
+/// let values = expiry.fetch_values().unwrap();
+/// let result = expiry.check(30.0, 7.0);
+/// println!("{}", result);
+/// ```
+pub struct CertificateExpiry {
+    name: String,
+    source: Source,
+    server_name: Option<String>,
+    timeout: Duration,
+}
+
+impl CertificateExpiry {
+    /// Creates a checker of the certificate of the server at the address
+    /// like `example.com:443`.
+    pub fn connect(name: &str, address: impl Into<String>) -> CertificateExpiry {
+        CertificateExpiry::new(name, Source::Address(address.into()))
+    }
+
+    /// Creates a checker of the certificate in the PEM file.
+    pub fn file(name: &str, path: impl Into<PathBuf>) -> CertificateExpiry {
+        CertificateExpiry::new(name, Source::File(path.into()))
+    }
+
+    fn new(name: &str, source: Source) -> CertificateExpiry {
+        CertificateExpiry {
+            name: name
+                .chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                    _ => '_',
+                })
+                .collect(),
+            source,
+            server_name: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Sets the server name for SNI, which defaults to the host of the
+    /// address.
+    pub fn server_name(mut self, server_name: impl Into<String>) -> CertificateExpiry {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// Sets the timeout of connecting and the handshake.
+    pub fn timeout(mut self, timeout: Duration) -> CertificateExpiry {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the graph of the days until the certificates expire.
+    pub fn graphs() -> Vec<Graph> {
+        vec![crate::graph! {
+            name: "certificate.expiry",
+            label: "Certificate expiry",
+            unit: "float",
+            metrics: [{ name: "*", label: "%1" }],
+        }]
+    }
+
+    /// Returns the expiry time of the certificate.
+    pub fn not_after(&self) -> Result<SystemTime, Error> {
+        let certificate = match &self.source {
+            Source::Address(address) => self.peer_certificate(address)?,
+            Source::File(path) => {
+                let pem = std::fs::read_to_string(path)
+                    .map_err(|e| format!("read {} failed: {}", path.display(), e))?;
+                pem_certificate(&pem)
+                    .ok_or_else(|| format!("certificate not found in {}", path.display()))?
+            }
+        };
+        not_after(&certificate).ok_or_else(|| "invalid certificate".into())
+    }
+
+    /// Returns the days until the certificate expires, which is negative when
+    /// the certificate has expired.
+    pub fn days_remaining(&self, now: SystemTime) -> Result<f64, Error> {
+        Ok(days_between(now, self.not_after()?))
+    }
+
+    /// Returns the metric value of the days until the certificate expires.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        Ok(HashMap::from([(
+            format!("certificate.expiry.{}", self.name),
+            self.days_remaining(SystemTime::now())?.into(),
+        )]))
+    }
+
+    /// Checks the days until the certificate expires with the thresholds.
+    pub fn check(&self, warning_days: f64, critical_days: f64) -> CheckResult {
+        let not_after = match self.not_after() {
+            Ok(not_after) => not_after,
+            Err(err) => return CheckResult::new(CheckStatus::Unknown, err.to_string()),
+        };
+        let days = days_between(SystemTime::now(), not_after);
+        let status = if days < critical_days {
+            CheckStatus::Critical
+        } else if days < warning_days {
+            CheckStatus::Warning
+        } else {
+            CheckStatus::Ok
+        };
+        let message = if days < 0.0 {
+            format!("certificate expired at {}", rfc3339::format(not_after))
+        } else {
+            format!(
+                "certificate expires in {:.1} days ({})",
+                days,
+                rfc3339::format(not_after)
+            )
+        };
+        CheckResult::new(status, message)
+    }
+
+    fn peer_certificate(&self, address: &str) -> Result<Vec<u8>, Error> {
+        let err = |e: &dyn std::fmt::Display| format!("connect to {} failed: {}", address, e);
+        let server_name = match &self.server_name {
+            Some(server_name) => server_name.clone(),
+            None => address
+                .rsplit_once(':')
+                .map_or(address, |(host, _)| host)
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_owned(),
+        };
+        let server_name = ServerName::try_from(server_name).map_err(|e| err(&e))?;
+        let addr = address
+            .to_socket_addrs()
+            .map_err(|e| err(&e))?
+            .next()
+            .ok_or_else(|| err(&"no address"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout).map_err(|e| err(&e))?;
+        stream
+            .set_read_timeout(Some(self.timeout))
+            .and_then(|_| stream.set_write_timeout(Some(self.timeout)))
+            .map_err(|e| err(&e))?;
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| err(&e))?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerification(provider)))
+            .with_no_client_auth();
+        let mut connection =
+            ClientConnection::new(Arc::new(config), server_name).map_err(|e| err(&e))?;
+        while connection.is_handshaking() {
+            connection.complete_io(&mut stream).map_err(|e| err(&e))?;
+        }
+        let certificate = connection
+            .peer_certificates()
+            .and_then(|certificates| certificates.first())
+            .ok_or_else(|| err(&"no certificate"))?
+            .to_vec();
+        connection.send_close_notify();
+        let _ = connection.write_tls(&mut stream);
+        let _ = stream.flush();
+        Ok(certificate)
+    }
+}
+
+fn days_between(now: SystemTime, not_after: SystemTime) -> f64 {
+    match not_after.duration_since(now) {
+        Ok(duration) => duration.as_secs_f64() / 86400.0,
+        Err(err) => -err.duration().as_secs_f64() / 86400.0,
+    }
+}
+
+/// A verifier which accepts any certificates, only for reading the expiry.
+#[derive(Debug)]
+struct NoVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _: &CertificateDer<'_>,
+        _: &[CertificateDer<'_>],
+        _: &ServerName<'_>,
+        _: &[u8],
+        _: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Returns the DER of the first certificate in the PEM.
+fn pem_certificate(pem: &str) -> Option<Vec<u8>> {
+    let (_, rest) = pem.split_once("-----BEGIN CERTIFICATE-----")?;
+    let (base64, _) = rest.split_once("-----END CERTIFICATE-----")?;
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in base64
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = buffer << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Returns the notAfter of the validity of the DER certificate.
+fn not_after(certificate: &[u8]) -> Option<SystemTime> {
+    let (_, certificate, _) = der_element(certificate)?;
+    let (_, mut tbs, _) = der_element(certificate)?;
+    // skip the version, the serial number, the signature, and the issuer
+    if tbs.first() == Some(&0xa0) {
+        tbs = der_element(tbs)?.2;
+    }
+    for _ in 0..3 {
+        tbs = der_element(tbs)?.2;
+    }
+    let (_, validity, _) = der_element(tbs)?;
+    let (_, _, validity) = der_element(validity)?;
+    let (tag, time, _) = der_element(validity)?;
+    let time = std::str::from_utf8(time).ok()?;
+    let time = match tag {
+        // UTCTime represents the years from 1950 to 2049 in two digits
+        0x17 if time.len() == 13 => {
+            let century = if time[..2].parse::<u8>().ok()? < 50 {
+                "20"
+            } else {
+                "19"
+            };
+            century.to_owned() + time
+        }
+        0x18 if time.len() == 15 => time.to_owned(),
+        _ => return None,
+    };
+    rfc3339::parse(&format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &time[..4],
+        &time[4..6],
+        &time[6..8],
+        &time[8..10],
+        &time[10..12],
+        &time[12..14]
+    ))
+}
+
+/// Returns the tag, the content, and the rest of the DER element.
+fn der_element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&length, mut input) = input.split_first()?;
+    let length = if length < 0x80 {
+        length as usize
+    } else {
+        let count = (length & 0x7f) as usize;
+        if count > 4 || input.len() < count {
+            return None;
+        }
+        let (bytes, rest) = input.split_at(count);
+        input = rest;
+        bytes.iter().fold(0, |length, &b| length << 8 | b as usize)
+    };
+    if input.len() < length {
+        return None;
+    }
+    let (content, rest) = input.split_at(length);
+    Some((tag, content, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::time::UNIX_EPOCH;
+
+    #[rstest]
+    #[case(0x17, "491231235959Z", Some(2524607999))]
+    #[case(0x17, "991231235959Z", Some(946684799))]
+    #[case(0x18, "20600101000000Z", Some(2840140800))]
+    #[case(0x18, "206001010000Z", None)]
+    #[case(0x04, "491231235959Z", None)]
+    fn test_not_after(#[case] tag: u8, #[case] time: &str, #[case] expected: Option<u64>) {
+        let sequence = |content: Vec<u8>| [vec![0x30, content.len() as u8], content].concat();
+        let validity = sequence(
+            [
+                vec![0x17, 13],
+                b"200101000000Z".to_vec(),
+                vec![tag, time.len() as u8],
+                time.as_bytes().to_vec(),
+            ]
+            .concat(),
+        );
+        let tbs = sequence(
+            [
+                vec![0xa0, 3, 0x02, 1, 2],
+                vec![0x02, 1, 1],
+                sequence(vec![]),
+                sequence(vec![]),
+                validity,
+                sequence(vec![]),
+            ]
+            .concat(),
+        );
+        let certificate = sequence(tbs);
+        assert_eq!(
+            not_after(&certificate),
+            expected.map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+        );
+    }
+}
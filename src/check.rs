@@ -0,0 +1,52 @@
+use strum::Display;
+
+/// The status of a check plugin, which is reported by the exit code.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Display)]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl CheckStatus {
+    /// Returns the exit code of the status for the check monitoring of
+    /// mackerel-agent.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CheckStatus::Ok => 0,
+            CheckStatus::Warning => 1,
+            CheckStatus::Critical => 2,
+            CheckStatus::Unknown => 3,
+        }
+    }
+}
+
+/// The result of a check, which is displayed like `WARNING: message`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct CheckResult {
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl CheckResult {
+    pub fn new(status: CheckStatus, message: impl Into<String>) -> CheckResult {
+        CheckResult {
+            status,
+            message: message.into(),
+        }
+    }
+
+    /// Prints the result and exits with the code of the status.
+    pub fn exit(&self) -> ! {
+        println!("{}", self);
+        std::process::exit(self.status.exit_code())
+    }
+}
+
+impl std::fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.status, self.message)
+    }
+}
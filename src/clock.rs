@@ -0,0 +1,151 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A trait which represents a clock used for timestamping metric values.
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+impl<T: Clock + ?Sized> Clock for &T {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+}
+
+/// A fixed time is a clock which always returns the same time.
+impl Clock for SystemTime {
+    fn now(&self) -> SystemTime {
+        *self
+    }
+}
+
+/// A timestamp is a clock fixed to the time.
+impl Clock for Epoch {
+    fn now(&self) -> SystemTime {
+        (*self).into()
+    }
+}
+
+/// A fixed date time is a clock which always returns the same time.
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> Clock for chrono::DateTime<Tz> {
+    fn now(&self) -> SystemTime {
+        self.clone().into()
+    }
+}
+
+/// A clock which returns the current system time.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A mode of timestamping the metric values. All the metric values in a run
+/// share the same timestamp regardless of the mode, so that the series
+/// fetched together are plotted at the same epoch.
+#[derive(Default, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Timestamping {
+    /// Timestamps the values with the time before fetching them.
+    #[default]
+    BeforeFetch,
+    /// Timestamps the values with the time after fetching them, which is
+    /// closer to the time the values are read if fetching takes a while.
+    AfterFetch,
+    /// Timestamps the values with the time reported by `Plugin::source_time`,
+    /// such as the time the upstream source collected the statistics. Falls
+    /// back to the time before fetching if the plugin reports no time.
+    Source,
+}
+
+/// A timestamp of the metric values in the seconds since the Unix epoch,
+/// which is converted from and to `SystemTime`, `i64`, and `chrono::DateTime`
+/// (`chrono` feature).
+///
+/// ```rust
+/// use mackerel_plugin::Epoch;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let epoch = Epoch::from(UNIX_EPOCH + Duration::from_millis(1700000000500));
+/// assert_eq!(epoch, Epoch::new(1700000000));
+/// assert_eq!(epoch.floor(60).as_secs(), 1699999980);
+/// assert_eq!(i64::from(epoch), 1700000000);
+/// ```
+#[derive(Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub struct Epoch(i64);
+
+impl Epoch {
+    pub fn new(secs: i64) -> Epoch {
+        Epoch(secs)
+    }
+
+    pub fn as_secs(self) -> i64 {
+        self.0
+    }
+
+    /// Floors the timestamp to the multiple of the seconds, such as a minute.
+    pub fn floor(self, secs: i64) -> Epoch {
+        Epoch(self.0 - self.0.rem_euclid(secs))
+    }
+}
+
+impl std::fmt::Display for Epoch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<i64> for Epoch {
+    fn from(secs: i64) -> Epoch {
+        Epoch(secs)
+    }
+}
+
+impl From<Epoch> for i64 {
+    fn from(epoch: Epoch) -> i64 {
+        epoch.0
+    }
+}
+
+/// Converts the time to the timestamp, truncating the subseconds towards the
+/// past, even for the time before the Unix epoch.
+impl From<SystemTime> for Epoch {
+    fn from(time: SystemTime) -> Epoch {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => Epoch(duration.as_secs() as i64),
+            Err(err) => {
+                let duration = err.duration();
+                Epoch(-(duration.as_secs() as i64) - i64::from(duration.subsec_nanos() > 0))
+            }
+        }
+    }
+}
+
+impl From<Epoch> for SystemTime {
+    fn from(epoch: Epoch) -> SystemTime {
+        if epoch.0 >= 0 {
+            UNIX_EPOCH + Duration::from_secs(epoch.0 as u64)
+        } else {
+            UNIX_EPOCH - Duration::from_secs(epoch.0.unsigned_abs())
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for Epoch {
+    fn from(time: chrono::DateTime<Tz>) -> Epoch {
+        Epoch(time.timestamp())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<Epoch> for chrono::DateTime<chrono::Utc> {
+    type Error = String;
+
+    fn try_from(epoch: Epoch) -> Result<chrono::DateTime<chrono::Utc>, String> {
+        chrono::DateTime::from_timestamp(epoch.0, 0)
+            .ok_or_else(|| format!("timestamp out of range: {}", epoch))
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use crate::aws::{Client, Credentials};
+use crate::error::Error;
+use crate::rfc3339;
+use crate::value::Value;
+
+const MAX_QUERIES: usize = 500;
+
+/// A query of a CloudWatch metric, which is mapped to the metric key of the
+/// plugin.
+///
+/// ```rust
+/// use mackerel_plugin::MetricQuery;
+///
+/// let query = MetricQuery::new("rds.cpu.db1", "AWS/RDS", "CPUUtilization")
+///     .dimension("DBInstanceIdentifier", "db1")
+///     .stat("Maximum");
+/// ```
+#[derive(Clone, Debug)]
+pub struct MetricQuery {
+    pub key: String,
+    pub namespace: String,
+    pub metric_name: String,
+    pub dimensions: Vec<(String, String)>,
+    pub stat: String,
+    pub period: u32,
+}
+
+impl MetricQuery {
+    /// Creates a query of the metric with the `Average` statistic of 60
+    /// seconds period.
+    pub fn new(
+        key: impl Into<String>,
+        namespace: impl Into<String>,
+        metric_name: impl Into<String>,
+    ) -> MetricQuery {
+        MetricQuery {
+            key: key.into(),
+            namespace: namespace.into(),
+            metric_name: metric_name.into(),
+            dimensions: Vec::new(),
+            stat: "Average".to_owned(),
+            period: 60,
+        }
+    }
+
+    pub fn dimension(mut self, name: impl Into<String>, value: impl Into<String>) -> MetricQuery {
+        self.dimensions.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn stat(mut self, stat: impl Into<String>) -> MetricQuery {
+        self.stat = stat.into();
+        self
+    }
+
+    pub fn period(mut self, period: u32) -> MetricQuery {
+        self.period = period;
+        self
+    }
+}
+
+/// A client of CloudWatch for fetching the metrics of the AWS resources with
+/// `GetMetricData`, handling the pagination and the throttling.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{CloudWatch, MetricQuery};
+///
+/// let cloudwatch = CloudWatch::from_env().unwrap();
+/// let values = cloudwatch
+///     .fetch_values(&[MetricQuery::new("sqs.messages.jobs", "AWS/SQS", "ApproximateNumberOfMessagesVisible")
+///         .dimension("QueueName", "jobs")])
+///     .unwrap();
+/// ```
+pub struct CloudWatch {
+    client: Client,
+    window: Duration,
+}
+
+impl CloudWatch {
+    pub fn new(region: impl Into<String>, credentials: Credentials) -> CloudWatch {
+        CloudWatch {
+            client: Client::new(region.into(), "monitoring", credentials),
+            window: Duration::from_secs(600),
+        }
+    }
+
+    /// Creates a client with the region of `AWS_REGION` (or
+    /// `AWS_DEFAULT_REGION`) and the credentials of the environment variables.
+    pub fn from_env() -> Result<CloudWatch, Error> {
+        Ok(CloudWatch::new(
+            Client::region_from_env()?,
+            Credentials::from_env()?,
+        ))
+    }
+
+    /// Sets the endpoint of the API.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> CloudWatch {
+        self.client.endpoint = endpoint.into().trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Sets the time window to look back for the latest data points, which
+    /// defaults to 10 minutes since the metrics of CloudWatch are delayed.
+    pub fn window(mut self, window: Duration) -> CloudWatch {
+        self.window = window;
+        self
+    }
+
+    /// Returns the latest values of the queries, with the timestamps of the
+    /// data points so that the staleness policy of the plugin applies. The
+    /// queries of no data points in the window are omitted.
+    pub fn fetch_values(&self, queries: &[MetricQuery]) -> Result<HashMap<String, Value>, Error> {
+        let end = SystemTime::now();
+        let start = end.checked_sub(self.window).unwrap_or(end);
+        Ok(self
+            .get_metric_data(queries, start, end)?
+            .into_iter()
+            .filter_map(|(key, datapoints)| {
+                let &(time, value) = datapoints.iter().max_by_key(|(time, _)| *time)?;
+                Some((key, Value::Observed(value, time)))
+            })
+            .collect())
+    }
+
+    /// Returns the data points of the queries between the times, keyed by the
+    /// metric keys of the queries.
+    pub fn get_metric_data(
+        &self,
+        queries: &[MetricQuery],
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<HashMap<String, Vec<(SystemTime, f64)>>, Error> {
+        let mut results = HashMap::new();
+        for queries in queries.chunks(MAX_QUERIES) {
+            let mut next_token = None;
+            loop {
+                let mut params = vec![
+                    ("Action".to_owned(), "GetMetricData".to_owned()),
+                    ("Version".to_owned(), "2010-08-01".to_owned()),
+                    ("StartTime".to_owned(), rfc3339::format(start)),
+                    ("EndTime".to_owned(), rfc3339::format(end)),
+                    ("ScanBy".to_owned(), "TimestampDescending".to_owned()),
+                ];
+                for (i, query) in queries.iter().enumerate() {
+                    let prefix = format!("MetricDataQueries.member.{}", i + 1);
+                    let metric = format!("{}.MetricStat.Metric", prefix);
+                    params.push((format!("{}.Id", prefix), format!("m{}", i)));
+                    params.push((format!("{}.Namespace", metric), query.namespace.clone()));
+                    params.push((format!("{}.MetricName", metric), query.metric_name.clone()));
+                    for (j, (name, value)) in query.dimensions.iter().enumerate() {
+                        let dimension = format!("{}.Dimensions.member.{}", metric, j + 1);
+                        params.push((format!("{}.Name", dimension), name.clone()));
+                        params.push((format!("{}.Value", dimension), value.clone()));
+                    }
+                    params.push((
+                        format!("{}.MetricStat.Period", prefix),
+                        query.period.to_string(),
+                    ));
+                    params.push((format!("{}.MetricStat.Stat", prefix), query.stat.clone()));
+                }
+                if let Some(next_token) = next_token.take() {
+                    params.push(("NextToken".to_owned(), next_token));
+                }
+                let response = self.client.request("GetMetricData", &params)?;
+                let result = response
+                    .child("GetMetricDataResult")
+                    .ok_or("GetMetricData failed: invalid response")?;
+                for member in result
+                    .child("MetricDataResults")
+                    .map_or(&[][..], |results| &results.children)
+                {
+                    let Some(query) = member
+                        .child_text("Id")
+                        .and_then(|id| id.strip_prefix('m')?.parse::<usize>().ok())
+                        .and_then(|i| queries.get(i))
+                    else {
+                        continue;
+                    };
+                    let times = member.child("Timestamps").map_or(&[][..], |e| &e.children);
+                    let values = member.child("Values").map_or(&[][..], |e| &e.children);
+                    let datapoints: &mut Vec<_> = results.entry(query.key.clone()).or_default();
+                    for (time, value) in times.iter().zip(values) {
+                        if let (Some(time), Ok(value)) =
+                            (rfc3339::parse(&time.text), value.text.parse())
+                        {
+                            datapoints.push((time, value));
+                        }
+                    }
+                }
+                match result.child_text("NextToken") {
+                    Some(token) if !token.is_empty() => next_token = Some(token.to_owned()),
+                    _ => break,
+                }
+            }
+        }
+        Ok(results)
+    }
+}
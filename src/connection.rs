@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::metric::Metric;
+use crate::value::Value;
+
+/// The TCP states in the order of the codes in procfs, with the names in the
+/// output of `netstat` of BSD.
+const STATES: [(&str, &str, &str); 11] = [
+    ("established", "Established", "ESTABLISHED"),
+    ("syn_sent", "Syn Sent", "SYN_SENT"),
+    ("syn_recv", "Syn Recv", "SYN_RCVD"),
+    ("fin_wait1", "Fin Wait 1", "FIN_WAIT_1"),
+    ("fin_wait2", "Fin Wait 2", "FIN_WAIT_2"),
+    ("time_wait", "Time Wait", "TIME_WAIT"),
+    ("close", "Close", "CLOSED"),
+    ("close_wait", "Close Wait", "CLOSE_WAIT"),
+    ("last_ack", "Last Ack", "LAST_ACK"),
+    ("listen", "Listen", "LISTEN"),
+    ("closing", "Closing", "CLOSING"),
+];
+
+enum Source {
+    Procfs(PathBuf),
+    Netstat(OsString),
+}
+
+/// A collector of the TCP connections read from `/proc/net/tcp` and
+/// `/proc/net/tcp6` on Linux, or by `netstat -an -p tcp` on the other systems
+/// such as macOS and FreeBSD.
+///
+/// The metrics are the number of the connections in each state, and those
+/// of the configured local ports.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::Connections;
+///
+/// let connections = Connections::new().port(80).port(443);
+/// let values = connections.fetch_values().unwrap();
+/// ```
+pub struct Connections {
+    ports: Vec<u16>,
+    source: Source,
+}
+
+impl Default for Connections {
+    fn default() -> Connections {
+        Connections::new()
+    }
+}
+
+impl Connections {
+    pub fn new() -> Connections {
+        Connections {
+            ports: Vec::new(),
+            source: if cfg!(target_os = "linux") {
+                Source::Procfs(PathBuf::from("/proc"))
+            } else {
+                Source::Netstat("netstat".into())
+            },
+        }
+    }
+
+    /// Adds the local port to count the connections of.
+    pub fn port(mut self, port: u16) -> Connections {
+        self.ports.push(port);
+        self
+    }
+
+    /// Sets the directory of procfs, which is read by default on Linux.
+    pub fn proc_dir(mut self, proc_dir: impl Into<PathBuf>) -> Connections {
+        self.source = Source::Procfs(proc_dir.into());
+        self
+    }
+
+    /// Sets the command of `netstat`, which is run by default on the systems
+    /// other than Linux.
+    pub fn netstat(mut self, command: impl Into<OsString>) -> Connections {
+        self.source = Source::Netstat(command.into());
+        self
+    }
+
+    /// Returns the graphs of the connections.
+    pub fn graphs() -> Vec<Graph> {
+        let metrics = || {
+            STATES
+                .iter()
+                .map(|&(name, label, _)| crate::metric! { name: name, label: label, stacked: true })
+                .collect::<Vec<Metric>>()
+        };
+        vec![
+            crate::graph! {
+                name: "tcp.state",
+                label: "TCP connections",
+                unit: "integer",
+                metrics: metrics(),
+            },
+            crate::graph! {
+                name: "tcp.port.#",
+                label: "TCP connections by port",
+                unit: "integer",
+                metrics: metrics(),
+            },
+        ]
+    }
+
+    /// Returns the metric values of the connections.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the connections, killing `netstat` when
+    /// the fetch is cancelled.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        let connections = match &self.source {
+            Source::Procfs(proc_dir) => read_procfs(proc_dir)?,
+            Source::Netstat(command) => read_netstat(ctx, command)?,
+        };
+        let mut states = [0usize; STATES.len()];
+        let mut ports = vec![[0usize; STATES.len()]; self.ports.len()];
+        for (port, state) in connections {
+            states[state] += 1;
+            for (p, counts) in self.ports.iter().zip(&mut ports) {
+                if *p == port {
+                    counts[state] += 1;
+                }
+            }
+        }
+        let mut values = HashMap::new();
+        for ((name, _, _), count) in STATES.iter().zip(states) {
+            values.insert(format!("tcp.state.{}", name), (count as f64).into());
+        }
+        for (port, counts) in self.ports.iter().zip(ports) {
+            for ((name, _, _), count) in STATES.iter().zip(counts) {
+                values.insert(format!("tcp.port.{}.{}", port, name), (count as f64).into());
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// Reads the local ports and the states of the connections from procfs.
+fn read_procfs(proc_dir: &Path) -> Result<Vec<(u16, usize)>, Error> {
+    let mut connections = Vec::new();
+    let mut found = false;
+    for file in ["tcp", "tcp6"] {
+        let path = proc_dir.join("net").join(file);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            // IPv6 can be disabled
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("read {} failed: {}", path.display(), e).into()),
+        };
+        found = true;
+        connections.extend(content.lines().skip(1).filter_map(parse_line));
+    }
+    if !found {
+        return Err(format!("read {} failed", proc_dir.join("net/tcp").display()).into());
+    }
+    Ok(connections)
+}
+
+/// Reads the local ports and the states of the connections by `netstat`.
+fn read_netstat(ctx: &Context, command: &OsStr) -> Result<Vec<(u16, usize)>, Error> {
+    let output = ctx
+        .output(Command::new(command).args(["-an", "-p", "tcp"]))
+        .map_err(|e| format!("netstat -an -p tcp failed: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "netstat -an -p tcp failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(parse_netstat_line)
+        .collect())
+}
+
+/// Parses the line like `0: 0100007F:0050 00000000:0000 0A ...` into the local
+/// port and the index of the state.
+fn parse_line(line: &str) -> Option<(u16, usize)> {
+    let mut fields = line.split_whitespace().skip(1);
+    let (local, _, state) = (fields.next()?, fields.next()?, fields.next()?);
+    let port = u16::from_str_radix(local.rsplit_once(':')?.1, 16).ok()?;
+    let state = usize::from_str_radix(state, 16).ok()?.checked_sub(1)?;
+    (state < STATES.len()).then_some((port, state))
+}
+
+/// Parses the line like `tcp4 0 0 127.0.0.1.80 127.0.0.1.50000 ESTABLISHED`
+/// into the local port and the index of the state.
+fn parse_netstat_line(line: &str) -> Option<(u16, usize)> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let [proto, _, _, local, _, state] = fields[..] else {
+        return None;
+    };
+    if !proto.starts_with("tcp") {
+        return None;
+    }
+    let port = local.rsplit_once('.')?.1.parse().ok()?;
+    let state = STATES.iter().position(|&(_, _, name)| name == state)?;
+    Some((port, state))
+}
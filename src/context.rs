@@ -0,0 +1,407 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, Write};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::clock::{Clock, Epoch, SystemClock};
+use crate::error::Error;
+#[cfg(feature = "json")]
+use crate::rate_limit::RateLimiter;
+use crate::state::{FileStateStore, StateStore};
+
+/// A token to cancel the fetch, which is shared by the clones.
+///
+/// ```rust
+/// use mackerel_plugin::{CancellationToken, Context};
+///
+/// let token = CancellationToken::new();
+/// let ctx = Context::new().with_cancellation(token.clone());
+/// assert!(!ctx.is_cancelled());
+/// token.cancel();
+/// assert!(ctx.is_cancelled());
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancels the fetch of the contexts sharing the token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The context of fetching the metric values, which is passed to
+/// `fetch_metrics_ctx` of the plugin.
+///
+/// The context carries the command line arguments, the environment variables,
+/// the metric values of the previous run, the deadline of the fetch, the state
+/// store and the clock of the run, and the logger. The deadline is configured by the environment variable
+/// `MACKEREL_PLUGIN_TIMEOUT` in seconds, which should be shorter than the
+/// timeout of the plugin in mackerel-agent.
+///
+/// The fetch is cancelled on the deadline or by the `CancellationToken`. The
+/// collectors fetching from the slow sources, such as the commands, the HTTP
+/// servers, and the databases, respect the context by `fetch_values_ctx`, so
+/// that a slow source does not make the plugin lose the values of the others.
+///
+/// ```rust
+/// use mackerel_plugin::{Context, Graph, Plugin, Value};
+/// use std::collections::HashMap;
+///
+/// struct DicePlugin {}
+///
+/// impl Plugin for DicePlugin {
+///     fn fetch_metrics_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, String> {
+///         let sides = ctx.option("sides").unwrap_or("6").parse::<f64>();
+///         let sides = sides.map_err(|e| format!("invalid sides: {}", e))?;
+///         if ctx.previous_value("dice.sides").is_some_and(|prev| prev != sides) {
+///             ctx.log("the sides of the dice changed");
+///         }
+///         Ok(HashMap::from([("dice.sides".to_owned(), sides.into())]))
+///     }
+///
+///     fn graph_definition(&self) -> Vec<Graph> {
+///         Vec::new()
+///     }
+/// }
+///
+/// let ctx = Context::new().with_args(["-sides", "20"]);
+/// let values = DicePlugin {}.fetch_metrics_ctx(&ctx).unwrap();
+/// assert_eq!(values["dice.sides"], Value::Float(20.0));
+/// ```
+pub struct Context<'a> {
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    previous_timestamp: Option<Epoch>,
+    previous_values: HashMap<String, f64>,
+    deadline: Option<Instant>,
+    cancellation: CancellationToken,
+    #[cfg(feature = "json")]
+    rate_limiter: Option<Arc<RateLimiter<'static>>>,
+    state: &'a (dyn StateStore + Sync),
+    clock: &'a (dyn Clock + Sync),
+    logger: Box<dyn Fn(&str) + Send + Sync>,
+}
+
+impl Default for Context<'_> {
+    fn default() -> Self {
+        Context::new()
+    }
+}
+
+impl Context<'static> {
+    /// Creates an empty context, which keeps the state in the working
+    /// directory and logs to the standard error.
+    pub fn new() -> Context<'static> {
+        Context {
+            args: Vec::new(),
+            env: HashMap::new(),
+            previous_timestamp: None,
+            previous_values: HashMap::new(),
+            deadline: None,
+            cancellation: CancellationToken::new(),
+            #[cfg(feature = "json")]
+            rate_limiter: None,
+            state: &FileStateStore,
+            clock: &SystemClock,
+            logger: Box::new(|message| {
+                let _ = writeln!(std::io::stderr(), "{}", message);
+            }),
+        }
+    }
+
+    /// Creates a context of the command line arguments and the environment
+    /// variables of the process.
+    pub fn from_env() -> Context<'static> {
+        let ctx = Context::new().with_args(std::env::args().skip(1));
+        let ctx = std::env::vars().fold(ctx, |ctx, (name, value)| ctx.with_env(name, value));
+        match std::env::var("MACKEREL_PLUGIN_TIMEOUT")
+            .ok()
+            .and_then(|timeout| timeout.parse::<f64>().ok())
+            .and_then(|timeout| Duration::try_from_secs_f64(timeout).ok())
+        {
+            Some(timeout) => ctx.with_deadline(Instant::now() + timeout),
+            None => ctx,
+        }
+    }
+}
+
+impl<'a> Context<'a> {
+    /// Sets the command line arguments, excluding the executable.
+    pub fn with_args<I: IntoIterator<Item = S>, S: Into<String>>(mut self, args: I) -> Context<'a> {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the environment variable.
+    pub fn with_env(mut self, name: impl Into<String>, value: impl Into<String>) -> Context<'a> {
+        self.env.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets the metric values of the previous run and their timestamp.
+    pub fn with_previous_values(
+        mut self,
+        timestamp: impl Into<Epoch>,
+        values: HashMap<String, f64>,
+    ) -> Context<'a> {
+        self.previous_timestamp = Some(timestamp.into());
+        self.previous_values = values;
+        self
+    }
+
+    /// Sets the deadline of the fetch.
+    pub fn with_deadline(mut self, deadline: Instant) -> Context<'a> {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the token to cancel the fetch.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Context<'a> {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Sets the rate limiter of the requests, which is respected by the
+    /// collectors requesting the HTTP servers and the databases.
+    #[cfg(feature = "json")]
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter<'static>>) -> Context<'a> {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Sets the state store of the run, which keeps the cache and the
+    /// discovery of the plugin.
+    pub fn with_state_store(mut self, state: &'a (dyn StateStore + Sync)) -> Context<'a> {
+        self.state = state;
+        self
+    }
+
+    /// Sets the clock of the run.
+    pub fn with_clock(mut self, clock: &'a (dyn Clock + Sync)) -> Context<'a> {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets the logger, which receives the messages of `log`.
+    pub fn with_logger(mut self, logger: impl Fn(&str) + Send + Sync + 'static) -> Context<'a> {
+        self.logger = Box::new(logger);
+        self
+    }
+
+    /// Returns the command line arguments, excluding the executable.
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Returns the value of the command line option, which is specified as
+    /// `-name value`, `--name value`, or `--name=value`. The last one is used
+    /// when the option is specified more than once.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        let mut value = None;
+        let mut args = self.args.iter();
+        while let Some(arg) = args.next() {
+            let Some(option) = arg
+                .strip_prefix("--")
+                .or_else(|| arg.strip_prefix('-'))
+                .filter(|option| option.starts_with(name))
+            else {
+                continue;
+            };
+            match &option[name.len()..] {
+                "" => value = args.next().map(String::as_str).or(value),
+                rest => {
+                    if let Some(rest) = rest.strip_prefix('=') {
+                        value = Some(rest);
+                    }
+                }
+            }
+        }
+        value
+    }
+
+    /// Returns whether the command line flag is specified as `-name` or
+    /// `--name`.
+    pub fn flag(&self, name: &str) -> bool {
+        self.args.iter().any(|arg| {
+            arg.strip_prefix("--")
+                .or_else(|| arg.strip_prefix('-'))
+                .is_some_and(|flag| flag == name)
+        })
+    }
+
+    /// Returns the value of the environment variable.
+    pub fn env(&self, name: &str) -> Option<&str> {
+        self.env.get(name).map(String::as_str)
+    }
+
+    /// Returns the timestamp of the previous run, which is `None` on the first
+    /// run or when the state is not saved.
+    pub fn previous_timestamp(&self) -> Option<i64> {
+        self.previous_timestamp.map(Epoch::as_secs)
+    }
+
+    /// Returns the metric value of the previous run, before the differences
+    /// are calculated.
+    pub fn previous_value(&self, name: &str) -> Option<f64> {
+        self.previous_values.get(name).copied()
+    }
+
+    /// Returns the deadline of the fetch.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns the remaining time until the deadline, which is zero after the
+    /// deadline.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Returns the token to cancel the fetch, which can be passed to the other
+    /// threads.
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Returns the state store of the run, which is `FileStateStore` unless
+    /// the plugin runs by `run_with`.
+    pub fn state_store(&self) -> &'a (dyn StateStore + Sync) {
+        self.state
+    }
+
+    /// Returns the clock of the run.
+    pub fn clock(&self) -> &'a (dyn Clock + Sync) {
+        self.clock
+    }
+
+    /// Returns whether the fetch is cancelled by the token or the deadline.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled() || self.remaining() == Some(Duration::ZERO)
+    }
+
+    /// Returns an error if the fetch is cancelled, which is useful for
+    /// checking between the steps of the fetch.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.cancellation.is_cancelled() {
+            Err("fetch cancelled".into())
+        } else if self.remaining() == Some(Duration::ZERO) {
+            Err("fetch deadline exceeded".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Takes a token of the rate limiter before a request, waiting for it
+    /// until the deadline. Returns an error if the fetch is cancelled or no
+    /// token is available before the deadline.
+    pub fn acquire(&self) -> Result<(), Error> {
+        self.check()?;
+        #[cfg(feature = "json")]
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire(self)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the timeout bounded by the remaining time until the deadline.
+    pub fn timeout(&self, timeout: Duration) -> Duration {
+        self.remaining()
+            .map_or(timeout, |remaining| remaining.min(timeout))
+    }
+
+    /// Sleeps for a random duration up to the maximum before the fetch, so that
+    /// the plugins on many hosts do not request a shared source at the same
+    /// second. The duration is limited to half of the remaining time until the
+    /// deadline, and the sleep is deducted from the time left for the fetch.
+    /// Returns the slept duration.
+    pub fn sleep_jitter(&self, max: Duration) -> Duration {
+        let max = self
+            .remaining()
+            .map_or(max, |remaining| max.min(remaining / 2));
+        let random = RandomState::new().build_hasher().finish();
+        let jitter = max.mul_f64(random as f64 / u64::MAX as f64);
+        std::thread::sleep(jitter);
+        jitter
+    }
+
+    /// Runs the blocking function, which cannot be cancelled by itself, in a
+    /// thread and gives up waiting for it when the fetch is cancelled.
+    pub fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, Error> {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(f());
+        });
+        loop {
+            match rx.recv_timeout(Duration::from_millis(10)) {
+                Ok(value) => return Ok(value),
+                Err(RecvTimeoutError::Timeout) => self.check()?,
+                Err(RecvTimeoutError::Disconnected) => return Err("fetch panicked".into()),
+            }
+        }
+    }
+
+    /// Runs the command and collects the output like `Command::output`, but
+    /// kills the command when the fetch is cancelled.
+    pub fn output(&self, command: &mut Command) -> std::io::Result<Output> {
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // read the pipes in the threads not to block the command
+        let read = |pipe: Option<Box<dyn Read + Send>>| {
+            std::thread::spawn(move || {
+                let mut buf = Vec::new();
+                if let Some(mut pipe) = pipe {
+                    let _ = pipe.read_to_end(&mut buf);
+                }
+                buf
+            })
+        };
+        let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+        let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if let Err(err) = self.check() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    err.to_string(),
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        Ok(Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
+
+    /// Logs the message, which is written to the standard error by default
+    /// and logged by mackerel-agent.
+    pub fn log(&self, message: &str) {
+        (self.logger)(message);
+    }
+}
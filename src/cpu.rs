@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// The states of the CPU time, in the order of the fields of `/proc/stat`.
+const STATES: [(&str, &str); 8] = [
+    ("user", "User"),
+    ("nice", "Nice"),
+    ("system", "System"),
+    ("idle", "Idle"),
+    ("iowait", "IO wait"),
+    ("irq", "IRQ"),
+    ("softirq", "Soft IRQ"),
+    ("steal", "Steal"),
+];
+
+/// The clock ticks per second of the CPU times in procfs and of the host
+/// statistics of macOS.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const CLOCK_TICKS: f64 = 100.0;
+
+/// The flavor of `host_statistics` of the CPU ticks, which are of the user,
+/// the system, the idle, and the nice states.
+#[cfg(target_os = "macos")]
+const HOST_CPU_LOAD_INFO: std::ffi::c_int = 3;
+
+/// A collector of the CPU usage of the host, which reads the CPU times from
+/// `/proc/stat` on Linux, by `host_statistics` on macOS, and by the
+/// `kern.cp_time` sysctl on FreeBSD.
+///
+/// The metrics are the percentages of the CPU time in the states, such as
+/// `cpu.user` and `cpu.idle`, summed over the CPUs, which are differentiated
+/// between the runs. The states not available on the system are omitted, such
+/// as `iowait` on macOS and FreeBSD, and `irq` on macOS.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::CpuStats;
+///
+/// let values = CpuStats::new().fetch_values().unwrap();
+/// ```
+pub struct CpuStats {
+    #[cfg(target_os = "linux")]
+    proc_dir: PathBuf,
+}
+
+impl Default for CpuStats {
+    fn default() -> CpuStats {
+        CpuStats::new()
+    }
+}
+
+impl CpuStats {
+    pub fn new() -> CpuStats {
+        CpuStats {
+            #[cfg(target_os = "linux")]
+            proc_dir: PathBuf::from("/proc"),
+        }
+    }
+
+    /// Sets the directory of procfs.
+    #[cfg(target_os = "linux")]
+    pub fn proc_dir(mut self, proc_dir: impl Into<PathBuf>) -> CpuStats {
+        self.proc_dir = proc_dir.into();
+        self
+    }
+
+    /// Returns the graph of the CPU usage.
+    pub fn graphs() -> Vec<Graph> {
+        vec![crate::graph! {
+            name: "cpu",
+            label: "CPU",
+            unit: "percentage",
+            metrics: STATES.iter().map(|&(name, label)| {
+                crate::metric! { name: name, label: label, stacked: true, diff: true }
+            }),
+        }]
+    }
+
+    /// Returns the metric values of the CPU usage.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        Ok(self
+            .read_seconds()?
+            .into_iter()
+            // the seconds differentiated per minute make the percentage
+            .map(|(state, seconds)| (format!("cpu.{}", state), (seconds * 100.0 / 60.0).into()))
+            .collect())
+    }
+
+    /// Reads the CPU times in seconds from the `cpu` line of `/proc/stat`,
+    /// where `user` and `nice` include `guest` and `guest_nice`.
+    #[cfg(target_os = "linux")]
+    fn read_seconds(&self) -> Result<Vec<(&'static str, f64)>, Error> {
+        let path = self.proc_dir.join("stat");
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("read {} failed: {}", path.display(), e))?;
+        let fields = content
+            .lines()
+            .find_map(|line| line.strip_prefix("cpu "))
+            .ok_or_else(|| format!("read {} failed: no cpu line", path.display()))?;
+        Ok(STATES
+            .iter()
+            .zip(fields.split_whitespace())
+            .filter_map(|(&(state, _), ticks)| {
+                Some((state, ticks.parse::<u64>().ok()? as f64 / CLOCK_TICKS))
+            })
+            .collect())
+    }
+
+    /// Reads the CPU times in seconds by `host_statistics`, which are the
+    /// ticks of the CPUs in 32 bits.
+    #[cfg(target_os = "macos")]
+    fn read_seconds(&self) -> Result<Vec<(&'static str, f64)>, Error> {
+        let [user, system, idle, nice] =
+            crate::host::host_statistics::<[u32; 4]>(HOST_CPU_LOAD_INFO)?;
+        Ok([
+            ("user", user),
+            ("nice", nice),
+            ("system", system),
+            ("idle", idle),
+        ]
+        .into_iter()
+        .map(|(state, ticks)| (state, ticks as f64 / CLOCK_TICKS))
+        .collect())
+    }
+
+    /// Reads the CPU times in seconds by the `kern.cp_time` sysctl, which are
+    /// the ticks of the statistics clock of `kern.clockrate`.
+    #[cfg(target_os = "freebsd")]
+    fn read_seconds(&self) -> Result<Vec<(&'static str, f64)>, Error> {
+        use std::ffi::{c_int, c_long};
+
+        let [user, nice, system, interrupt, idle] =
+            crate::host::sysctl_value::<[c_long; 5]>("kern.cp_time")?;
+        // hz, tick, spare, stathz, and profhz of struct clockinfo
+        let [hz, _, _, stathz, _] = crate::host::sysctl_value::<[c_int; 5]>("kern.clockrate")?;
+        let ticks = if stathz > 0 { stathz } else { hz } as f64;
+        Ok([
+            ("user", user),
+            ("nice", nice),
+            ("system", system),
+            ("idle", idle),
+            ("irq", interrupt),
+        ]
+        .into_iter()
+        .map(|(state, count)| (state, count as f64 / ticks))
+        .collect())
+    }
+}
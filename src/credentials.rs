@@ -0,0 +1,178 @@
+//! The secrets of the plugins, such as the passwords of the databases and the
+//! API keys, resolved from the environment variables, the files, or the
+//! keyring of the OS.
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+/// A secret such as a password or an API key, whose value is hidden in the
+/// debug output so that it is not leaked to the logs.
+#[derive(PartialEq, Eq, Clone)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Secret {
+        Secret(value.into())
+    }
+
+    /// Returns the value of the secret.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Resolves the secret from the first source containing it.
+    ///
+    /// ```rust
+    /// use mackerel_plugin::{Secret, SecretSource};
+    ///
+    /// std::env::set_var("DICE_API_KEY", "xxxxxxxx");
+    /// let secret = Secret::resolve(&[
+    ///     "file:/etc/mackerel-agent/dice-api-key".parse().unwrap(),
+    ///     SecretSource::Env("DICE_API_KEY".to_owned()),
+    /// ]);
+    /// assert_eq!(secret.unwrap().expose(), "xxxxxxxx");
+    /// ```
+    pub fn resolve(sources: &[SecretSource]) -> Result<Secret, Error> {
+        for source in sources {
+            if let Some(secret) = source.resolve()? {
+                return Ok(secret);
+            }
+        }
+        Err(format!(
+            "secret not found in {}",
+            sources
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into())
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Secret(****)")
+    }
+}
+
+/// The source of a secret, which is parsed from `env:NAME`, `file:PATH`, or
+/// `keyring:SERVICE/ACCOUNT`, so that the configuration of mackerel-agent
+/// refers to the secret rather than containing it.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum SecretSource {
+    /// The environment variable of the name.
+    Env(String),
+    /// The file containing the secret, which must not be accessible by the
+    /// group and the others. The trailing newline is removed.
+    File(PathBuf),
+    /// The generic password in the keyring of the OS; the Keychain by
+    /// `security` on macOS, or the Secret Service by `secret-tool` on Linux.
+    Keyring { service: String, account: String },
+}
+
+impl SecretSource {
+    /// Returns the secret of the source, or `None` if the source does not
+    /// contain the secret.
+    pub fn resolve(&self) -> Result<Option<Secret>, Error> {
+        match self {
+            SecretSource::Env(name) => Ok(std::env::var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .map(Secret)),
+            SecretSource::File(path) => resolve_file(path),
+            SecretSource::Keyring { service, account } => resolve_keyring(service, account),
+        }
+    }
+}
+
+impl FromStr for SecretSource {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SecretSource, Error> {
+        match s.split_once(':') {
+            Some(("env", name)) if !name.is_empty() => Ok(SecretSource::Env(name.to_owned())),
+            Some(("file", path)) if !path.is_empty() => Ok(SecretSource::File(path.into())),
+            Some(("keyring", name)) => match name.split_once('/') {
+                Some((service, account)) if !service.is_empty() && !account.is_empty() => {
+                    Ok(SecretSource::Keyring {
+                        service: service.to_owned(),
+                        account: account.to_owned(),
+                    })
+                }
+                _ => Err(format!("invalid secret source: {}", s).into()),
+            },
+            _ => Err(format!("invalid secret source: {}", s).into()),
+        }
+    }
+}
+
+impl std::fmt::Display for SecretSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SecretSource::Env(name) => write!(f, "env:{}", name),
+            SecretSource::File(path) => write!(f, "file:{}", path.display()),
+            SecretSource::Keyring { service, account } => {
+                write!(f, "keyring:{}/{}", service, account)
+            }
+        }
+    }
+}
+
+fn resolve_file(path: &std::path::Path) -> Result<Option<Secret>, Error> {
+    let error = |err: &dyn std::fmt::Display| format!("read {} failed: {}", path.display(), err);
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(error(&err).into()),
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = file
+            .metadata()
+            .map_err(|err| error(&err))?
+            .permissions()
+            .mode();
+        if mode & 0o077 != 0 {
+            return Err(format!(
+                "permissions {:04o} of {} are too open",
+                mode & 0o7777,
+                path.display()
+            )
+            .into());
+        }
+    }
+    let contents = std::io::read_to_string(file).map_err(|err| error(&err))?;
+    let contents = contents.strip_suffix('\n').unwrap_or(&contents);
+    let contents = contents.strip_suffix('\r').unwrap_or(contents);
+    Ok(Some(Secret(contents.to_owned())).filter(|secret| !secret.0.is_empty()))
+}
+
+fn resolve_keyring(service: &str, account: &str) -> Result<Option<Secret>, Error> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("security");
+        command.args(["find-generic-password", "-w", "-s", service, "-a", account]);
+        command
+    } else if cfg!(unix) {
+        let mut command = Command::new("secret-tool");
+        command.args(["lookup", "service", service, "account", account]);
+        command
+    } else {
+        return Err("keyring is not supported on this platform".into());
+    };
+    let output = command
+        .output()
+        .map_err(|err| format!("read keyring failed: {}", err))?;
+    // the commands exit with the non-zero status when the item is not found
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let value = String::from_utf8(output.stdout)
+        .map_err(|_| "read keyring failed: invalid secret encoding")?;
+    let value = value.strip_suffix('\n').unwrap_or(&value);
+    Ok(Some(Secret(value.to_owned())).filter(|secret| !secret.0.is_empty()))
+}
@@ -0,0 +1,232 @@
+use crate::graph::Graph;
+use crate::metric::Metric;
+use crate::unit::Unit;
+
+/// A set of graph definitions, which is compared for the semantic equality by
+/// [`Definitions::canonical`] regardless of the construction order.
+///
+/// ```rust
+/// use mackerel_plugin::{graph, Definitions};
+///
+/// let old = Definitions::new(vec![graph! {
+///     name: "dice",
+///     label: "My Dice",
+///     unit: "integer",
+///     metrics: [{ name: "d6", label: "Die 6" }, { name: "d20", label: "Die 20" }],
+/// }]);
+/// let new = Definitions::new(vec![graph! {
+///     name: "dice",
+///     label: " My  Dice ",
+///     unit: "integer",
+///     metrics: [{ name: "d20", label: "Die 20" }, { name: "d6", label: "Die 6" }],
+/// }]);
+/// assert_ne!(old, new);
+/// assert_eq!(old.canonical(), new.canonical());
+/// ```
+#[derive(Default, PartialEq, Clone, Debug)]
+pub struct Definitions {
+    pub graphs: Vec<Graph>,
+}
+
+impl Definitions {
+    pub fn new(graphs: Vec<Graph>) -> Definitions {
+        Definitions { graphs }
+    }
+
+    /// Returns the canonical form of the definitions, where the graphs and the
+    /// metrics are sorted by the names, and the whitespaces in the labels are
+    /// trimmed and collapsed.
+    pub fn canonical(&self) -> Definitions {
+        let mut graphs = self.graphs.clone();
+        graphs.sort_by(|g1, g2| g1.name.cmp(&g2.name));
+        for graph in &mut graphs {
+            graph.label = normalize_label(&graph.label);
+            graph.metrics.sort_by(|m1, m2| m1.name.cmp(&m2.name));
+            for metric in &mut graph.metrics {
+                metric.label = normalize_label(&metric.label);
+            }
+        }
+        Definitions { graphs }
+    }
+
+    /// Returns the definitions without the fields excluded from the meta
+    /// output, such as `diff` and `threshold` of the metrics, which is
+    /// compared with the definitions parsed from the meta output.
+    pub fn published(&self) -> Definitions {
+        let mut graphs = self.graphs.clone();
+        for metric in graphs.iter_mut().flat_map(|graph| &mut graph.metrics) {
+            metric.diff = false;
+            metric.unit = None;
+            metric.threshold = None;
+            metric.precision = None;
+        }
+        Definitions { graphs }
+    }
+
+    /// Compares the definitions with the new definitions.
+    pub fn diff(&self, new: &Definitions) -> DefinitionsDiff {
+        definitions_diff(&self.graphs, &new.graphs)
+    }
+}
+
+impl From<Vec<Graph>> for Definitions {
+    fn from(graphs: Vec<Graph>) -> Definitions {
+        Definitions::new(graphs)
+    }
+}
+
+fn normalize_label(label: &str) -> String {
+    label.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A difference between two sets of graph definitions.
+#[derive(Default, PartialEq, Clone, Debug)]
+pub struct DefinitionsDiff {
+    pub added_graphs: Vec<String>,
+    pub removed_graphs: Vec<String>,
+    pub changed_graphs: Vec<GraphDiff>,
+}
+
+impl DefinitionsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_graphs.is_empty()
+            && self.removed_graphs.is_empty()
+            && self.changed_graphs.is_empty()
+    }
+}
+
+/// A difference between two definitions of a graph.
+#[derive(Default, PartialEq, Clone, Debug)]
+pub struct GraphDiff {
+    pub name: String,
+    pub label: Option<(String, String)>,
+    pub unit: Option<(Unit, Unit)>,
+    pub added_metrics: Vec<String>,
+    pub removed_metrics: Vec<String>,
+    pub changed_metrics: Vec<MetricDiff>,
+}
+
+impl GraphDiff {
+    pub fn is_empty(&self) -> bool {
+        self.label.is_none()
+            && self.unit.is_none()
+            && self.added_metrics.is_empty()
+            && self.removed_metrics.is_empty()
+            && self.changed_metrics.is_empty()
+    }
+}
+
+/// A difference between two definitions of a metric.
+#[derive(Default, PartialEq, Clone, Debug)]
+pub struct MetricDiff {
+    pub name: String,
+    pub label: Option<(String, String)>,
+    pub stacked: Option<(bool, bool)>,
+    pub diff: Option<(bool, bool)>,
+    pub unit: Option<(Option<Unit>, Option<Unit>)>,
+}
+
+impl MetricDiff {
+    pub fn is_empty(&self) -> bool {
+        self.label.is_none() && self.stacked.is_none() && self.diff.is_none() && self.unit.is_none()
+    }
+}
+
+fn changed<T: PartialEq + Clone>(old: &T, new: &T) -> Option<(T, T)> {
+    (old != new).then(|| (old.clone(), new.clone()))
+}
+
+impl Graph {
+    /// Compares two definitions of a graph.
+    pub fn diff(old: &Graph, new: &Graph) -> GraphDiff {
+        GraphDiff {
+            name: new.name.clone(),
+            label: changed(&old.label, &new.label),
+            unit: changed(&old.unit, &new.unit),
+            added_metrics: new
+                .metrics
+                .iter()
+                .filter(|metric| !old.metrics.iter().any(|m| m.name == metric.name))
+                .map(|metric| metric.name.clone())
+                .collect(),
+            removed_metrics: old
+                .metrics
+                .iter()
+                .filter(|metric| !new.metrics.iter().any(|m| m.name == metric.name))
+                .map(|metric| metric.name.clone())
+                .collect(),
+            changed_metrics: new
+                .metrics
+                .iter()
+                .filter_map(|metric| {
+                    old.metrics
+                        .iter()
+                        .find(|m| m.name == metric.name)
+                        .map(|m| Metric::diff(m, metric))
+                        .filter(|diff| !diff.is_empty())
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Metric {
+    /// Compares two definitions of a metric.
+    pub fn diff(old: &Metric, new: &Metric) -> MetricDiff {
+        MetricDiff {
+            name: new.name.clone(),
+            label: changed(&old.label, &new.label),
+            stacked: changed(&old.stacked, &new.stacked),
+            diff: changed(&old.diff, &new.diff),
+            unit: changed(&old.unit, &new.unit),
+        }
+    }
+}
+
+/// Compares two sets of graph definitions.
+///
+/// This is useful for detecting accidental renames of metrics before releasing
+/// a new version of a plugin.
+///
+/// ```rust
+/// use mackerel_plugin::{definitions_diff, graph};
+///
+/// let old = vec![graph! {
+///     name: "dice",
+///     label: "My Dice",
+///     unit: "integer",
+///     metrics: [{ name: "d6", label: "Die 6" }],
+/// }];
+/// let new = vec![graph! {
+///     name: "dice",
+///     label: "My Dice",
+///     unit: "integer",
+///     metrics: [{ name: "d8", label: "Die 8" }],
+/// }];
+/// let diff = definitions_diff(&old, &new);
+/// assert_eq!(diff.changed_graphs[0].added_metrics, vec!["d8"]);
+/// assert_eq!(diff.changed_graphs[0].removed_metrics, vec!["d6"]);
+/// ```
+pub fn definitions_diff(old: &[Graph], new: &[Graph]) -> DefinitionsDiff {
+    DefinitionsDiff {
+        added_graphs: new
+            .iter()
+            .filter(|graph| !old.iter().any(|g| g.name == graph.name))
+            .map(|graph| graph.name.clone())
+            .collect(),
+        removed_graphs: old
+            .iter()
+            .filter(|graph| !new.iter().any(|g| g.name == graph.name))
+            .map(|graph| graph.name.clone())
+            .collect(),
+        changed_graphs: new
+            .iter()
+            .filter_map(|graph| {
+                old.iter()
+                    .find(|g| g.name == graph.name)
+                    .map(|g| Graph::diff(g, graph))
+                    .filter(|diff| !diff.is_empty())
+            })
+            .collect(),
+    }
+}
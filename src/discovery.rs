@@ -0,0 +1,60 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::label::Transform;
+
+/// The targets discovered by the plugin, such as the databases or the queues,
+/// keyed by the names used in the metric names with the display names.
+///
+/// The discovery parameterizes both the graph definitions and the fetch; the
+/// names are the targets to fetch, and the display names are used for the
+/// labels of the graphs, or the labels of the wildcard series by `transform`.
+///
+/// ```rust
+/// use mackerel_plugin::{expand_label, Discovery};
+///
+/// let discovery = Discovery::new()
+///     .target("db_1", "Users")
+///     .target("db_2", "Orders");
+/// assert_eq!(discovery.names().collect::<Vec<_>>(), ["db_1", "db_2"]);
+/// assert_eq!(
+///     expand_label("Database %1", &["db_2"], &[discovery.transform()]),
+///     "Database Orders",
+/// );
+/// ```
+#[derive(Default, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct Discovery {
+    targets: BTreeMap<String, String>,
+}
+
+impl Discovery {
+    pub fn new() -> Discovery {
+        Discovery::default()
+    }
+
+    /// Adds the target of the name with the display name.
+    pub fn target(mut self, name: impl Into<String>, display_name: impl Into<String>) -> Discovery {
+        self.targets.insert(name.into(), display_name.into());
+        self
+    }
+
+    /// Returns the names of the targets in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.targets.keys().map(String::as_str)
+    }
+
+    /// Returns the display name of the target.
+    pub fn display_name(&self, name: &str) -> Option<&str> {
+        self.targets.get(name).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Returns the transformation mapping the names to the display names,
+    /// which is useful for `Plugin::label_transforms`.
+    pub fn transform(&self) -> Transform {
+        Transform::Map(self.targets.clone().into_iter().collect())
+    }
+}
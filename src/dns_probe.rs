@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use strum::{Display, EnumString};
+
+use crate::check::{CheckResult, CheckStatus};
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// The latency or the error of a query to the resolver.
+type Lookup = (SocketAddr, Result<Duration, String>);
+
+/// The type of the DNS record to query.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Display, EnumString)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum RecordType {
+    A,
+    NS,
+    CNAME,
+    SOA,
+    PTR,
+    MX,
+    TXT,
+    AAAA,
+    SRV,
+}
+
+impl RecordType {
+    fn code(&self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::NS => 2,
+            RecordType::CNAME => 5,
+            RecordType::SOA => 6,
+            RecordType::PTR => 12,
+            RecordType::MX => 15,
+            RecordType::TXT => 16,
+            RecordType::AAAA => 28,
+            RecordType::SRV => 33,
+        }
+    }
+}
+
+/// A prober of the DNS resolution, which queries the records to the resolvers
+/// over UDP.
+///
+/// The metrics are the lookup latency and the success (1 when the response has
+/// no error and some answers, otherwise 0) of each record and resolver. The
+/// resolvers default to the name servers in `/etc/resolv.conf`.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{DnsProbe, RecordType};
+///
+/// let probe = DnsProbe::new()
+///     .record("www", "www.example.com", RecordType::A)
+///     .resolver("8.8.8.8:53".parse().unwrap())
+///     .resolver("1.1.1.1:53".parse().unwrap());
+/// let values = probe.fetch_values().unwrap();
+/// ```
+pub struct DnsProbe {
+    records: Vec<(String, String, RecordType)>,
+    resolvers: Vec<SocketAddr>,
+    timeout: Duration,
+}
+
+impl Default for DnsProbe {
+    fn default() -> DnsProbe {
+        DnsProbe::new()
+    }
+}
+
+impl DnsProbe {
+    pub fn new() -> DnsProbe {
+        DnsProbe {
+            records: Vec::new(),
+            resolvers: Vec::new(),
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    /// Adds the record to query.
+    pub fn record(
+        mut self,
+        name: &str,
+        domain: impl Into<String>,
+        record_type: RecordType,
+    ) -> DnsProbe {
+        self.records
+            .push((metric_key(name), domain.into(), record_type));
+        self
+    }
+
+    /// Adds the resolver to query the records to.
+    pub fn resolver(mut self, resolver: SocketAddr) -> DnsProbe {
+        self.resolvers.push(resolver);
+        self
+    }
+
+    /// Sets the timeout of each query.
+    pub fn timeout(mut self, timeout: Duration) -> DnsProbe {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the graphs of the DNS probes.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "dns.latency.#",
+                label: "DNS lookup latency",
+                unit: "seconds",
+                metrics: [{ name: "*", label: "%2" }],
+            },
+            crate::graph! {
+                name: "dns.success.#",
+                label: "DNS lookup success",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%2" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the probe. The latency is omitted when the
+    /// query timed out or failed.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        let mut values = HashMap::new();
+        for ((name, _, _), results) in self.records.iter().zip(self.probe()?) {
+            for (resolver, result) in results {
+                let key = format!("{}.{}", name, metric_key(&resolver.ip().to_string()));
+                if let Ok(latency) = result {
+                    values.insert(format!("dns.latency.{}", key), latency.into());
+                }
+                values.insert(format!("dns.success.{}", key), result.is_ok().into());
+            }
+        }
+        Ok(values)
+    }
+
+    /// Checks the records are resolved by the resolvers; the status is critical
+    /// when a record is not resolved by any resolvers, and warning when some
+    /// resolvers fail.
+    pub fn check(&self) -> CheckResult {
+        let results = match self.probe() {
+            Ok(results) => results,
+            Err(err) => return CheckResult::new(CheckStatus::Unknown, err.to_string()),
+        };
+        let mut status = CheckStatus::Ok;
+        let mut failures = Vec::new();
+        for ((_, domain, record_type), results) in self.records.iter().zip(results) {
+            let failed = results
+                .iter()
+                .filter_map(|(resolver, result)| {
+                    let err = result.as_ref().err()?;
+                    Some(format!(
+                        "{} {} via {}: {}",
+                        domain, record_type, resolver, err
+                    ))
+                })
+                .collect::<Vec<_>>();
+            if failed.len() == results.len() {
+                status = status.max(CheckStatus::Critical);
+            } else if !failed.is_empty() {
+                status = status.max(CheckStatus::Warning);
+            }
+            failures.extend(failed);
+        }
+        let message = if failures.is_empty() {
+            format!("{} records resolved", self.records.len())
+        } else {
+            failures.join(", ")
+        };
+        CheckResult::new(status, message)
+    }
+
+    fn resolvers(&self) -> Vec<SocketAddr> {
+        if !self.resolvers.is_empty() {
+            return self.resolvers.clone();
+        }
+        std::fs::read_to_string("/etc/resolv.conf")
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                (fields.next()? == "nameserver").then_some(())?;
+                let ip = fields.next()?.split('%').next()?.parse().ok()?;
+                Some(SocketAddr::new(ip, 53))
+            })
+            .collect()
+    }
+
+    /// Queries the records to the resolvers, and returns the latencies or the
+    /// errors of the resolvers for each record.
+    fn probe(&self) -> Result<Vec<Vec<Lookup>>, Error> {
+        let resolvers = self.resolvers();
+        if resolvers.is_empty() {
+            return Err("no resolvers configured".into());
+        }
+        Ok(self
+            .records
+            .iter()
+            .map(|(_, domain, record_type)| {
+                resolvers
+                    .iter()
+                    .map(|&resolver| (resolver, self.query(resolver, domain, *record_type)))
+                    .collect()
+            })
+            .collect())
+    }
+
+    fn query(
+        &self,
+        resolver: SocketAddr,
+        domain: &str,
+        record_type: RecordType,
+    ) -> Result<Duration, String> {
+        let id = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.subsec_nanos() as u16);
+        let request = query_message(id, domain, record_type)?;
+        let local: SocketAddr = if resolver.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local).map_err(|e| e.to_string())?;
+        socket.connect(resolver).map_err(|e| e.to_string())?;
+        socket
+            .set_read_timeout(Some(self.timeout))
+            .map_err(|e| e.to_string())?;
+        let started = Instant::now();
+        socket.send(&request).map_err(|e| e.to_string())?;
+        let mut response = [0; 512];
+        loop {
+            let len = socket.recv(&mut response).map_err(|e| match e.kind() {
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                    "timed out".to_owned()
+                }
+                _ => e.to_string(),
+            })?;
+            let response = &response[..len];
+            // ignore the responses of other queries
+            if len < 12 || response[..2] != id.to_be_bytes() || response[2] & 0x80 == 0 {
+                if started.elapsed() >= self.timeout {
+                    return Err("timed out".to_owned());
+                }
+                continue;
+            }
+            let latency = started.elapsed();
+            let rcode = response[3] & 0x0f;
+            let answers = u16::from_be_bytes([response[6], response[7]]);
+            return match rcode {
+                0 if answers > 0 => Ok(latency),
+                0 => Err("no answers".to_owned()),
+                2 => Err("SERVFAIL".to_owned()),
+                3 => Err("NXDOMAIN".to_owned()),
+                5 => Err("REFUSED".to_owned()),
+                _ => Err(format!("RCODE {}", rcode)),
+            };
+        }
+    }
+}
+
+/// Builds the query message with the recursion desired.
+fn query_message(id: u16, domain: &str, record_type: RecordType) -> Result<Vec<u8>, String> {
+    let mut message = Vec::with_capacity(512);
+    message.extend(id.to_be_bytes());
+    message.extend([0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in domain.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("invalid domain: {}", domain));
+        }
+        message.push(label.len() as u8);
+        message.extend(label.as_bytes());
+    }
+    message.push(0);
+    message.extend(record_type.code().to_be_bytes());
+    message.extend(1u16.to_be_bytes());
+    Ok(message)
+}
+
+fn metric_key(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
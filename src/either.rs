@@ -0,0 +1,49 @@
+/// An iterator of either of the two iterator types, which allows returning
+/// different iterators from the branches as `impl Iterator`.
+pub(crate) enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+impl<L, R> Iterator for Either<L, R>
+where
+    L: Iterator,
+    R: Iterator<Item = L::Item>,
+{
+    type Item = L::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Either::Left(iter) => iter.next(),
+            Either::Right(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Either::Left(iter) => iter.size_hint(),
+            Either::Right(iter) => iter.size_hint(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn either(left: bool) -> impl Iterator<Item = i32> {
+        if left {
+            Either::Left(1..4)
+        } else {
+            Either::Right(Some(5).into_iter())
+        }
+    }
+
+    #[test]
+    fn test_either() {
+        assert_eq!(either(true).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(either(true).size_hint(), (3, Some(3)));
+        assert_eq!(either(false).collect::<Vec<_>>(), vec![5]);
+        assert_eq!(either(false).size_hint(), (1, Some(1)));
+    }
+}
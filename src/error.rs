@@ -0,0 +1,61 @@
+/// An error which occurs on running a plugin.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Error {
+    /// The output pipe is closed by the reader.
+    BrokenPipe,
+    /// The output device has no space left.
+    StorageFull,
+    /// Writing to the output failed by other reasons.
+    Write(String),
+    /// Other errors, such as failures on fetching metrics.
+    Other(String),
+}
+
+impl Error {
+    /// Returns the exit status code for the error.
+    ///
+    /// The broken pipe error results in 141 (128 + SIGPIPE), following the
+    /// convention of shells, so that the agent can distinguish plugins stopped
+    /// by the closed pipe from plugins failed by other reasons.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::BrokenPipe => 141,
+            _ => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::BrokenPipe => write!(f, "write failed: broken pipe"),
+            Error::StorageFull => write!(f, "write failed: no space left on device"),
+            Error::Write(e) => write!(f, "write failed: {}", e),
+            Error::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        match err.kind() {
+            std::io::ErrorKind::BrokenPipe => Error::BrokenPipe,
+            std::io::ErrorKind::StorageFull => Error::StorageFull,
+            _ => Error::Write(err.to_string()),
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(err: String) -> Error {
+        Error::Other(err)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(err: &str) -> Error {
+        Error::Other(err.to_owned())
+    }
+}
@@ -0,0 +1,465 @@
+use std::collections::{HashMap, HashSet};
+#[cfg(target_os = "linux")]
+use std::ffi::c_ulong;
+use std::ffi::{c_char, c_int, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+
+use crate::error::Error;
+use crate::filter::Filter;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// The filesystem types excluded by default, which are the pseudo filesystems
+/// of the kernel and those in memory, and the loopback mounts of BSD.
+const PSEUDO_FS_TYPES: [&str; 28] = [
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fdescfs",
+    "fusectl",
+    "hugetlbfs",
+    "linprocfs",
+    "linsysfs",
+    "mqueue",
+    "nsfs",
+    "nullfs",
+    "proc",
+    "procfs",
+    "pstore",
+    "ramfs",
+    "rpc_pipefs",
+    "securityfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+// fsblkcnt_t and fsfilcnt_t are unsigned long in glibc, and 64 bits in musl
+#[cfg(all(target_os = "linux", target_env = "musl"))]
+type Count = u64;
+#[cfg(all(target_os = "linux", not(target_env = "musl")))]
+type Count = c_ulong;
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[allow(dead_code)]
+struct StatVfs {
+    f_bsize: c_ulong,
+    f_frsize: c_ulong,
+    f_blocks: Count,
+    f_bfree: Count,
+    f_bavail: Count,
+    f_files: Count,
+    f_ffree: Count,
+    f_favail: Count,
+    // f_fsid, f_flag, f_namemax and the spares, which are not read
+    f_rest: [c_ulong; 16],
+}
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn statvfs(path: *const c_char, buf: *mut StatVfs) -> c_int;
+}
+
+/// The statistics of the filesystem of the 64-bit inodes on macOS.
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[allow(dead_code)]
+struct StatFs {
+    f_bsize: u32,
+    f_iosize: i32,
+    f_blocks: u64,
+    f_bfree: u64,
+    f_bavail: u64,
+    f_files: u64,
+    f_ffree: u64,
+    // f_fsid, f_owner, f_type, f_flags, and f_fssubtype, which are not read
+    f_rest: [u32; 6],
+    f_fstypename: [c_char; 16],
+    f_mntonname: [c_char; 1024],
+    f_mntfromname: [c_char; 1024],
+    // f_flags_ext and the spares
+    f_spare: [u32; 8],
+}
+
+#[cfg(target_os = "macos")]
+const _: () = assert!(std::mem::size_of::<StatFs>() == 2168);
+
+/// The statistics of the filesystem since FreeBSD 12.
+#[cfg(target_os = "freebsd")]
+#[repr(C)]
+#[allow(dead_code)]
+struct StatFs {
+    f_version: u32,
+    f_type: u32,
+    f_flags: u64,
+    f_bsize: u64,
+    f_iosize: u64,
+    f_blocks: u64,
+    f_bfree: u64,
+    f_bavail: i64,
+    f_files: u64,
+    f_ffree: i64,
+    // the counts of the I/O, f_nvnodelistsize, and the spares, which are not
+    // read
+    f_rest: [u64; 14],
+    // f_owner and f_fsid
+    f_owner_fsid: [u32; 3],
+    f_charspare: [c_char; 80],
+    f_fstypename: [c_char; 16],
+    f_mntfromname: [c_char; 1024],
+    f_mntonname: [c_char; 1024],
+}
+
+#[cfg(target_os = "freebsd")]
+const _: () = assert!(std::mem::size_of::<StatFs>() == 2344);
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+const MNT_NOWAIT: c_int = 2;
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+extern "C" {
+    #[cfg_attr(
+        all(target_os = "macos", target_arch = "x86_64"),
+        link_name = "statfs$INODE64"
+    )]
+    fn statfs(path: *const c_char, buf: *mut StatFs) -> c_int;
+    #[cfg_attr(
+        all(target_os = "macos", target_arch = "x86_64"),
+        link_name = "getmntinfo$INODE64"
+    )]
+    fn getmntinfo(mntbufp: *mut *mut StatFs, flags: c_int) -> c_int;
+}
+
+/// A collector of the disk and inode usage of the mounted filesystems, which
+/// reads the mounts from `/proc/self/mountinfo` and the usage by `statvfs` on
+/// Linux, or the mounts by `getmntinfo` and the usage by `statfs` on macOS and
+/// FreeBSD.
+///
+/// The metrics are keyed by the devices, such as `sda1` and `mapper_vg0-root`
+/// for `/dev/sda1` and `/dev/mapper/vg0-root`, or by the mount points for the
+/// filesystems without the devices. The bind mounts and the filesystems
+/// mounted more than once are collected once by the first mount of the whole
+/// filesystem. The pseudo filesystems like `proc`, `tmpfs`, and `devfs` are
+/// excluded by default, and the filesystems without the blocks are ignored.
+/// The APFS volumes of macOS are collected separately, sharing the available
+/// space of the container.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{Filesystems, Filter};
+///
+/// let filesystems = Filesystems::new()
+///     .mount_points(Filter::new().exclude("/var/lib/docker/*"))
+///     .fs_types(Filter::new().include("ext4").include("xfs"));
+/// let values = filesystems.fetch_values().unwrap();
+/// ```
+pub struct Filesystems {
+    mount_points: Filter,
+    fs_types: Filter,
+    #[cfg(target_os = "linux")]
+    mountinfo: PathBuf,
+}
+
+impl Default for Filesystems {
+    fn default() -> Filesystems {
+        Filesystems::new()
+    }
+}
+
+impl Filesystems {
+    pub fn new() -> Filesystems {
+        Filesystems {
+            mount_points: Filter::new(),
+            fs_types: PSEUDO_FS_TYPES
+                .iter()
+                .fold(Filter::new(), |filter, fs_type| filter.exclude(*fs_type)),
+            #[cfg(target_os = "linux")]
+            mountinfo: PathBuf::from("/proc/self/mountinfo"),
+        }
+    }
+
+    /// Sets the filter of the mount points.
+    pub fn mount_points(mut self, filter: Filter) -> Filesystems {
+        self.mount_points = filter;
+        self
+    }
+
+    /// Sets the filter of the filesystem types, which replaces the default
+    /// filter excluding the pseudo filesystems.
+    pub fn fs_types(mut self, filter: Filter) -> Filesystems {
+        self.fs_types = filter;
+        self
+    }
+
+    /// Sets the path of the mountinfo file, which is read on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn mountinfo(mut self, mountinfo: impl Into<PathBuf>) -> Filesystems {
+        self.mountinfo = mountinfo.into();
+        self
+    }
+
+    /// Returns the graphs of the filesystems.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "disk.usage.#",
+                label: "Disk usage",
+                unit: "bytes",
+                metrics: [
+                    { name: "used", label: "used", stacked: true },
+                    { name: "available", label: "available", stacked: true },
+                ],
+            },
+            crate::graph! {
+                name: "disk.percentage.#",
+                label: "Disk percentage",
+                unit: "percentage",
+                metrics: [{ name: "used", label: "used %" }],
+            },
+            crate::graph! {
+                name: "inode.count.#",
+                label: "Inode count",
+                unit: "integer",
+                metrics: [
+                    { name: "used", label: "used" },
+                    { name: "total", label: "total" },
+                ],
+            },
+            crate::graph! {
+                name: "inode.percentage.#",
+                label: "Inode percentage",
+                unit: "percentage",
+                metrics: [{ name: "used", label: "used %" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the filesystems.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        #[cfg(target_os = "linux")]
+        let mounts = read_mountinfo(&self.mountinfo)?;
+        #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+        let mounts = read_mntinfo()?;
+        let mut mounts = mounts
+            .into_iter()
+            .filter(|mount| {
+                self.mount_points.matches(&mount.mount_point)
+                    && self.fs_types.matches(&mount.fs_type)
+            })
+            .collect::<Vec<_>>();
+        // the mounts of the whole filesystems precede the bind mounts
+        mounts.sort_by_key(|mount| mount.root != "/");
+        let mut devices = HashSet::new();
+        let mut values = HashMap::new();
+        for mount in mounts {
+            if !devices.insert(mount.device.clone()) {
+                continue;
+            }
+            // the filesystems can be inaccessible, such as the stale NFS
+            let Some(stat) = stat(Path::new(&mount.mount_point)) else {
+                continue;
+            };
+            if stat.blocks == 0.0 {
+                continue;
+            }
+            let key = mount_key(&mount);
+            let mut insert = |graph: &str, name: &str, value: f64| {
+                values.insert(format!("{}.{}.{}", graph, key, name), value.into());
+            };
+            let size = stat.block_size;
+            let used = (stat.blocks - stat.blocks_free) * size;
+            // the available blocks can be negative on FreeBSD when the
+            // reserved blocks are used
+            let available = stat.blocks_available.max(0.0) * size;
+            insert("disk.usage", "used", used);
+            insert("disk.usage", "available", available);
+            if used + available > 0.0 {
+                insert("disk.percentage", "used", used / (used + available) * 100.0);
+            }
+            // some filesystems like btrfs and vfat have no inode count
+            if stat.files > 0.0 {
+                let used = stat.files - stat.files_free.max(0.0);
+                let total = stat.files;
+                insert("inode.count", "used", used);
+                insert("inode.count", "total", total);
+                insert("inode.percentage", "used", used / total * 100.0);
+            }
+        }
+        Ok(values)
+    }
+}
+
+struct Mount {
+    device: String,
+    root: String,
+    mount_point: String,
+    fs_type: String,
+    source: String,
+}
+
+/// The usage of the filesystem, where the blocks are in the block size.
+struct Stat {
+    block_size: f64,
+    blocks: f64,
+    blocks_free: f64,
+    blocks_available: f64,
+    files: f64,
+    files_free: f64,
+}
+
+#[cfg(target_os = "linux")]
+fn read_mountinfo(path: &Path) -> Result<Vec<Mount>, Error> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("read {} failed: {}", path.display(), e))?;
+    Ok(content.lines().filter_map(parse_line).collect())
+}
+
+/// Reads the mounts by `getmntinfo`, without waiting for the statistics of
+/// the filesystems, where the device is the mounted source.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn read_mntinfo() -> Result<Vec<Mount>, Error> {
+    let mut buf = std::ptr::null_mut();
+    let count = unsafe { getmntinfo(&mut buf, MNT_NOWAIT) };
+    if count <= 0 || buf.is_null() {
+        return Err(format!("getmntinfo failed: {}", std::io::Error::last_os_error()).into());
+    }
+    // the buffer is allocated by getmntinfo and reused on the next call
+    let mounts = unsafe { std::slice::from_raw_parts(buf, count as usize) };
+    Ok(mounts
+        .iter()
+        .map(|mount| {
+            let source = c_string(&mount.f_mntfromname);
+            Mount {
+                device: source.clone(),
+                root: "/".to_owned(),
+                mount_point: c_string(&mount.f_mntonname),
+                fs_type: c_string(&mount.f_fstypename),
+                source,
+            }
+        })
+        .collect())
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn c_string(chars: &[c_char]) -> String {
+    let bytes = chars
+        .iter()
+        .map(|&c| c as u8)
+        .take_while(|&b| b != 0)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Parses the line of mountinfo, which is formatted as `36 35 98:0 /mnt1
+/// /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue`.
+#[cfg(target_os = "linux")]
+fn parse_line(line: &str) -> Option<Mount> {
+    let (mount, filesystem) = line.split_once(" - ")?;
+    let mut fields = mount.split(' ').skip(2);
+    let device = fields.next()?.to_owned();
+    let root = unescape(fields.next()?);
+    let mount_point = unescape(fields.next()?);
+    let mut fields = filesystem.split(' ');
+    let fs_type = fields.next()?.to_owned();
+    let source = unescape(fields.next()?);
+    Some(Mount {
+        device,
+        root,
+        mount_point,
+        fs_type,
+        source,
+    })
+}
+
+/// Unescapes the octal escapes of the space, the tab, the newline, and the
+/// backslash.
+#[cfg(target_os = "linux")]
+fn unescape(s: &str) -> String {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let code = tail
+            .get(..3)
+            .filter(|code| b == b'\\' && code.iter().all(|c| (b'0'..=b'7').contains(c)));
+        match code {
+            Some(code) => {
+                bytes.push(code.iter().fold(0u8, |n, c| n.wrapping_mul(8) + (c - b'0')));
+                rest = &tail[3..];
+            }
+            None => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Returns the key of the filesystem, which is the device name without
+/// `/dev/`, or the mount point for the filesystems without the devices.
+fn mount_key(mount: &Mount) -> String {
+    let name = match mount.source.strip_prefix("/dev/") {
+        Some(name) => name,
+        None => match mount.mount_point.trim_start_matches('/') {
+            "" => "root",
+            name => name,
+        },
+    };
+    metric_key(name)
+}
+
+#[cfg(target_os = "linux")]
+fn stat(path: &Path) -> Option<Stat> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<StatVfs>::uninit();
+    if unsafe { statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+    Some(Stat {
+        block_size: stat.f_frsize as f64,
+        blocks: stat.f_blocks as f64,
+        blocks_free: stat.f_bfree as f64,
+        blocks_available: stat.f_bavail as f64,
+        files: stat.f_files as f64,
+        files_free: stat.f_ffree as f64,
+    })
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn stat(path: &Path) -> Option<Stat> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<StatFs>::uninit();
+    if unsafe { statfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+    Some(Stat {
+        block_size: stat.f_bsize as f64,
+        blocks: stat.f_blocks as f64,
+        blocks_free: stat.f_bfree as f64,
+        blocks_available: stat.f_bavail as f64,
+        files: stat.f_files as f64,
+        files_free: stat.f_ffree as f64,
+    })
+}
+
+fn metric_key(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
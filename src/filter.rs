@@ -0,0 +1,122 @@
+/// A filter of the values of wildcard segments (`*` and `#`) in metric names.
+///
+/// The patterns can contain `*` which matches any characters, and `?` which
+/// matches a character. A value is accepted when it matches any of the
+/// include patterns (or there is no include pattern), and does not match any
+/// of the exclude patterns.
+///
+/// ```rust
+/// use mackerel_plugin::Filter;
+///
+/// let filter = Filter::new().include("sd*").exclude("sdb?");
+/// assert!(filter.matches("sda"));
+/// assert!(filter.matches("sdb"));
+/// assert!(!filter.matches("sdb1"));
+/// assert!(!filter.matches("nvme0n1"));
+/// ```
+#[derive(Default, PartialEq, Clone, Debug)]
+pub struct Filter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter::default()
+    }
+
+    /// Creates a filter from the environment variables `MACKEREL_PLUGIN_INCLUDE`
+    /// and `MACKEREL_PLUGIN_EXCLUDE`, which are comma-separated patterns.
+    pub fn from_env() -> Filter {
+        Filter {
+            include: env_patterns("MACKEREL_PLUGIN_INCLUDE"),
+            exclude: env_patterns("MACKEREL_PLUGIN_EXCLUDE"),
+        }
+    }
+
+    /// Creates a filter of the graph from the environment variables suffixed
+    /// with the graph name, such as `MACKEREL_PLUGIN_INCLUDE_DISK` for the
+    /// graph `disk.#`, which take precedence over the variables without the
+    /// suffix. The suffix is the graph name without the wildcard segments, in
+    /// upper case with the dots and the hyphens replaced with underscores.
+    ///
+    /// ```rust
+    /// use mackerel_plugin::Filter;
+    ///
+    /// assert_eq!(Filter::env_suffix("disk.#"), "DISK");
+    /// assert_eq!(Filter::env_suffix("interface.*.packets"), "INTERFACE_PACKETS");
+    /// ```
+    pub fn from_env_for(graph_name: &str) -> Filter {
+        let suffix = Filter::env_suffix(graph_name);
+        if suffix.is_empty() {
+            return Filter::from_env();
+        }
+        let patterns = |name: &str| match std::env::var(format!("{}_{}", name, suffix)) {
+            Ok(value) => split_patterns(&value),
+            Err(_) => env_patterns(name),
+        };
+        Filter {
+            include: patterns("MACKEREL_PLUGIN_INCLUDE"),
+            exclude: patterns("MACKEREL_PLUGIN_EXCLUDE"),
+        }
+    }
+
+    /// Returns the suffix of the environment variables of the graph filter.
+    pub fn env_suffix(graph_name: &str) -> String {
+        graph_name
+            .split('.')
+            .filter(|segment| !segment.is_empty() && *segment != "*" && *segment != "#")
+            .map(|segment| segment.to_ascii_uppercase().replace('-', "_"))
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+
+    pub fn include(mut self, pattern: impl Into<String>) -> Filter {
+        self.include.push(pattern.into());
+        self
+    }
+
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Filter {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| glob_match(p, value)))
+            && !self.exclude.iter().any(|p| glob_match(p, value))
+    }
+}
+
+fn env_patterns(name: &str) -> Vec<String> {
+    std::env::var(name).map_or_else(|_| Vec::new(), |value| split_patterns(&value))
+}
+
+fn split_patterns(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let (pattern, value) = (pattern.as_bytes(), value.as_bytes());
+    let (mut i, mut j, mut star) = (0, 0, None);
+    while j < value.len() {
+        if i < pattern.len() && (pattern[i] == b'?' || pattern[i] == value[j]) {
+            i += 1;
+            j += 1;
+        } else if i < pattern.len() && pattern[i] == b'*' {
+            star = Some((i, j));
+            i += 1;
+        } else if let Some((si, sj)) = star {
+            star = Some((si, sj + 1));
+            i = si + 1;
+            j = sj + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[i..].iter().all(|&c| c == b'*')
+}
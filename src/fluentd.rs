@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::http::HttpClient;
+use crate::unit::Unit;
+use crate::value::Value;
+use crate::wildcard::escape_segment;
+
+// (field, label, unit, diff)
+const METRICS: [(&str, &str, Unit, bool); 13] = [
+    (
+        "buffer_queue_length",
+        "Fluentd Buffer Queue Length",
+        Unit::Integer,
+        false,
+    ),
+    (
+        "buffer_total_queued_size",
+        "Fluentd Buffer Total Queued Size",
+        Unit::Bytes,
+        false,
+    ),
+    ("retry_count", "Fluentd Retry Count", Unit::Integer, true),
+    (
+        "emit_records",
+        "Fluentd Emitted Records",
+        Unit::Integer,
+        true,
+    ),
+    ("emit_count", "Fluentd Emit Count", Unit::Integer, true),
+    ("write_count", "Fluentd Write Count", Unit::Integer, true),
+    (
+        "rollback_count",
+        "Fluentd Rollback Count",
+        Unit::Integer,
+        true,
+    ),
+    (
+        "slow_flush_count",
+        "Fluentd Slow Flush Count",
+        Unit::Integer,
+        true,
+    ),
+    (
+        "flush_time_count",
+        "Fluentd Flush Time",
+        Unit::Milliseconds,
+        true,
+    ),
+    (
+        "buffer_stage_length",
+        "Fluentd Buffer Stage Length",
+        Unit::Integer,
+        false,
+    ),
+    (
+        "buffer_stage_byte_size",
+        "Fluentd Buffer Stage Byte Size",
+        Unit::Bytes,
+        false,
+    ),
+    (
+        "buffer_queue_byte_size",
+        "Fluentd Buffer Queue Byte Size",
+        Unit::Bytes,
+        false,
+    ),
+    (
+        "buffer_available_buffer_space_ratios",
+        "Fluentd Available Buffer Space Ratio",
+        Unit::Percentage,
+        false,
+    ),
+];
+
+/// A collector of the plugins of Fluentd by the `monitor_agent` input, which
+/// fetches `/api/plugins.json`.
+///
+/// The metrics are the buffers, the retries, the emits, the writes, and the
+/// flushes of each plugin by the plugin id, whose metric names are compatible
+/// with mackerel-plugin-fluentd. The cumulative counts are emitted as the
+/// counters, whose differences are graphed. The plugins without `@id` are
+/// identified by the object ids, which change on restarting Fluentd.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::FluentdStats;
+///
+/// let stats = FluentdStats::new("http://localhost:24220");
+/// let values = stats.fetch_values().unwrap();
+/// ```
+pub struct FluentdStats {
+    endpoint: String,
+    client: HttpClient,
+    plugin_type: Option<String>,
+}
+
+impl FluentdStats {
+    pub fn new(endpoint: impl Into<String>) -> FluentdStats {
+        FluentdStats {
+            endpoint: endpoint.into().trim_end_matches('/').to_owned(),
+            client: HttpClient::new(),
+            plugin_type: None,
+        }
+    }
+
+    /// Sets the client for the certificates and the proxy.
+    pub fn client(mut self, client: HttpClient) -> FluentdStats {
+        self.client = client;
+        self
+    }
+
+    /// Limits the plugins to the type, such as `forward` or `s3`.
+    pub fn plugin_type(mut self, plugin_type: impl Into<String>) -> FluentdStats {
+        self.plugin_type = Some(plugin_type.into());
+        self
+    }
+
+    /// Returns the graphs of the plugins.
+    pub fn graphs() -> Vec<Graph> {
+        METRICS
+            .iter()
+            .map(|&(name, label, unit, diff)| {
+                crate::graph! {
+                    name: &format!("fluentd.{}", name),
+                    label: label,
+                    unit: unit,
+                    metrics: [{ name: "*", label: "%1", diff: diff }],
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the metric values of the plugins.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the plugins, whose request times out on
+    /// the deadline of the fetch.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        let text = crate::server_status::get(
+            &self.client,
+            ctx,
+            &(self.endpoint.clone() + "/api/plugins.json"),
+        )?;
+        parse_plugins(&text, self.plugin_type.as_deref())
+    }
+
+    /// Parses the response of `/api/plugins.json`.
+    pub fn parse(text: &str) -> Result<HashMap<String, Value>, Error> {
+        parse_plugins(text, None)
+    }
+}
+
+fn parse_plugins(text: &str, plugin_type: Option<&str>) -> Result<HashMap<String, Value>, Error> {
+    let response: serde_json::Value = serde_json::from_str(text)
+        .map_err(|err| format!("parse the Fluentd plugins failed: {}", err))?;
+    let plugins = response["plugins"]
+        .as_array()
+        .ok_or("parse the Fluentd plugins failed: no plugins found")?;
+    let mut values = HashMap::new();
+    for plugin in plugins {
+        if plugin_type.is_some_and(|plugin_type| plugin["type"].as_str() != Some(plugin_type)) {
+            continue;
+        }
+        let Some(id) = plugin["plugin_id"].as_str() else {
+            continue;
+        };
+        let id = escape_segment(id);
+        for (name, _, _, diff) in METRICS {
+            let value = &plugin[name];
+            // the counters are reset on restarting Fluentd
+            let value = if diff {
+                value.as_u64().map(Value::counter)
+            } else {
+                value.as_f64().map(Value::from)
+            };
+            if let Some(value) = value {
+                values.insert(format!("fluentd.{}.{}", name, id), value);
+            }
+        }
+    }
+    Ok(values)
+}
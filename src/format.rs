@@ -0,0 +1,47 @@
+/// A formatter of the metric values written by the sinks.
+///
+/// The sinks format the values by [`DefaultFormatter`], and the embedders
+/// which need a different format, such as the fixed decimal places, can set
+/// another formatter to the sinks. The closures of `Fn(f64) -> String` are
+/// also formatters. The formatter of `JsonSink` must return a JSON number.
+///
+/// ```rust
+/// use mackerel_plugin::{MetricSink, TsvSink};
+///
+/// let mut out = Vec::new();
+/// let mut sink = TsvSink::new(&mut out).formatter(|value: f64| format!("{:.3}", value));
+/// sink.write_value("dice.d6", 3.0, 1700000000).unwrap();
+/// sink.finish().unwrap();
+/// drop(sink);
+/// assert_eq!(out, b"dice.d6\t3.000\t1700000000\n");
+/// ```
+pub trait ValueFormatter {
+    fn format(&self, value: f64) -> String;
+}
+
+impl<F: Fn(f64) -> String> ValueFormatter for F {
+    fn format(&self, value: f64) -> String {
+        self(value)
+    }
+}
+
+/// The default formatter of the metric values, which formats the values by
+/// [`format_value`].
+#[derive(Clone, Copy, Default, Debug)]
+pub struct DefaultFormatter;
+
+impl ValueFormatter for DefaultFormatter {
+    fn format(&self, value: f64) -> String {
+        format_value(value)
+    }
+}
+
+/// Formats the metric value in the shortest decimal representation which is
+/// parsed back to the same value, such as `3`, `-0.5`, or `12345678.9`.
+///
+/// The format never depends on the locale of the environment; the decimal
+/// separator is always a period, and neither the digit grouping nor the
+/// exponent is used, so that mackerel-agent parses the values on any hosts.
+pub fn format_value(value: f64) -> String {
+    value.to_string()
+}
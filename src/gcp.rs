@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::rfc3339;
+use crate::value::Value;
+
+const MAX_RETRIES: u32 = 3;
+
+/// A query of the time series of Cloud Monitoring, which is mapped to the
+/// metric key of the plugin.
+///
+/// The key can contain the placeholders of the labels like `{instance_id}`,
+/// which are replaced with the values of the metric labels or the resource
+/// labels of each time series, so that a query can produce the wildcard
+/// metrics.
+///
+/// ```rust
+/// use mackerel_plugin::TimeSeriesQuery;
+///
+/// let query = TimeSeriesQuery::new(
+///     "gce.cpu.{instance_id}",
+///     r#"metric.type = "compute.googleapis.com/instance/cpu/utilization""#,
+/// )
+/// .aligner("ALIGN_MAX");
+/// ```
+#[derive(Clone, Debug)]
+pub struct TimeSeriesQuery {
+    pub key: String,
+    pub filter: String,
+    pub aligner: String,
+    pub reducer: Option<String>,
+    pub group_by: Vec<String>,
+    pub alignment_period: Duration,
+}
+
+impl TimeSeriesQuery {
+    /// Creates a query of the filter with the `ALIGN_MEAN` aligner of 60
+    /// seconds alignment period.
+    pub fn new(key: impl Into<String>, filter: impl Into<String>) -> TimeSeriesQuery {
+        TimeSeriesQuery {
+            key: key.into(),
+            filter: filter.into(),
+            aligner: "ALIGN_MEAN".to_owned(),
+            reducer: None,
+            group_by: Vec::new(),
+            alignment_period: Duration::from_secs(60),
+        }
+    }
+
+    pub fn aligner(mut self, aligner: impl Into<String>) -> TimeSeriesQuery {
+        self.aligner = aligner.into();
+        self
+    }
+
+    pub fn reducer(mut self, reducer: impl Into<String>) -> TimeSeriesQuery {
+        self.reducer = Some(reducer.into());
+        self
+    }
+
+    pub fn group_by(mut self, field: impl Into<String>) -> TimeSeriesQuery {
+        self.group_by.push(field.into());
+        self
+    }
+
+    /// Sets the alignment period, which is rounded up to the multiple of a
+    /// minute, the resolution of Mackerel.
+    pub fn alignment_period(mut self, alignment_period: Duration) -> TimeSeriesQuery {
+        self.alignment_period = alignment_period;
+        self
+    }
+}
+
+/// A client of Cloud Monitoring for fetching the metrics of the Google Cloud
+/// resources, handling the pagination and the rate limit.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{CloudMonitoring, TimeSeriesQuery};
+///
+/// let monitoring = CloudMonitoring::from_env().unwrap();
+/// let values = monitoring
+///     .fetch_values(&[TimeSeriesQuery::new(
+///         "pubsub.undelivered.{subscription_id}",
+///         r#"metric.type = "pubsub.googleapis.com/subscription/num_undelivered_messages""#,
+///     )])
+///     .unwrap();
+/// ```
+pub struct CloudMonitoring {
+    project: String,
+    access_token: String,
+    endpoint: String,
+    window: Duration,
+    agent: ureq::Agent,
+}
+
+impl CloudMonitoring {
+    pub fn new(project: impl Into<String>, access_token: impl Into<String>) -> CloudMonitoring {
+        CloudMonitoring {
+            project: project.into(),
+            access_token: access_token.into(),
+            endpoint: "https://monitoring.googleapis.com".to_owned(),
+            window: Duration::from_secs(300),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+        }
+    }
+
+    /// Creates a client with the project of `GOOGLE_CLOUD_PROJECT` and the
+    /// access token of `GOOGLE_OAUTH_ACCESS_TOKEN`. When they are not set,
+    /// they are retrieved from the metadata server of the instance, which can
+    /// be overridden by `GCE_METADATA_HOST`.
+    pub fn from_env() -> Result<CloudMonitoring, Error> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let metadata = |path: &str| -> Result<String, Error> {
+            let host = var("GCE_METADATA_HOST").unwrap_or("metadata.google.internal".to_owned());
+            let url = format!("http://{}/computeMetadata/v1/{}", host, path);
+            ureq::get(&url)
+                .set("Metadata-Flavor", "Google")
+                .timeout(Duration::from_secs(5))
+                .call()
+                .map_err(|err| format!("GET {} failed: {}", url, err))?
+                .into_string()
+                .map_err(|err| format!("GET {} failed: {}", url, err).into())
+        };
+        let project = match var("GOOGLE_CLOUD_PROJECT") {
+            Some(project) => project,
+            None => metadata("project/project-id")?,
+        };
+        let access_token = match var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+            Some(access_token) => access_token,
+            None => {
+                let token: serde_json::Value =
+                    serde_json::from_str(&metadata("instance/service-accounts/default/token")?)
+                        .map_err(|err| err.to_string())?;
+                token["access_token"]
+                    .as_str()
+                    .ok_or("access token not found in the metadata")?
+                    .to_owned()
+            }
+        };
+        Ok(CloudMonitoring::new(project, access_token))
+    }
+
+    /// Sets the endpoint of the API.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> CloudMonitoring {
+        self.endpoint = endpoint.into().trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Sets the time window to look back for the latest points, which
+    /// defaults to 5 minutes since the metrics are sampled with delays.
+    pub fn window(mut self, window: Duration) -> CloudMonitoring {
+        self.window = window;
+        self
+    }
+
+    /// Returns the latest values of the queries, with the end times of the
+    /// points so that the staleness policy of the plugin applies. The window
+    /// ends at the start of the current minute, not to get the values of
+    /// the partial periods.
+    pub fn fetch_values(
+        &self,
+        queries: &[TimeSeriesQuery],
+    ) -> Result<HashMap<String, Value>, Error> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs();
+        let end = UNIX_EPOCH + Duration::from_secs(now - now % 60);
+        let start = end.checked_sub(self.window).unwrap_or(end);
+        let mut values = HashMap::new();
+        for query in queries {
+            for (key, points) in self.list_time_series(query, start, end)? {
+                if let Some(&(time, value)) = points.iter().max_by_key(|(time, _)| *time) {
+                    values.insert(key, Value::Observed(value, time));
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// Returns the points of the time series between the times, keyed by the
+    /// metric keys of the query with the placeholders replaced.
+    pub fn list_time_series(
+        &self,
+        query: &TimeSeriesQuery,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<HashMap<String, Vec<(SystemTime, f64)>>, Error> {
+        let path = format!("/v3/projects/{}/timeSeries", self.project);
+        let period = query.alignment_period.as_secs().div_ceil(60).max(1) * 60;
+        let mut results = HashMap::new();
+        let mut page_token = String::new();
+        loop {
+            let mut params = vec![
+                ("filter", query.filter.clone()),
+                ("interval.startTime", rfc3339::format(start)),
+                ("interval.endTime", rfc3339::format(end)),
+                ("aggregation.alignmentPeriod", format!("{}s", period)),
+                ("aggregation.perSeriesAligner", query.aligner.clone()),
+            ];
+            if let Some(reducer) = &query.reducer {
+                params.push(("aggregation.crossSeriesReducer", reducer.clone()));
+            }
+            for field in &query.group_by {
+                params.push(("aggregation.groupByFields", field.clone()));
+            }
+            if !page_token.is_empty() {
+                params.push(("pageToken", page_token.clone()));
+            }
+            let response = self.get(&path, &params)?;
+            for series in response["timeSeries"].as_array().into_iter().flatten() {
+                let points: &mut Vec<_> =
+                    results.entry(series_key(&query.key, series)).or_default();
+                for point in series["points"].as_array().into_iter().flatten() {
+                    let time = point["interval"]["endTime"]
+                        .as_str()
+                        .and_then(rfc3339::parse);
+                    let value = &point["value"];
+                    let value = value["doubleValue"].as_f64().or_else(|| {
+                        value["int64Value"]
+                            .as_str()
+                            .and_then(|value| value.parse().ok())
+                            .or_else(|| value["boolValue"].as_bool().map(|b| b as u8 as f64))
+                    });
+                    if let (Some(time), Some(value)) = (time, value) {
+                        points.push((time, value));
+                    }
+                }
+            }
+            match response["nextPageToken"].as_str() {
+                Some(token) if !token.is_empty() => page_token = token.to_owned(),
+                _ => break,
+            }
+        }
+        Ok(results)
+    }
+
+    fn get(&self, path: &str, params: &[(&str, String)]) -> Result<serde_json::Value, Error> {
+        let mut retries = 0;
+        loop {
+            let mut request = self
+                .agent
+                .get(&(self.endpoint.clone() + path))
+                .set("Authorization", &format!("Bearer {}", self.access_token));
+            for (key, value) in params {
+                request = request.query(key, value);
+            }
+            match request.call() {
+                Ok(response) => {
+                    return serde_json::from_reader(response.into_reader())
+                        .map_err(|err| format!("GET {} failed: {}", path, err).into())
+                }
+                Err(ureq::Error::Status(status, _))
+                    if (status == 429 || status >= 500) && retries < MAX_RETRIES =>
+                {
+                    std::thread::sleep(Duration::from_secs(1 << retries));
+                    retries += 1;
+                }
+                Err(ureq::Error::Status(status, response)) => {
+                    let response: serde_json::Value =
+                        serde_json::from_reader(response.into_reader()).unwrap_or_default();
+                    let message = response["error"]["message"].as_str().unwrap_or_default();
+                    return Err(format!("GET {} failed: {} {}", path, status, message).into());
+                }
+                Err(err) => return Err(format!("GET {} failed: {}", path, err).into()),
+            }
+        }
+    }
+}
+
+/// Replaces the placeholders of the labels in the key with the label values
+/// of the time series. The characters which are not allowed in the metric
+/// names are replaced with `_`.
+fn series_key(key: &str, series: &serde_json::Value) -> String {
+    let mut result = String::new();
+    let mut rest = key;
+    while let Some((prefix, suffix)) = rest.split_once('{') {
+        let Some((label, suffix)) = suffix.split_once('}') else {
+            break;
+        };
+        result.push_str(prefix);
+        let value = [&series["metric"]["labels"], &series["resource"]["labels"]]
+            .iter()
+            .find_map(|labels| labels[label].as_str())
+            .unwrap_or_default();
+        result.extend(value.chars().map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        }));
+        rest = suffix;
+    }
+    result + rest
+}
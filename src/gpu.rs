@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use nvml_wrapper::enum_wrappers::device::TemperatureSensor;
+use nvml_wrapper::error::NvmlError;
+use nvml_wrapper::{Device, Nvml};
+
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// A collector of the NVIDIA GPU metrics by NVML.
+///
+/// The metrics are the utilization, the memory, the temperature, and the
+/// power usage of each GPU, named by the index like `gpu0`. The metrics not
+/// supported by the GPU are omitted. The NVML library is loaded at runtime,
+/// so the plugins fail on the hosts without the NVIDIA driver.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::GpuDevices;
+///
+/// let devices = GpuDevices::new().unwrap();
+/// let values = devices.fetch_values().unwrap();
+/// ```
+pub struct GpuDevices {
+    nvml: Nvml,
+}
+
+impl GpuDevices {
+    /// Loads and initializes the NVML library.
+    pub fn new() -> Result<GpuDevices, Error> {
+        let nvml = Nvml::init().map_err(|err| format!("initialize NVML failed: {}", err))?;
+        Ok(GpuDevices { nvml })
+    }
+
+    /// Returns the graphs of the GPUs.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "gpu.utilization.#",
+                label: "GPU utilization",
+                unit: "percentage",
+                metrics: [
+                    { name: "gpu", label: "GPU" },
+                    { name: "memory", label: "Memory" },
+                ],
+            },
+            crate::graph! {
+                name: "gpu.memory.#",
+                label: "GPU memory",
+                unit: "bytes",
+                metrics: [
+                    { name: "used", label: "Used", stacked: true },
+                    { name: "free", label: "Free", stacked: true },
+                ],
+            },
+            crate::graph! {
+                name: "gpu.temperature",
+                label: "GPU temperature",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "gpu.power",
+                label: "GPU power usage (W)",
+                unit: "float",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the GPUs.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        let count = self
+            .nvml
+            .device_count()
+            .map_err(|err| format!("get the device count failed: {}", err))?;
+        let mut values = HashMap::new();
+        for index in 0..count {
+            let device = self
+                .nvml
+                .device_by_index(index)
+                .map_err(|err| format!("get the device {} failed: {}", index, err))?;
+            device_values(&device, &format!("gpu{}", index), &mut values)
+                .map_err(|err| format!("read the device {} failed: {}", index, err))?;
+        }
+        Ok(values)
+    }
+}
+
+fn device_values(
+    device: &Device,
+    name: &str,
+    values: &mut HashMap<String, Value>,
+) -> Result<(), NvmlError> {
+    if let Some(utilization) = supported(device.utilization_rates())? {
+        values.insert(
+            format!("gpu.utilization.{}.gpu", name),
+            (utilization.gpu as f64).into(),
+        );
+        values.insert(
+            format!("gpu.utilization.{}.memory", name),
+            (utilization.memory as f64).into(),
+        );
+    }
+    if let Some(memory) = supported(device.memory_info())? {
+        values.insert(
+            format!("gpu.memory.{}.used", name),
+            (memory.used as f64).into(),
+        );
+        values.insert(
+            format!("gpu.memory.{}.free", name),
+            (memory.free as f64).into(),
+        );
+    }
+    if let Some(temperature) = supported(device.temperature(TemperatureSensor::Gpu))? {
+        values.insert(
+            format!("gpu.temperature.{}", name),
+            (temperature as f64).into(),
+        );
+    }
+    if let Some(power) = supported(device.power_usage())? {
+        // the power usage is reported in milliwatts
+        values.insert(
+            format!("gpu.power.{}", name),
+            (power as f64 / 1000.0).into(),
+        );
+    }
+    Ok(())
+}
+
+fn supported<T>(result: Result<T, NvmlError>) -> Result<Option<T>, NvmlError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(NvmlError::NotSupported) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
@@ -4,7 +4,10 @@ use crate::metric::Metric;
 use crate::unit::Unit;
 
 /// A graph represents a Mackerel graph schema.
+///
+/// The name is validated on deserializing, as well as by [`Graph::new`].
 #[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "RawGraph")]
 pub struct Graph {
     #[serde(skip_serializing)]
     pub name: String,
@@ -13,11 +16,126 @@ pub struct Graph {
     pub metrics: Vec<Metric>,
 }
 
+/// A graph deserialized before the validation of the name.
+#[derive(Deserialize)]
+struct RawGraph {
+    name: String,
+    label: String,
+    unit: Unit,
+    metrics: Vec<Metric>,
+}
+
+impl TryFrom<RawGraph> for Graph {
+    type Error = String;
+
+    fn try_from(raw: RawGraph) -> Result<Graph, String> {
+        if !is_valid_graph_name(&raw.name) {
+            return Err(format!("invalid graph name: {}", raw.name));
+        }
+        Ok(Graph {
+            name: raw.name,
+            label: raw.label,
+            unit: raw.unit,
+            metrics: raw.metrics,
+        })
+    }
+}
+
 impl Graph {
+    /// Creates a new graph, or returns an error if the name or the unit is
+    /// invalid. Unlike [`graph!`], this never panics on the names built at
+    /// runtime.
+    pub fn new(
+        name: impl Into<String>,
+        label: impl Into<String>,
+        unit: &str,
+        metrics: Vec<Metric>,
+    ) -> Result<Graph, String> {
+        let name = name.into();
+        if !is_valid_graph_name(&name) {
+            return Err(format!("invalid graph name: {}", name));
+        }
+        Ok(Graph {
+            name,
+            label: label.into(),
+            unit: unit
+                .parse()
+                .map_err(|_| format!("invalid unit: {}", unit))?,
+            metrics,
+        })
+    }
+
     #[doc(hidden)]
     pub fn has_diff(&self) -> bool {
         self.metrics.iter().any(|metric| metric.diff)
     }
+
+    /// Returns whether the graph mixes the stacked and the unstacked metrics,
+    /// which Mackerel renders confusingly.
+    pub fn mixes_stacking(&self) -> bool {
+        let stacked = self.metrics.iter().filter(|metric| metric.stacked).count();
+        stacked > 0 && stacked < self.metrics.len()
+    }
+
+    /// Returns the unit of the metric, which defaults to the unit of the graph.
+    pub fn unit_of<'a>(&'a self, metric: &'a Metric) -> &'a Unit {
+        metric.unit.as_ref().unwrap_or(&self.unit)
+    }
+
+    /// Validates the graph definition and returns the warnings, which are the
+    /// units of the metrics conflicting with the unit of the graph, and the
+    /// suspicious combinations of the options; the differences of the
+    /// percentages, the stacked gauges of `float`, which are usually not
+    /// additive like the load averages, and the mixed stacking of the metrics.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.mixes_stacking() {
+            warnings.push(format!(
+                "graph {} mixes the stacked and unstacked metrics",
+                self.name
+            ));
+        }
+        for metric in &self.metrics {
+            let unit = self.unit_of(metric);
+            if unit != &self.unit {
+                warnings.push(format!(
+                    "unit of metric {} ({}) conflicts with unit of graph {} ({})",
+                    metric.name, unit, self.name, self.unit
+                ));
+            }
+            if metric.diff && unit == &Unit::Percentage {
+                warnings.push(format!(
+                    "metric {} of graph {} takes the difference of the percentage",
+                    metric.name, self.name
+                ));
+            }
+            if metric.stacked && !metric.diff && unit == &Unit::Float {
+                warnings.push(format!(
+                    "metric {} of graph {} stacks the float gauge, which may not be additive",
+                    metric.name, self.name
+                ));
+            }
+        }
+        warnings
+    }
+}
+
+/// Returns whether the name is valid for a graph name, which consists of
+/// alphanumeric characters, hyphens, underscores, wildcards, and dots.
+#[doc(hidden)]
+pub const fn is_valid_graph_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if !matches!(
+            bytes[i],
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'*' | b'#'
+        ) {
+            return false;
+        }
+        i += 1;
+    }
+    bytes.is_empty() || (bytes[0] != b'.' && bytes[bytes.len() - 1] != b'.')
 }
 
 /// Builds a new [`Graph`].
@@ -35,25 +153,127 @@ impl Graph {
 ///     ],
 /// };
 /// ```
+///
+/// The label and metrics can be built from expressions.
+///
+/// ```rust
+/// use mackerel_plugin::{graph, metric};
+///
+/// let devices = ["sda", "sdb"];
+/// let graph = graph! {
+///     name: "disk.io",
+///     label: format!("Disk IO ({} devices)", devices.len()),
+///     unit: "iops",
+///     metrics: devices.iter().map(|&device| metric! {
+///         name: device,
+///         label: device.to_uppercase(),
+///         diff: true,
+///     }),
+/// };
+/// assert_eq!(graph.metrics.len(), 2);
+/// ```
+///
+/// The unit is either a literal, or an expression of [`Unit`] or a string,
+/// which is validated at runtime.
+///
+/// ```rust
+/// use mackerel_plugin::{graph, Unit};
+///
+/// let graph = graph! {
+///     name: "disk.usage",
+///     label: "Disk Usage",
+///     unit: Unit::Bytes,
+///     metrics: [
+///         { name: "used", label: "Used" },
+///     ],
+/// };
+/// assert_eq!(graph.unit, Unit::Bytes);
+/// ```
+///
+/// The literal graph name and unit are validated at compile time.
+///
+/// ```rust,compile_fail
+/// use mackerel_plugin::graph;
+///
+/// let graph = graph! {
+///     name: "linux.swap.",
+///     label: "Linux Swap Usage",
+///     unit: "integer",
+///     metrics: [
+///         { name: "pswpin", label: "Swap In", diff: true },
+///     ],
+/// };
+/// ```
+///
+/// ```rust,compile_fail
+/// use mackerel_plugin::graph;
+///
+/// let graph = graph! {
+///     name: "linux.swap",
+///     label: "Linux Swap Usage",
+///     unit: "integers",
+///     metrics: [
+///         { name: "pswpin", label: "Swap In", diff: true },
+///     ],
+/// };
+/// ```
 #[macro_export]
 macro_rules! graph {
+    (name: $name:literal, $( $rest:tt )*) => {{
+        const _: () = assert!(
+            $crate::is_valid_graph_name($name),
+            concat!("invalid graph name: ", $name)
+        );
+        $crate::graph!(@graph $name, $( $rest )*)
+    }};
+
+    (name: $name:expr, $( $rest:tt )*) => {{
+        assert!($crate::is_valid_graph_name($name), "invalid graph name: {}", $name);
+        $crate::graph!(@graph $name, $( $rest )*)
+    }};
+
+    (
+        @graph $name:expr,
+        label: $label:expr,
+        unit: $unit:literal,
+        $( $rest:tt )*
+    ) => {
+        $crate::graph!(@graph $name, label: $label, unit: {
+            const UNIT: $crate::Unit = match $crate::Unit::from_name($unit) {
+                Some(unit) => unit,
+                None => panic!(concat!("invalid unit: ", $unit)),
+            };
+            UNIT
+        }, $( $rest )*)
+    };
+
     (
-        name: $name:expr,
+        @graph $name:expr,
         label: $label:expr,
         unit: $unit:expr,
         metrics: [$( {$( $metrics:tt )*} ),+ $(,)?] $(,)?
-    ) => {{
-        assert!(
-            str::chars($name).all(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '*' | '#'))
-                && !$name.starts_with('.') && !$name.ends_with('.')
-        );
+    ) => {
         $crate::Graph {
             name: $name.into(),
             label: $label.into(),
-            unit: $unit.parse().unwrap(),
+            unit: $crate::IntoUnit::into_unit($unit),
             metrics: vec![$( $crate::metric! {$( $metrics )*} ),+],
         }
-    }};
+    };
+
+    (
+        @graph $name:expr,
+        label: $label:expr,
+        unit: $unit:expr,
+        metrics: $metrics:expr $(,)?
+    ) => {
+        $crate::Graph {
+            name: $name.into(),
+            label: $label.into(),
+            unit: $crate::IntoUnit::into_unit($unit),
+            metrics: ::std::iter::IntoIterator::into_iter($metrics).collect(),
+        }
+    };
 
     ($($_:tt)*) => {
         compile_error!("name, label, unit, and metrics are required");
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::http::HttpClient;
+use crate::unit::Unit;
+use crate::value::Value;
+use crate::wildcard::escape_segment;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// (graph, label, unit, [(metric, label, field, diff)])
+type GraphSpec = (
+    &'static str,
+    &'static str,
+    Unit,
+    &'static [(&'static str, &'static str, &'static str, bool)],
+);
+
+const TOTAL_GRAPHS: [GraphSpec; 3] = [
+    (
+        "sessions",
+        "HAProxy Total Sessions",
+        Unit::Integer,
+        &[("sessions", "Sessions", "stot", true)],
+    ),
+    (
+        "bytes",
+        "HAProxy Total Bytes",
+        Unit::Integer,
+        &[
+            ("bytes_in", "Bytes In", "bin", true),
+            ("bytes_out", "Bytes Out", "bout", true),
+        ],
+    ),
+    (
+        "connection_errors",
+        "HAProxy Total Connection Errors",
+        Unit::Integer,
+        &[("connection_errors", "Connection Errors", "econ", true)],
+    ),
+];
+
+const PROXY_GRAPHS: [GraphSpec; 4] = [
+    (
+        "sessions",
+        "Sessions",
+        Unit::Integer,
+        &[
+            ("current", "Current", "scur", false),
+            ("total", "Total", "stot", true),
+        ],
+    ),
+    (
+        "errors",
+        "Errors",
+        Unit::Integer,
+        &[
+            ("connection", "Connection", "econ", true),
+            ("response", "Response", "eresp", true),
+        ],
+    ),
+    (
+        "queue",
+        "Queue",
+        Unit::Integer,
+        &[("current", "Current", "qcur", false)],
+    ),
+    (
+        "bytes",
+        "Bytes",
+        Unit::Bytes,
+        &[("in", "In", "bin", true), ("out", "Out", "bout", true)],
+    ),
+];
+
+/// A collector of the statistics of HAProxy, which reads the CSV of the stats
+/// page (`;csv`) or the stats socket (`show stat`).
+///
+/// The metrics are the sessions, the connection and response errors, the
+/// queue, and the bytes of each backend and server. The total sessions, bytes,
+/// and connection errors of the backends are compatible with
+/// mackerel-plugin-haproxy. The names of the proxies and the servers are
+/// escaped by `escape_segment`.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::HaproxyStats;
+///
+/// let stats = HaproxyStats::new("http://localhost/haproxy?stats;csv");
+/// let values = stats.fetch_values().unwrap();
+///
+/// let stats = HaproxyStats::socket("/var/run/haproxy.sock");
+/// let values = stats.fetch_values().unwrap();
+/// ```
+pub struct HaproxyStats {
+    source: Source,
+    client: HttpClient,
+}
+
+enum Source {
+    Url(String),
+    Socket(String),
+}
+
+impl HaproxyStats {
+    /// Creates a collector of the URL of the stats page in CSV.
+    pub fn new(url: impl Into<String>) -> HaproxyStats {
+        HaproxyStats {
+            source: Source::Url(url.into()),
+            client: HttpClient::new(),
+        }
+    }
+
+    /// Creates a collector of the stats socket, which is the path of the UNIX
+    /// domain socket or the host and the port.
+    pub fn socket(addr: impl Into<String>) -> HaproxyStats {
+        HaproxyStats {
+            source: Source::Socket(addr.into()),
+            client: HttpClient::new(),
+        }
+    }
+
+    /// Sets the client for the certificates and the proxy.
+    pub fn client(mut self, client: HttpClient) -> HaproxyStats {
+        self.client = client;
+        self
+    }
+
+    /// Returns the graphs of the statistics.
+    pub fn graphs() -> Vec<Graph> {
+        let mut graphs = graphs_of(&TOTAL_GRAPHS, "haproxy.total", "", "");
+        graphs.extend(graphs_of(
+            &PROXY_GRAPHS,
+            "haproxy.backend",
+            ".#",
+            "HAProxy Backend ",
+        ));
+        graphs.extend(graphs_of(
+            &PROXY_GRAPHS,
+            "haproxy.server",
+            ".#.#",
+            "HAProxy Server ",
+        ));
+        graphs
+    }
+
+    /// Returns the metric values of the statistics.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the statistics, whose request times out on
+    /// the deadline of the fetch.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        let text = match &self.source {
+            Source::Url(url) => crate::server_status::get(&self.client, ctx, url)?,
+            Source::Socket(addr) => {
+                let error =
+                    |err: &dyn std::fmt::Display| format!("HAProxy stats {} failed: {}", addr, err);
+                ctx.acquire().map_err(|err| error(&err))?;
+                read_socket(addr, ctx.timeout(TIMEOUT)).map_err(|err| error(&err))?
+            }
+        };
+        HaproxyStats::parse(&text)
+    }
+
+    /// Parses the CSV of the statistics, whose first line is the header of the
+    /// field names starting with `# `.
+    pub fn parse(text: &str) -> Result<HashMap<String, Value>, Error> {
+        let mut lines = text.lines();
+        let fields = lines
+            .next()
+            .and_then(|line| line.strip_prefix("# "))
+            .ok_or("parse the HAProxy stats failed: no header found")?
+            .split(',')
+            .collect::<Vec<_>>();
+        let index = |field: &str| {
+            fields
+                .iter()
+                .position(|&name| name == field)
+                .ok_or_else(|| format!("parse the HAProxy stats failed: no {} field", field))
+        };
+        let (pxname, svname) = (index("pxname")?, index("svname")?);
+        let mut totals = HashMap::new();
+        let mut values = HashMap::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let row = line.split(',').collect::<Vec<_>>();
+            let (Some(proxy), Some(server)) = (row.get(pxname), row.get(svname)) else {
+                continue;
+            };
+            let field = |name: &str| -> Result<Option<f64>, Error> {
+                let Some(&value) = index(name).ok().and_then(|i| row.get(i)) else {
+                    return Ok(None);
+                };
+                if value.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(value.parse::<f64>().map_err(|_| {
+                    format!(
+                        "parse the HAProxy stats failed: invalid {}: {:?}",
+                        name, value
+                    )
+                })?))
+            };
+            let (prefix, key) = match *server {
+                "FRONTEND" => continue,
+                "BACKEND" => {
+                    for (_, _, _, metrics) in TOTAL_GRAPHS {
+                        for (name, _, field_name, _) in metrics {
+                            if let Some(value) = field(field_name)? {
+                                *totals.entry(*name).or_insert(0.0) += value;
+                            }
+                        }
+                    }
+                    ("backend", escape_segment(proxy))
+                }
+                _ => (
+                    "server",
+                    format!("{}.{}", escape_segment(proxy), escape_segment(server)),
+                ),
+            };
+            for (graph, _, _, metrics) in PROXY_GRAPHS {
+                for (name, _, field_name, _) in metrics {
+                    if let Some(value) = field(field_name)? {
+                        values.insert(
+                            format!("haproxy.{}.{}.{}.{}", prefix, graph, key, name),
+                            value.into(),
+                        );
+                    }
+                }
+            }
+        }
+        for (graph, _, _, metrics) in TOTAL_GRAPHS {
+            for (name, _, _, _) in metrics {
+                if let Some(&value) = totals.get(name) {
+                    values.insert(format!("haproxy.total.{}.{}", graph, name), value.into());
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+fn graphs_of(specs: &[GraphSpec], prefix: &str, wildcard: &str, label: &str) -> Vec<Graph> {
+    specs
+        .iter()
+        .map(|&(name, graph_label, unit, metrics)| {
+            crate::graph! {
+                name: &format!("{}.{}{}", prefix, name, wildcard),
+                label: &format!("{}{}", label, graph_label),
+                unit: unit,
+                metrics: metrics.iter().map(|&(name, label, _, diff)| {
+                    crate::metric! { name: name, label: label, diff: diff }
+                }),
+            }
+        })
+        .collect()
+}
+
+fn read_socket(addr: &str, timeout: Duration) -> Result<String, std::io::Error> {
+    let mut text = String::new();
+    #[cfg(unix)]
+    if addr.contains('/') {
+        let mut stream = std::os::unix::net::UnixStream::connect(addr)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.write_all(b"show stat\n")?;
+        stream.read_to_string(&mut text)?;
+        return Ok(text);
+    }
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "cannot resolve"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.write_all(b"show stat\n")?;
+    stream.read_to_string(&mut text)?;
+    Ok(text)
+}
@@ -0,0 +1,141 @@
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::mem::{size_of, MaybeUninit};
+
+use crate::error::Error;
+
+extern "C" {
+    fn sysctlbyname(
+        name: *const c_char,
+        oldp: *mut c_void,
+        oldlenp: *mut usize,
+        newp: *const c_void,
+        newlen: usize,
+    ) -> c_int;
+    #[cfg(target_os = "macos")]
+    static mach_task_self_: u32;
+    #[cfg(target_os = "macos")]
+    fn mach_host_self() -> u32;
+    #[cfg(target_os = "macos")]
+    fn mach_port_deallocate(task: u32, name: u32) -> c_int;
+    #[cfg(target_os = "macos")]
+    fn host_statistics64(host: u32, flavor: c_int, info: *mut c_int, count: *mut u32) -> c_int;
+    #[cfg(target_os = "freebsd")]
+    fn sysctlnametomib(name: *const c_char, mibp: *mut c_int, sizep: *mut usize) -> c_int;
+    #[cfg(target_os = "freebsd")]
+    fn sysctl(
+        name: *const c_int,
+        namelen: u32,
+        oldp: *mut c_void,
+        oldlenp: *mut usize,
+        newp: *const c_void,
+        newlen: usize,
+    ) -> c_int;
+}
+
+/// Reads the value of the sysctl by the name, which must be of the size of
+/// the type. The type must be valid for any bytes, such as the integers and
+/// the structures of them.
+pub(crate) fn sysctl_value<T: Copy>(name: &str) -> Result<T, Error> {
+    let c_name = CString::new(name).map_err(|e| format!("sysctl {} failed: {}", name, e))?;
+    let mut value = MaybeUninit::<T>::uninit();
+    let mut size = size_of::<T>();
+    let ret = unsafe {
+        sysctlbyname(
+            c_name.as_ptr(),
+            value.as_mut_ptr() as *mut c_void,
+            &mut size,
+            std::ptr::null(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(format!(
+            "sysctl {} failed: {}",
+            name,
+            std::io::Error::last_os_error()
+        )
+        .into());
+    }
+    if size != size_of::<T>() {
+        return Err(format!("sysctl {} failed: unexpected size {}", name, size).into());
+    }
+    Ok(unsafe { value.assume_init() })
+}
+
+/// Reads the unsigned integer of the sysctl by the name, which is either 32
+/// bits or 64 bits depending on the versions of the systems.
+pub(crate) fn sysctl_uint(name: &str) -> Result<u64, Error> {
+    sysctl_value::<u64>(name).or_else(|_| sysctl_value::<u32>(name).map(u64::from))
+}
+
+/// Reads the values of the sysctl node by the name, such as `vm.swap_info`,
+/// whose children are indexed from 0.
+#[cfg(target_os = "freebsd")]
+pub(crate) fn sysctl_values<T: Copy>(name: &str) -> Result<Vec<T>, Error> {
+    let c_name = CString::new(name).map_err(|e| format!("sysctl {} failed: {}", name, e))?;
+    let mut mib = [0 as c_int; 16];
+    let mut len = mib.len() - 1;
+    if unsafe { sysctlnametomib(c_name.as_ptr(), mib.as_mut_ptr(), &mut len) } != 0 {
+        return Err(format!(
+            "sysctl {} failed: {}",
+            name,
+            std::io::Error::last_os_error()
+        )
+        .into());
+    }
+    let mut values = Vec::new();
+    for index in 0.. {
+        mib[len] = index;
+        let mut value = MaybeUninit::<T>::uninit();
+        let mut size = size_of::<T>();
+        let ret = unsafe {
+            sysctl(
+                mib.as_ptr(),
+                len as u32 + 1,
+                value.as_mut_ptr() as *mut c_void,
+                &mut size,
+                std::ptr::null(),
+                0,
+            )
+        };
+        if ret != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::NotFound {
+                break;
+            }
+            return Err(format!("sysctl {}.{} failed: {}", name, index, err).into());
+        }
+        if size != size_of::<T>() {
+            return Err(format!("sysctl {} failed: unexpected size {}", name, size).into());
+        }
+        values.push(unsafe { value.assume_init() });
+    }
+    Ok(values)
+}
+
+/// Reads the statistics of the host of the flavor by `host_statistics64`,
+/// which passes the flavors other than those of 64 bits to `host_statistics`.
+/// The type must be of the size of the count of the flavor in the 32-bit
+/// integers.
+#[cfg(target_os = "macos")]
+pub(crate) fn host_statistics<T: Copy>(flavor: c_int) -> Result<T, Error> {
+    let mut info = MaybeUninit::<T>::uninit();
+    let mut count = (size_of::<T>() / size_of::<c_int>()) as u32;
+    let ret = unsafe {
+        let host = mach_host_self();
+        let ret = host_statistics64(host, flavor, info.as_mut_ptr() as *mut c_int, &mut count);
+        mach_port_deallocate(mach_task_self_, host);
+        ret
+    };
+    if ret != 0 {
+        return Err(format!("host_statistics {} failed: {:#x}", flavor, ret).into());
+    }
+    if count as usize * size_of::<c_int>() != size_of::<T>() {
+        return Err(format!(
+            "host_statistics {} failed: unexpected count {}",
+            flavor, count
+        )
+        .into());
+    }
+    Ok(unsafe { info.assume_init() })
+}
@@ -0,0 +1,233 @@
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ClientConfig, RootCertStore};
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::Error;
+
+/// The settings of the HTTP requests of the collectors, such as the status
+/// endpoints behind the mutual TLS.
+///
+/// The server certificates are verified by the custom CA bundles if added, or
+/// the bundled Mozilla roots otherwise. The proxy is taken from `HTTPS_PROXY`,
+/// `HTTP_PROXY`, or `ALL_PROXY` unless it is set explicitly. The services
+/// exposing the stats over the UNIX domain sockets, such as Docker or HAProxy,
+/// are requested by `unix_socket` like `curl --unix-socket`.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{HttpClient, NginxStatus};
+///
+/// let client = HttpClient::new()
+///     .ca_file("/etc/pki/internal-ca.pem")
+///     .client_certificate("/etc/pki/mackerel.pem", "/etc/pki/mackerel-key.pem");
+/// let status = NginxStatus::new("https://lb.internal/nginx_status").client(client);
+/// let values = status.fetch_values().unwrap();
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct HttpClient {
+    ca_files: Vec<PathBuf>,
+    client_certificate: Option<(PathBuf, PathBuf)>,
+    proxy: Option<String>,
+    unix_socket: Option<PathBuf>,
+}
+
+impl HttpClient {
+    pub fn new() -> HttpClient {
+        HttpClient::default()
+    }
+
+    /// Adds the PEM file of the CA certificates verifying the servers, which
+    /// replace the bundled roots.
+    pub fn ca_file(mut self, path: impl Into<PathBuf>) -> HttpClient {
+        self.ca_files.push(path.into());
+        self
+    }
+
+    /// Sets the PEM files of the client certificate chain and the private key
+    /// for the mutual TLS.
+    pub fn client_certificate(
+        mut self,
+        certificate: impl Into<PathBuf>,
+        key: impl Into<PathBuf>,
+    ) -> HttpClient {
+        self.client_certificate = Some((certificate.into(), key.into()));
+        self
+    }
+
+    /// Sets the proxy, such as `http://proxy.internal:3128`, overriding the
+    /// environment variables.
+    pub fn proxy(mut self, proxy: impl Into<String>) -> HttpClient {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Connects to the UNIX domain socket instead of the host of the URLs, such
+    /// as `/var/run/docker.sock`. The requests are sent by HTTP/1.1 without
+    /// TLS and the redirects are not followed.
+    pub fn unix_socket(mut self, path: impl Into<PathBuf>) -> HttpClient {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Requests the URL over the UNIX domain socket if set, and returns the
+    /// status code and the body.
+    pub(crate) fn request_unix(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(String, String)],
+        timeout: Duration,
+    ) -> Option<Result<(u16, Vec<u8>), Error>> {
+        let path = self.unix_socket.as_ref()?;
+        #[cfg(unix)]
+        {
+            Some(
+                request_unix(path, method, url, headers, timeout)
+                    .map_err(|err| format!("{}: {}", path.display(), err).into()),
+            )
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (path, method, url, headers, timeout);
+            Some(Err("UNIX domain socket is not supported".into()))
+        }
+    }
+
+    /// Returns the builder of the agent with the settings, loading the
+    /// certificate files.
+    pub(crate) fn agent_builder(&self) -> Result<ureq::AgentBuilder, Error> {
+        let mut builder = ureq::AgentBuilder::new();
+        builder = match &self.proxy {
+            Some(proxy) => builder
+                .proxy(ureq::Proxy::new(proxy).map_err(|err| format!("invalid proxy: {}", err))?),
+            None => builder.try_proxy_from_env(true),
+        };
+        if self.ca_files.is_empty() && self.client_certificate.is_none() {
+            return Ok(builder);
+        }
+        Ok(builder.tls_config(Arc::new(self.tls_config()?)))
+    }
+
+    fn tls_config(&self) -> Result<ClientConfig, Error> {
+        let mut roots = RootCertStore::empty();
+        if self.ca_files.is_empty() {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        }
+        for path in &self.ca_files {
+            for certificate in certificates(path)? {
+                roots
+                    .add(certificate)
+                    .map_err(|err| load_error(path, &err))?;
+            }
+        }
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|err| format!("configure TLS failed: {}", err))?
+            .with_root_certificates(roots);
+        Ok(match &self.client_certificate {
+            Some((certificate, key)) => builder
+                .with_client_auth_cert(
+                    certificates(certificate)?,
+                    PrivateKeyDer::from_pem_file(key).map_err(|err| load_error(key, &err))?,
+                )
+                .map_err(|err| load_error(key, &err))?,
+            None => builder.with_no_client_auth(),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn request_unix(
+    path: &Path,
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    timeout: Duration,
+) -> Result<(u16, Vec<u8>), std::io::Error> {
+    let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let (host, target) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let mut stream = std::os::unix::net::UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+        method,
+        target,
+        if host.is_empty() { "localhost" } else { host }
+    );
+    for (name, value) in headers {
+        request += &format!("{}: {}\r\n", name, value);
+    }
+    stream.write_all((request + "\r\n").as_bytes())?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .strip_prefix("HTTP/1.")
+        .and_then(|rest| rest.get(2..5))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| invalid("invalid status line"))?;
+    let (mut length, mut chunked) = (None, false);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                length = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid("invalid content length"))?,
+                );
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            }
+        }
+    }
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| invalid("invalid chunk"))?;
+            if size == 0 {
+                break;
+            }
+            reader.by_ref().take(size as u64).read_to_end(&mut body)?;
+            line.clear();
+            reader.read_line(&mut line)?;
+        }
+    } else if let Some(length) = length {
+        reader.take(length).read_to_end(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+    Ok((status, body))
+}
+
+fn certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| load_error(path, &err))?;
+    if certificates.is_empty() {
+        return Err(load_error(path, &"no certificate"));
+    }
+    Ok(certificates)
+}
+
+fn load_error(path: &Path, err: &dyn std::fmt::Display) -> Error {
+    format!("load {} failed: {}", path.display(), err).into()
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::check::{CheckResult, CheckStatus};
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::http::HttpClient;
+use crate::value::Value;
+
+const STATUS_CLASSES: [&str; 4] = ["2xx", "3xx", "4xx", "5xx"];
+
+/// A prober of the HTTP endpoint, which times the requests and counts the
+/// responses by the status class.
+///
+/// The probe sends the requests sequentially on each run, and emits the
+/// percentiles of the latencies, the numbers of the responses by the status
+/// class and the failed requests, and the availability (the percentage of the
+/// responses of 2xx or 3xx). The redirects are not followed.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::HttpProbe;
+///
+/// let probe = HttpProbe::new("api", "https://api.example.com/health")
+///     .header("Authorization", "Bearer token")
+///     .count(5);
+/// let values = probe.fetch_values().unwrap();
+/// ```
+pub struct HttpProbe {
+    name: String,
+    url: String,
+    method: String,
+    headers: Vec<(String, String)>,
+    count: usize,
+    timeout: Duration,
+    client: HttpClient,
+}
+
+/// The results of the requests of a probe.
+struct Results {
+    latencies: Vec<Duration>,
+    statuses: [usize; STATUS_CLASSES.len()],
+    errors: usize,
+    last_error: Option<String>,
+}
+
+impl HttpProbe {
+    pub fn new(name: &str, url: impl Into<String>) -> HttpProbe {
+        HttpProbe {
+            name: name
+                .chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                    _ => '_',
+                })
+                .collect(),
+            url: url.into(),
+            method: "GET".to_owned(),
+            headers: Vec::new(),
+            count: 3,
+            timeout: Duration::from_secs(10),
+            client: HttpClient::new(),
+        }
+    }
+
+    pub fn method(mut self, method: impl Into<String>) -> HttpProbe {
+        self.method = method.into();
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> HttpProbe {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the timeout of each request.
+    pub fn timeout(mut self, timeout: Duration) -> HttpProbe {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the client for the certificates and the proxy.
+    pub fn client(mut self, client: HttpClient) -> HttpProbe {
+        self.client = client;
+        self
+    }
+
+    /// Sets the number of the requests on each run.
+    pub fn count(mut self, count: usize) -> HttpProbe {
+        self.count = count.max(1);
+        self
+    }
+
+    /// Returns the graphs of the HTTP probes.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "http.latency.#",
+                label: "HTTP latency",
+                unit: "seconds",
+                metrics: [
+                    { name: "p50", label: "p50" },
+                    { name: "p90", label: "p90" },
+                    { name: "p99", label: "p99" },
+                    { name: "max", label: "Max" },
+                ],
+            },
+            crate::graph! {
+                name: "http.status.#",
+                label: "HTTP responses",
+                unit: "integer",
+                metrics: [
+                    { name: "2xx", label: "2xx", stacked: true },
+                    { name: "3xx", label: "3xx", stacked: true },
+                    { name: "4xx", label: "4xx", stacked: true },
+                    { name: "5xx", label: "5xx", stacked: true },
+                    { name: "error", label: "Error", stacked: true },
+                ],
+            },
+            crate::graph! {
+                name: "http.availability",
+                label: "HTTP availability",
+                unit: "percentage",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the probe. The latencies are omitted when
+    /// all the requests failed.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        let results = self.probe();
+        let mut values = HashMap::new();
+        if !results.latencies.is_empty() {
+            for (name, percentile) in [("p50", 50), ("p90", 90), ("p99", 99), ("max", 100)] {
+                values.insert(
+                    format!("http.latency.{}.{}", self.name, name),
+                    results.percentile(percentile).into(),
+                );
+            }
+        }
+        for (class, count) in STATUS_CLASSES.iter().zip(results.statuses) {
+            values.insert(
+                format!("http.status.{}.{}", self.name, class),
+                (count as f64).into(),
+            );
+        }
+        values.insert(
+            format!("http.status.{}.error", self.name),
+            (results.errors as f64).into(),
+        );
+        values.insert(
+            format!("http.availability.{}", self.name),
+            results.availability().into(),
+        );
+        Ok(values)
+    }
+
+    /// Checks the availability and the maximum latency of the probe.
+    pub fn check(&self, warning: Duration, critical: Duration) -> CheckResult {
+        let results = self.probe();
+        let succeeded = results.statuses[0] + results.statuses[1];
+        let max = results.percentile(100);
+        let status = if succeeded == 0 || max >= critical {
+            CheckStatus::Critical
+        } else if succeeded < self.count || max >= warning {
+            CheckStatus::Warning
+        } else {
+            CheckStatus::Ok
+        };
+        let mut message = format!(
+            "{} of {} requests to {} succeeded",
+            succeeded, self.count, self.url
+        );
+        if !results.latencies.is_empty() {
+            message += &format!(", max latency {:.3}s", max.as_secs_f64());
+        }
+        if let Some(err) = results.last_error {
+            message += &format!(" ({})", err);
+        }
+        CheckResult::new(status, message)
+    }
+
+    fn probe(&self) -> Results {
+        let mut results = Results {
+            latencies: Vec::with_capacity(self.count),
+            statuses: [0; STATUS_CLASSES.len()],
+            errors: 0,
+            last_error: None,
+        };
+        let agent = match self.client.agent_builder() {
+            Ok(builder) => builder.timeout(self.timeout).redirects(0).build(),
+            Err(err) => {
+                results.errors = self.count;
+                results.last_error = Some(err.to_string());
+                return results;
+            }
+        };
+        // the latency includes reading the body
+        let request = || -> Result<u16, String> {
+            if let Some(result) =
+                self.client
+                    .request_unix(&self.method, &self.url, &self.headers, self.timeout)
+            {
+                return result
+                    .map(|(status, _)| status)
+                    .map_err(|err| err.to_string());
+            }
+            let mut request = agent.request(&self.method, &self.url);
+            for (name, value) in &self.headers {
+                request = request.set(name, value);
+            }
+            let response = match request.call() {
+                Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+                Err(err) => return Err(err.to_string()),
+            };
+            let status = response.status();
+            std::io::copy(&mut response.into_reader(), &mut std::io::sink())
+                .map_err(|err| err.to_string())?;
+            Ok(status)
+        };
+        for _ in 0..self.count {
+            let started = Instant::now();
+            let status = match request() {
+                Ok(status) => status,
+                Err(err) => {
+                    results.errors += 1;
+                    results.last_error = Some(err);
+                    continue;
+                }
+            };
+            results.latencies.push(started.elapsed());
+            match status {
+                200..=599 => results.statuses[status as usize / 100 - 2] += 1,
+                _ => {
+                    results.errors += 1;
+                    results.last_error = Some(format!("unexpected status: {}", status));
+                }
+            }
+        }
+        results.latencies.sort();
+        results
+    }
+}
+
+impl Results {
+    /// Returns the percentile of the latencies by the nearest-rank method.
+    fn percentile(&self, percentile: usize) -> Duration {
+        let rank = (percentile * self.latencies.len()).div_ceil(100).max(1);
+        self.latencies.get(rank - 1).copied().unwrap_or_default()
+    }
+
+    fn availability(&self) -> f64 {
+        let total = self.statuses.iter().sum::<usize>() + self.errors;
+        if total == 0 {
+            return 0.0;
+        }
+        (self.statuses[0] + self.statuses[1]) as f64 * 100.0 / total as f64
+    }
+}
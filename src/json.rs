@@ -0,0 +1,382 @@
+//! A lightweight JSON serializer and parser for the meta output and the plugin
+//! state, which keeps the output path free from `serde_json`.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::graph::Graph;
+
+/// Writes the string as a JSON string.
+pub(crate) fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    write_escaped(out, s);
+    out.push('"');
+}
+
+fn write_escaped(out: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Writes the graph definitions in the meta output format, keyed by the names
+/// of the graphs joined to the metric key prefix.
+///
+/// The graphs are streamed to the writer one by one through a reused buffer,
+/// so the memory does not grow with the size of the graphs, and the names are
+/// joined on writing without building the strings. The graphs of the same
+/// name are written only for the last one, so that the keys are unique.
+pub(crate) fn write_graphs_json<'a>(
+    out: &mut dyn std::io::Write,
+    prefix: &str,
+    graphs: impl IntoIterator<Item = &'a Graph>,
+) -> std::io::Result<()> {
+    let mut graphs = graphs.into_iter().collect::<Vec<_>>();
+    let mut names = HashSet::with_capacity(graphs.len());
+    graphs.reverse();
+    graphs.retain(|graph| names.insert(graph.name.as_str()));
+    graphs.reverse();
+    out.write_all(b"{\"graphs\":{")?;
+    let mut buf = String::new();
+    for (i, graph) in graphs.into_iter().enumerate() {
+        buf.clear();
+        if i > 0 {
+            buf.push(',');
+        }
+        buf.push('"');
+        write_escaped(&mut buf, prefix);
+        if !prefix.is_empty() && !graph.name.is_empty() {
+            buf.push('.');
+        }
+        write_escaped(&mut buf, &graph.name);
+        buf.push('"');
+        buf.push_str(":{\"label\":");
+        write_string(&mut buf, &graph.label);
+        buf.push_str(",\"unit\":");
+        write_string(&mut buf, &graph.unit.to_string());
+        buf.push_str(",\"metrics\":[");
+        for (j, metric) in graph.metrics.iter().enumerate() {
+            if j > 0 {
+                buf.push(',');
+            }
+            buf.push_str("{\"name\":");
+            write_string(&mut buf, &metric.name);
+            buf.push_str(",\"label\":");
+            write_string(&mut buf, &metric.label);
+            buf.push_str(",\"stacked\":");
+            buf.push_str(if metric.stacked { "true" } else { "false" });
+            buf.push('}');
+        }
+        buf.push_str("]}");
+        out.write_all(buf.as_bytes())?;
+    }
+    out.write_all(b"}}\n")
+}
+
+/// Returns the JSON object of the timestamp and the metric values, skipping
+/// the values which are not finite.
+pub(crate) fn values_json(timestamp: i64, values: &HashMap<String, f64>) -> String {
+    let mut out = format!("{{\"timestamp\":{},\"values\":{{", timestamp);
+    for (i, (name, value)) in values
+        .iter()
+        .filter(|(_, value)| value.is_finite())
+        .enumerate()
+    {
+        if i > 0 {
+            out.push(',');
+        }
+        write_string(&mut out, name);
+        out.push_str(&format!(":{:?}", value));
+    }
+    out.push_str("}}");
+    out
+}
+
+/// Parses the JSON object of the timestamp and the metric values.
+pub(crate) fn parse_values(s: &str) -> Result<(i64, HashMap<String, f64>), String> {
+    let mut parser = Parser { s, pos: 0 };
+    let (mut timestamp, mut values) = (None, HashMap::new());
+    parser.parse_object(|parser, key| {
+        match key.as_str() {
+            "timestamp" => {
+                timestamp = Some(
+                    parser
+                        .parse_number()?
+                        .parse()
+                        .map_err(|_| "invalid timestamp")?,
+                )
+            }
+            "values" => parser.parse_object(|parser, name| {
+                // the values which are not finite were written as null
+                if parser.peek() == Some('n') {
+                    return parser.expect_literal("null");
+                }
+                let value = parser.parse_number()?;
+                values.insert(name, value.parse().map_err(|_| "invalid value")?);
+                Ok(())
+            })?,
+            _ => return Err(format!("unknown key: {}", key)),
+        }
+        Ok(())
+    })?;
+    parser.skip_whitespace();
+    if parser.pos < parser.s.len() {
+        return Err("unexpected trailing characters".to_owned());
+    }
+    Ok((timestamp.ok_or("timestamp not found")?, values))
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.s[self.pos..].starts_with(c) {
+            self.pos += c.len_utf8();
+            Ok(())
+        } else {
+            Err(format!("expected {:?} at {}", c, self.pos))
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &str) -> Result<(), String> {
+        if self.s[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(format!("expected {} at {}", literal, self.pos))
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.s[self.pos..].chars().next()
+    }
+
+    fn parse_object(
+        &mut self,
+        mut f: impl FnMut(&mut Self, String) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.expect('{')?;
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            let key = self.parse_string()?;
+            self.expect(':')?;
+            f(self, key)?;
+            if self.peek() == Some(',') {
+                self.pos += 1;
+            } else {
+                return self.expect('}');
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        let mut chars = self.s[self.pos..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('/') => out.push('/'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('u') => {
+                        let mut code = parse_hex(&mut chars)?;
+                        if (0xd800..0xdc00).contains(&code) {
+                            if chars.next().map(|(_, c)| c) != Some('\\')
+                                || chars.next().map(|(_, c)| c) != Some('u')
+                            {
+                                return Err("invalid surrogate pair".to_owned());
+                            }
+                            let low = parse_hex(&mut chars)?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err("invalid surrogate pair".to_owned());
+                            }
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        out.push(char::from_u32(code).ok_or("invalid unicode escape")?);
+                    }
+                    _ => return Err("invalid escape".to_owned()),
+                },
+                c => out.push(c),
+            }
+        }
+        Err("unterminated string".to_owned())
+    }
+
+    fn parse_number(&mut self) -> Result<&str, String> {
+        self.skip_whitespace();
+        let rest = &self.s[self.pos..];
+        let len = rest
+            .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(format!("expected number at {}", self.pos));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+}
+
+fn parse_hex(chars: &mut std::str::CharIndices) -> Result<u32, String> {
+    let mut code = 0;
+    for _ in 0..4 {
+        let digit = chars
+            .next()
+            .and_then(|(_, c)| c.to_digit(16))
+            .ok_or("invalid unicode escape")?;
+        code = code * 16 + digit;
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("foo", r#""foo""#)]
+    #[case("a\"b\\c", r#""a\"b\\c""#)]
+    #[case("\n\r\t\u{1}", r#""\n\r\t\u0001""#)]
+    #[case("日本語", r#""日本語""#)]
+    fn test_write_string(#[case] s: &str, #[case] expected: &str) {
+        let mut out = String::new();
+        write_string(&mut out, s);
+        assert_eq!(out, expected);
+        let mut parser = Parser { s: &out, pos: 0 };
+        assert_eq!(parser.parse_string(), Ok(s.to_owned()));
+    }
+
+    #[test]
+    fn test_write_graphs_json() {
+        let graphs = [
+            crate::graph! {
+                name: "foo",
+                label: "Foo \"bar\"",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1", stacked: true }],
+            },
+            crate::graph! {
+                name: "baz",
+                label: "Baz",
+                unit: "bytes/sec",
+                metrics: [{ name: "qux", label: "Qux" }],
+            },
+            crate::graph! {
+                name: "foo",
+                label: "Foo",
+                unit: "integer",
+                metrics: [{ name: "bar", label: "Bar" }],
+            },
+        ];
+        let mut out = Vec::new();
+        write_graphs_json(&mut out, "x", &graphs[..2]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"{"graphs":{"x.foo":{"label":"Foo \"bar\"","unit":"integer","#,
+                r#""metrics":[{"name":"*","label":"%1","stacked":true}]},"#,
+                r#""x.baz":{"label":"Baz","unit":"bytes/sec","#,
+                r#""metrics":[{"name":"qux","label":"Qux","stacked":false}]}}}"#,
+                "\n"
+            )
+        );
+        let mut out = Vec::new();
+        write_graphs_json(&mut out, "x", &graphs).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"{"graphs":{"x.baz":{"label":"Baz","unit":"bytes/sec","#,
+                r#""metrics":[{"name":"qux","label":"Qux","stacked":false}]},"#,
+                r#""x.foo":{"label":"Foo","unit":"integer","#,
+                r#""metrics":[{"name":"bar","label":"Bar","stacked":false}]}}}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_values_json() {
+        let values = HashMap::from([
+            ("foo.bar".to_owned(), 1.5),
+            ("foo.\"baz\"".to_owned(), -2.0),
+            ("foo.nan".to_owned(), f64::NAN),
+            ("foo.large".to_owned(), 1e300),
+        ]);
+        let json = values_json(1700000000, &values);
+        let (timestamp, parsed) = parse_values(&json).unwrap();
+        assert_eq!(timestamp, 1700000000);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed["foo.bar"], 1.5);
+        assert_eq!(parsed["foo.\"baz\""], -2.0);
+        assert_eq!(parsed["foo.large"], 1e300);
+    }
+
+    #[rstest]
+    #[case(r#"{"timestamp":1700000000,"values":{}}"#, 1700000000, vec![])]
+    #[case(
+        r#" { "values" : { "aé😀" : 1.0e2 } , "timestamp" : -1 } "#,
+        -1,
+        vec![("aé😀", 100.0)]
+    )]
+    #[case(r#"{"timestamp":1}"#, 1, vec![])]
+    #[case(r#"{"timestamp":1,"values":{"a":null,"b":2}}"#, 1, vec![("b", 2.0)])]
+    fn test_parse_values(
+        #[case] s: &str,
+        #[case] timestamp: i64,
+        #[case] values: Vec<(&str, f64)>,
+    ) {
+        assert_eq!(
+            parse_values(s),
+            Ok((
+                timestamp,
+                values
+                    .into_iter()
+                    .map(|(name, value)| (name.to_owned(), value))
+                    .collect()
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case(r#"{"values":{}}"#, "timestamp not found")]
+    #[case(r#"{"timestamp":1,"values":{"a":nan}}"#, "expected null at 29")]
+    #[case(r#"{"timestamp":1,"values":{"a":"1"}}"#, "expected number at 29")]
+    #[case(r#"{"timestamp":1,"foo":1}"#, "unknown key: foo")]
+    #[case(r#"{"timestamp":1}}"#, "unexpected trailing characters")]
+    #[case(r#"{"timestamp":1"#, "expected '}' at 14")]
+    #[case(r#"{"timestamp"#, "unterminated string")]
+    #[case(r#"{"\ud800":1}"#, "invalid surrogate pair")]
+    fn test_parse_values_error(#[case] s: &str, #[case] expected: &str) {
+        assert_eq!(parse_values(s), Err(expected.to_owned()));
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::process::Command;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// The columns of `jstat -gc` and their graphs, where the capacities are in
+/// KB and the times are in seconds.
+const JSTAT_COLUMNS: [(&str, &str); 18] = [
+    ("S0C", "new_space"),
+    ("S1C", "new_space"),
+    ("S0U", "new_space"),
+    ("S1U", "new_space"),
+    ("EC", "new_space"),
+    ("EU", "new_space"),
+    ("OC", "old_space"),
+    ("OU", "old_space"),
+    ("MC", "metaspace"),
+    ("MU", "metaspace"),
+    ("CCSC", "metaspace"),
+    ("CCSU", "metaspace"),
+    ("YGC", "gc_events"),
+    ("YGCT", "gc_time"),
+    ("FGC", "gc_events"),
+    ("FGCT", "gc_time"),
+    ("CGC", "gc_events"),
+    ("CGCT", "gc_time"),
+];
+
+/// A collector of the heap and the garbage collection of the JVM, whose
+/// metric names are compatible with mackerel-plugin-jvm.
+///
+/// The metrics are read from `jstat -gc`, or parsed from the GC logs of the
+/// unified logging (`-Xlog:gc`) or `-XX:+PrintGCDetails`. The events and the
+/// times of the garbage collection are counters to be differentiated, and the
+/// spaces are in bytes. The metric keys are prefixed by `jvm.` and the name of
+/// the JVM, so the graphs are shared by the JVMs of the plugin.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::JvmStats;
+///
+/// let stats = JvmStats::new("tomcat");
+/// let pid = std::fs::read_to_string("/var/run/tomcat.pid").unwrap();
+/// let values = stats.fetch_values(pid.trim().parse().unwrap()).unwrap();
+/// ```
+pub struct JvmStats {
+    name: String,
+    command: OsString,
+}
+
+impl JvmStats {
+    pub fn new(name: &str) -> JvmStats {
+        JvmStats {
+            name: name
+                .chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                    _ => '_',
+                })
+                .collect(),
+            command: "jstat".into(),
+        }
+    }
+
+    /// Sets the command of `jstat`, which is required to be run by the user
+    /// of the JVM.
+    pub fn command(mut self, command: impl Into<OsString>) -> JvmStats {
+        self.command = command.into();
+        self
+    }
+
+    /// Returns the graphs of the JVMs.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "jvm.#.gc_events",
+                label: "JVM GC events",
+                unit: "integer",
+                metrics: [
+                    { name: "YGC", label: "Young GC event", diff: true },
+                    { name: "FGC", label: "Full GC event", diff: true },
+                    { name: "CGC", label: "Concurrent GC event", diff: true },
+                ],
+            },
+            crate::graph! {
+                name: "jvm.#.gc_time",
+                label: "JVM GC time (sec)",
+                unit: "float",
+                metrics: [
+                    { name: "YGCT", label: "Young GC time", diff: true },
+                    { name: "FGCT", label: "Full GC time", diff: true },
+                    { name: "CGCT", label: "Concurrent GC time", diff: true },
+                ],
+            },
+            crate::graph! {
+                name: "jvm.#.heap",
+                label: "JVM Heap memory",
+                unit: "bytes",
+                metrics: [
+                    { name: "used", label: "Used" },
+                    { name: "committed", label: "Committed" },
+                ],
+            },
+            crate::graph! {
+                name: "jvm.#.new_space",
+                label: "JVM New Space memory",
+                unit: "bytes",
+                metrics: [
+                    { name: "EC", label: "Eden current" },
+                    { name: "EU", label: "Eden used" },
+                    { name: "S0C", label: "Survivor0 current" },
+                    { name: "S0U", label: "Survivor0 used" },
+                    { name: "S1C", label: "Survivor1 current" },
+                    { name: "S1U", label: "Survivor1 used" },
+                ],
+            },
+            crate::graph! {
+                name: "jvm.#.old_space",
+                label: "JVM Old Space memory",
+                unit: "bytes",
+                metrics: [
+                    { name: "OC", label: "Old current" },
+                    { name: "OU", label: "Old used" },
+                ],
+            },
+            crate::graph! {
+                name: "jvm.#.metaspace",
+                label: "JVM Metaspace",
+                unit: "bytes",
+                metrics: [
+                    { name: "MC", label: "Metaspace current" },
+                    { name: "MU", label: "Metaspace used" },
+                    { name: "CCSC", label: "Compressed Class Space current" },
+                    { name: "CCSU", label: "Compressed Class Space used" },
+                ],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the JVM of the process ID by `jstat -gc`.
+    pub fn fetch_values(&self, pid: u32) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new(), pid)
+    }
+
+    /// Returns the metric values of the JVM of the process ID by `jstat -gc`,
+    /// killing `jstat` when the fetch is cancelled.
+    pub fn fetch_values_ctx(
+        &self,
+        ctx: &Context,
+        pid: u32,
+    ) -> Result<HashMap<String, Value>, Error> {
+        let output = ctx
+            .output(Command::new(&self.command).arg("-gc").arg(pid.to_string()))
+            .map_err(|e| format!("jstat -gc failed: {}", e))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() {
+            // jstat reports the errors to stdout
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = if stderr.trim().is_empty() {
+                stdout.trim()
+            } else {
+                stderr.trim()
+            };
+            return Err(format!("jstat -gc failed: {}", message).into());
+        }
+        self.parse_jstat(&stdout)
+    }
+
+    /// Parses the output of `jstat -gc`, which is the header and the rows of
+    /// the samples, where the last row is used. The columns unavailable in
+    /// the JVM, shown as `-`, are omitted.
+    ///
+    /// ```text
+    ///  S0C    S1C    S0U    S1U      EC       EU        OC         OU       MC     MU    CCSC   CCSU   YGC     YGCT    FGC    FGCT    CGC    CGCT     GCT
+    ///  0.0   2048.0  0.0   2048.0 26624.0   4096.0   233472.0    6214.3  11776.0 11468.3 1280.0 1125.4      3    0.012   0      0.000   2      0.003    0.015
+    /// ```
+    pub fn parse_jstat(&self, text: &str) -> Result<HashMap<String, Value>, Error> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let (Some(header), Some(row)) = (lines.next(), lines.next_back()) else {
+            return Err(format!("parse the jstat output failed: {:?}", text.trim()).into());
+        };
+        let mut values = HashMap::new();
+        let (mut used, mut committed) = (0.0, 0.0);
+        for (column, value) in header.split_whitespace().zip(row.split_whitespace()) {
+            let Some(&(_, graph)) = JSTAT_COLUMNS.iter().find(|&&(name, _)| name == column) else {
+                continue;
+            };
+            if value == "-" {
+                continue;
+            }
+            let mut value = value.parse::<f64>().map_err(|_| {
+                format!(
+                    "parse the jstat output failed: invalid {}: {:?}",
+                    column, value
+                )
+            })?;
+            if graph.ends_with("space") {
+                value *= 1024.0;
+                if graph != "metaspace" {
+                    if column.ends_with('U') {
+                        used += value;
+                    } else {
+                        committed += value;
+                    }
+                }
+            }
+            values.insert(self.key(graph, column), value.into());
+        }
+        if values.is_empty() {
+            return Err(format!("parse the jstat output failed: {:?}", header.trim()).into());
+        }
+        values.insert(self.key("heap", "used"), used.into());
+        values.insert(self.key("heap", "committed"), committed.into());
+        Ok(values)
+    }
+
+    /// Parses the GC log, and returns the numbers and the total times of the
+    /// young, full, and concurrent (remark and cleanup) pauses, and the heap
+    /// after the last pause. The counters are the totals of the given log, so
+    /// the whole log of the running JVM should be passed.
+    ///
+    /// ```text
+    /// [0.210s][info][gc] GC(0) Pause Young (Normal) (G1 Evacuation Pause) 24M->4M(256M) 3.456ms
+    /// 2.345: [Full GC (Ergonomics) [PSYoungGen: ...] 12345K->6789K(98304K), [Metaspace: ...], 0.0234567 secs]
+    /// ```
+    pub fn parse_gc_log(&self, text: &str) -> HashMap<String, Value> {
+        let mut counts = [(0.0, 0.0); 3];
+        let mut heap = None;
+        for line in text.lines() {
+            let (kind, seconds) = if let Some((_, event)) = line.split_once("] GC(") {
+                // the unified logging: GC(0) Pause Young ... 24M->4M(256M) 3.456ms
+                let Some((_, event)) = event.split_once(") Pause ") else {
+                    continue;
+                };
+                let kind = match event.split_whitespace().next() {
+                    Some("Young") => 0,
+                    Some("Full") => 1,
+                    Some("Remark" | "Cleanup") => 2,
+                    _ => continue,
+                };
+                let Some(seconds) = event
+                    .rsplit(' ')
+                    .next()
+                    .and_then(|time| time.strip_suffix("ms"))
+                    .and_then(|time| time.parse::<f64>().ok())
+                else {
+                    continue;
+                };
+                (kind, seconds / 1000.0)
+            } else if let Some(index) = line.find("[GC (").or_else(|| line.find("[Full GC (")) {
+                // -XX:+PrintGCDetails: [GC (...) ... 123K->45K(678K), 0.0012 secs]
+                let kind = if line[index..].starts_with("[GC") {
+                    0
+                } else {
+                    1
+                };
+                let Some(seconds) = line
+                    .split(" [Times:")
+                    .next()
+                    .and_then(|line| line.rsplit_once(" secs]"))
+                    .and_then(|(line, _)| line.rsplit([' ', ',']).next())
+                    .and_then(|time| time.parse::<f64>().ok())
+                else {
+                    continue;
+                };
+                (kind, seconds)
+            } else {
+                continue;
+            };
+            counts[kind].0 += 1.0;
+            counts[kind].1 += seconds;
+            heap = line
+                .split_whitespace()
+                .filter_map(|word| {
+                    let (_, after) = word.split_once("->")?;
+                    let (used, committed) = after.trim_end_matches(',').split_once('(')?;
+                    Some((size(used)?, size(committed.strip_suffix(')')?)?))
+                })
+                .next_back()
+                .or(heap);
+        }
+        let mut values = HashMap::new();
+        for ((count, time), (events, times)) in
+            counts
+                .into_iter()
+                .zip([("YGC", "YGCT"), ("FGC", "FGCT"), ("CGC", "CGCT")])
+        {
+            values.insert(self.key("gc_events", events), count.into());
+            values.insert(self.key("gc_time", times), time.into());
+        }
+        if let Some((used, committed)) = heap {
+            values.insert(self.key("heap", "used"), used.into());
+            values.insert(self.key("heap", "committed"), committed.into());
+        }
+        values
+    }
+
+    fn key(&self, graph: &str, name: &str) -> String {
+        format!("jvm.{}.{}.{}", self.name, graph, name)
+    }
+}
+
+/// Parses the size in the GC logs, such as `24M` or `12345K`.
+fn size(size: &str) -> Option<f64> {
+    let (size, scale) = match size.as_bytes().last()? {
+        b'B' => (&size[..size.len() - 1], 1.0),
+        b'K' => (&size[..size.len() - 1], 1024.0),
+        b'M' => (&size[..size.len() - 1], 1024.0 * 1024.0),
+        b'G' => (&size[..size.len() - 1], 1024.0 * 1024.0 * 1024.0),
+        _ => return None,
+    };
+    Some(size.parse::<f64>().ok()? * scale)
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// A collector of the lag of the Kafka consumer groups by the HTTP API of
+/// Burrow.
+///
+/// The metrics are the total lag of each group, and the total and the maximum
+/// lag of the partitions of each topic consumed by the group. The lag of each
+/// partition is also collected if enabled. The groups default to all the
+/// consumer groups of the cluster.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::KafkaLag;
+///
+/// let lag = KafkaLag::new("http://localhost:8000", "local")
+///     .group("orders")
+///     .group("payments");
+/// let values = lag.fetch_values().unwrap();
+/// ```
+pub struct KafkaLag {
+    endpoint: String,
+    cluster: String,
+    groups: Vec<String>,
+    partitions: bool,
+    agent: ureq::Agent,
+}
+
+impl KafkaLag {
+    pub fn new(endpoint: impl Into<String>, cluster: impl Into<String>) -> KafkaLag {
+        KafkaLag {
+            endpoint: endpoint.into().trim_end_matches('/').to_owned(),
+            cluster: cluster.into(),
+            groups: Vec::new(),
+            partitions: false,
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(10))
+                .build(),
+        }
+    }
+
+    /// Adds the consumer group to collect the lag of.
+    pub fn group(mut self, group: impl Into<String>) -> KafkaLag {
+        self.groups.push(group.into());
+        self
+    }
+
+    /// Sets whether to collect the lag of each partition, which can be many
+    /// metrics for the topics of many partitions.
+    pub fn partitions(mut self, partitions: bool) -> KafkaLag {
+        self.partitions = partitions;
+        self
+    }
+
+    /// Returns the graphs of the consumer lag.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "kafka.group_lag",
+                label: "Kafka consumer group lag",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "kafka.lag.#",
+                label: "Kafka consumer lag by topic",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%2" }],
+            },
+            crate::graph! {
+                name: "kafka.max_lag.#",
+                label: "Kafka consumer max partition lag by topic",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%2" }],
+            },
+            crate::graph! {
+                name: "kafka.partition_lag.#.#",
+                label: "Kafka consumer lag by partition",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%3" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the consumer lag. The partitions whose lag
+    /// is not evaluated by Burrow yet are omitted.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        let groups = if self.groups.is_empty() {
+            let response = self.get(&format!("/v3/kafka/{}/consumer", self.cluster))?;
+            response["consumers"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|group| Some(group.as_str()?.to_owned()))
+                .collect()
+        } else {
+            self.groups.clone()
+        };
+        let mut values = HashMap::new();
+        for group in groups {
+            let response = self.get(&format!(
+                "/v3/kafka/{}/consumer/{}/lag",
+                self.cluster, group
+            ))?;
+            let group = metric_key(&group);
+            let mut topics = HashMap::<String, (u64, u64)>::new();
+            for partition in response["status"]["partitions"]
+                .as_array()
+                .into_iter()
+                .flatten()
+            {
+                let (Some(topic), Some(id), Some(lag)) = (
+                    partition["topic"].as_str(),
+                    partition["partition"].as_u64(),
+                    partition["current_lag"].as_u64(),
+                ) else {
+                    continue;
+                };
+                let topic = metric_key(topic);
+                if self.partitions {
+                    values.insert(
+                        format!("kafka.partition_lag.{}.{}.{}", group, topic, id),
+                        (lag as f64).into(),
+                    );
+                }
+                let (total, max) = topics.entry(topic).or_default();
+                *total += lag;
+                *max = (*max).max(lag);
+            }
+            let mut group_lag = 0;
+            for (topic, (total, max)) in topics {
+                values.insert(
+                    format!("kafka.lag.{}.{}", group, topic),
+                    (total as f64).into(),
+                );
+                values.insert(
+                    format!("kafka.max_lag.{}.{}", group, topic),
+                    (max as f64).into(),
+                );
+                group_lag += total;
+            }
+            values.insert(
+                format!("kafka.group_lag.{}", group),
+                (group_lag as f64).into(),
+            );
+        }
+        Ok(values)
+    }
+
+    fn get(&self, path: &str) -> Result<serde_json::Value, Error> {
+        match self.agent.get(&(self.endpoint.clone() + path)).call() {
+            Ok(response) => serde_json::from_reader(response.into_reader())
+                .map_err(|err| format!("GET {} failed: {}", path, err).into()),
+            Err(ureq::Error::Status(status, response)) => {
+                let response: serde_json::Value =
+                    serde_json::from_reader(response.into_reader()).unwrap_or_default();
+                let message = response["message"].as_str().unwrap_or_default();
+                Err(format!("GET {} failed: {} {}", path, status, message).into())
+            }
+            Err(err) => Err(format!("GET {} failed: {}", path, err).into()),
+        }
+    }
+}
+
+fn metric_key(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+/// A transformation applied to the wildcard values on expanding labels.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Transform {
+    Uppercase,
+    Lowercase,
+    StripPrefix(String),
+    Map(HashMap<String, String>),
+}
+
+impl Transform {
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Transform::Uppercase => value.to_uppercase(),
+            Transform::Lowercase => value.to_lowercase(),
+            Transform::StripPrefix(prefix) => value
+                .strip_prefix(prefix.as_str())
+                .unwrap_or(value)
+                .to_owned(),
+            Transform::Map(table) => table.get(value).map_or(value, String::as_str).to_owned(),
+        }
+    }
+}
+
+/// Expands the placeholders (`%1`, `%2`, ...) in the label with the wildcard
+/// values, applying the transformations in order.
+///
+/// ```rust
+/// use mackerel_plugin::{expand_label, Transform};
+///
+/// assert_eq!(
+///     expand_label("Disk %1", &["dev_sda"], &[Transform::StripPrefix("dev_".into()), Transform::Uppercase]),
+///     "Disk SDA",
+/// );
+/// ```
+pub fn expand_label(label: &str, values: &[&str], transforms: &[Transform]) -> String {
+    let mut result = String::with_capacity(label.len());
+    let mut chars = label.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '%' {
+            let mut j = i + 1;
+            while let Some(&(k, d)) = chars.peek() {
+                if !d.is_ascii_digit() {
+                    break;
+                }
+                j = k + 1;
+                chars.next();
+            }
+            match label[i + 1..j].parse::<usize>() {
+                Ok(n) if 1 <= n && n <= values.len() => {
+                    result.push_str(
+                        &transforms
+                            .iter()
+                            .fold(values[n - 1].to_owned(), |value, transform| {
+                                transform.apply(&value)
+                            }),
+                    );
+                }
+                _ => result.push_str(&label[i..j]),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
@@ -1,9 +1,251 @@
+#[cfg(feature = "json")]
+pub use crate::aggregator::Aggregator;
+#[cfg(feature = "api")]
+pub use crate::api::Client;
+#[cfg(any(feature = "cloudwatch", feature = "sqs"))]
+pub use crate::aws::Credentials;
+pub use crate::bpf::{BpfMap, PerCpu};
+#[cfg(feature = "json")]
+pub use crate::cache::Cache;
+#[cfg(feature = "tls")]
+pub use crate::certificate::CertificateExpiry;
+pub use crate::check::{CheckResult, CheckStatus};
+pub use crate::clock::{Clock, Epoch, SystemClock, Timestamping};
+#[cfg(feature = "cloudwatch")]
+pub use crate::cloudwatch::{CloudWatch, MetricQuery};
+pub use crate::connection::Connections;
+pub use crate::context::{CancellationToken, Context};
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+pub use crate::cpu::CpuStats;
+pub use crate::credentials::{Secret, SecretSource};
+pub use crate::diff::{definitions_diff, Definitions, DefinitionsDiff, GraphDiff, MetricDiff};
+pub use crate::discovery::Discovery;
+pub use crate::dns_probe::{DnsProbe, RecordType};
+#[cfg(feature = "elasticsearch")]
+pub use crate::elasticsearch::ElasticsearchStats;
+pub use crate::error::Error;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+pub use crate::filesystem::Filesystems;
+pub use crate::filter::Filter;
+#[cfg(feature = "http")]
+pub use crate::fluentd::FluentdStats;
+pub use crate::format::{format_value, DefaultFormatter, ValueFormatter};
+#[cfg(feature = "gcp")]
+pub use crate::gcp::{CloudMonitoring, TimeSeriesQuery};
+#[cfg(feature = "nvml")]
+pub use crate::gpu::GpuDevices;
+#[doc(hidden)]
+pub use crate::graph::is_valid_graph_name;
 pub use crate::graph::Graph;
+#[cfg(feature = "http")]
+pub use crate::haproxy::HaproxyStats;
+#[cfg(feature = "http")]
+pub use crate::http::HttpClient;
+#[cfg(feature = "http")]
+pub use crate::http_probe::HttpProbe;
+pub use crate::jvm::JvmStats;
+#[cfg(feature = "kafka")]
+pub use crate::kafka::KafkaLag;
+pub use crate::label::{expand_label, Transform};
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+pub use crate::memory::MemoryStats;
 pub use crate::metric::Metric;
-pub use crate::plugin::Plugin;
+#[doc(hidden)]
+pub use crate::metric::{is_valid_metric_name, IntoField};
+pub use crate::metric_map::{MetricMap, MetricScope};
+#[cfg(feature = "json")]
+pub use crate::migration::PrefixMigration;
+#[cfg(feature = "json")]
+pub use crate::mongodb::MongoDbStats;
+pub use crate::monitor::{Monitor, MonitorGenerator};
+#[cfg(feature = "mysql")]
+pub use crate::mysql::MySqlStats;
+pub use crate::ntp::{NtpDaemon, NtpOffset};
+#[cfg(feature = "json")]
+pub use crate::output::{parse_definitions, parse_meta_output};
+pub use crate::output::{parse_values, META_HEADER};
+#[cfg(feature = "json")]
+pub use crate::packaging::{Package, DEFAULT_TARGETS};
+#[cfg(windows)]
+pub use crate::perf_counter::PerfCounters;
+#[cfg(feature = "ping")]
+pub use crate::ping::{PingProbe, PingProtocol};
+pub use crate::plugin::{Plugin, SyncPlugin};
+#[cfg(feature = "postgres")]
+pub use crate::postgres::PostgresStats;
+pub use crate::process::Processes;
+pub use crate::queue::{Beanstalkd, QueueBackend, QueueDepth, QueueStats};
+#[cfg(feature = "rabbitmq")]
+pub use crate::rabbitmq::RabbitMq;
+#[cfg(feature = "json")]
+pub use crate::rate_limit::RateLimiter;
+pub use crate::rename::Rename;
+#[cfg(feature = "api")]
+pub use crate::replay::{Replay, ReplayStats};
+#[cfg(feature = "api")]
+pub use crate::resource::ResourceSink;
+#[cfg(feature = "scaffold")]
+pub use crate::scaffold::Scaffold;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub use crate::sensor::Sensors;
+#[cfg(feature = "http")]
+pub use crate::server_status::{ApacheStatus, NginxStatus, PhpFpmStatus};
+#[cfg(feature = "api")]
+pub use crate::service::ServiceSink;
+#[cfg(feature = "json")]
+pub use crate::sidekiq::{Resque, Sidekiq};
+pub use crate::sink::{
+    estimate_output_size, FileSink, JsonSink, LtsvSink, MetricSink, TeeSink, TsvSink,
+};
+#[cfg(feature = "smart")]
+pub use crate::smart::SmartDisks;
+#[cfg(feature = "sqs")]
+pub use crate::sqs::Sqs;
+pub use crate::squid::SquidStats;
+pub use crate::staleness::{StaleAction, Staleness};
+pub use crate::state::{workdir, FileStateStore, MemoryStateStore, StateStore};
+pub use crate::systemd::SystemdUnits;
+pub use crate::template::GraphTemplate;
+#[cfg(feature = "json")]
+pub use crate::threshold::thresholds_json;
+pub use crate::threshold::{metric_thresholds, MetricThreshold, Operator, Threshold};
+pub use crate::timing::{section_durations, SectionTimer};
+#[doc(hidden)]
+pub use crate::unit::IntoUnit;
 pub use crate::unit::Unit;
+#[cfg(feature = "json")]
+pub use crate::uwsgi::UwsgiStats;
+pub use crate::value::Value;
+#[cfg(feature = "json")]
+pub use crate::varnish::VarnishStats;
+pub use crate::wildcard::{escape_segment, matches_metric};
 
+#[cfg(feature = "json")]
+mod aggregator;
+#[cfg(feature = "api")]
+mod api;
+#[cfg(any(feature = "cloudwatch", feature = "sqs"))]
+mod aws;
+mod bpf;
+#[cfg(feature = "json")]
+mod cache;
+#[cfg(feature = "tls")]
+mod certificate;
+mod check;
+mod clock;
+#[cfg(feature = "cloudwatch")]
+mod cloudwatch;
+mod connection;
+mod context;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+mod cpu;
+mod credentials;
+mod diff;
+mod discovery;
+mod dns_probe;
+mod either;
+#[cfg(feature = "elasticsearch")]
+mod elasticsearch;
+mod error;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+mod filesystem;
+mod filter;
+#[cfg(feature = "http")]
+mod fluentd;
+mod format;
+#[cfg(feature = "gcp")]
+mod gcp;
+#[cfg(feature = "nvml")]
+mod gpu;
 mod graph;
+#[cfg(feature = "http")]
+mod haproxy;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+mod host;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "http")]
+mod http_probe;
+mod json;
+mod jvm;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod label;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+mod memory;
 mod metric;
+mod metric_map;
+#[cfg(feature = "json")]
+mod migration;
+#[cfg(feature = "json")]
+mod mongodb;
+mod monitor;
+#[cfg(feature = "mysql")]
+mod mysql;
+mod ntp;
+mod output;
+#[cfg(feature = "json")]
+mod packaging;
+#[cfg(windows)]
+mod perf_counter;
+#[cfg(feature = "ping")]
+mod ping;
 mod plugin;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod process;
+mod queue;
+#[cfg(feature = "rabbitmq")]
+mod rabbitmq;
+#[cfg(feature = "json")]
+mod rate_limit;
+#[cfg(feature = "json")]
+mod redis;
+mod rename;
+#[cfg(feature = "api")]
+mod replay;
+#[cfg(feature = "api")]
+mod resource;
+#[cfg(any(
+    feature = "cloudwatch",
+    feature = "gcp",
+    feature = "sqs",
+    feature = "tls"
+))]
+mod rfc3339;
+#[cfg(feature = "scaffold")]
+mod scaffold;
+mod self_metrics;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod sensor;
+#[cfg(feature = "json")]
+mod series;
+#[cfg(feature = "http")]
+mod server_status;
+#[cfg(feature = "api")]
+mod service;
+#[cfg(feature = "json")]
+mod sidekiq;
+mod sink;
+#[cfg(feature = "smart")]
+mod smart;
+#[cfg(target_os = "macos")]
+mod smc;
+#[cfg(feature = "sqs")]
+mod sqs;
+mod squid;
+mod staleness;
+mod state;
+mod systemd;
+mod template;
+#[cfg(feature = "json")]
+pub mod testing;
+mod threshold;
+mod timing;
 mod unit;
+#[cfg(feature = "json")]
+mod uwsgi;
+mod value;
+#[cfg(feature = "json")]
+mod varnish;
+mod wildcard;
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// The flavor of `host_statistics` of the virtual memory in 64 bits.
+#[cfg(target_os = "macos")]
+const HOST_VM_INFO64: std::ffi::c_int = 4;
+
+/// The statistics of the virtual memory of macOS in the pages.
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct VmStatistics64 {
+    free_count: u32,
+    active_count: u32,
+    inactive_count: u32,
+    wire_count: u32,
+    // zero_fill_count, reactivations, pageins, pageouts, faults, cow_faults,
+    // lookups, hits, and purges, which are not read
+    counts: [u64; 9],
+    purgeable_count: u32,
+    speculative_count: u32,
+    // decompressions, compressions, swapins, and swapouts
+    compressor_counts: [u64; 4],
+    compressor_page_count: u32,
+    throttled_count: u32,
+    external_page_count: u32,
+    internal_page_count: u32,
+    total_uncompressed_pages_in_compressor: u64,
+}
+
+#[cfg(target_os = "macos")]
+const _: () = assert!(std::mem::size_of::<VmStatistics64>() == 152);
+
+/// The usage of the swap of macOS by the `vm.swapusage` sysctl.
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct XswUsage {
+    xsu_total: u64,
+    xsu_avail: u64,
+    xsu_used: u64,
+    xsu_pagesize: u32,
+    xsu_encrypted: u8,
+}
+
+/// The swap device of FreeBSD by the `vm.swap_info` sysctl, whose blocks are
+/// in the pages.
+#[cfg(target_os = "freebsd")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct XswDev {
+    xsw_version: u32,
+    xsw_dev: u64,
+    xsw_flags: i32,
+    xsw_nblks: i32,
+    xsw_used: i32,
+}
+
+/// A collector of the memory and the swap usage of the host, which reads them
+/// from `/proc/meminfo` on Linux, by `host_statistics64` and the sysctls on
+/// macOS, and by the sysctls on FreeBSD.
+///
+/// The available memory is `MemAvailable` on Linux, the memory other than the
+/// wired, the compressed, and the anonymous pages not purgeable on macOS,
+/// which is the memory used in Activity Monitor, and the free and the inactive
+/// pages on FreeBSD.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::MemoryStats;
+///
+/// let values = MemoryStats::new().fetch_values().unwrap();
+/// ```
+pub struct MemoryStats {
+    #[cfg(target_os = "linux")]
+    proc_dir: PathBuf,
+}
+
+impl Default for MemoryStats {
+    fn default() -> MemoryStats {
+        MemoryStats::new()
+    }
+}
+
+impl MemoryStats {
+    pub fn new() -> MemoryStats {
+        MemoryStats {
+            #[cfg(target_os = "linux")]
+            proc_dir: PathBuf::from("/proc"),
+        }
+    }
+
+    /// Sets the directory of procfs.
+    #[cfg(target_os = "linux")]
+    pub fn proc_dir(mut self, proc_dir: impl Into<PathBuf>) -> MemoryStats {
+        self.proc_dir = proc_dir.into();
+        self
+    }
+
+    /// Returns the graphs of the memory and the swap.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "memory",
+                label: "Memory",
+                unit: "bytes",
+                metrics: [
+                    { name: "used", label: "Used", stacked: true },
+                    { name: "available", label: "Available", stacked: true },
+                ],
+            },
+            crate::graph! {
+                name: "swap",
+                label: "Swap",
+                unit: "bytes",
+                metrics: [
+                    { name: "used", label: "Used", stacked: true },
+                    { name: "free", label: "Free", stacked: true },
+                ],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the memory and the swap.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        let usage = self.read_usage()?;
+        Ok(HashMap::from([
+            (
+                "memory.used".to_owned(),
+                (usage.total.saturating_sub(usage.available) as f64).into(),
+            ),
+            (
+                "memory.available".to_owned(),
+                (usage.available as f64).into(),
+            ),
+            (
+                "swap.used".to_owned(),
+                (usage.swap_total.saturating_sub(usage.swap_free) as f64).into(),
+            ),
+            ("swap.free".to_owned(), (usage.swap_free as f64).into()),
+        ]))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn read_usage(&self) -> Result<Usage, Error> {
+        let path = self.proc_dir.join("meminfo");
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("read {} failed: {}", path.display(), e))?;
+        let fields = content
+            .lines()
+            .filter_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                let kb = value.split_whitespace().next()?.parse::<u64>().ok()?;
+                Some((name, kb * 1024))
+            })
+            .collect::<HashMap<_, _>>();
+        let field = |name: &str| {
+            fields
+                .get(name)
+                .copied()
+                .ok_or_else(|| format!("read {} failed: no {}", path.display(), name))
+        };
+        // MemAvailable is estimated by the kernel since Linux 3.14
+        let available = match field("MemAvailable") {
+            Ok(available) => available,
+            Err(_) => field("MemFree")? + field("Buffers")? + field("Cached")?,
+        };
+        Ok(Usage {
+            total: field("MemTotal")?,
+            available,
+            swap_total: field("SwapTotal")?,
+            swap_free: field("SwapFree")?,
+        })
+    }
+
+    #[cfg(target_os = "macos")]
+    fn read_usage(&self) -> Result<Usage, Error> {
+        use crate::host::{host_statistics, sysctl_uint, sysctl_value};
+
+        let total = sysctl_uint("hw.memsize")?;
+        let page_size = sysctl_uint("hw.pagesize")?;
+        let vm = host_statistics::<VmStatistics64>(HOST_VM_INFO64)?;
+        let used = (vm.internal_page_count as u64).saturating_sub(vm.purgeable_count as u64)
+            + vm.wire_count as u64
+            + vm.compressor_page_count as u64;
+        let swap = sysctl_value::<XswUsage>("vm.swapusage")?;
+        Ok(Usage {
+            total,
+            available: total.saturating_sub(used * page_size),
+            swap_total: swap.xsu_total,
+            swap_free: swap.xsu_avail,
+        })
+    }
+
+    #[cfg(target_os = "freebsd")]
+    fn read_usage(&self) -> Result<Usage, Error> {
+        use crate::host::{sysctl_uint, sysctl_values};
+
+        let page_size = sysctl_uint("vm.stats.vm.v_page_size")?;
+        let available =
+            sysctl_uint("vm.stats.vm.v_free_count")? + sysctl_uint("vm.stats.vm.v_inactive_count")?;
+        let (swap_total, swap_used) =
+            sysctl_values::<XswDev>("vm.swap_info")?
+                .iter()
+                .fold((0, 0), |(total, used), dev| {
+                    (
+                        total + dev.xsw_nblks.max(0) as u64,
+                        used + dev.xsw_used.max(0) as u64,
+                    )
+                });
+        Ok(Usage {
+            total: sysctl_uint("hw.physmem")?,
+            available: available * page_size,
+            swap_total: swap_total * page_size,
+            swap_free: swap_total.saturating_sub(swap_used) * page_size,
+        })
+    }
+}
+
+/// The usage of the memory and the swap in bytes.
+struct Usage {
+    total: u64,
+    available: u64,
+    swap_total: u64,
+    swap_free: u64,
+}
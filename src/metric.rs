@@ -1,13 +1,146 @@
 use serde_derive::{Deserialize, Serialize};
 
+use crate::threshold::Threshold;
+use crate::unit::{IntoUnit, Unit};
+
 /// A metric represents a Mackerel metric schema.
+///
+/// The name is validated on deserializing, as well as by [`Metric::new`].
 #[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "RawMetric")]
 pub struct Metric {
     pub name: String,
     pub label: String,
     pub stacked: bool,
     #[serde(skip_serializing)]
     pub diff: bool,
+    /// The unit of the metric, which overrides the unit of the graph.
+    /// Mackerel does not support per-metric units, so this is not included in
+    /// the graph definitions, but used by alternative exporters.
+    #[serde(skip_serializing, default)]
+    pub unit: Option<Unit>,
+    /// The advisory threshold of the metric, which is not included in the
+    /// graph definitions either, but exported for generating the monitors.
+    #[serde(skip_serializing, default)]
+    pub threshold: Option<Threshold>,
+    /// The number of the decimal places of the output values, which
+    /// overrides the precision of the unit. The values are rounded half to
+    /// even.
+    #[serde(skip_serializing, default)]
+    pub precision: Option<u32>,
+}
+
+impl Metric {
+    /// Creates a new metric, or returns an error if the name is invalid.
+    /// Unlike [`metric!`], this never panics on the names built at runtime.
+    pub fn new(name: impl Into<String>, label: impl Into<String>) -> Result<Metric, String> {
+        let name = name.into();
+        if !is_valid_metric_name(&name) {
+            return Err(format!("invalid metric name: {}", name));
+        }
+        Ok(Metric {
+            name,
+            label: label.into(),
+            stacked: false,
+            diff: false,
+            unit: None,
+            threshold: None,
+            precision: None,
+        })
+    }
+}
+
+/// A metric deserialized before the validation of the name.
+#[derive(Deserialize)]
+struct RawMetric {
+    name: String,
+    label: String,
+    stacked: bool,
+    diff: bool,
+    #[serde(default)]
+    unit: Option<Unit>,
+    #[serde(default)]
+    threshold: Option<Threshold>,
+    #[serde(default)]
+    precision: Option<u32>,
+}
+
+impl TryFrom<RawMetric> for Metric {
+    type Error = String;
+
+    fn try_from(raw: RawMetric) -> Result<Metric, String> {
+        Ok(Metric {
+            stacked: raw.stacked,
+            diff: raw.diff,
+            unit: raw.unit,
+            threshold: raw.threshold,
+            precision: raw.precision,
+            ..Metric::new(raw.name, raw.label)?
+        })
+    }
+}
+
+/// Converts the value of the field in [`metric!`].
+#[doc(hidden)]
+pub trait IntoField<T> {
+    fn into_field(self) -> T;
+}
+
+impl<T> IntoField<T> for T {
+    fn into_field(self) -> T {
+        self
+    }
+}
+
+impl IntoField<Option<Unit>> for Unit {
+    fn into_field(self) -> Option<Unit> {
+        Some(self)
+    }
+}
+
+impl IntoField<Option<Unit>> for &str {
+    fn into_field(self) -> Option<Unit> {
+        Some(self.into_unit())
+    }
+}
+
+impl IntoField<Option<Unit>> for String {
+    fn into_field(self) -> Option<Unit> {
+        Some(self.into_unit())
+    }
+}
+
+impl IntoField<Option<Threshold>> for Threshold {
+    fn into_field(self) -> Option<Threshold> {
+        Some(self)
+    }
+}
+
+impl IntoField<Option<u32>> for u32 {
+    fn into_field(self) -> Option<u32> {
+        Some(self)
+    }
+}
+
+/// Returns whether the name is valid for a metric name, which consists of
+/// alphanumeric characters, hyphens, and underscores, or is a wildcard.
+#[doc(hidden)]
+pub const fn is_valid_metric_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    if bytes.is_empty() {
+        return false;
+    }
+    if bytes.len() == 1 && matches!(bytes[0], b'*' | b'#') {
+        return true;
+    }
+    let mut i = 0;
+    while i < bytes.len() {
+        if !matches!(bytes[i], b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_') {
+            return false;
+        }
+        i += 1;
+    }
+    true
 }
 
 /// Builds a new [`Metric`].
@@ -21,7 +154,7 @@ pub struct Metric {
 /// };
 /// ```
 ///
-/// You can also specify `stacked` and `diff` options.
+/// You can also specify `stacked`, `diff`, `unit`, and `threshold` options.
 ///
 /// ```rust
 /// use mackerel_plugin::metric;
@@ -31,30 +164,89 @@ pub struct Metric {
 ///     label: "Foo metric",
 ///     stacked: true,
 ///     diff: true,
+///     unit: "bytes",
+/// };
+/// ```
+///
+/// The literal metric name and unit are validated at compile time.
+///
+/// ```rust,compile_fail
+/// use mackerel_plugin::metric;
+///
+/// let metric = metric! {
+///     name: "foo.bar",
+///     label: "Foo metric",
+/// };
+/// ```
+///
+/// ```rust,compile_fail
+/// use mackerel_plugin::metric;
+///
+/// let metric = metric! {
+///     name: "foo",
+///     label: "Foo metric",
+///     unit: "byte",
 /// };
 /// ```
 #[macro_export]
 macro_rules! metric {
     (
-        name: $name:expr,
+        name: $name:literal,
         label: $label:expr
-        $( , $field:ident: $value:expr )* $(,)?
+        $( , $( $fields:tt )* )?
     ) => {{
-        assert!(
-            !$name.is_empty()
-                && (str::chars($name).all(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_'))
-                    || matches!($name, "*" | "#"))
+        const _: () = assert!(
+            $crate::is_valid_metric_name($name),
+            concat!("invalid metric name: ", $name)
         );
+        $crate::metric!(@metric $name, $label, [] $( $( $fields )* )?)
+    }};
+
+    (
+        name: $name:expr,
+        label: $label:expr
+        $( , $( $fields:tt )* )?
+    ) => {{
+        assert!($crate::is_valid_metric_name($name), "invalid metric name: {}", $name);
+        $crate::metric!(@metric $name, $label, [] $( $( $fields )* )?)
+    }};
+
+    (
+        @metric $name:expr, $label:expr, [$( $acc:tt )*]
+        unit: $unit:literal $( , $( $fields:tt )* )?
+    ) => {
+        $crate::metric!(@metric $name, $label, [$( $acc )* unit: Some({
+            const UNIT: $crate::Unit = match $crate::Unit::from_name($unit) {
+                Some(unit) => unit,
+                None => panic!(concat!("invalid unit: ", $unit)),
+            };
+            UNIT
+        }),] $( $( $fields )* )?)
+    };
+
+    (
+        @metric $name:expr, $label:expr, [$( $acc:tt )*]
+        $field:ident: $value:expr $( , $( $fields:tt )* )?
+    ) => {
+        $crate::metric!(@metric $name, $label, [
+            $( $acc )* $field: $crate::IntoField::into_field($value),
+        ] $( $( $fields )* )?)
+    };
+
+    (@metric $name:expr, $label:expr, [$( $acc:tt )*]) => {
         $crate::Metric {
-            $( $field: $value, )*
+            $( $acc )*
             ..$crate::Metric {
                 name: $name.into(),
                 label: $label.into(),
                 stacked: false,
                 diff: false,
+                unit: None,
+                threshold: None,
+                precision: None,
             }
         }
-    }};
+    };
 
     ($($_:tt)*) => {
         compile_error!("name and label are required");
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use crate::value::Value;
+
+/// A map of metric values, which helps building the metric names with prefixes.
+///
+/// ```rust
+/// use mackerel_plugin::{MetricMap, Value};
+///
+/// let mut metrics = MetricMap::new();
+/// metrics.insert("uptime", 123.0);
+/// {
+///     let mut queue = metrics.with_prefix("queue");
+///     queue.insert("size", 10_u64);
+///     queue.with_prefix("default").insert("latency", 0.5);
+/// }
+/// assert_eq!(metrics.get("queue.size"), Some(&Value::Float(10.0)));
+/// assert_eq!(metrics.get("queue.default.latency"), Some(&Value::Float(0.5)));
+/// ```
+#[derive(Default, PartialEq, Clone, Debug)]
+pub struct MetricMap {
+    values: HashMap<String, Value>,
+}
+
+impl MetricMap {
+    pub fn new() -> MetricMap {
+        MetricMap::default()
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<Value>) -> &mut MetricMap {
+        self.values.insert(name.into(), value.into());
+        self
+    }
+
+    /// Returns a scope which inserts the metric values with the prefix.
+    pub fn with_prefix(&mut self, prefix: impl Into<String>) -> MetricScope<'_> {
+        MetricScope {
+            map: self,
+            prefix: prefix.into(),
+        }
+    }
+
+    /// Merges the metric values of the other map, overwriting the values of
+    /// the same names.
+    pub fn merge(&mut self, other: MetricMap) -> &mut MetricMap {
+        self.values.extend(other.values);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.values.iter()
+    }
+}
+
+/// A scope of [`MetricMap`] with a prefix of the metric names.
+#[derive(Debug)]
+pub struct MetricScope<'a> {
+    map: &'a mut MetricMap,
+    prefix: String,
+}
+
+impl MetricScope<'_> {
+    pub fn insert(&mut self, name: impl AsRef<str>, value: impl Into<Value>) -> &mut Self {
+        let name = self.name(name.as_ref());
+        self.map.insert(name, value);
+        self
+    }
+
+    /// Returns a nested scope with the prefix appended.
+    pub fn with_prefix(&mut self, prefix: impl AsRef<str>) -> MetricScope<'_> {
+        let prefix = self.name(prefix.as_ref());
+        self.map.with_prefix(prefix)
+    }
+
+    /// Merges the metric values of the other map with the prefix.
+    pub fn merge(&mut self, other: MetricMap) -> &mut Self {
+        for (name, value) in other.values {
+            self.insert(name, value);
+        }
+        self
+    }
+
+    fn name(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_owned()
+        } else if name.is_empty() {
+            self.prefix.clone()
+        } else {
+            self.prefix.clone() + "." + name
+        }
+    }
+}
+
+impl FromIterator<(String, Value)> for MetricMap {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(iter: I) -> MetricMap {
+        MetricMap {
+            values: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for MetricMap {
+    type Item = (String, Value);
+    type IntoIter = std::collections::hash_map::IntoIter<String, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
+
+impl From<MetricMap> for HashMap<String, Value> {
+    fn from(map: MetricMap) -> HashMap<String, Value> {
+        map.values
+    }
+}
+
+impl From<MetricMap> for HashMap<String, f64> {
+    fn from(map: MetricMap) -> HashMap<String, f64> {
+        map.values
+            .into_iter()
+            .map(|(name, value)| (name, value.as_f64()))
+            .collect()
+    }
+}
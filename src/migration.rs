@@ -0,0 +1,56 @@
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::state::StateStore;
+
+/// A migration of the metric key prefix.
+///
+/// While the migration is in the period, the metric values are emitted with
+/// both the old and new prefixes, so that the historical graphs continue
+/// until the new graphs are populated. The period starts on the first run
+/// with the migration, which is recorded in the state store.
+#[derive(PartialEq, Clone, Debug)]
+pub struct PrefixMigration {
+    pub old_prefix: String,
+    pub period: Duration,
+}
+
+impl PrefixMigration {
+    pub fn new(old_prefix: impl Into<String>, period: Duration) -> PrefixMigration {
+        PrefixMigration {
+            old_prefix: old_prefix.into(),
+            period,
+        }
+    }
+
+    pub(crate) fn is_active(
+        &self,
+        state: &dyn StateStore,
+        path: &str,
+        now: i64,
+    ) -> Result<bool, String> {
+        let path = path.to_owned() + ".migration";
+        let started_at = match state
+            .load(&path)?
+            .and_then(|bytes| serde_json::from_slice::<MigrationState>(&bytes).ok())
+        {
+            Some(migration) if migration.old_prefix == self.old_prefix => migration.started_at,
+            _ => {
+                let migration = MigrationState {
+                    old_prefix: self.old_prefix.clone(),
+                    started_at: now,
+                };
+                let bytes = serde_json::to_vec(&migration).map_err(|e| e.to_string())?;
+                state.save(&path, &bytes)?;
+                now
+            }
+        };
+        Ok(now < started_at.saturating_add(self.period.as_secs().try_into().unwrap_or(i64::MAX)))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MigrationState {
+    old_prefix: String,
+    started_at: i64,
+}
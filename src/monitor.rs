@@ -0,0 +1,128 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::plugin::{join_name, Plugin};
+use crate::threshold::{Operator, Threshold};
+
+/// A monitor definition of a host metric, in the format of the Mackerel API
+/// and `mkr monitors`.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Monitor {
+    #[serde(rename = "type")]
+    pub monitor_type: String,
+    pub name: String,
+    pub metric: String,
+    pub operator: Operator,
+    pub warning: Option<f64>,
+    pub critical: Option<f64>,
+    pub duration: u32,
+    pub max_check_attempts: u32,
+}
+
+/// A generator of the monitor definitions from the graph definitions.
+///
+/// The monitors are generated for the metrics with the thresholds, and the
+/// selected metrics whose thresholds are left as placeholders (`null`) to be
+/// filled in.
+///
+/// ```rust
+/// # use mackerel_plugin::{Graph, Plugin};
+/// # use std::collections::HashMap;
+/// # struct DicePlugin {}
+/// # impl Plugin for DicePlugin {
+/// #   fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> { unimplemented!() }
+/// #   fn graph_definition(&self) -> Vec<Graph> { Vec::new() }
+/// # }
+/// use mackerel_plugin::MonitorGenerator;
+///
+/// let monitors = MonitorGenerator::new()
+///     .select("dice.d6")
+///     .duration(5)
+///     .generate(&DicePlugin {});
+/// ```
+#[derive(Clone, Debug)]
+pub struct MonitorGenerator {
+    selected: Vec<String>,
+    duration: u32,
+    max_check_attempts: u32,
+}
+
+impl Default for MonitorGenerator {
+    fn default() -> MonitorGenerator {
+        MonitorGenerator {
+            selected: Vec::new(),
+            duration: 1,
+            max_check_attempts: 1,
+        }
+    }
+}
+
+impl MonitorGenerator {
+    pub fn new() -> MonitorGenerator {
+        MonitorGenerator::default()
+    }
+
+    /// Selects the metric by the name with the metric key prefix, such as
+    /// `dice.d6`, or the graph by the name to select all the metrics in it.
+    pub fn select(mut self, name: impl Into<String>) -> MonitorGenerator {
+        self.selected.push(name.into());
+        self
+    }
+
+    /// Sets the duration in minutes of the average to compare with the
+    /// thresholds.
+    pub fn duration(mut self, duration: u32) -> MonitorGenerator {
+        self.duration = duration;
+        self
+    }
+
+    /// Sets the number of the consecutive checks to alert.
+    pub fn max_check_attempts(mut self, max_check_attempts: u32) -> MonitorGenerator {
+        self.max_check_attempts = max_check_attempts;
+        self
+    }
+
+    /// Returns the monitor definitions of the plugin.
+    pub fn generate<P: Plugin + ?Sized>(&self, plugin: &P) -> Vec<Monitor> {
+        let prefix = plugin.metric_key_prefix();
+        let mut monitors = Vec::new();
+        for graph in plugin.graph_definition_ref().iter() {
+            let graph_name = join_name(&prefix, &graph.name);
+            for metric in &graph.metrics {
+                let metric_name = join_name(&graph_name, &metric.name);
+                let threshold = match metric.threshold {
+                    Some(threshold) => threshold,
+                    None if self.is_selected(&graph_name, &metric_name) => Threshold::above(),
+                    None => continue,
+                };
+                monitors.push(Monitor {
+                    monitor_type: "host".to_owned(),
+                    name: format!("{} {}", graph.label, metric.label),
+                    metric: "custom.".to_owned() + &metric_name,
+                    operator: threshold.operator,
+                    warning: threshold.warning,
+                    critical: threshold.critical,
+                    duration: self.duration,
+                    max_check_attempts: self.max_check_attempts,
+                });
+            }
+        }
+        monitors
+    }
+
+    /// Returns the JSON document of the monitor definitions of the plugin,
+    /// which can be applied by `mkr monitors push`.
+    #[cfg(feature = "json")]
+    pub fn generate_json<P: Plugin + ?Sized>(&self, plugin: &P) -> String {
+        serde_json::to_string_pretty(&serde_json::json!({
+            "monitors": self.generate(plugin),
+        }))
+        .unwrap_or_default()
+    }
+
+    fn is_selected(&self, graph_name: &str, metric_name: &str) -> bool {
+        self.selected
+            .iter()
+            .any(|name| name == graph_name || name == metric_name)
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::process::Command;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// The daemon of the time synchronization.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum NtpDaemon {
+    /// chrony, whose tracking is read by `chronyc -c tracking`.
+    Chrony,
+    /// ntpd of the NTP reference implementation or NTPsec, whose system
+    /// variables are read by `ntpq -c rv`.
+    Ntpd,
+}
+
+/// A collector of the clock offset of the system from the NTP servers, which
+/// affects the timestamps of the metrics and the differences of the counters.
+///
+/// The metrics are the offset and the jitter, the root delay and the root
+/// dispersion in milliseconds, the stratum, and whether the clock is
+/// synchronized, which is 0 when the daemon has not synchronized the clock
+/// or has lost the servers.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{NtpDaemon, NtpOffset};
+///
+/// let offset = NtpOffset::new().daemon(NtpDaemon::Ntpd);
+/// let values = offset.fetch_values().unwrap();
+/// ```
+pub struct NtpOffset {
+    daemon: NtpDaemon,
+    command: Option<OsString>,
+}
+
+impl Default for NtpOffset {
+    fn default() -> NtpOffset {
+        NtpOffset::new()
+    }
+}
+
+impl NtpOffset {
+    pub fn new() -> NtpOffset {
+        NtpOffset {
+            daemon: NtpDaemon::Chrony,
+            command: None,
+        }
+    }
+
+    /// Sets the daemon, which defaults to chrony.
+    pub fn daemon(mut self, daemon: NtpDaemon) -> NtpOffset {
+        self.daemon = daemon;
+        self
+    }
+
+    /// Sets the command of `chronyc` or `ntpq`.
+    pub fn command(mut self, command: impl Into<OsString>) -> NtpOffset {
+        self.command = Some(command.into());
+        self
+    }
+
+    /// Returns the graphs of the clock.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "ntp.offset",
+                label: "NTP offset (ms)",
+                unit: "float",
+                metrics: [
+                    { name: "offset", label: "Offset" },
+                    { name: "jitter", label: "Jitter" },
+                ],
+            },
+            crate::graph! {
+                name: "ntp.root",
+                label: "NTP root distance (ms)",
+                unit: "float",
+                metrics: [
+                    { name: "delay", label: "Root delay" },
+                    { name: "dispersion", label: "Root dispersion" },
+                ],
+            },
+            crate::graph! {
+                name: "ntp.stratum",
+                label: "NTP stratum",
+                unit: "integer",
+                metrics: [{ name: "stratum", label: "Stratum" }],
+            },
+            crate::graph! {
+                name: "ntp.sync",
+                label: "NTP synchronized",
+                unit: "integer",
+                metrics: [{ name: "synchronized", label: "Synchronized" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the clock.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the clock, killing the command when the
+    /// fetch is cancelled.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        let (command, args, name) = match self.daemon {
+            NtpDaemon::Chrony => ("chronyc", ["-c", "tracking"], "chronyc tracking"),
+            NtpDaemon::Ntpd => ("ntpq", ["-c", "rv"], "ntpq -c rv"),
+        };
+        let output = ctx
+            .output(Command::new(self.command.as_deref().unwrap_or(command.as_ref())).args(args))
+            .map_err(|e| format!("{} failed: {}", name, e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("{} failed: {}", name, stderr.trim()).into());
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        match self.daemon {
+            NtpDaemon::Chrony => NtpOffset::parse_chrony(&stdout),
+            NtpDaemon::Ntpd => NtpOffset::parse_ntpq(&stdout),
+        }
+    }
+
+    /// Parses the output of `chronyc -c tracking`, which is the fields of the
+    /// tracking separated by commas, where the times are in seconds.
+    ///
+    /// ```text
+    /// A9FEA97B,169.254.169.123,4,1791766800.123456789,0.000001234,-0.000012345,0.000023456,-12.345,0.001,0.012,0.000456789,0.000234567,64.1,Normal
+    /// ```
+    pub fn parse_chrony(text: &str) -> Result<HashMap<String, Value>, Error> {
+        let fields = text.trim().split(',').collect::<Vec<_>>();
+        if fields.len() < 14 {
+            return Err(format!("parse the chronyc tracking failed: {:?}", text.trim()).into());
+        }
+        let field = |index: usize, name: &str| {
+            fields[index].parse::<f64>().map_err(|_| {
+                format!(
+                    "parse the chronyc tracking failed: invalid {}: {:?}",
+                    name, fields[index]
+                )
+            })
+        };
+        let mut values = HashMap::new();
+        values.insert(
+            "ntp.offset.offset".to_owned(),
+            (field(5, "last offset")? * 1000.0).into(),
+        );
+        values.insert(
+            "ntp.offset.jitter".to_owned(),
+            (field(6, "RMS offset")? * 1000.0).into(),
+        );
+        values.insert(
+            "ntp.root.delay".to_owned(),
+            (field(10, "root delay")? * 1000.0).into(),
+        );
+        values.insert(
+            "ntp.root.dispersion".to_owned(),
+            (field(11, "root dispersion")? * 1000.0).into(),
+        );
+        let stratum = field(2, "stratum")?;
+        values.insert("ntp.stratum.stratum".to_owned(), stratum.into());
+        let synchronized = fields[13] != "Not synchronised" && stratum < 16.0;
+        values.insert(
+            "ntp.sync.synchronized".to_owned(),
+            (synchronized as u8 as f64).into(),
+        );
+        Ok(values)
+    }
+
+    /// Parses the output of `ntpq -c rv`, which is the system variables
+    /// separated by commas, where the times are in milliseconds.
+    ///
+    /// ```text
+    /// associd=0 status=0615 leap_none, sync_ntp, 1 event, clock_sync,
+    /// version="ntpd 4.2.8p15@1.3728-o", processor="x86_64", system="Linux",
+    /// leap=00, stratum=3, precision=-24, rootdelay=12.345, rootdisp=23.456,
+    /// refid=10.0.0.1, offset=-0.123456, frequency=-12.345, sys_jitter=0.234567
+    /// ```
+    pub fn parse_ntpq(text: &str) -> Result<HashMap<String, Value>, Error> {
+        let variables = text
+            .split(',')
+            .flat_map(str::split_whitespace)
+            .filter_map(|variable| variable.split_once('='))
+            .collect::<HashMap<_, _>>();
+        let mut values = HashMap::new();
+        for (variable, key) in [
+            ("offset", "ntp.offset.offset"),
+            ("sys_jitter", "ntp.offset.jitter"),
+            ("rootdelay", "ntp.root.delay"),
+            ("rootdisp", "ntp.root.dispersion"),
+            ("stratum", "ntp.stratum.stratum"),
+        ] {
+            let Some(value) = variables.get(variable) else {
+                continue;
+            };
+            let value = value.parse::<f64>().map_err(|_| {
+                format!(
+                    "parse the ntpq output failed: invalid {}: {:?}",
+                    variable, value
+                )
+            })?;
+            values.insert(key.to_owned(), value.into());
+        }
+        let (Some(leap), Some(stratum)) = (variables.get("leap"), variables.get("stratum")) else {
+            return Err(format!("parse the ntpq output failed: {:?}", text.trim()).into());
+        };
+        // the leap indicator of 11 is the alarm of the unsynchronized clock
+        let synchronized = *leap != "11" && *stratum != "16";
+        values.insert(
+            "ntp.sync.synchronized".to_owned(),
+            (synchronized as u8 as f64).into(),
+        );
+        Ok(values)
+    }
+}
@@ -0,0 +1,120 @@
+//! The output format of the plugins for mackerel-agent.
+
+use crate::error::Error;
+#[cfg(feature = "json")]
+use crate::graph::Graph;
+#[cfg(feature = "json")]
+use crate::metric::Metric;
+
+/// The header line of the graph definitions output for mackerel-agent, which
+/// is followed by the JSON of the definitions.
+pub const META_HEADER: &str = "# mackerel-agent-plugin";
+
+/// Parses the metric values output by a plugin for mackerel-agent, which are
+/// the lines of the name, the value, and the timestamp separated by tabs. The
+/// empty lines are ignored.
+///
+/// ```rust
+/// use mackerel_plugin::parse_values;
+///
+/// assert_eq!(
+///     parse_values("dice.d6\t3\t1700000000\ndice.d20\t17.5\t1700000000\n"),
+///     Ok(vec![
+///         ("dice.d6".to_owned(), 3.0, 1700000000),
+///         ("dice.d20".to_owned(), 17.5, 1700000000),
+///     ]),
+/// );
+/// ```
+pub fn parse_values(output: &str) -> Result<Vec<(String, f64, i64)>, Error> {
+    output
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut fields = line.split('\t');
+            match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                (Some(name), Some(value), Some(timestamp), None) => Ok((
+                    name.to_owned(),
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid value: {}", line))?,
+                    timestamp
+                        .parse()
+                        .map_err(|_| format!("invalid timestamp: {}", line))?,
+                )),
+                _ => Err(Error::Other(format!("invalid line: {}", line))),
+            }
+        })
+        .collect()
+}
+
+/// Parses the graph definitions output by a plugin for mackerel-agent, which
+/// starts with the header line. The leading byte order mark and whitespaces
+/// are ignored. The graphs are ordered by the names.
+///
+/// ```rust
+/// use mackerel_plugin::{parse_meta_output, Unit};
+///
+/// let output = "# mackerel-agent-plugin\n{\"graphs\":{\"temperature\":{\"label\":\"温度 ℃\",\"unit\":\"float\",\"metrics\":[{\"name\":\"*\",\"label\":\"%1\",\"stacked\":false}]}}}\n";
+/// let graphs = parse_meta_output(output).unwrap();
+/// assert_eq!(graphs[0].name, "temperature");
+/// assert_eq!(graphs[0].label, "温度 ℃");
+/// assert_eq!(graphs[0].unit, Unit::Float);
+/// ```
+#[cfg(feature = "json")]
+pub fn parse_meta_output(output: &str) -> Result<Vec<Graph>, Error> {
+    let output = output.trim_start_matches('\u{feff}').trim_start();
+    let json = output
+        .strip_prefix(META_HEADER)
+        .filter(|rest| rest.starts_with(['\n', '\r']))
+        .ok_or_else(|| format!("meta output should start with {:?}", META_HEADER))?;
+    parse_definitions(json)
+}
+
+/// Parses the JSON of the graph definitions, which follows the header line in
+/// the output for mackerel-agent. The graphs are ordered by the names. The
+/// names of the graphs and the metrics are validated as by [`Graph::new`] and
+/// [`Metric::new`].
+#[cfg(feature = "json")]
+pub fn parse_definitions(json: &str) -> Result<Vec<Graph>, Error> {
+    let error = |message: &str| format!("invalid graph definitions: {}", message);
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|e| error(&e.to_string()))?;
+    let graphs = value
+        .get("graphs")
+        .and_then(serde_json::Value::as_object)
+        .ok_or_else(|| error("graphs not found"))?;
+    let string = |value: &serde_json::Value, key: &str| {
+        value
+            .get(key)
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_owned)
+            .ok_or_else(|| error(&format!("{} not found", key)))
+    };
+    graphs
+        .iter()
+        .map(|(name, graph)| {
+            let metrics = graph
+                .get("metrics")
+                .and_then(serde_json::Value::as_array)
+                .ok_or_else(|| error("metrics not found"))?
+                .iter()
+                .map(|metric| {
+                    Ok(Metric {
+                        stacked: metric
+                            .get("stacked")
+                            .and_then(serde_json::Value::as_bool)
+                            .unwrap_or_default(),
+                        ..Metric::new(string(metric, "name")?, string(metric, "label")?)
+                            .map_err(|e| error(&e))?
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(Graph::new(
+                name.clone(),
+                string(graph, "label")?,
+                &string(graph, "unit")?,
+                metrics,
+            )
+            .map_err(|e| error(&e))?)
+        })
+        .collect()
+}
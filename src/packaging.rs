@@ -0,0 +1,130 @@
+use serde_json::json;
+
+/// The default build targets of the release archives.
+pub const DEFAULT_TARGETS: &[&str] = &[
+    "x86_64-unknown-linux-musl",
+    "aarch64-unknown-linux-musl",
+    "x86_64-apple-darwin",
+    "aarch64-apple-darwin",
+];
+
+/// A package of a plugin released for `mkr plugin install`.
+///
+/// The `mkr plugin install <owner>/<repo>` command downloads the release
+/// archive named `<repo>_<os>_<arch>.zip`, where the os and arch follow the Go
+/// naming convention (`linux_amd64`, `darwin_arm64`, etc.), and installs the
+/// executables in the archive.
+///
+/// ```rust
+/// use mackerel_plugin::Package;
+///
+/// let package = Package::new("mackerel-plugin-dice", "0.1.0", "itchyny/mackerel-plugin-dice");
+/// assert_eq!(
+///     package.archive_name("x86_64-unknown-linux-musl").unwrap(),
+///     "mackerel-plugin-dice_linux_amd64.zip",
+/// );
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub source: String,
+    pub description: String,
+}
+
+impl Package {
+    pub fn new(
+        name: impl Into<String>,
+        version: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Package {
+        Package {
+            name: name.into(),
+            version: version.into(),
+            source: source.into(),
+            description: String::new(),
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Package {
+        self.description = description.into();
+        self
+    }
+
+    fn repository(&self) -> &str {
+        self.source.rsplit('/').next().unwrap_or(&self.source)
+    }
+
+    /// Returns the directory name in the archive for the target triple.
+    pub fn archive_dir(&self, target: &str) -> Result<String, String> {
+        let (os, arch) = go_platform(target)?;
+        Ok(format!("{}_{}_{}", self.repository(), os, arch))
+    }
+
+    /// Returns the archive file name for the target triple.
+    pub fn archive_name(&self, target: &str) -> Result<String, String> {
+        Ok(self.archive_dir(target)? + ".zip")
+    }
+
+    /// Returns the executable file name for the target triple.
+    pub fn executable_name(&self, target: &str) -> String {
+        if target.contains("-windows-") {
+            self.name.clone() + ".exe"
+        } else {
+            self.name.clone()
+        }
+    }
+
+    /// Returns the entry of the plugin registry, which is placed at
+    /// `plugins/<name>.json` of the registry repository.
+    pub fn registry_json(&self) -> serde_json::Value {
+        json!({
+            "description": self.description,
+            "source": self.source,
+        })
+    }
+
+    /// Returns the metadata of the release, including the archive layouts.
+    pub fn metadata_json(&self, targets: &[&str]) -> Result<serde_json::Value, String> {
+        Ok(json!({
+            "name": self.name,
+            "version": self.version,
+            "install": format!("mkr plugin install {}@v{}", self.source, self.version),
+            "registry": self.registry_json(),
+            "archives": targets
+                .iter()
+                .map(|&target| {
+                    let dir = self.archive_dir(target)?;
+                    Ok(json!({
+                        "target": target,
+                        "file": dir.clone() + ".zip",
+                        "entries": [dir + "/" + &self.executable_name(target)],
+                    }))
+                })
+                .collect::<Result<Vec<_>, String>>()?,
+        }))
+    }
+}
+
+/// Converts the target triple to the pair of os and arch in the Go naming.
+fn go_platform(target: &str) -> Result<(&'static str, &'static str), String> {
+    let arch = match target.split('-').next() {
+        Some("x86_64") => "amd64",
+        Some("i686" | "i586") => "386",
+        Some("aarch64") => "arm64",
+        Some("arm" | "armv7") => "arm",
+        _ => return Err(format!("unsupported target: {}", target)),
+    };
+    let os = if target.contains("-linux-") {
+        "linux"
+    } else if target.contains("-apple-darwin") {
+        "darwin"
+    } else if target.contains("-windows-") {
+        "windows"
+    } else if target.contains("-freebsd") {
+        "freebsd"
+    } else {
+        return Err(format!("unsupported target: {}", target));
+    };
+    Ok((os, arch))
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::ffi::{c_void, OsStr};
+use std::os::windows::ffi::OsStrExt;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::value::Value;
+
+type PdhHandle = *mut c_void;
+
+const PDH_FMT_DOUBLE: u32 = 0x0000_0200;
+const PDH_MORE_DATA: u32 = 0x8000_07d2;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PdhFmtCounterValue {
+    status: u32,
+    value: f64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PdhFmtCounterValueItem {
+    name: *const u16,
+    value: PdhFmtCounterValue,
+}
+
+#[link(name = "pdh")]
+extern "system" {
+    fn PdhOpenQueryW(data_source: *const u16, user_data: usize, query: *mut PdhHandle) -> u32;
+    fn PdhAddEnglishCounterW(
+        query: PdhHandle,
+        path: *const u16,
+        user_data: usize,
+        counter: *mut PdhHandle,
+    ) -> u32;
+    fn PdhCollectQueryData(query: PdhHandle) -> u32;
+    fn PdhGetFormattedCounterArrayW(
+        counter: PdhHandle,
+        format: u32,
+        buffer_size: *mut u32,
+        item_count: *mut u32,
+        items: *mut PdhFmtCounterValueItem,
+    ) -> u32;
+    fn PdhCloseQuery(query: PdhHandle) -> u32;
+}
+
+/// A collector of the Windows performance counters by the PDH (Performance
+/// Data Helper) API.
+///
+/// The counters are specified by the English paths, so that the plugins work
+/// on the localized systems. The counters of the wildcard instances like
+/// `\Processor(*)\% Processor Time` produce the metrics suffixed with the
+/// instance names. The counters of the rates need two samples, so the values
+/// are collected twice with the interval.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::PerfCounters;
+///
+/// let counters = PerfCounters::new()
+///     .counter("iis.connections", r"\Web Service(_Total)\Current Connections")
+///     .counter("cpu.processor", r"\Processor(*)\% Processor Time");
+/// let values = counters.fetch_values().unwrap();
+/// ```
+pub struct PerfCounters {
+    counters: Vec<(String, String)>,
+    interval: Duration,
+}
+
+impl Default for PerfCounters {
+    fn default() -> PerfCounters {
+        PerfCounters::new()
+    }
+}
+
+impl PerfCounters {
+    pub fn new() -> PerfCounters {
+        PerfCounters {
+            counters: Vec::new(),
+            interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Adds the counter of the path, mapped to the metric key.
+    pub fn counter(mut self, key: impl Into<String>, path: impl Into<String>) -> PerfCounters {
+        self.counters.push((key.into(), path.into()));
+        self
+    }
+
+    /// Sets the interval between the samples.
+    pub fn interval(mut self, interval: Duration) -> PerfCounters {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the metric values of the counters. The instances of the
+    /// invalid data, such as those which appeared between the samples, are
+    /// omitted.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        let query = Query::open()?;
+        let mut counters = Vec::with_capacity(self.counters.len());
+        for (key, path) in &self.counters {
+            counters.push((key, path, query.add_counter(path)?));
+        }
+        query.collect()?;
+        std::thread::sleep(self.interval);
+        query.collect()?;
+        let mut values = HashMap::new();
+        for (key, path, counter) in counters {
+            let wildcard = path.contains("(*)");
+            for (instance, value) in counter_values(counter)
+                .map_err(|status| format!("read {} failed: 0x{:08x}", path, status))?
+            {
+                let key = if wildcard {
+                    format!("{}.{}", key, instance_key(&instance))
+                } else {
+                    key.clone()
+                };
+                values.insert(key, value.into());
+            }
+        }
+        Ok(values)
+    }
+}
+
+struct Query(PdhHandle);
+
+impl Query {
+    fn open() -> Result<Query, Error> {
+        let mut query = std::ptr::null_mut();
+        match unsafe { PdhOpenQueryW(std::ptr::null(), 0, &mut query) } {
+            0 => Ok(Query(query)),
+            status => Err(format!("open the PDH query failed: 0x{:08x}", status).into()),
+        }
+    }
+
+    fn add_counter(&self, path: &str) -> Result<PdhHandle, Error> {
+        let wide = OsStr::new(path)
+            .encode_wide()
+            .chain([0])
+            .collect::<Vec<_>>();
+        let mut counter = std::ptr::null_mut();
+        match unsafe { PdhAddEnglishCounterW(self.0, wide.as_ptr(), 0, &mut counter) } {
+            0 => Ok(counter),
+            status => Err(format!("add the counter {} failed: 0x{:08x}", path, status).into()),
+        }
+    }
+
+    fn collect(&self) -> Result<(), Error> {
+        match unsafe { PdhCollectQueryData(self.0) } {
+            0 => Ok(()),
+            status => Err(format!("collect the PDH query failed: 0x{:08x}", status).into()),
+        }
+    }
+}
+
+impl Drop for Query {
+    fn drop(&mut self) {
+        unsafe { PdhCloseQuery(self.0) };
+    }
+}
+
+/// Returns the values of the instances of the counter.
+fn counter_values(counter: PdhHandle) -> Result<Vec<(String, f64)>, u32> {
+    let (mut size, mut count) = (0, 0);
+    let status = unsafe {
+        PdhGetFormattedCounterArrayW(
+            counter,
+            PDH_FMT_DOUBLE,
+            &mut size,
+            &mut count,
+            std::ptr::null_mut(),
+        )
+    };
+    if status != PDH_MORE_DATA {
+        return Err(status);
+    }
+    // the buffer contains the items followed by the instance names
+    let item_size = std::mem::size_of::<PdhFmtCounterValueItem>();
+    let mut buffer = vec![
+        PdhFmtCounterValueItem {
+            name: std::ptr::null(),
+            value: PdhFmtCounterValue {
+                status: 0,
+                value: 0.0,
+            },
+        };
+        (size as usize).div_ceil(item_size)
+    ];
+    let status = unsafe {
+        PdhGetFormattedCounterArrayW(
+            counter,
+            PDH_FMT_DOUBLE,
+            &mut size,
+            &mut count,
+            buffer.as_mut_ptr(),
+        )
+    };
+    if status != 0 {
+        return Err(status);
+    }
+    Ok(buffer[..count as usize]
+        .iter()
+        // PDH_CSTATUS_VALID_DATA or PDH_CSTATUS_NEW_DATA
+        .filter(|item| item.value.status <= 1)
+        .map(|item| {
+            let name = if item.name.is_null() {
+                String::new()
+            } else {
+                let len = (0..)
+                    .take_while(|&i| unsafe { *item.name.add(i) } != 0)
+                    .count();
+                String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(item.name, len) })
+            };
+            (name, item.value.value)
+        })
+        .collect())
+}
+
+fn instance_key(instance: &str) -> String {
+    instance
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
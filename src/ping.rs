@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// The port of the UDP probes, which is unlikely to be listened.
+const UDP_PORT: u16 = 33434;
+
+/// The protocol of the ping probe.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PingProtocol {
+    /// The ICMP echo requests, with the unprivileged ICMP sockets (allowed by
+    /// `net.ipv4.ping_group_range` on Linux) or the raw sockets (requiring
+    /// `CAP_NET_RAW`).
+    Icmp,
+    /// The UDP datagrams to a closed port, whose RTT is measured by the ICMP
+    /// port unreachable errors.
+    Udp,
+}
+
+/// A prober of the round-trip times of the targets by ping.
+///
+/// The metrics are the minimum, average, and maximum RTT, and the packet loss
+/// of each target. The ICMP sockets require the privilege, so the probe fails
+/// with the error describing how to permit them, or falls back to the UDP
+/// probes if enabled.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::PingProbe;
+///
+/// let probe = PingProbe::new()
+///     .target("gateway", "192.168.0.1")
+///     .target("dns", "8.8.8.8")
+///     .udp_fallback(true);
+/// let values = probe.fetch_values().unwrap();
+/// ```
+pub struct PingProbe {
+    targets: Vec<(String, String)>,
+    protocol: PingProtocol,
+    udp_fallback: bool,
+    count: usize,
+    timeout: Duration,
+}
+
+impl Default for PingProbe {
+    fn default() -> PingProbe {
+        PingProbe::new()
+    }
+}
+
+impl PingProbe {
+    pub fn new() -> PingProbe {
+        PingProbe {
+            targets: Vec::new(),
+            protocol: PingProtocol::Icmp,
+            udp_fallback: false,
+            count: 3,
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    /// Adds the target host or address.
+    pub fn target(mut self, name: &str, host: impl Into<String>) -> PingProbe {
+        let name = name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect();
+        self.targets.push((name, host.into()));
+        self
+    }
+
+    pub fn protocol(mut self, protocol: PingProtocol) -> PingProbe {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Sets whether to fall back to the UDP probes when the ICMP sockets are
+    /// not permitted.
+    pub fn udp_fallback(mut self, udp_fallback: bool) -> PingProbe {
+        self.udp_fallback = udp_fallback;
+        self
+    }
+
+    /// Sets the number of the packets to each target.
+    pub fn count(mut self, count: usize) -> PingProbe {
+        self.count = count.max(1);
+        self
+    }
+
+    /// Sets the timeout of each packet.
+    pub fn timeout(mut self, timeout: Duration) -> PingProbe {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the graphs of the ping probes.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "ping.rtt.#",
+                label: "Ping RTT",
+                unit: "seconds",
+                metrics: [
+                    { name: "min", label: "Min" },
+                    { name: "avg", label: "Average" },
+                    { name: "max", label: "Max" },
+                ],
+            },
+            crate::graph! {
+                name: "ping.loss",
+                label: "Ping packet loss",
+                unit: "percentage",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the probe. The RTTs are omitted when all
+    /// the packets are lost.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        let mut values = HashMap::new();
+        for (name, host) in &self.targets {
+            let addr = (&host[..], 0)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .ok_or_else(|| format!("ping {} failed: cannot resolve the host", host))?
+                .ip();
+            let rtts = self
+                .ping(addr)
+                .map_err(|err| format!("ping {} failed: {}", host, err))?;
+            if let (Some(min), Some(max)) = (rtts.iter().min(), rtts.iter().max()) {
+                let avg = rtts.iter().sum::<Duration>() / rtts.len() as u32;
+                for (stat, rtt) in [("min", min), ("avg", &avg), ("max", max)] {
+                    values.insert(format!("ping.rtt.{}.{}", name, stat), (*rtt).into());
+                }
+            }
+            let loss = (self.count - rtts.len()) as f64 * 100.0 / self.count as f64;
+            values.insert(format!("ping.loss.{}", name), loss.into());
+        }
+        Ok(values)
+    }
+
+    /// Returns the RTTs of the replied packets.
+    fn ping(&self, addr: IpAddr) -> Result<Vec<Duration>, String> {
+        if self.protocol == PingProtocol::Udp {
+            return self.ping_udp(addr);
+        }
+        let socket = match icmp_socket(addr) {
+            Ok(socket) => socket,
+            Err(err) if err.kind() == ErrorKind::PermissionDenied && self.udp_fallback => {
+                return self.ping_udp(addr);
+            }
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+                return Err(format!(
+                    "{} (permit the ICMP sockets by net.ipv4.ping_group_range or CAP_NET_RAW, \
+                     or enable the UDP fallback)",
+                    err
+                ))
+            }
+            Err(err) => return Err(err.to_string()),
+        };
+        socket
+            .connect(&SocketAddr::new(addr, 0).into())
+            .map_err(|e| e.to_string())?;
+        let id = std::process::id() as u16;
+        let mut rtts = Vec::new();
+        for seq in 0..self.count as u16 {
+            let request = echo_request(addr.is_ipv6(), id, seq);
+            let started = Instant::now();
+            socket.send(&request).map_err(|e| e.to_string())?;
+            while let Some(timeout) = self.timeout.checked_sub(started.elapsed()) {
+                if timeout.is_zero() {
+                    break;
+                }
+                socket
+                    .set_read_timeout(Some(timeout))
+                    .map_err(|e| e.to_string())?;
+                let mut reply = [0; 1500];
+                let len = match (&socket).read(&mut reply) {
+                    Ok(len) => len,
+                    Err(err)
+                        if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                    {
+                        break
+                    }
+                    Err(err) => return Err(err.to_string()),
+                };
+                if is_echo_reply(&reply[..len], addr.is_ipv6(), seq) {
+                    rtts.push(started.elapsed());
+                    break;
+                }
+            }
+        }
+        Ok(rtts)
+    }
+
+    fn ping_udp(&self, addr: IpAddr) -> Result<Vec<Duration>, String> {
+        let local: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let mut rtts = Vec::new();
+        for _ in 0..self.count {
+            let socket = UdpSocket::bind(local).map_err(|e| e.to_string())?;
+            socket
+                .connect((addr, UDP_PORT))
+                .map_err(|e| e.to_string())?;
+            socket
+                .set_read_timeout(Some(self.timeout))
+                .map_err(|e| e.to_string())?;
+            let started = Instant::now();
+            socket.send(b"mackerel-plugin").map_err(|e| e.to_string())?;
+            // the port unreachable error is reported on the connected socket
+            match socket.recv(&mut [0; 64]) {
+                Ok(_) => rtts.push(started.elapsed()),
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset
+                    ) =>
+                {
+                    rtts.push(started.elapsed())
+                }
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+        Ok(rtts)
+    }
+}
+
+/// Opens the unprivileged ICMP socket, or the raw socket if not permitted.
+fn icmp_socket(addr: IpAddr) -> std::io::Result<Socket> {
+    let (domain, protocol) = match addr {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+    };
+    Socket::new(domain, Type::DGRAM, Some(protocol)).or_else(|err| {
+        Socket::new(domain, Type::RAW, Some(protocol)).map_err(|raw_err| {
+            if raw_err.kind() == ErrorKind::PermissionDenied {
+                raw_err
+            } else {
+                err
+            }
+        })
+    })
+}
+
+fn echo_request(ipv6: bool, id: u16, seq: u16) -> Vec<u8> {
+    let mut packet = vec![if ipv6 { 128 } else { 8 }, 0, 0, 0];
+    packet.extend(id.to_be_bytes());
+    packet.extend(seq.to_be_bytes());
+    packet.extend(b"mackerel-plugin");
+    // the checksum of ICMPv6 is calculated by the kernel
+    if !ipv6 {
+        let checksum = checksum(&packet);
+        packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    }
+    packet
+}
+
+fn is_echo_reply(packet: &[u8], ipv6: bool, seq: u16) -> bool {
+    // the raw sockets of IPv4 receive the packets with the IP header
+    let packet = match packet.first() {
+        Some(b) if !ipv6 && b >> 4 == 4 => packet.get(((b & 0x0f) as usize) * 4..).unwrap_or(&[]),
+        _ => packet,
+    };
+    // the identifier is rewritten by the kernel for the unprivileged sockets
+    packet.len() >= 8
+        && packet[0] == if ipv6 { 129 } else { 0 }
+        && packet[6..8] == seq.to_be_bytes()
+        && packet[8..].starts_with(b"mackerel-plugin")
+}
+
+fn checksum(packet: &[u8]) -> u16 {
+    let mut sum = packet
+        .chunks(2)
+        .map(|chunk| u16::from_be_bytes([chunk[0], *chunk.get(1).unwrap_or(&0)]) as u32)
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_request() {
+        let packet = echo_request(false, 0x1234, 1);
+        assert_eq!(&packet[..8], &[8, 0, 0xda, 0xcf, 0x12, 0x34, 0, 1]);
+        assert_eq!(checksum(&packet), 0);
+        let mut reply = vec![0x45; 20];
+        reply.extend([0, 0, 0, 0, 0x56, 0x78, 0, 1]);
+        reply.extend(b"mackerel-plugin");
+        assert!(is_echo_reply(&reply, false, 1));
+        assert!(is_echo_reply(&reply[20..], false, 1));
+        assert!(!is_echo_reply(&reply[20..], false, 2));
+        assert!(!is_echo_reply(&reply[20..], true, 1));
+    }
+}
@@ -1,67 +1,709 @@
-use auto_enums::auto_enum;
-use serde_derive::{Deserialize, Serialize};
-use serde_json::json;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
 
+#[cfg(feature = "api")]
+use crate::api::Client;
+#[cfg(feature = "json")]
+use crate::cache::{load_fetched_values, save_fetched_values, Cache};
+use crate::clock::{Clock, Epoch, SystemClock, Timestamping};
+use crate::context::Context;
+use crate::discovery::Discovery;
+use crate::either::Either;
+use crate::error::Error;
+use crate::filter::Filter;
 use crate::graph::Graph;
+use crate::json;
+use crate::label::{expand_label, Transform};
 use crate::metric::Metric;
+#[cfg(feature = "json")]
+use crate::migration::PrefixMigration;
+use crate::output::META_HEADER;
+#[cfg(feature = "json")]
+use crate::rate_limit::RateLimiter;
+use crate::rename::{apply_renames, Rename};
+#[cfg(feature = "api")]
+use crate::resource::ResourceSink;
+use crate::self_metrics::SelfMetrics;
+#[cfg(feature = "json")]
+use crate::series::{is_wildcard_graph, rollup, SeriesSelection};
+#[cfg(feature = "api")]
+use crate::service::ServiceSink;
+use crate::sink::{estimate_output_size, JsonSink, LtsvSink, MetricSink, TeeSink, TsvSink};
+use crate::staleness::{StaleAction, Staleness};
+use crate::state::{
+    fallback_store, is_writable, warn_fallback, workdir, FileStateStore, StateStore,
+};
+#[cfg(feature = "json")]
+use crate::threshold::thresholds_json;
+use crate::timing::{take_section_durations, SectionTimer};
+use crate::unit::{round_half_even, Unit};
+use crate::value::Value;
+use crate::wildcard;
 
-#[derive(Default, Serialize, Deserialize)]
+/// The number of the values output between the flushes of the sink.
+const FLUSH_LINES: usize = 10000;
+
+#[derive(Default)]
 struct MetricValues {
-    timestamp: i64,
+    timestamp: Epoch,
     values: HashMap<String, f64>,
+    counters32: HashSet<String>,
 }
 
 impl MetricValues {
-    fn new(timestamp: i64, values: HashMap<String, f64>) -> MetricValues {
-        MetricValues { timestamp, values }
+    fn new(timestamp: Epoch, values: HashMap<String, Value>) -> MetricValues {
+        MetricValues {
+            timestamp,
+            counters32: values
+                .iter()
+                .filter(|(_, value)| matches!(value, Value::Counter32(_)))
+                .map(|(name, _)| name.clone())
+                .collect(),
+            values: values
+                .into_iter()
+                .map(|(name, value)| (name, value.as_f64()))
+                .collect(),
+        }
     }
 }
 
 /// A trait which represents a Plugin.
 ///
-/// You can create a plugin by implementing `fetch_metrics` and `graph_definition`.
+/// You can create a plugin by implementing `fetch_metrics` (or `fetch_values`)
+/// and `graph_definition`.
 pub trait Plugin {
-    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String>;
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Err("either fetch_metrics or fetch_values should be implemented".to_owned())
+    }
+
+    /// Fetches the metric values of various types, such as counters and booleans.
+    ///
+    /// By default, this method calls `fetch_metrics`.
+    fn fetch_values(&self) -> Result<HashMap<String, Value>, String> {
+        Ok(self
+            .fetch_metrics()?
+            .into_iter()
+            .map(|(name, value)| (name, Value::Float(value)))
+            .collect())
+    }
+
+    /// Fetches the metric values with the context, which carries the command
+    /// line arguments, the environment variables, the metric values of the
+    /// previous run, the deadline, and the logger.
+    ///
+    /// By default, this method calls `fetch_values`.
+    fn fetch_metrics_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, String> {
+        let _ = ctx;
+        self.fetch_values()
+    }
+
+    /// Returns whether to fetch the metric values per graph by
+    /// `fetch_metrics_for`, so that a failure of the source of a graph drops
+    /// only the values of the graph. The errors are logged to the standard
+    /// error, and the fetch fails only when all the graphs fail.
+    fn fetch_per_graph(&self) -> bool {
+        false
+    }
+
+    /// Fetches the metric values of the graph, which is called for each graph
+    /// in the definitions when `fetch_per_graph` returns true.
+    fn fetch_metrics_for(&self, graph: &Graph) -> Result<HashMap<String, Value>, String> {
+        let _ = graph;
+        Err("fetch_metrics_for should be implemented to fetch per graph".to_owned())
+    }
+
+    /// Returns the plugin shared between the threads to fetch the graphs in
+    /// parallel, which is typically `Some(self)` for the plugins of
+    /// `Send + Sync`. The graphs are fetched sequentially by default.
+    fn parallel_fetch(&self) -> Option<&dyn SyncPlugin> {
+        None
+    }
 
     fn graph_definition(&self) -> Vec<Graph>;
 
+    /// Returns the graph definitions borrowed from the plugin, which is used
+    /// on the output instead of `graph_definition`. The plugins which keep
+    /// many graphs can override this to avoid building them on each run.
+    fn graph_definition_ref(&self) -> Cow<'_, [Graph]> {
+        Cow::Owned(self.graph_definition())
+    }
+
     fn metric_key_prefix(&self) -> String {
         "".to_owned()
     }
 
-    #[doc(hidden)]
-    fn output_values(&self, out: &mut dyn std::io::Write) -> Result<(), String> {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map_err(|e| e.to_string())?;
-        let metric_values = MetricValues::new(now.as_secs() as i64, self.fetch_metrics()?);
+    /// Returns the mode of timestamping the metric values.
+    fn timestamping(&self) -> Timestamping {
+        Timestamping::BeforeFetch
+    }
+
+    /// Returns the time the fetched values were collected by the source. This
+    /// method is called after fetching the values, and used for timestamping
+    /// when the mode is `Timestamping::Source`.
+    fn source_time(&self) -> Option<std::time::SystemTime> {
+        None
+    }
+
+    /// Returns the maximum number of series emitted per wildcard graph, which
+    /// keeps the graphs of high-cardinality sources useful. The series of the
+    /// largest values are selected, and the selection is kept stable across
+    /// runs using the state store.
+    #[cfg(feature = "json")]
+    fn series_limit(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns whether to aggregate the series dropped by the series limit
+    /// into the `other` series, which replaces the wildcard segments of the
+    /// metric name with `other`, so that the totals remain accurate. The
+    /// values of the series named `other` are merged into the aggregate.
+    #[cfg(feature = "json")]
+    fn series_rollup(&self) -> bool {
+        false
+    }
+
+    /// Returns the rate limiter of the requests to the upstream sources, which
+    /// is passed to `fetch_metrics_ctx` in the context and respected by the
+    /// collectors requesting the HTTP servers and the databases.
+    ///
+    /// ```rust,no_run
+    /// # use mackerel_plugin::{FileStateStore, Graph, Plugin, RateLimiter};
+    /// # use std::collections::HashMap;
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// struct ApiPlugin {
+    ///     rate_limiter: Arc<RateLimiter<'static>>,
+    /// }
+    ///
+    /// impl ApiPlugin {
+    ///     fn new() -> ApiPlugin {
+    ///         let path = mackerel_plugin::workdir().join("mackerel-plugin-api.ratelimit");
+    ///         ApiPlugin {
+    ///             // 100 requests per hour across the runs
+    ///             rate_limiter: Arc::new(RateLimiter::new(
+    ///                 &FileStateStore,
+    ///                 path.to_string_lossy(),
+    ///                 100,
+    ///                 Duration::from_secs(3600),
+    ///             )),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// impl Plugin for ApiPlugin {
+    /// #   fn graph_definition(&self) -> Vec<Graph> { unimplemented!() }
+    ///     fn rate_limiter(&self) -> Option<Arc<RateLimiter<'static>>> {
+    ///         Some(self.rate_limiter.clone())
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "json")]
+    fn rate_limiter(&self) -> Option<std::sync::Arc<RateLimiter<'static>>> {
+        None
+    }
+
+    /// Returns the time to live of the fetched metric values, which are cached
+    /// next to the state file and reused by the invocations within the time,
+    /// such as printing the series labels before outputting the values. The
+    /// renames are applied to the cached values on each invocation.
+    #[cfg(feature = "json")]
+    fn fetch_cache_ttl(&self) -> Option<std::time::Duration> {
+        None
+    }
+
+    /// Returns the maximum of the random delay before fetching the metric
+    /// values, which spreads the requests of the plugins on many hosts to a
+    /// shared source. The delay is deducted from the timeout of the fetch.
+    ///
+    /// By default, the maximum is configured by the environment variable
+    /// `MACKEREL_PLUGIN_JITTER` in seconds.
+    fn fetch_jitter(&self) -> Option<std::time::Duration> {
+        std::env::var("MACKEREL_PLUGIN_JITTER")
+            .ok()
+            .and_then(|jitter| jitter.parse::<f64>().ok())
+            .and_then(|jitter| std::time::Duration::try_from_secs_f64(jitter).ok())
+    }
+
+    /// Returns the policy for the metric values observed by the source long
+    /// ago. The values without the observed time are always considered fresh.
+    fn staleness(&self) -> Option<Staleness> {
+        None
+    }
+
+    /// Returns whether to floor the timestamps of the metric values to the
+    /// minute, which is the resolution of Mackerel. The differences of the
+    /// metric values are calculated with the elapsed time between the actual
+    /// timestamps, so the alignment does not affect the rates.
+    fn align_timestamps(&self) -> bool {
+        false
+    }
+
+    /// Returns the migration of the metric key prefix, which emits the metric
+    /// values with the old prefix as well in the migration period.
+    #[cfg(feature = "json")]
+    fn prefix_migration(&self) -> Option<PrefixMigration> {
+        None
+    }
+
+    /// Returns the renames of the metric names, which are applied to the
+    /// fetched metrics before matching them with the graph definitions.
+    ///
+    /// By default, the renames are loaded from the JSON file specified by the
+    /// environment variable `MACKEREL_PLUGIN_RENAME_FILE`.
+    fn metric_renames(&self) -> Result<Vec<Rename>, String> {
+        Rename::from_env()
+    }
+
+    /// Returns whether to fail on the fetched metric names matching no metric
+    /// in the graph definitions, which catches the typos of the names at
+    /// deploy time rather than as the silently missing graphs. The names are
+    /// checked after the renames, regardless of the wildcard filter. The
+    /// names matching multiple metrics also fail, which are otherwise emitted
+    /// only for the first metric with a warning. The graphs mixing the stacked
+    /// and the unstacked metrics fail on the output of the graph definitions.
+    ///
+    /// By default, the strict mode is enabled by the environment variable
+    /// `MACKEREL_PLUGIN_STRICT`.
+    fn strict(&self) -> bool {
+        std::env::var("MACKEREL_PLUGIN_STRICT").is_ok_and(|value| !value.is_empty())
+    }
+
+    /// Returns the character to escape the characters in the fetched metric
+    /// names which the wildcard segments do not match, such as colons and
+    /// spaces, after the renames, so that the series do not vanish. The names
+    /// not matching the graph definitions are escaped to match them, where a
+    /// wildcard segment takes the dots in the name, such as the queue names
+    /// with dots. The character should be an alphanumeric character, a hyphen,
+    /// or an underscore.
+    ///
+    /// The escaped names colliding with the other names are emitted only for
+    /// the first name in the order of the names, preferring the names matching
+    /// without the escape, with a warning, or fail in the strict mode.
+    ///
+    /// ```rust
+    /// # use mackerel_plugin::{graph, Graph, Plugin};
+    /// # use std::collections::HashMap;
+    /// struct QueuePlugin {}
+    ///
+    /// impl Plugin for QueuePlugin {
+    ///     fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+    ///         // emitted as queue.jobs_high.depth
+    ///         Ok(HashMap::from([("queue.jobs.high.depth".to_owned(), 3.0)]))
+    ///     }
+    ///
+    ///     fn graph_definition(&self) -> Vec<Graph> {
+    ///         vec![graph! {
+    ///             name: "queue.#",
+    ///             label: "Queue",
+    ///             unit: "integer",
+    ///             metrics: [{ name: "depth", label: "Depth" }]
+    ///         }]
+    ///     }
+    ///
+    ///     fn escape_metric_names(&self) -> Option<char> {
+    ///         Some('_')
+    ///     }
+    /// }
+    /// ```
+    fn escape_metric_names(&self) -> Option<char> {
+        None
+    }
+
+    /// Returns the transformations applied to the wildcard values on expanding
+    /// the labels of the discovered series.
+    fn label_transforms(&self) -> Vec<Transform> {
+        Vec::new()
+    }
+
+    /// Fetches the metrics and returns the labels of the discovered series
+    /// keyed by the metric names, expanding the placeholders (`%1`, `%2`, ...)
+    /// in the labels. This is useful for previewing the graphs and generating
+    /// per-series dashboard definitions. The fetched values are cached in the
+    /// state store for `fetch_cache_ttl`.
+    fn series_labels(
+        &self,
+        state: &(dyn StateStore + Sync),
+        clock: &(dyn Clock + Sync),
+    ) -> Result<HashMap<String, String>, Error> {
+        let metric_values = fetch_renamed_values(self, &fetch_context(self, state, clock), &mut 0)?;
         let prefix = self.metric_key_prefix();
-        let graphs = self.graph_definition();
-        let has_diff = graphs.iter().any(|graph| graph.has_diff());
-        let path = self.tempfile_path(&prefix)?;
-        let prev_metric_values = if has_diff {
-            load_values(&path).unwrap_or_default()
-        } else {
-            MetricValues::default()
-        };
-        for graph in graphs {
-            for metric in graph.metrics {
-                format_values(
-                    out,
-                    &prefix,
-                    &graph.name,
-                    metric,
-                    &metric_values,
-                    &prev_metric_values,
-                );
+        let transforms = self.label_transforms();
+        let mut labels = HashMap::new();
+        for graph in self.graph_definition_ref().iter() {
+            let filter = self.wildcard_filter(graph);
+            for metric in &graph.metrics {
+                let pattern = join_name(&graph.name, &metric.name);
+                for name in metric_values.keys() {
+                    if let Some(values) = wildcard::capture(&pattern, name)
+                        .filter(|values| values.iter().all(|value| filter.matches(value)))
+                    {
+                        labels.insert(
+                            join_name(&prefix, name),
+                            expand_label(&metric.label, &values, &transforms),
+                        );
+                    }
+                }
             }
         }
-        if has_diff {
-            save_values(&path, &metric_values)?;
+        Ok(labels)
+    }
+
+    /// Fetches the metrics and returns the graph definitions expanded to the
+    /// discovered series, where the wildcard segments are replaced with the
+    /// concrete values and the placeholders in the labels are expanded. The
+    /// metrics without wildcards are kept as they are. This is useful for
+    /// generating the documentation of the graphs. The fetched values are
+    /// cached in the state store for `fetch_cache_ttl`.
+    fn expanded_graph_definition(
+        &self,
+        state: &(dyn StateStore + Sync),
+        clock: &(dyn Clock + Sync),
+    ) -> Result<Vec<Graph>, Error> {
+        let metric_values = fetch_renamed_values(self, &fetch_context(self, state, clock), &mut 0)?;
+        let mut names = metric_values.keys().collect::<Vec<_>>();
+        names.sort();
+        let transforms = self.label_transforms();
+        let mut graphs: Vec<Graph> = Vec::new();
+        for graph in self.graph_definition_ref().iter() {
+            let filter = self.wildcard_filter(graph);
+            let depth = graph.name.split('.').filter(|s| !s.is_empty()).count();
+            let wildcards = graph
+                .name
+                .split('.')
+                .filter(|s| *s == "*" || *s == "#")
+                .count();
+            for metric in &graph.metrics {
+                let pattern = join_name(&graph.name, &metric.name);
+                if !pattern.contains('*') && !pattern.contains('#') {
+                    expanded_graph(&mut graphs, graph, graph.name.clone(), graph.label.clone())
+                        .metrics
+                        .push(metric.clone());
+                    continue;
+                }
+                for name in &names {
+                    let Some(values) = wildcard::capture(&pattern, name)
+                        .filter(|values| values.iter().all(|value| filter.matches(value)))
+                    else {
+                        continue;
+                    };
+                    let (graph_name, metric_name) = match depth
+                        .checked_sub(1)
+                        .and_then(|n| name.match_indices('.').nth(n))
+                    {
+                        Some((i, _)) => (&name[..i], &name[i + 1..]),
+                        None => ("", &name[..]),
+                    };
+                    let label = expand_label(&graph.label, &values[..wildcards], &transforms);
+                    expanded_graph(&mut graphs, graph, graph_name.to_owned(), label)
+                        .metrics
+                        .push(Metric {
+                            name: metric_name.to_owned(),
+                            label: expand_label(&metric.label, &values, &transforms),
+                            ..metric.clone()
+                        });
+                }
+            }
         }
-        Ok(())
+        Ok(graphs)
+    }
+
+    /// Returns the number of the decimal places of the output values of the
+    /// unit, which are rounded half to even. The metrics can override this by
+    /// `precision` of each metric. Defaults to `None`, which outputs the values
+    /// as they are, and `Unit::recommended_precision` rounds the percentages
+    /// to 2 decimal places.
+    ///
+    /// ```rust
+    /// use mackerel_plugin::Unit;
+    ///
+    /// # struct MyPlugin;
+    /// # impl MyPlugin {
+    /// fn precision(&self, unit: &Unit) -> Option<u32> {
+    ///     unit.recommended_precision()
+    /// }
+    /// # }
+    /// ```
+    fn precision(&self, unit: &Unit) -> Option<u32> {
+        let _ = unit;
+        None
+    }
+
+    /// Returns the sane size of the output in bytes, over which a warning is
+    /// reported to the standard error before the output. The size is
+    /// estimated by `estimate_output_size` from the fetched values.
+    fn output_size_limit(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns whether to output the graph definitions expanded to the
+    /// discovered series by `expanded_graph_definition` for mackerel-agent,
+    /// so that the labels of the series are concrete instead of the
+    /// placeholders. The series discovered after the definitions are output
+    /// are not shown until mackerel-agent requests the definitions again.
+    fn expand_definitions(&self) -> bool {
+        false
+    }
+
+    /// Resolves the graph and the metric in the graph definitions which the
+    /// fetched metric name belongs to, in the same way as the values are
+    /// collected for output. Returns the graph name with the metric key
+    /// prefix, as in the meta output, and the metric name of the definition.
+    /// The metric renames are not applied to the name.
+    fn resolve(&self, name: &str) -> Option<(String, String)> {
+        self.graph_definition_ref().iter().find_map(|graph| {
+            let filter = self.wildcard_filter(graph);
+            graph
+                .metrics
+                .iter()
+                .find(|metric| {
+                    matches_filtered(&join_name(&graph.name, &metric.name), name, &filter)
+                })
+                .map(|metric| {
+                    (
+                        join_name(&self.metric_key_prefix(), &graph.name),
+                        metric.name.clone(),
+                    )
+                })
+        })
+    }
+
+    /// Returns the cache persisted next to the state file of the plugin, which
+    /// is useful for storing expensive discovery results between runs. The
+    /// cache is kept in the state store of the context.
+    ///
+    /// ```rust,no_run
+    /// # use mackerel_plugin::{Context, Graph, Plugin, Value};
+    /// # use std::collections::HashMap;
+    /// # use std::time::Duration;
+    /// # fn list_databases() -> Result<Vec<String>, String> { unimplemented!() }
+    /// struct DatabasePlugin {}
+    ///
+    /// impl Plugin for DatabasePlugin {
+    ///     fn fetch_metrics_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, String> {
+    ///         let cache = self.cache(ctx).map_err(|e| e.to_string())?;
+    ///         let databases: Vec<String> = cache
+    ///             .get_or_refresh("databases", Duration::from_secs(600), list_databases)
+    ///             .map_err(|e| e.to_string())?;
+    ///         // fetch the metrics of the databases
+    /// #       unimplemented!()
+    ///     }
+    /// #   fn graph_definition(&self) -> Vec<Graph> { unimplemented!() }
+    /// }
+    /// ```
+    #[cfg(feature = "json")]
+    fn cache<'a>(&self, ctx: &Context<'a>) -> Result<Cache<'a>, Error> {
+        let path = self.tempfile_path(&self.metric_key_prefix())?;
+        Ok(Cache::new(ctx.state_store(), path + ".cache").clock(ctx.clock()))
+    }
+
+    /// Discovers the targets of the plugin, such as the databases or the
+    /// queues, with the display names. Use `discovered` to share the result
+    /// between the graph definitions and the fetch.
+    fn discover(&self) -> Result<Discovery, String> {
+        Ok(Discovery::new())
+    }
+
+    /// Returns the time to live of the discovered targets in the cache.
+    #[cfg(feature = "json")]
+    fn discovery_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(600)
+    }
+
+    /// Returns the targets by `discover`, which are cached in the state store
+    /// of the context for `discovery_ttl`, so that the invocations for the
+    /// graph definitions and the values do not discover the targets each time.
+    ///
+    /// ```rust,no_run
+    /// # use mackerel_plugin::{graph, Context, Discovery, Graph, Plugin, Transform, Value};
+    /// # use std::collections::HashMap;
+    /// # fn list_databases() -> Result<Vec<(String, String)>, String> { unimplemented!() }
+    /// # fn fetch_database(name: &str) -> Result<f64, String> { unimplemented!() }
+    /// struct DatabasePlugin {}
+    ///
+    /// impl Plugin for DatabasePlugin {
+    ///     fn discover(&self) -> Result<Discovery, String> {
+    ///         Ok(list_databases()?
+    ///             .into_iter()
+    ///             .fold(Discovery::new(), |discovery, (name, display_name)| {
+    ///                 discovery.target(name, display_name)
+    ///             }))
+    ///     }
+    ///
+    ///     fn fetch_metrics_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, String> {
+    ///         let discovery = self.discovered(ctx).map_err(|e| e.to_string())?;
+    ///         discovery
+    ///             .names()
+    ///             .map(|name| {
+    ///                 let size = fetch_database(name)?;
+    ///                 Ok((format!("database.size.{}", name), size.into()))
+    ///             })
+    ///             .collect()
+    ///     }
+    ///
+    ///     fn graph_definition(&self) -> Vec<Graph> {
+    ///         vec![graph! {
+    ///             name: "database.size",
+    ///             label: "Database Size",
+    ///             unit: "bytes",
+    ///             metrics: [{ name: "*", label: "%1" }]
+    ///         }]
+    ///     }
+    ///
+    ///     fn label_transforms(&self) -> Vec<Transform> {
+    ///         self.discovered(&Context::new())
+    ///             .map(|discovery| vec![discovery.transform()])
+    ///             .unwrap_or_default()
+    ///     }
+    /// }
+    /// ```
+    #[cfg_attr(not(feature = "json"), allow(unused_variables))]
+    fn discovered(&self, ctx: &Context) -> Result<Discovery, Error> {
+        #[cfg(feature = "json")]
+        return self
+            .cache(ctx)?
+            .get_or_refresh("discovery", self.discovery_ttl(), || self.discover());
+        #[cfg(not(feature = "json"))]
+        return Ok(self.discover()?);
+    }
+
+    /// Returns the filter of the values of wildcard segments of the graph.
+    ///
+    /// By default, the filter is configured by the environment variables
+    /// `MACKEREL_PLUGIN_INCLUDE` and `MACKEREL_PLUGIN_EXCLUDE`, or the ones
+    /// suffixed with the graph name by `Filter::from_env_for`, such as
+    /// `MACKEREL_PLUGIN_INCLUDE_DISK=sd*` for the graph `disk.#`.
+    fn wildcard_filter(&self, graph: &Graph) -> Filter {
+        Filter::from_env_for(&graph.name)
+    }
+
+    /// Returns whether to emit the graphs about the plugin itself; the number
+    /// of fetched metrics, the dropped values, the fetch errors, and the fetch
+    /// duration.
+    ///
+    /// On the fetch errors, the metrics about the plugin are still emitted
+    /// before returning the error, so that the operators can alert on them.
+    fn emit_self_metrics(&self) -> bool {
+        false
+    }
+
+    /// Starts measuring the duration of the section until the returned timer
+    /// is dropped, which is emitted in the graph of the plugin duration.
+    ///
+    /// ```rust
+    /// # use mackerel_plugin::{Graph, Plugin};
+    /// # use std::collections::HashMap;
+    /// struct DatabasePlugin {}
+    ///
+    /// impl Plugin for DatabasePlugin {
+    ///     fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+    ///         let _t = self.time_section("db_query");
+    ///         // query the database
+    /// #       Ok(HashMap::new())
+    ///     }
+    /// #   fn graph_definition(&self) -> Vec<Graph> { unimplemented!() }
+    ///
+    ///     fn emit_self_metrics(&self) -> bool {
+    ///         true
+    ///     }
+    /// }
+    /// ```
+    fn time_section(&self, name: &str) -> SectionTimer {
+        SectionTimer::start(name)
+    }
+
+    /// Returns the name of the service to post the metric values as the
+    /// service metrics, which are not tied to a host.
+    ///
+    /// When the name is returned, `run` and `run_with` post the metric values
+    /// to the service with the API key of `MACKEREL_APIKEY`, instead of
+    /// writing them to the output for mackerel-agent. The graph definitions
+    /// are still written to the output on `MACKEREL_AGENT_PLUGIN_META`.
+    #[cfg(feature = "api")]
+    fn service_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the custom identifier of the resource of the metric, such as a
+    /// load balancer scraped from a central API.
+    ///
+    /// The values of the resources are posted as the metrics of the hosts of
+    /// the custom identifiers with the API key of `MACKEREL_APIKEY`, rather than
+    /// written to the standard output as the metrics of the invoking host.
+    #[cfg(feature = "api")]
+    fn custom_identifier(&self, metric_name: &str) -> Option<String> {
+        let _ = metric_name;
+        None
+    }
+
+    /// Returns the sinks which receive the metric values in addition to the
+    /// output for mackerel-agent, such as a file for auditing the output.
+    ///
+    /// The errors of these sinks are reported to the standard error, and never
+    /// break the output for mackerel-agent.
+    fn secondary_sinks(&self) -> Vec<Box<dyn MetricSink>> {
+        Vec::new()
+    }
+
+    #[doc(hidden)]
+    fn output_values(&self, out: &mut dyn std::io::Write) -> Result<(), Error> {
+        self.output_values_with(out, &FileStateStore, &SystemClock)
+    }
+
+    #[doc(hidden)]
+    fn output_values_with(
+        &self,
+        out: &mut dyn std::io::Write,
+        state: &(dyn StateStore + Sync),
+        clock: &(dyn Clock + Sync),
+    ) -> Result<(), Error> {
+        self.output_values_to(&mut TsvSink::new(out), state, clock)
+    }
+
+    /// Outputs the metric values as a JSON array of the objects with `name`,
+    /// `value`, and `time`, which is useful for piping to `jq` or collectors
+    /// other than mackerel-agent.
+    #[doc(hidden)]
+    fn output_values_json(
+        &self,
+        out: &mut dyn std::io::Write,
+        state: &(dyn StateStore + Sync),
+        clock: &(dyn Clock + Sync),
+    ) -> Result<(), Error> {
+        self.output_values_to(&mut JsonSink::new(out), state, clock)
+    }
+
+    /// Outputs the metric values to the sink.
+    #[doc(hidden)]
+    fn output_values_to(
+        &self,
+        sink: &mut dyn MetricSink,
+        state: &(dyn StateStore + Sync),
+        clock: &(dyn Clock + Sync),
+    ) -> Result<(), Error> {
+        let mut stats = SelfMetrics::default();
+        let result = collect_values(self, state, clock, &mut stats);
+        let values = result.as_deref().unwrap_or_default();
+        for (i, (name, value, timestamp)) in values.iter().enumerate() {
+            sink.write_value(name, *value, *timestamp)?;
+            // flush the large output in chunks for the consumer to read early
+            if (i + 1) % FLUSH_LINES == 0 && i + 1 < values.len() {
+                sink.flush()?;
+                std::thread::yield_now();
+            }
+        }
+        if self.emit_self_metrics() {
+            if result.is_err() {
+                stats.errors += 1;
+            }
+            let prefix = self.metric_key_prefix();
+            for (name, value) in stats.values() {
+                sink.write_value(&join_name(&prefix, &name), value, stats.timestamp.as_secs())?;
+            }
+        }
+        sink.finish()?;
+        result.map(|_| ())
     }
 
     #[doc(hidden)]
@@ -80,184 +722,755 @@ pub trait Plugin {
         } else {
             "mackerel-plugin-".to_owned() + prefix
         };
-        Ok(std::env::var("MACKEREL_PLUGIN_WORKDIR")
-            .map_or_else(
-                |_| std::env::temp_dir(),
-                |path| std::path::PathBuf::from(&path),
-            )
+        Ok(workdir()
             .join(name)
             .to_str()
             .ok_or("invalid plugin working directory")?
             .to_owned())
     }
 
+    /// Returns the warnings of the graph definitions, such as the differences
+    /// of the percentages, which are written to the standard error on the
+    /// output of the graph definitions to catch the mistakes early.
+    fn graph_warnings(&self) -> Vec<String> {
+        self.graph_definition_ref()
+            .iter()
+            .flat_map(Graph::warnings)
+            .collect()
+    }
+
     #[doc(hidden)]
-    fn output_definitions(&self, out: &mut dyn std::io::Write) -> Result<(), String> {
-        writeln!(out, "# mackerel-agent-plugin").map_err(|e| format!("{}", e))?;
-        let prefix = self.metric_key_prefix();
-        let json = json!({
-            "graphs": self.graph_definition()
+    fn output_definitions(&self, out: &mut dyn std::io::Write) -> Result<(), Error> {
+        self.output_definitions_with(out, &FileStateStore, &SystemClock)
+    }
+
+    #[doc(hidden)]
+    fn output_definitions_with(
+        &self,
+        out: &mut dyn std::io::Write,
+        state: &(dyn StateStore + Sync),
+        clock: &(dyn Clock + Sync),
+    ) -> Result<(), Error> {
+        if self.strict() {
+            if let Some(graph) = self
+                .graph_definition_ref()
                 .iter()
-                .map(|graph|
-                    (
-                        if prefix.is_empty() {
-                            graph.name.clone()
-                        } else if graph.name.is_empty() {
-                            prefix.clone()
-                        } else {
-                            prefix.clone() + "." + graph.name.as_ref()
-                        },
-                        graph
-                    )
+                .find(|graph| graph.mixes_stacking())
+            {
+                return Err(format!(
+                    "graph {} mixes the stacked and unstacked metrics",
+                    graph.name
                 )
-                .collect::<HashMap<_, _>>(),
-        });
-        writeln!(out, "{}", json).map_err(|e| format!("{}", e))?;
+                .into());
+            }
+        }
+        for warning in self.graph_warnings() {
+            let _ = writeln!(std::io::stderr(), "graph definition: {}", warning);
+        }
+        writeln!(out, "{}", META_HEADER)?;
+        let prefix = self.metric_key_prefix();
+        let graphs = if self.expand_definitions() {
+            self.expanded_graph_definition(state, clock)
+                .map(Cow::Owned)
+                .unwrap_or_else(|err| {
+                    let _ = writeln!(
+                        std::io::stderr(),
+                        "expand graph definitions failed: {}",
+                        err
+                    );
+                    self.graph_definition_ref()
+                })
+        } else {
+            self.graph_definition_ref()
+        };
+        let self_graphs = if self.emit_self_metrics() {
+            SelfMetrics::graphs()
+        } else {
+            Vec::new()
+        };
+        json::write_graphs_json(out, &prefix, graphs.iter().chain(&self_graphs))?;
         Ok(())
     }
 
+    #[doc(hidden)]
+    fn output(
+        &self,
+        out: &mut dyn std::io::Write,
+        state: &(dyn StateStore + Sync),
+        clock: &(dyn Clock + Sync),
+    ) -> Result<(), Error> {
+        #[cfg(feature = "json")]
+        if std::env::var("MACKEREL_PLUGIN_THRESHOLDS").is_ok_and(|value| !value.is_empty()) {
+            writeln!(out, "{}", thresholds_json(self))?;
+            return Ok(());
+        }
+        if std::env::var("MACKEREL_AGENT_PLUGIN_META").is_ok_and(|value| !value.is_empty()) {
+            self.output_definitions_with(out, state, clock)
+        } else {
+            #[cfg(feature = "api")]
+            if let Some(service) = self.service_name() {
+                let client = Client::from_env()?;
+                let mut sink = ServiceSink::new(&client, service)?;
+                return self.output_values_to(&mut sink, state, clock);
+            }
+            let primary: Box<dyn MetricSink> =
+                match std::env::var("MACKEREL_PLUGIN_OUTPUT_FORMAT").as_deref() {
+                    Err(_) | Ok("" | "tsv") => Box::new(TsvSink::new(out)),
+                    Ok("json") => Box::new(JsonSink::new(out)),
+                    Ok("ltsv") => Box::new(LtsvSink::new(out)),
+                    Ok(format) => return Err(format!("unknown output format: {}", format).into()),
+                };
+            #[cfg(feature = "api")]
+            let primary = Box::new(ResourceSink::new(primary, |name: &str| {
+                self.custom_identifier(name)
+            }));
+            let mut sink = self
+                .secondary_sinks()
+                .into_iter()
+                .fold(TeeSink::new(primary), TeeSink::sink);
+            self.output_values_to(&mut sink, state, clock)
+        }
+    }
+
+    /// Runs the plugin, which saves the state to the working directory.
+    ///
+    /// When the working directory is not writable, for example on read-only
+    /// root filesystems, the state is kept in memory with a warning, and the
+    /// metrics of difference are not reported.
     fn run(&self) -> Result<(), String> {
+        self.try_run().map_err(|err| err.to_string())
+    }
+
+    /// Runs the plugin like `run`, returning the error which tells the broken
+    /// pipe and the full storage apart, and the exit status code by
+    /// `Error::exit_code`.
+    fn try_run(&self) -> Result<(), Error> {
         let stdout = std::io::stdout();
         let mut out = std::io::BufWriter::new(stdout.lock());
-        if std::env::var("MACKEREL_AGENT_PLUGIN_META").map_or(false, |value| !value.is_empty()) {
-            self.output_definitions(&mut out)
+        let dir = workdir();
+        let state: &(dyn StateStore + Sync) = if is_writable(&dir) {
+            &FileStateStore
+        } else {
+            warn_fallback(&format!("{} is not writable", dir.display()));
+            fallback_store()
+        };
+        self.output(&mut out, state, &SystemClock)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Runs the plugin with the specified output, state store, and clock.
+    ///
+    /// This is useful for driving the plugin in-process without touching the
+    /// standard output and the file system. The context of the fetch carries
+    /// the state store and the clock, so the cache and the discovery of the
+    /// plugin by the context are also kept in the state store.
+    fn run_with(
+        &self,
+        mut out: impl std::io::Write,
+        state: impl StateStore + Sync,
+        clock: impl Clock + Sync,
+    ) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        self.output(&mut out, &state, &clock)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// A plugin which can be shared between threads, which is implemented for
+/// the plugins of `Send + Sync` automatically.
+///
+/// This is useful for embedders driving the plugins from multiple threads.
+///
+/// ```rust
+/// use mackerel_plugin::{Graph, Plugin, SyncPlugin};
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+///
+/// struct DicePlugin {}
+///
+/// impl Plugin for DicePlugin {
+///     fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+///         Ok(HashMap::from([("dice.d6".to_owned(), 3.0)]))
+///     }
+///
+///     fn graph_definition(&self) -> Vec<Graph> {
+///         Vec::new()
+///     }
+/// }
+///
+/// let plugins: Vec<Arc<dyn SyncPlugin>> = vec![Arc::new(DicePlugin {})];
+/// std::thread::scope(|s| {
+///     for plugin in &plugins {
+///         s.spawn(|| plugin.fetch_metrics());
+///     }
+/// });
+/// ```
+pub trait SyncPlugin: Plugin + Send + Sync {}
+
+impl<T: Plugin + Send + Sync + ?Sized> SyncPlugin for T {}
+
+/// Fetches the metric values and returns the names, the values, and the
+/// timestamps to output, saving the state for the next run.
+fn collect_values<P: Plugin + ?Sized>(
+    plugin: &P,
+    state: &(dyn StateStore + Sync),
+    clock: &(dyn Clock + Sync),
+    stats: &mut SelfMetrics,
+) -> Result<Vec<(String, f64, i64)>, Error> {
+    let before = clock.now();
+    stats.timestamp = before.into();
+    let prefix = plugin.metric_key_prefix();
+    let path = plugin.tempfile_path(&prefix)?;
+    let prev_metric_values = load_values(state, &path);
+    let ctx = match &prev_metric_values {
+        Ok(prev) => fetch_context(plugin, state, clock)
+            .with_previous_values(prev.timestamp, prev.values.clone()),
+        Err(_) => fetch_context(plugin, state, clock),
+    };
+    let prev_metric_values = prev_metric_values.unwrap_or_default();
+    if let Some(jitter) = plugin.fetch_jitter() {
+        ctx.sleep_jitter(jitter);
+    }
+    take_section_durations();
+    let started = std::time::Instant::now();
+    let fetched = fetch_renamed_values(plugin, &ctx, &mut stats.errors);
+    stats.fetch_duration = started.elapsed();
+    stats.sections = take_section_durations();
+    let mut values = fetched?;
+    stats.metrics = values.len();
+    if let Some(limit) = plugin.output_size_limit() {
+        let size = estimate_output_size(&prefix, &values);
+        if size > limit {
+            let _ = writeln!(
+                std::io::stderr(),
+                "output size estimated at {} bytes exceeds the limit of {} bytes",
+                size,
+                limit
+            );
+        }
+    }
+    let graphs = plugin.graph_definition_ref();
+    if plugin.strict() {
+        check_unmatched(&graphs, &values)?;
+    }
+    let now = match plugin.timestamping() {
+        Timestamping::BeforeFetch => before,
+        Timestamping::AfterFetch => clock.now(),
+        Timestamping::Source => plugin.source_time().unwrap_or(before),
+    };
+    if let Some(staleness) = plugin.staleness() {
+        values.retain(|name, value| match *value {
+            Value::Observed(_, observed) => match staleness.stale_age(observed, now) {
+                Some(age) if staleness.action == StaleAction::Warn => {
+                    let _ = writeln!(
+                        std::io::stderr(),
+                        "stale value of {} observed {}s ago",
+                        name,
+                        age.as_secs()
+                    );
+                    true
+                }
+                Some(_) => false,
+                None => true,
+            },
+            _ => true,
+        });
+        stats.dropped += stats.metrics - values.len();
+    }
+    let metric_values = MetricValues::new(now.into(), values);
+    stats.dropped += metric_values
+        .values
+        .values()
+        .filter(|value| !value.is_finite())
+        .count();
+    let timestamp = if plugin.align_timestamps() {
+        metric_values.timestamp.floor(60)
+    } else {
+        metric_values.timestamp
+    };
+    stats.timestamp = timestamp;
+    let has_diff = graphs.iter().any(|graph| graph.has_diff());
+    #[cfg_attr(not(feature = "json"), allow(unused_mut))]
+    let mut prefixes = vec![prefix.clone()];
+    #[cfg(feature = "json")]
+    if let Some(migration) = plugin.prefix_migration() {
+        if migration.is_active(state, &path, metric_values.timestamp.as_secs())? {
+            prefixes.push(migration.old_prefix);
+        }
+    }
+    #[cfg(feature = "json")]
+    let series_limit = plugin.series_limit();
+    #[cfg(feature = "json")]
+    let mut selection = if series_limit.is_some() {
+        SeriesSelection::load(state, &path)
+    } else {
+        SeriesSelection::default()
+    };
+    let mut output = Vec::new();
+    let mut emitted = HashMap::new();
+    let mut duplicates = Vec::new();
+    for graph in graphs.iter() {
+        let mut values = graph_values(
+            graph,
+            &plugin.wildcard_filter(graph),
+            &metric_values,
+            &prev_metric_values,
+            &|unit| plugin.precision(unit),
+        );
+        retain_unique(&mut values, &graph.name, &mut emitted, &mut duplicates);
+        #[cfg(feature = "json")]
+        if let Some(limit) = series_limit.filter(|_| is_wildcard_graph(graph)) {
+            let dropped = selection.select(graph, &mut values, limit);
+            if plugin.series_rollup() {
+                let mut rollups = Vec::new();
+                for (name, value) in rollup(graph, &dropped) {
+                    // merge into the selected series of the same name
+                    match values.iter_mut().find(|(selected, _)| *selected == name) {
+                        Some((_, total)) => *total += value,
+                        None => rollups.push((name, value)),
+                    }
+                }
+                retain_unique(&mut rollups, &graph.name, &mut emitted, &mut duplicates);
+                values.extend(rollups);
+            } else {
+                stats.dropped += dropped.len();
+            }
+        }
+        for (metric_name, value) in values {
+            for prefix in &prefixes {
+                output.push((join_name(prefix, &metric_name), value, timestamp.as_secs()));
+            }
+        }
+    }
+    if !duplicates.is_empty() {
+        if plugin.strict() {
+            return Err(format!("duplicate metric names: {}", duplicates.join(", ")).into());
+        }
+        for duplicate in &duplicates {
+            let _ = writeln!(std::io::stderr(), "duplicate metric name: {}", duplicate);
+        }
+        stats.dropped += duplicates.len();
+    }
+    if has_diff {
+        save_values(state, &path, &metric_values)?;
+    }
+    #[cfg(feature = "json")]
+    if series_limit.is_some() {
+        selection.save(state, &path)?;
+    }
+    Ok(output)
+}
+
+/// Retains the values of the names not emitted by the previous graphs, since
+/// the value matching multiple metrics is emitted only for the first one.
+fn retain_unique<'a>(
+    values: &mut Vec<(String, f64)>,
+    graph_name: &'a str,
+    emitted: &mut HashMap<String, &'a str>,
+    duplicates: &mut Vec<String>,
+) {
+    values.retain(|(name, _)| match emitted.entry(name.clone()) {
+        Entry::Vacant(entry) => {
+            entry.insert(graph_name);
+            true
+        }
+        Entry::Occupied(entry) => {
+            duplicates.push(format!(
+                "{} matches graphs {} and {}",
+                name,
+                entry.get(),
+                graph_name
+            ));
+            false
+        }
+    });
+}
+
+/// Returns the context of the fetch from the environment with the state store
+/// and the clock of the run, and the rate limiter of the plugin.
+fn fetch_context<'a, P: Plugin + ?Sized>(
+    plugin: &P,
+    state: &'a (dyn StateStore + Sync),
+    clock: &'a (dyn Clock + Sync),
+) -> Context<'a> {
+    let ctx = Context::from_env()
+        .with_state_store(state)
+        .with_clock(clock);
+    #[cfg(feature = "json")]
+    if let Some(rate_limiter) = plugin.rate_limiter() {
+        return ctx.with_rate_limiter(rate_limiter);
+    }
+    #[cfg(not(feature = "json"))]
+    let _ = plugin;
+    ctx
+}
+
+fn fetch_renamed_values<P: Plugin + ?Sized>(
+    plugin: &P,
+    ctx: &Context,
+    errors: &mut usize,
+) -> Result<HashMap<String, Value>, Error> {
+    #[cfg(feature = "json")]
+    let values = match plugin.fetch_cache_ttl() {
+        Some(ttl) => fetch_cached_values(plugin, ctx, ttl, errors)?,
+        None => fetch_values(plugin, ctx, errors)?,
+    };
+    #[cfg(not(feature = "json"))]
+    let values = fetch_values(plugin, ctx, errors)?;
+    let values = apply_renames(values, &plugin.metric_renames()?);
+    match plugin.escape_metric_names() {
+        Some(replacement) => escape_values(plugin, values, replacement),
+        None => Ok(values),
+    }
+}
+
+/// Escapes the metric names to match the graph definitions, and drops the
+/// values of the names colliding after the escape.
+fn escape_values<P: Plugin + ?Sized>(
+    plugin: &P,
+    values: HashMap<String, Value>,
+    replacement: char,
+) -> Result<HashMap<String, Value>, Error> {
+    if !wildcard::is_segment_char(replacement) {
+        return Err(format!("invalid escape character: {:?}", replacement).into());
+    }
+    let patterns = plugin
+        .graph_definition_ref()
+        .iter()
+        .flat_map(|graph| {
+            graph
+                .metrics
+                .iter()
+                .map(|metric| join_name(&graph.name, &metric.name))
+        })
+        .collect::<Vec<_>>();
+    let mut values = values
+        .into_iter()
+        .map(|(name, value)| {
+            let matched = patterns
+                .iter()
+                .any(|pattern| wildcard::matches_metric(pattern, &name));
+            (!matched, name, value)
+        })
+        .collect::<Vec<_>>();
+    // the names matching without the escape take precedence
+    values.sort_unstable_by(|(e1, n1, _), (e2, n2, _)| e1.cmp(e2).then_with(|| n1.cmp(n2)));
+    let mut escaped = HashMap::with_capacity(values.len());
+    let mut collisions = Vec::new();
+    for (escape, name, value) in values {
+        let escaped_name = if escape {
+            patterns
+                .iter()
+                .find_map(|pattern| wildcard::escape_to_pattern(pattern, &name, replacement))
+                .unwrap_or_else(|| wildcard::escape_name(&name, replacement))
         } else {
-            self.output_values(&mut out)
+            name.clone()
+        };
+        match escaped.entry(escaped_name) {
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+            Entry::Occupied(entry) => {
+                collisions.push(format!("{} is escaped to {}", name, entry.key()));
+            }
+        }
+    }
+    if !collisions.is_empty() {
+        if plugin.strict() {
+            return Err(format!("colliding metric names: {}", collisions.join(", ")).into());
+        }
+        for collision in &collisions {
+            let _ = writeln!(std::io::stderr(), "colliding metric name: {}", collision);
         }
     }
+    Ok(escaped)
+}
+
+/// Fetches the metric values, or returns the values cached within the time to
+/// live.
+#[cfg(feature = "json")]
+fn fetch_cached_values<P: Plugin + ?Sized>(
+    plugin: &P,
+    ctx: &Context,
+    ttl: std::time::Duration,
+    errors: &mut usize,
+) -> Result<HashMap<String, Value>, Error> {
+    let path = plugin.tempfile_path(&plugin.metric_key_prefix())?;
+    let now = ctx
+        .clock()
+        .now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+    if let Some(values) = load_fetched_values(ctx.state_store(), &path, now, ttl) {
+        return Ok(values);
+    }
+    let values = fetch_values(plugin, ctx, errors)?;
+    save_fetched_values(ctx.state_store(), &path, now, &values)?;
+    Ok(values)
+}
+
+fn fetch_values<P: Plugin + ?Sized>(
+    plugin: &P,
+    ctx: &Context,
+    errors: &mut usize,
+) -> Result<HashMap<String, Value>, Error> {
+    if plugin.fetch_per_graph() {
+        fetch_graph_values(plugin, ctx, errors)
+    } else {
+        Ok(plugin.fetch_metrics_ctx(ctx)?)
+    }
+}
+
+/// Fetches the metric values per graph, counting the graphs failed to fetch.
+/// Returns the error of the first graph if all the graphs fail.
+fn fetch_graph_values<P: Plugin + ?Sized>(
+    plugin: &P,
+    ctx: &Context,
+    errors: &mut usize,
+) -> Result<HashMap<String, Value>, Error> {
+    let graphs = plugin.graph_definition_ref();
+    let results = match plugin.parallel_fetch() {
+        Some(plugin) => std::thread::scope(|s| {
+            graphs
+                .iter()
+                .map(|graph| s.spawn(move || plugin.fetch_metrics_for(graph)))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err("fetch panicked".to_owned()))
+                })
+                .collect::<Vec<_>>()
+        }),
+        None => graphs
+            .iter()
+            .map(|graph| plugin.fetch_metrics_for(graph))
+            .collect(),
+    };
+    let mut values = HashMap::new();
+    let mut failures = Vec::new();
+    for (graph, result) in graphs.iter().zip(results) {
+        match result {
+            Ok(graph_values) => values.extend(graph_values),
+            Err(err) => {
+                ctx.log(&format!("fetch {} failed: {}", graph.name, err));
+                failures.push(err);
+            }
+        }
+    }
+    if !failures.is_empty() && failures.len() == graphs.len() {
+        return Err(failures.swap_remove(0).into());
+    }
+    *errors += failures.len();
+    Ok(values)
+}
+
+/// Returns an error if any metric name matches no metric in the graphs.
+fn check_unmatched(graphs: &[Graph], values: &HashMap<String, Value>) -> Result<(), Error> {
+    let patterns = graphs
+        .iter()
+        .flat_map(|graph| {
+            graph
+                .metrics
+                .iter()
+                .map(|metric| join_name(&graph.name, &metric.name))
+        })
+        .collect::<Vec<_>>();
+    let mut names = values
+        .keys()
+        .filter(|name| {
+            !patterns
+                .iter()
+                .any(|pattern| wildcard::matches_metric(pattern, name))
+        })
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        return Ok(());
+    }
+    names.sort_unstable();
+    Err(format!("unmatched metric names: {}", names.join(", ")).into())
 }
 
-fn load_values(path: &str) -> Result<MetricValues, String> {
-    let file = std::fs::File::open(path).map_err(|e| format!("open {} failed: {}", path, e))?;
-    serde_json::de::from_reader(file).map_err(|e| format!("read {} failed: {}", path, e))
+/// Returns the expanded graph of the name, which is added if missing.
+fn expanded_graph<'a>(
+    graphs: &'a mut Vec<Graph>,
+    graph: &Graph,
+    name: String,
+    label: String,
+) -> &'a mut Graph {
+    let index = match graphs.iter().position(|graph| graph.name == name) {
+        Some(index) => index,
+        None => {
+            graphs.push(Graph {
+                name,
+                label,
+                unit: graph.unit,
+                metrics: Vec::new(),
+            });
+            graphs.len() - 1
+        }
+    };
+    &mut graphs[index]
+}
+
+pub(crate) fn join_name(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_owned()
+    } else if name.is_empty() {
+        prefix.to_owned()
+    } else {
+        prefix.to_owned() + "." + name
+    }
 }
 
-fn save_values(path: &str, metric_values: &MetricValues) -> Result<(), String> {
-    let bytes = serde_json::to_vec(metric_values).unwrap();
-    atomic_write(path, bytes.as_slice())
+fn matches_filtered(pattern: &str, name: &str, filter: &Filter) -> bool {
+    wildcard::capture(pattern, name)
+        .is_some_and(|values| values.iter().all(|value| filter.matches(value)))
 }
 
-fn atomic_write(path: &str, bytes: &[u8]) -> Result<(), String> {
-    let tmp_path = &format!(
-        "{}.{}",
-        path,
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map_err(|e| e.to_string())?
-            .as_secs_f64()
-    );
-    let mut file =
-        std::fs::File::create(tmp_path).map_err(|e| format!("open {} failed: {}", tmp_path, e))?;
-    file.write(bytes)
-        .map_err(|e| format!("write to {} failed: {}", tmp_path, e))?;
-    drop(file);
-    std::fs::rename(tmp_path, path).map_err(|e| {
-        let _ = std::fs::remove_file(tmp_path);
-        format!("rename {} to {} failed: {}", tmp_path, path, e)
+fn load_values(state: &dyn StateStore, path: &str) -> Result<MetricValues, String> {
+    let bytes = state
+        .load(path)?
+        .ok_or_else(|| format!("{} not found", path))?;
+    let (timestamp, values) = std::str::from_utf8(&bytes)
+        .map_err(|e| e.to_string())
+        .and_then(json::parse_values)
+        .map_err(|e| format!("read {} failed: {}", path, e))?;
+    Ok(MetricValues {
+        timestamp: Epoch::new(timestamp),
+        values,
+        counters32: HashSet::new(),
     })
 }
 
-fn format_values(
-    out: &mut dyn std::io::Write,
-    prefix: &str,
-    graph_name: &str,
-    metric: Metric,
+fn save_values(
+    state: &dyn StateStore,
+    path: &str,
+    metric_values: &MetricValues,
+) -> Result<(), String> {
+    let json = json::values_json(metric_values.timestamp.as_secs(), &metric_values.values);
+    state.save(path, json.as_bytes())
+}
+
+fn graph_values(
+    graph: &Graph,
+    filter: &Filter,
     metric_values: &MetricValues,
     prev_metric_values: &MetricValues,
-) {
-    for (metric_name, value) in
-        collect_metric_values(graph_name, metric, metric_values, prev_metric_values)
-    {
-        if !value.is_nan() && value.is_finite() {
-            let name = if prefix.is_empty() {
-                metric_name
-            } else {
-                prefix.to_owned() + "." + metric_name.as_ref()
-            };
-            writeln!(out, "{}\t{}\t{}", name, value, metric_values.timestamp).unwrap();
-        }
-    }
+    precision: &dyn Fn(&Unit) -> Option<u32>,
+) -> Vec<(String, f64)> {
+    graph
+        .metrics
+        .iter()
+        .flat_map(|metric| {
+            let precision = metric
+                .precision
+                .or_else(|| precision(graph.unit_of(metric)));
+            collect_metric_values(
+                &graph.name,
+                metric,
+                filter,
+                metric_values,
+                prev_metric_values,
+            )
+            .map(move |(name, value)| match precision {
+                Some(precision) => (name, round_half_even(value, precision)),
+                None => (name, value),
+            })
+        })
+        .filter(|(_, value)| !value.is_nan() && value.is_finite())
+        .collect()
 }
 
-#[auto_enum(Iterator)]
 fn collect_metric_values<'a>(
     graph_name: &'a str,
-    metric: Metric,
+    metric: &'a Metric,
+    filter: &'a Filter,
     metric_values: &'a MetricValues,
     prev_metric_values: &'a MetricValues,
 ) -> impl Iterator<Item = (String, f64)> + 'a {
-    let metric_name = if graph_name.is_empty() {
-        metric.name
-    } else {
-        graph_name.to_owned() + "." + &metric.name
-    };
-    let count = metric_name.chars().filter(|&c| c == '.').count();
+    let metric_name = join_name(graph_name, &metric.name);
     if metric_name.contains('*') || metric_name.contains('#') {
-        metric_values
-            .values
-            .iter()
-            .filter(move |&(name, _)| {
-                name.chars().filter(|&c| c == '.').count() == count
-                    && metric_name.split('.').zip(name.split('.')).all(|(cs, ds)| {
-                        if cs == "*" || cs == "#" {
-                            !ds.is_empty()
-                                && ds.chars().all(
-                                    |c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_'),
+        Either::Left(
+            metric_values
+                .values
+                .iter()
+                .filter(move |&(name, _)| matches_filtered(&metric_name, name, filter))
+                .filter_map(move |(metric_name, &value)| {
+                    if metric.diff {
+                        prev_metric_values
+                            .values
+                            .get(metric_name)
+                            .and_then(|&prev_value| {
+                                calc_diff(
+                                    value,
+                                    metric_values.timestamp,
+                                    prev_value,
+                                    prev_metric_values.timestamp,
+                                    metric_values.counters32.contains(metric_name),
                                 )
-                        } else {
-                            cs == ds
-                        }
-                    })
-            })
-            .filter_map(move |(metric_name, &value)| {
-                if metric.diff {
-                    prev_metric_values
-                        .values
-                        .get(metric_name)
-                        .and_then(|&prev_value| {
-                            calc_diff(
-                                value,
-                                metric_values.timestamp,
-                                prev_value,
-                                prev_metric_values.timestamp,
-                            )
-                        })
-                } else {
-                    Some(value)
-                }
-                .map(|value| (metric_name.clone(), value))
-            })
+                            })
+                    } else {
+                        Some(value)
+                    }
+                    .map(|value| (metric_name.clone(), value))
+                }),
+        )
     } else {
-        metric_values
-            .values
-            .get(&metric_name)
-            .and_then(|&value| {
-                if metric.diff {
-                    prev_metric_values
-                        .values
-                        .get(&metric_name)
-                        .and_then(|&prev_value| {
-                            calc_diff(
-                                value,
-                                metric_values.timestamp,
-                                prev_value,
-                                prev_metric_values.timestamp,
-                            )
-                        })
-                } else {
-                    Some(value)
-                }
-            })
-            .map(|value| (metric_name, value))
-            .into_iter()
+        Either::Right(
+            metric_values
+                .values
+                .get(&metric_name)
+                .and_then(|&value| {
+                    if metric.diff {
+                        prev_metric_values
+                            .values
+                            .get(&metric_name)
+                            .and_then(|&prev_value| {
+                                calc_diff(
+                                    value,
+                                    metric_values.timestamp,
+                                    prev_value,
+                                    prev_metric_values.timestamp,
+                                    metric_values.counters32.contains(&metric_name),
+                                )
+                            })
+                    } else {
+                        Some(value)
+                    }
+                })
+                .map(|value| (metric_name, value))
+                .into_iter(),
+        )
     }
 }
 
 #[inline]
-fn calc_diff(value: f64, timestamp: i64, prev_value: f64, prev_timestamp: i64) -> Option<f64> {
-    if prev_timestamp < timestamp - 600 || timestamp <= prev_timestamp || prev_value > value {
+fn calc_diff(
+    value: f64,
+    timestamp: Epoch,
+    prev_value: f64,
+    prev_timestamp: Epoch,
+    counter32: bool,
+) -> Option<f64> {
+    let (timestamp, prev_timestamp) = (timestamp.as_secs(), prev_timestamp.as_secs());
+    if prev_timestamp < timestamp - 600 || timestamp <= prev_timestamp {
         None
-    } else {
+    } else if prev_value <= value {
         Some((value - prev_value) / ((timestamp - prev_timestamp) as f64 / 60.0))
+    } else if counter32 && prev_value <= u32::MAX as f64 {
+        // the 32-bit counter has wrapped around if the delta is smaller than
+        // the half of the range, otherwise it has been reset by the restart
+        let delta = value + (u32::MAX as f64 + 1.0 - prev_value);
+        (delta < (1u64 << 31) as f64).then(|| delta / ((timestamp - prev_timestamp) as f64 / 60.0))
+    } else {
+        None
     }
 }
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+
+use ::postgres::{Client, Config, NoTls};
+
+use crate::context::Context;
+use crate::credentials::Secret;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+const DATABASE_COLUMNS: [&str; 13] = [
+    "xact_commit",
+    "xact_rollback",
+    "blks_read",
+    "blks_hit",
+    "blk_read_time",
+    "blk_write_time",
+    "tup_returned",
+    "tup_fetched",
+    "tup_inserted",
+    "tup_updated",
+    "tup_deleted",
+    "deadlocks",
+    "temp_bytes",
+];
+
+const CONNECTION_STATES: [(&str, &str); 7] = [
+    ("active", "Active"),
+    ("active_waiting", "Active waiting"),
+    ("idle", "Idle"),
+    ("idle_in_transaction", "Idle in transaction"),
+    (
+        "idle_in_transaction_aborted",
+        "Idle in transaction (aborted)",
+    ),
+    ("fastpath_function_call", "Fast-path function call"),
+    ("disabled", "Disabled"),
+];
+
+/// A collector of the statistics of PostgreSQL, whose metric names are
+/// compatible with mackerel-plugin-postgres.
+///
+/// The metrics are the connections by the state, the statistics of the
+/// databases and the background writer, the WAL location, and the
+/// replication lag; the lag of each standby in bytes on the primary, and the
+/// replay delay in seconds on the standby. The servers of PostgreSQL 10 or
+/// later are supported.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::PostgresStats;
+///
+/// let stats = PostgresStats::new("host=localhost user=postgres").database("app");
+/// let values = stats.fetch_values().unwrap();
+/// ```
+pub struct PostgresStats {
+    config: String,
+    database: Option<String>,
+    password: Option<Secret>,
+}
+
+impl PostgresStats {
+    /// Creates a collector of the connection string, such as
+    /// `host=localhost user=postgres` or `postgresql://localhost/postgres`.
+    pub fn new(config: impl Into<String>) -> PostgresStats {
+        PostgresStats {
+            config: config.into(),
+            database: None,
+            password: None,
+        }
+    }
+
+    /// Limits the statistics of the databases to the database, which default
+    /// to the sum of all the databases.
+    pub fn database(mut self, database: impl Into<String>) -> PostgresStats {
+        self.database = Some(database.into());
+        self
+    }
+
+    /// Sets the password resolved by `Secret`, which overrides the password
+    /// in the connection string.
+    pub fn password(mut self, password: Secret) -> PostgresStats {
+        self.password = Some(password);
+        self
+    }
+
+    /// Returns the graphs of the statistics.
+    pub fn graphs() -> Vec<Graph> {
+        let metrics = |metrics: &[(&str, &str)], diff: bool| {
+            metrics
+                .iter()
+                .map(|&(name, label)| crate::metric! { name: name, label: label, diff: diff })
+                .collect::<Vec<_>>()
+        };
+        vec![
+            crate::graph! {
+                name: "postgres.connections",
+                label: "Postgres Connections",
+                unit: "integer",
+                metrics: CONNECTION_STATES
+                    .iter()
+                    .map(|&(name, label)| {
+                        crate::metric! { name: name, label: label, stacked: true }
+                    })
+                    .collect::<Vec<_>>(),
+            },
+            crate::graph! {
+                name: "postgres.commits",
+                label: "Postgres Commits",
+                unit: "integer",
+                metrics: metrics(
+                    &[("xact_commit", "Xact Commit"), ("xact_rollback", "Xact Rollback")],
+                    true,
+                ),
+            },
+            crate::graph! {
+                name: "postgres.blocks",
+                label: "Postgres Disk Blocks",
+                unit: "integer",
+                metrics: metrics(
+                    &[("blks_read", "Blocks Read"), ("blks_hit", "Blocks Hit")],
+                    true,
+                ),
+            },
+            crate::graph! {
+                name: "postgres.rows",
+                label: "Postgres Rows",
+                unit: "integer",
+                metrics: metrics(
+                    &[
+                        ("tup_returned", "Returned Rows"),
+                        ("tup_fetched", "Fetched Rows"),
+                        ("tup_inserted", "Inserted Rows"),
+                        ("tup_updated", "Updated Rows"),
+                        ("tup_deleted", "Deleted Rows"),
+                    ],
+                    true,
+                ),
+            },
+            crate::graph! {
+                name: "postgres.size",
+                label: "Postgres Data Size",
+                unit: "bytes",
+                metrics: metrics(&[("total_size", "Total Size")], false),
+            },
+            crate::graph! {
+                name: "postgres.deadlocks",
+                label: "Postgres Dead Locks",
+                unit: "integer",
+                metrics: metrics(&[("deadlocks", "Deadlocks")], true),
+            },
+            crate::graph! {
+                name: "postgres.iotime",
+                label: "Postgres Block I/O time",
+                unit: "float",
+                metrics: metrics(
+                    &[
+                        ("blk_read_time", "Block Read Time (ms)"),
+                        ("blk_write_time", "Block Write Time (ms)"),
+                    ],
+                    true,
+                ),
+            },
+            crate::graph! {
+                name: "postgres.tempfile",
+                label: "Postgres Temporary file",
+                unit: "bytes",
+                metrics: metrics(&[("temp_bytes", "Temporary file size (byte)")], true),
+            },
+            crate::graph! {
+                name: "postgres.xlog_location",
+                label: "Postgres WAL",
+                unit: "bytes",
+                metrics: metrics(&[("xlog_location_bytes", "WAL location (byte)")], true),
+            },
+            crate::graph! {
+                name: "postgres.bgwriter",
+                label: "Postgres Background Writer",
+                unit: "integer",
+                metrics: metrics(
+                    &[
+                        ("checkpoints_timed", "Scheduled Checkpoints"),
+                        ("checkpoints_req", "Requested Checkpoints"),
+                        ("buffers_checkpoint", "Buffers by Checkpoints"),
+                        ("buffers_clean", "Buffers by Background Writer"),
+                        ("buffers_backend", "Buffers by Backends"),
+                        ("buffers_alloc", "Allocated Buffers"),
+                    ],
+                    true,
+                ),
+            },
+            crate::graph! {
+                name: "postgres.replication_lag",
+                label: "Postgres Replication Lag",
+                unit: "bytes",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "postgres.replay_delay",
+                label: "Postgres Replay Delay",
+                unit: "seconds",
+                metrics: metrics(&[("seconds", "Delay")], false),
+            },
+        ]
+    }
+
+    /// Returns the metric values of the statistics.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the statistics, whose connection and
+    /// queries time out on the deadline of the fetch.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        let error = |err: &dyn std::fmt::Display| format!("connect to PostgreSQL failed: {}", err);
+        ctx.acquire().map_err(|err| error(&err))?;
+        let mut config = self.config.parse::<Config>().map_err(|err| error(&err))?;
+        if let Some(password) = &self.password {
+            config.password(password.expose());
+        }
+        // the connect timeout does not cover the startup of the session
+        let mut client = ctx
+            .run(move || config.connect(NoTls))
+            .map_err(|err| error(&err))?
+            .map_err(|err| error(&err))?;
+        if let Some(remaining) = ctx.remaining() {
+            // the statement timeout of zero disables the timeout
+            client
+                .batch_execute(&format!(
+                    "SET statement_timeout = {}",
+                    remaining.as_millis().max(1)
+                ))
+                .map_err(|err| format!("query PostgreSQL failed: {}", err))?;
+        }
+        let mut values = HashMap::new();
+        self.fetch_database_values(&mut client, &mut values)
+            .map_err(|err| format!("query PostgreSQL failed: {}", err))?;
+        Ok(values)
+    }
+
+    fn fetch_database_values(
+        &self,
+        client: &mut Client,
+        values: &mut HashMap<String, Value>,
+    ) -> Result<(), ::postgres::Error> {
+        let mut connections = CONNECTION_STATES
+            .iter()
+            .map(|&(name, _)| (name.to_owned(), 0.0))
+            .collect::<HashMap<_, _>>();
+        for row in client.query(
+            "SELECT state, wait_event IS NOT NULL, count(*)::float8 FROM pg_stat_activity \
+             WHERE state IS NOT NULL AND backend_type = 'client backend' GROUP BY 1, 2",
+            &[],
+        )? {
+            let (state, waiting): (String, bool) = (row.get(0), row.get(1));
+            let name = match &state[..] {
+                "active" if waiting => "active_waiting".to_owned(),
+                "idle in transaction (aborted)" => "idle_in_transaction_aborted".to_owned(),
+                state => state.replace([' ', '-'], "_"),
+            };
+            if let Some(count) = connections.get_mut(&name) {
+                *count += row.get::<_, f64>(2);
+            }
+        }
+        for (name, count) in connections {
+            values.insert(format!("postgres.connections.{}", name), count.into());
+        }
+
+        let mut insert = |key: String, value: f64| {
+            values.insert(key, value.into());
+        };
+        let columns = DATABASE_COLUMNS
+            .iter()
+            .map(|column| format!("COALESCE(SUM({}), 0)::float8", column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let row = client.query_one(
+            &format!(
+                "SELECT {} FROM pg_stat_database WHERE $1::text IS NULL OR datname = $1",
+                columns
+            ),
+            &[&self.database],
+        )?;
+        for (i, column) in DATABASE_COLUMNS.iter().enumerate() {
+            let graph = match *column {
+                "xact_commit" | "xact_rollback" => "commits",
+                "blks_read" | "blks_hit" => "blocks",
+                "blk_read_time" | "blk_write_time" => "iotime",
+                "deadlocks" => "deadlocks",
+                "temp_bytes" => "tempfile",
+                _ => "rows",
+            };
+            insert(format!("postgres.{}.{}", graph, column), row.get(i));
+        }
+
+        let row = client.query_one(
+            "SELECT COALESCE(SUM(pg_database_size(datname)), 0)::float8 FROM pg_database \
+             WHERE NOT datistemplate AND ($1::text IS NULL OR datname = $1)",
+            &[&self.database],
+        )?;
+        insert("postgres.size.total_size".to_owned(), row.get(0));
+
+        let row = client.query_one(
+            "SELECT pg_is_in_recovery(), pg_wal_lsn_diff(CASE WHEN pg_is_in_recovery() \
+             THEN pg_last_wal_replay_lsn() ELSE pg_current_wal_lsn() END, '0/0')::float8, \
+             EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp())::float8, \
+             current_setting('server_version_num')::int",
+            &[],
+        )?;
+        let (in_recovery, version): (bool, i32) = (row.get(0), row.get(3));
+        if let Some(location) = row.get::<_, Option<f64>>(1) {
+            insert(
+                "postgres.xlog_location.xlog_location_bytes".to_owned(),
+                location,
+            );
+        }
+        if in_recovery {
+            if let Some(delay) = row.get::<_, Option<f64>>(2) {
+                insert("postgres.replay_delay.seconds".to_owned(), delay.max(0.0));
+            }
+        } else {
+            for row in client.query(
+                "SELECT application_name, \
+                 pg_wal_lsn_diff(pg_current_wal_lsn(), replay_lsn)::float8 \
+                 FROM pg_stat_replication WHERE replay_lsn IS NOT NULL",
+                &[],
+            )? {
+                let name: String = row.get(0);
+                insert(
+                    format!("postgres.replication_lag.{}", metric_key(&name)),
+                    row.get(1),
+                );
+            }
+        }
+
+        // the statistics of the checkpoints are moved to pg_stat_checkpointer
+        // in PostgreSQL 17
+        let query = if version >= 170000 {
+            "SELECT c.num_timed::float8, c.num_requested::float8, c.buffers_written::float8, \
+             b.buffers_clean::float8, NULL::float8, b.buffers_alloc::float8 \
+             FROM pg_stat_bgwriter b, pg_stat_checkpointer c"
+        } else {
+            "SELECT checkpoints_timed::float8, checkpoints_req::float8, \
+             buffers_checkpoint::float8, buffers_clean::float8, buffers_backend::float8, \
+             buffers_alloc::float8 FROM pg_stat_bgwriter"
+        };
+        let row = client.query_one(query, &[])?;
+        for (i, name) in [
+            "checkpoints_timed",
+            "checkpoints_req",
+            "buffers_checkpoint",
+            "buffers_clean",
+            "buffers_backend",
+            "buffers_alloc",
+        ]
+        .iter()
+        .enumerate()
+        {
+            if let Some(value) = row.get::<_, Option<f64>>(i) {
+                insert(format!("postgres.bgwriter.{}", name), value);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn metric_key(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
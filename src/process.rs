@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// The clock ticks per second of the CPU times in procfs, which is 100 on
+/// almost all Linux systems.
+const CLOCK_TICKS: f64 = 100.0;
+
+enum Matcher {
+    Name(String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+    Cgroup(String),
+}
+
+enum Source {
+    Procfs(PathBuf),
+    Ps(OsString),
+}
+
+/// A collector of the processes read from procfs on Linux, or by `ps` on the
+/// other systems such as macOS and FreeBSD, which are grouped by the matchers.
+///
+/// The metrics of each group are the number of the processes, the CPU usage,
+/// the resident set size, and the number of the open file descriptors, keyed
+/// by the group name. A process can match multiple groups. The file
+/// descriptors and the cgroups are only available in procfs.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::Processes;
+///
+/// let processes = Processes::new()
+///     .name("nginx", "nginx")
+///     .cgroup("app", "/system.slice/app.service");
+/// let values = processes.fetch_values().unwrap();
+/// ```
+pub struct Processes {
+    groups: Vec<(String, Matcher)>,
+    source: Source,
+}
+
+impl Default for Processes {
+    fn default() -> Processes {
+        Processes::new()
+    }
+}
+
+impl Processes {
+    pub fn new() -> Processes {
+        Processes {
+            groups: Vec::new(),
+            source: if cfg!(target_os = "linux") {
+                Source::Procfs(PathBuf::from("/proc"))
+            } else {
+                Source::Ps("ps".into())
+            },
+        }
+    }
+
+    /// Adds the group of the processes of the command name.
+    pub fn name(mut self, group: &str, name: impl Into<String>) -> Processes {
+        self.groups
+            .push((group_key(group), Matcher::Name(name.into())));
+        self
+    }
+
+    /// Adds the group of the processes whose command line matches the regular
+    /// expression.
+    #[cfg(feature = "regex")]
+    pub fn regex(mut self, group: &str, regex: regex::Regex) -> Processes {
+        self.groups.push((group_key(group), Matcher::Regex(regex)));
+        self
+    }
+
+    /// Adds the group of the processes in the cgroup or its descendants.
+    pub fn cgroup(mut self, group: &str, path: impl Into<String>) -> Processes {
+        let path = path.into().trim_end_matches('/').to_owned();
+        self.groups.push((group_key(group), Matcher::Cgroup(path)));
+        self
+    }
+
+    /// Sets the directory of procfs, which is read by default on Linux.
+    pub fn proc_dir(mut self, proc_dir: impl Into<PathBuf>) -> Processes {
+        self.source = Source::Procfs(proc_dir.into());
+        self
+    }
+
+    /// Sets the command of `ps`, which is run by default on the systems other
+    /// than Linux.
+    pub fn ps(mut self, command: impl Into<OsString>) -> Processes {
+        self.source = Source::Ps(command.into());
+        self
+    }
+
+    /// Returns the graphs of the process groups.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "process.count",
+                label: "Processes",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "process.cpu",
+                label: "Process CPU",
+                unit: "percentage",
+                metrics: [{ name: "*", label: "%1", diff: true }],
+            },
+            crate::graph! {
+                name: "process.rss",
+                label: "Process RSS",
+                unit: "bytes",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "process.fds",
+                label: "Process file descriptors",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the process groups. The processes which
+    /// exit while reading are ignored, and the file descriptors of the
+    /// processes of other users are not counted without the privilege.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the process groups, killing `ps` when the
+    /// fetch is cancelled.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        let processes = match &self.source {
+            Source::Procfs(proc_dir) => read_procfs(proc_dir)?,
+            Source::Ps(command) => read_ps(ctx, command)?,
+        };
+        let mut stats = vec![(0usize, 0u64, 0u64, 0usize); self.groups.len()];
+        for process in processes {
+            for ((_, matcher), stat) in self.groups.iter().zip(&mut stats) {
+                if process.matches(matcher) {
+                    stat.0 += 1;
+                    stat.1 += process.cpu_ticks;
+                    stat.2 += process.rss;
+                    stat.3 += process.fds;
+                }
+            }
+        }
+        let mut values = HashMap::new();
+        for ((group, _), (count, cpu_ticks, rss, fds)) in self.groups.iter().zip(stats) {
+            values.insert(format!("process.count.{}", group), (count as f64).into());
+            // the CPU ticks differentiated per minute make the percentage
+            values.insert(
+                format!("process.cpu.{}", group),
+                (cpu_ticks as f64 * 100.0 / CLOCK_TICKS / 60.0).into(),
+            );
+            values.insert(format!("process.rss.{}", group), (rss as f64).into());
+            if let Source::Procfs(_) = self.source {
+                values.insert(format!("process.fds.{}", group), (fds as f64).into());
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// Reads the processes from procfs.
+fn read_procfs(proc_dir: &Path) -> Result<Vec<Process>, Error> {
+    let entries = std::fs::read_dir(proc_dir)
+        .map_err(|e| format!("read {} failed: {}", proc_dir.display(), e))?;
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let pid = name
+                .to_str()
+                .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))?;
+            Process::read(&proc_dir.join(pid))
+        })
+        .collect())
+}
+
+/// Reads the processes by `ps`, which reads them by the `kern.proc` sysctl on
+/// BSD. The command names are the paths of the executables on macOS.
+fn read_ps(ctx: &Context, command: &OsStr) -> Result<Vec<Process>, Error> {
+    #[cfg(feature = "regex")]
+    let cmdlines = ps(ctx, command, "pid=,command=")?
+        .lines()
+        .filter_map(|line| {
+            let (pid, cmdline) = next_field(line)?;
+            Some((pid.parse::<u32>().ok()?, cmdline.trim_end().to_owned()))
+        })
+        .collect::<HashMap<_, _>>();
+    Ok(ps(ctx, command, "pid=,rss=,time=,comm=")?
+        .lines()
+        .filter_map(|line| {
+            let (pid, rest) = next_field(line)?;
+            let (rss, rest) = next_field(rest)?;
+            let (time, comm) = next_field(rest)?;
+            #[cfg_attr(not(feature = "regex"), allow(unused_variables))]
+            let pid = pid.parse::<u32>().ok()?;
+            Some(Process {
+                name: comm.trim_end().rsplit('/').next()?.to_owned(),
+                #[cfg(feature = "regex")]
+                cmdline: cmdlines.get(&pid).cloned().unwrap_or_default(),
+                cgroups: Vec::new(),
+                cpu_ticks: parse_cpu_time(time)?,
+                rss: rss.parse::<u64>().ok()? * 1024,
+                fds: 0,
+            })
+        })
+        .collect())
+}
+
+fn ps(ctx: &Context, command: &OsStr, format: &str) -> Result<String, Error> {
+    let output = ctx
+        .output(Command::new(command).args(["-axww", "-o", format]))
+        .map_err(|e| format!("ps -o {} failed: {}", format, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "ps -o {} failed: {}",
+            format,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Splits the first field separated by the spaces.
+fn next_field(s: &str) -> Option<(&str, &str)> {
+    let (field, rest) = s.trim_start().split_once(char::is_whitespace)?;
+    Some((field, rest.trim_start()))
+}
+
+/// Parses the CPU time of `ps` like `12:34.56` and `1-02:03:04.56` into the
+/// clock ticks.
+fn parse_cpu_time(time: &str) -> Option<u64> {
+    let (days, time) = match time.split_once('-') {
+        Some((days, time)) => (days.parse::<u64>().ok()?, time),
+        None => (0, time),
+    };
+    let mut parts = time.rsplit(':');
+    let seconds = parts.next()?.parse::<f64>().ok()?;
+    let minutes = parts
+        .zip([1, 60])
+        .try_fold(days * 24 * 60, |minutes, (part, scale)| {
+            Some(minutes + part.parse::<u64>().ok()? * scale)
+        })?;
+    Some(((minutes as f64 * 60.0 + seconds) * CLOCK_TICKS).round() as u64)
+}
+
+struct Process {
+    name: String,
+    #[cfg(feature = "regex")]
+    cmdline: String,
+    cgroups: Vec<String>,
+    cpu_ticks: u64,
+    rss: u64,
+    fds: usize,
+}
+
+impl Process {
+    fn read(dir: &Path) -> Option<Process> {
+        let stat = std::fs::read_to_string(dir.join("stat")).ok()?;
+        // the command name can contain spaces and parentheses
+        let (name, fields) = stat.split_once(" (")?.1.rsplit_once(") ")?;
+        let fields = fields.split(' ').collect::<Vec<_>>();
+        // utime and stime are the 14th and 15th fields, after pid and comm
+        let cpu_ticks =
+            fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+        let rss = std::fs::read_to_string(dir.join("status"))
+            .ok()
+            .and_then(|status| {
+                let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+                let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+                Some(kb * 1024)
+            })
+            .unwrap_or_default();
+        #[cfg(feature = "regex")]
+        let cmdline = std::fs::read(dir.join("cmdline"))
+            .map(|bytes| {
+                String::from_utf8_lossy(&bytes)
+                    .trim_end_matches('\0')
+                    .replace('\0', " ")
+            })
+            .unwrap_or_default();
+        let cgroups = std::fs::read_to_string(dir.join("cgroup"))
+            .map(|cgroup| {
+                cgroup
+                    .lines()
+                    .filter_map(|line| Some(line.splitn(3, ':').nth(2)?.to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let fds = std::fs::read_dir(dir.join("fd")).map_or(0, |entries| entries.count());
+        Some(Process {
+            name: name.to_owned(),
+            #[cfg(feature = "regex")]
+            cmdline,
+            cgroups,
+            cpu_ticks,
+            rss,
+            fds,
+        })
+    }
+
+    fn matches(&self, matcher: &Matcher) -> bool {
+        match matcher {
+            Matcher::Name(name) => self.name == *name,
+            #[cfg(feature = "regex")]
+            Matcher::Regex(regex) => regex.is_match(&self.cmdline),
+            Matcher::Cgroup(path) => self.cgroups.iter().any(|cgroup| {
+                cgroup
+                    .strip_prefix(path.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            }),
+        }
+    }
+}
+
+fn group_key(group: &str) -> String {
+    group
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
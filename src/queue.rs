@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::plugin::Plugin;
+use crate::value::Value;
+
+/// The statistics of a queue.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct QueueStats {
+    pub name: String,
+    /// The number of the messages ready to be consumed.
+    pub depth: u64,
+    /// The number of the messages being processed by the consumers.
+    pub in_flight: Option<u64>,
+    pub consumers: Option<u64>,
+    /// The time the oldest message has been waiting in the queue.
+    pub latency: Option<Duration>,
+    /// The number of the failed messages scheduled to be retried.
+    pub retries: Option<u64>,
+    /// The number of the messages which exhausted the retries.
+    pub dead: Option<u64>,
+}
+
+impl QueueStats {
+    pub fn new(name: impl Into<String>, depth: u64) -> QueueStats {
+        QueueStats {
+            name: name.into(),
+            depth,
+            in_flight: None,
+            consumers: None,
+            latency: None,
+            retries: None,
+            dead: None,
+        }
+    }
+
+    pub fn in_flight(mut self, in_flight: u64) -> QueueStats {
+        self.in_flight = Some(in_flight);
+        self
+    }
+
+    pub fn consumers(mut self, consumers: u64) -> QueueStats {
+        self.consumers = Some(consumers);
+        self
+    }
+
+    pub fn latency(mut self, latency: Duration) -> QueueStats {
+        self.latency = Some(latency);
+        self
+    }
+
+    pub fn retries(mut self, retries: u64) -> QueueStats {
+        self.retries = Some(retries);
+        self
+    }
+
+    pub fn dead(mut self, dead: u64) -> QueueStats {
+        self.dead = Some(dead);
+        self
+    }
+}
+
+/// A backend of the message queues, which lists the statistics of the queues.
+pub trait QueueBackend {
+    /// Returns the name of the backend, which is the default prefix of the
+    /// metric keys.
+    fn name(&self) -> &str;
+
+    fn queues(&self) -> Result<Vec<QueueStats>, Error>;
+}
+
+/// A plugin of the depth of the message queues, which only needs a
+/// [`QueueBackend`] for each system.
+///
+/// The metrics are the depth, the in-flight messages, the consumers, the
+/// latency, and the retried and dead messages of each queue, and the totals
+/// of all the queues. The number of the queues in
+/// the graphs can be limited to the largest ones, and the others are
+/// aggregated into the `other` series.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{Beanstalkd, Plugin, QueueDepth};
+///
+/// let plugin = QueueDepth::new(Beanstalkd::new("localhost:11300"));
+/// match plugin.run() {
+///     Ok(_) => {}
+///     Err(err) => {
+///         eprintln!("mackerel-plugin-beanstalkd: {}", err);
+///         std::process::exit(1);
+///     }
+/// }
+/// ```
+pub struct QueueDepth<B> {
+    backend: B,
+    prefix: String,
+    #[cfg_attr(not(feature = "json"), allow(dead_code))]
+    top: Option<usize>,
+}
+
+impl<B: QueueBackend> QueueDepth<B> {
+    pub fn new(backend: B) -> QueueDepth<B> {
+        QueueDepth {
+            prefix: backend.name().to_owned(),
+            backend,
+            top: None,
+        }
+    }
+
+    /// Sets the prefix of the metric keys, which defaults to the name of the
+    /// backend.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> QueueDepth<B> {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Limits the queues in each graph to the largest ones.
+    #[cfg(feature = "json")]
+    pub fn top(mut self, top: usize) -> QueueDepth<B> {
+        self.top = Some(top);
+        self
+    }
+}
+
+impl<B: QueueBackend> Plugin for QueueDepth<B> {
+    fn fetch_values(&self) -> Result<HashMap<String, Value>, String> {
+        let queues = self.backend.queues().map_err(|err| err.to_string())?;
+        let mut values = HashMap::new();
+        let (mut depth, mut in_flight) = (0, 0);
+        for queue in &queues {
+            let name = metric_key(&queue.name);
+            values.insert(format!("depth.{}", name), (queue.depth as f64).into());
+            depth += queue.depth;
+            if let Some(count) = queue.in_flight {
+                values.insert(format!("in_flight.{}", name), (count as f64).into());
+                in_flight += count;
+            }
+            if let Some(count) = queue.consumers {
+                values.insert(format!("consumers.{}", name), (count as f64).into());
+            }
+            if let Some(latency) = queue.latency {
+                values.insert(format!("latency.{}", name), latency.into());
+            }
+            if let Some(count) = queue.retries {
+                values.insert(format!("retries.{}", name), (count as f64).into());
+            }
+            if let Some(count) = queue.dead {
+                values.insert(format!("dead.{}", name), (count as f64).into());
+            }
+        }
+        values.insert("total.depth".to_owned(), (depth as f64).into());
+        values.insert("total.in_flight".to_owned(), (in_flight as f64).into());
+        values.insert("total.queues".to_owned(), (queues.len() as f64).into());
+        Ok(values)
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "depth",
+                label: "Queue depth",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "in_flight",
+                label: "Queue in-flight messages",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "consumers",
+                label: "Queue consumers",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "latency",
+                label: "Queue latency",
+                unit: "float",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "retries",
+                label: "Queue retries",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "dead",
+                label: "Queue dead messages",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "total",
+                label: "Queue total",
+                unit: "integer",
+                metrics: [
+                    { name: "depth", label: "Depth" },
+                    { name: "in_flight", label: "In-flight" },
+                    { name: "queues", label: "Queues" },
+                ],
+            },
+        ]
+    }
+
+    fn metric_key_prefix(&self) -> String {
+        self.prefix.clone()
+    }
+
+    #[cfg(feature = "json")]
+    fn series_limit(&self) -> Option<usize> {
+        self.top
+    }
+
+    #[cfg(feature = "json")]
+    fn series_rollup(&self) -> bool {
+        true
+    }
+}
+
+/// A backend of the tubes of beanstalkd. The depth is the number of the ready
+/// jobs, and the in-flight messages are the reserved jobs.
+pub struct Beanstalkd {
+    addr: String,
+    timeout: Duration,
+}
+
+impl Beanstalkd {
+    pub fn new(addr: impl Into<String>) -> Beanstalkd {
+        Beanstalkd {
+            addr: addr.into(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Beanstalkd {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends the command, and returns the YAML data of the response, or
+    /// `None` if the tube is not found.
+    fn command(
+        reader: &mut BufReader<TcpStream>,
+        command: &str,
+    ) -> Result<Option<String>, std::io::Error> {
+        write!(reader.get_mut(), "{}\r\n", command)?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line == "NOT_FOUND" {
+            return Ok(None);
+        }
+        let len = line
+            .strip_prefix("OK ")
+            .and_then(|len| len.parse::<usize>().ok())
+            .ok_or_else(|| std::io::Error::other(format!("unexpected response: {}", line)))?;
+        let mut data = vec![0; len + 2];
+        reader.read_exact(&mut data)?;
+        data.truncate(len);
+        Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+    }
+}
+
+impl QueueBackend for Beanstalkd {
+    fn name(&self) -> &str {
+        "beanstalkd"
+    }
+
+    fn queues(&self) -> Result<Vec<QueueStats>, Error> {
+        let error = |err: std::io::Error| format!("beanstalkd {} failed: {}", self.addr, err);
+        let addr = self
+            .addr
+            .to_socket_addrs()
+            .map_err(error)?
+            .next()
+            .ok_or_else(|| format!("beanstalkd {} failed: cannot resolve", self.addr))?;
+        let stream = TcpStream::connect_timeout(&addr, self.timeout).map_err(error)?;
+        stream.set_read_timeout(Some(self.timeout)).map_err(error)?;
+        let mut reader = BufReader::new(stream);
+        let tubes = Beanstalkd::command(&mut reader, "list-tubes")
+            .map_err(error)?
+            .unwrap_or_default();
+        let mut queues = Vec::new();
+        for tube in tubes.lines().filter_map(|line| line.strip_prefix("- ")) {
+            let Some(stats) =
+                Beanstalkd::command(&mut reader, &format!("stats-tube {}", tube)).map_err(error)?
+            else {
+                continue;
+            };
+            let stats = stats
+                .lines()
+                .filter_map(|line| {
+                    let (key, value) = line.split_once(": ")?;
+                    Some((key, value.trim().parse::<u64>().ok()?))
+                })
+                .collect::<HashMap<_, _>>();
+            let stat = |key: &str| stats.get(key).copied().unwrap_or_default();
+            queues.push(
+                QueueStats::new(tube, stat("current-jobs-ready"))
+                    .in_flight(stat("current-jobs-reserved"))
+                    .consumers(stat("current-watching")),
+            );
+        }
+        Ok(queues)
+    }
+}
+
+fn metric_key(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,140 @@
+use serde_derive::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::context::Context;
+use crate::error::Error;
+use crate::state::StateStore;
+
+/// A token bucket limiting the requests to the upstream sources, which is
+/// shared by the fetchers of a plugin to stay under the quotas of the APIs.
+///
+/// The bucket holds the tokens up to the capacity, which are refilled evenly
+/// over the interval. The bucket is persisted to the state store, so that the
+/// budget is kept across the runs of the plugin.
+///
+/// ```rust
+/// use mackerel_plugin::{MemoryStateStore, RateLimiter};
+/// use std::time::Duration;
+///
+/// let state = MemoryStateStore::new();
+/// let limiter = RateLimiter::new(&state, "mackerel-plugin-api.ratelimit", 2, Duration::from_secs(60));
+/// assert_eq!(limiter.try_acquire(), Ok(true));
+/// assert_eq!(limiter.try_acquire(), Ok(true));
+/// assert_eq!(limiter.try_acquire(), Ok(false));
+/// ```
+pub struct RateLimiter<'a> {
+    state: &'a (dyn StateStore + Sync),
+    path: String,
+    capacity: f64,
+    interval: Duration,
+    clock: &'a (dyn Clock + Sync),
+    bucket: Mutex<Option<Bucket>>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Bucket {
+    tokens: f64,
+    updated_at: f64,
+}
+
+impl<'a> RateLimiter<'a> {
+    /// Creates a rate limiter allowing the requests of the capacity per the
+    /// interval.
+    pub fn new(
+        state: &'a (dyn StateStore + Sync),
+        path: impl Into<String>,
+        capacity: u32,
+        interval: Duration,
+    ) -> RateLimiter<'a> {
+        RateLimiter {
+            state,
+            path: path.into(),
+            capacity: capacity as f64,
+            interval,
+            clock: &SystemClock,
+            bucket: Mutex::new(None),
+        }
+    }
+
+    /// Sets the clock for refilling the tokens.
+    pub fn clock(mut self, clock: &'a (dyn Clock + Sync)) -> RateLimiter<'a> {
+        self.clock = clock;
+        self
+    }
+
+    /// Takes a token if available, and returns whether it is taken.
+    pub fn try_acquire(&self) -> Result<bool, Error> {
+        self.update(|bucket| {
+            let acquired = bucket.tokens >= 1.0;
+            if acquired {
+                bucket.tokens -= 1.0;
+            }
+            acquired
+        })
+    }
+
+    /// Takes a token, waiting for it to be refilled until the deadline of the
+    /// context. Returns an error if no token is available before the deadline.
+    pub fn acquire(&self, ctx: &Context) -> Result<(), Error> {
+        loop {
+            if self.try_acquire()? {
+                return Ok(());
+            }
+            let wait = self.update(|bucket| self.time_for(1.0 - bucket.tokens))?;
+            if ctx.remaining().is_some_and(|remaining| remaining < wait) {
+                return Err("rate limit exceeded".into());
+            }
+            ctx.check()?;
+            std::thread::sleep(wait.min(Duration::from_millis(100)));
+        }
+    }
+
+    /// Returns the number of the available tokens.
+    pub fn available(&self) -> Result<f64, Error> {
+        self.update(|bucket| bucket.tokens)
+    }
+
+    fn time_for(&self, tokens: f64) -> Duration {
+        if self.capacity > 0.0 {
+            self.interval.mul_f64(tokens.max(0.0) / self.capacity)
+        } else {
+            Duration::MAX
+        }
+    }
+
+    /// Refills the bucket, applies the function, and saves the bucket.
+    fn update<T>(&self, f: impl FnOnce(&mut Bucket) -> T) -> Result<T, Error> {
+        let now = self
+            .clock
+            .now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs_f64();
+        let mut guard = self.bucket.lock().map_err(|e| e.to_string())?;
+        let bucket = guard.get_or_insert_with(|| {
+            self.state
+                .load(&self.path)
+                .ok()
+                .flatten()
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+                .unwrap_or(Bucket {
+                    tokens: self.capacity,
+                    updated_at: now,
+                })
+        });
+        let elapsed = (now - bucket.updated_at).max(0.0);
+        if !self.interval.is_zero() {
+            bucket.tokens += self.capacity * elapsed / self.interval.as_secs_f64();
+        } else {
+            bucket.tokens = self.capacity;
+        }
+        bucket.tokens = bucket.tokens.min(self.capacity);
+        bucket.updated_at = now;
+        let value = f(bucket);
+        let bytes = serde_json::to_vec(bucket).map_err(|e| e.to_string())?;
+        self.state.save(&self.path, &bytes)?;
+        Ok(value)
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// A reply of Redis in RESP2.
+#[derive(Debug)]
+pub(crate) enum Reply {
+    Nil,
+    Integer(i64),
+    Bulk(String),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    pub(crate) fn integer(&self) -> Option<i64> {
+        match self {
+            Reply::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn bulk(&self) -> Option<&str> {
+        match self {
+            Reply::Bulk(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the strings of the array, skipping the other elements.
+    pub(crate) fn strings(&self) -> Vec<&str> {
+        match self {
+            Reply::Array(replies) => replies.iter().filter_map(Reply::bulk).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A minimal blocking connection to Redis, which sends the commands one by one.
+pub(crate) struct Connection {
+    reader: BufReader<TcpStream>,
+}
+
+impl Connection {
+    pub(crate) fn connect(addr: &str, timeout: Duration) -> Result<Connection, std::io::Error> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| std::io::Error::other("cannot resolve"))?;
+        let stream = TcpStream::connect_timeout(&addr, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        Ok(Connection {
+            reader: BufReader::new(stream),
+        })
+    }
+
+    pub(crate) fn command(&mut self, args: &[&str]) -> Result<Reply, std::io::Error> {
+        let mut command = format!("*{}\r\n", args.len());
+        for arg in args {
+            command += &format!("${}\r\n{}\r\n", arg.len(), arg);
+        }
+        self.reader.get_mut().write_all(command.as_bytes())?;
+        self.read_reply()
+    }
+
+    fn read_reply(&mut self) -> Result<Reply, std::io::Error> {
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        let line = line.trim_end_matches("\r\n");
+        let invalid = || std::io::Error::other(format!("unexpected reply: {:?}", line));
+        let (kind, rest) = (line.get(..1).ok_or_else(invalid)?, &line[1..]);
+        match kind {
+            "+" => Ok(Reply::Bulk(rest.to_owned())),
+            "-" => Err(std::io::Error::other(rest.to_owned())),
+            ":" => Ok(Reply::Integer(rest.parse().map_err(|_| invalid())?)),
+            "$" => {
+                let Ok(len) = rest.parse::<usize>() else {
+                    return Ok(Reply::Nil);
+                };
+                let mut data = vec![0; len + 2];
+                self.reader.read_exact(&mut data)?;
+                data.truncate(len);
+                Ok(Reply::Bulk(String::from_utf8_lossy(&data).into_owned()))
+            }
+            "*" => {
+                let Ok(len) = rest.parse::<usize>() else {
+                    return Ok(Reply::Nil);
+                };
+                (0..len)
+                    .map(|_| self.read_reply())
+                    .collect::<Result<_, _>>()
+                    .map(Reply::Array)
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A rename of the metric names fetched by the plugin.
+///
+/// The rename applies to the metric names which equal to `from`, or start with
+/// `from` followed by a dot. When `keep_old` is true, the metric is emitted with
+/// both the old and new names, which is useful in the transition period.
+///
+/// ```rust
+/// use mackerel_plugin::Rename;
+///
+/// let rename = Rename::new("disk.io", "disk.iops");
+/// assert_eq!(rename.apply("disk.io.sda.read"), Some("disk.iops.sda.read".to_owned()));
+/// assert_eq!(rename.apply("disk.iowait"), None);
+/// ```
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub keep_old: bool,
+}
+
+impl Rename {
+    pub fn new(from: impl Into<String>, to: impl Into<String>) -> Rename {
+        Rename {
+            from: from.into(),
+            to: to.into(),
+            keep_old: false,
+        }
+    }
+
+    pub fn keep_old(mut self) -> Rename {
+        self.keep_old = true;
+        self
+    }
+
+    /// Loads the renames from the JSON file specified by the environment
+    /// variable `MACKEREL_PLUGIN_RENAME_FILE`.
+    ///
+    /// ```json
+    /// [{ "from": "disk.io", "to": "disk.iops", "keep_old": true }]
+    /// ```
+    ///
+    /// The file is not supported without the `json` feature.
+    pub fn from_env() -> Result<Vec<Rename>, String> {
+        match std::env::var("MACKEREL_PLUGIN_RENAME_FILE") {
+            #[cfg(feature = "json")]
+            Ok(path) if !path.is_empty() => {
+                let file = std::fs::File::open(&path)
+                    .map_err(|e| format!("open {} failed: {}", path, e))?;
+                serde_json::from_reader(file).map_err(|e| format!("read {} failed: {}", path, e))
+            }
+            #[cfg(not(feature = "json"))]
+            Ok(path) if !path.is_empty() => Err(format!(
+                "read {} failed: the json feature is disabled",
+                path
+            )),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the renamed metric name, or `None` if the rename does not apply.
+    pub fn apply(&self, name: &str) -> Option<String> {
+        let rest = name.strip_prefix(self.from.as_str())?;
+        (rest.is_empty() || rest.starts_with('.')).then(|| self.to.clone() + rest)
+    }
+}
+
+pub(crate) fn apply_renames<V: Clone>(
+    metrics: HashMap<String, V>,
+    renames: &[Rename],
+) -> HashMap<String, V> {
+    if renames.is_empty() {
+        return metrics;
+    }
+    let mut renamed = HashMap::with_capacity(metrics.len());
+    for (name, value) in metrics {
+        match renames
+            .iter()
+            .find_map(|rename| rename.apply(&name).map(|new_name| (rename, new_name)))
+        {
+            Some((rename, new_name)) => {
+                if rename.keep_old {
+                    renamed.insert(name, value.clone());
+                }
+                renamed.insert(new_name, value);
+            }
+            None => {
+                renamed.insert(name, value);
+            }
+        }
+    }
+    renamed
+}
@@ -0,0 +1,185 @@
+use serde_derive::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::api::Client;
+use crate::clock::{Clock, SystemClock};
+use crate::error::Error;
+use crate::state::StateStore;
+
+/// A replay of the files written by `FileSink`, which posts the recorded
+/// values to Mackerel as the custom metrics of the host.
+///
+/// The values are posted in batches with the interval between requests, and
+/// the progress is saved to the state store after each batch, so that the
+/// replay resumes from where it stopped. The values older than the maximum age
+/// are skipped, since Mackerel does not accept too old values.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{Client, FileSink, FileStateStore, Replay};
+///
+/// let client = Client::from_env().unwrap();
+/// let files = FileSink::new("/var/lib/mackerel-plugin", "mackerel-plugin-dice")
+///     .files()
+///     .unwrap();
+/// let state = FileStateStore;
+/// let stats = Replay::new(&client, "2pS7Kx8Wy1u", &state, "/var/lib/mackerel-plugin/replay")
+///     .run(&files)
+///     .unwrap();
+/// println!("posted {} values", stats.posted);
+/// ```
+pub struct Replay<'a> {
+    client: &'a Client,
+    host_id: String,
+    state: &'a dyn StateStore,
+    path: String,
+    batch_size: usize,
+    interval: Duration,
+    max_age: Duration,
+    clock: &'a dyn Clock,
+}
+
+/// The statistics of a replay.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct ReplayStats {
+    /// The number of the posted values.
+    pub posted: usize,
+    /// The number of the values skipped by the age or the invalid format.
+    pub skipped: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Progress {
+    file: PathBuf,
+    line: usize,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(
+        client: &'a Client,
+        host_id: impl Into<String>,
+        state: &'a dyn StateStore,
+        path: impl Into<String>,
+    ) -> Replay<'a> {
+        Replay {
+            client,
+            host_id: host_id.into(),
+            state,
+            path: path.into(),
+            batch_size: 100,
+            interval: Duration::from_secs(1),
+            max_age: Duration::from_secs(24 * 60 * 60),
+            clock: &SystemClock,
+        }
+    }
+
+    /// Sets the number of the values posted in a request.
+    pub fn batch_size(mut self, batch_size: usize) -> Replay<'a> {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets the interval between requests.
+    pub fn interval(mut self, interval: Duration) -> Replay<'a> {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the maximum age of the values to post.
+    pub fn max_age(mut self, max_age: Duration) -> Replay<'a> {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Sets the clock for checking the age of the values.
+    pub fn clock(mut self, clock: &'a dyn Clock) -> Replay<'a> {
+        self.clock = clock;
+        self
+    }
+
+    /// Posts the values in the files, which should be ordered by the time as
+    /// returned by `FileSink::files`. The files before the file of the saved
+    /// progress are skipped.
+    pub fn run(&self, files: &[PathBuf]) -> Result<ReplayStats, Error> {
+        let now = self
+            .clock
+            .now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs() as i64;
+        let oldest = now.saturating_sub(self.max_age.as_secs().try_into().unwrap_or(i64::MAX));
+        let progress = self.load_progress();
+        let start = progress
+            .as_ref()
+            .and_then(|progress| files.iter().position(|file| *file == progress.file))
+            .unwrap_or(0);
+        let mut stats = ReplayStats::default();
+        let mut requested = false;
+        for file in &files[start..] {
+            let skip = match &progress {
+                Some(progress) if progress.file == *file => progress.line,
+                _ => 0,
+            };
+            let content = std::fs::read_to_string(file)
+                .map_err(|e| format!("read {} failed: {}", file.display(), e))?;
+            let lines = content.lines().collect::<Vec<_>>();
+            let mut batch = Vec::new();
+            for (i, line) in lines.iter().enumerate().skip(skip) {
+                match parse_line(line) {
+                    Some((name, value, time)) if time >= oldest => {
+                        batch.push(("custom.".to_owned() + name, value, time))
+                    }
+                    Some(_) => stats.skipped += 1,
+                    None => {
+                        let _ = writeln!(
+                            std::io::stderr(),
+                            "invalid line {} of {}",
+                            i + 1,
+                            file.display()
+                        );
+                        stats.skipped += 1;
+                    }
+                }
+                if batch.len() >= self.batch_size || i + 1 == lines.len() {
+                    if !batch.is_empty() {
+                        if std::mem::replace(&mut requested, true) {
+                            std::thread::sleep(self.interval);
+                        }
+                        self.client.post_host_metrics(&self.host_id, &batch)?;
+                        stats.posted += batch.len();
+                        batch.clear();
+                    }
+                    self.save_progress(file, i + 1)?;
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    fn load_progress(&self) -> Option<Progress> {
+        self.state
+            .load(&self.path)
+            .ok()
+            .flatten()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+    }
+
+    fn save_progress(&self, file: &std::path::Path, line: usize) -> Result<(), Error> {
+        let progress = Progress {
+            file: file.to_owned(),
+            line,
+        };
+        let bytes = serde_json::to_vec(&progress).map_err(|e| e.to_string())?;
+        Ok(self.state.save(&self.path, &bytes)?)
+    }
+}
+
+fn parse_line(line: &str) -> Option<(&str, f64, i64)> {
+    let mut fields = line.split('\t');
+    let (name, value, time) = (fields.next()?, fields.next()?, fields.next()?);
+    if fields.next().is_some() {
+        return None;
+    }
+    Some((name, value.parse().ok()?, time.parse().ok()?))
+}
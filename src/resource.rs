@@ -0,0 +1,130 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::api::Client;
+use crate::error::Error;
+use crate::sink::MetricSink;
+
+type Identify<'a> = Box<dyn Fn(&str) -> Option<String> + 'a>;
+
+/// A sink which groups the values by the custom identifiers of the resources,
+/// and posts them as the metrics of the hosts of the resources. The values of
+/// no custom identifier are written to the primary sink.
+///
+/// This is useful for the plugins scraping a central API, such as a cloud
+/// provider or a load balancer, which produce the metrics of many resources.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{Client, MetricSink, ResourceSink, TsvSink};
+///
+/// let client = Client::from_env().unwrap();
+/// let mut sink = ResourceSink::new(TsvSink::new(std::io::stdout()), |name: &str| {
+///     // the metric names are like lb.requests.<load balancer id>
+///     name.strip_prefix("lb.requests.").map(|id| format!("lb-{}", id))
+/// })
+/// .client(&client);
+/// sink.write_value("lb.requests.lb1", 42.0, 1700000000).unwrap();
+/// sink.finish().unwrap();
+/// ```
+pub struct ResourceSink<'a> {
+    primary: Box<dyn MetricSink + 'a>,
+    identify: Identify<'a>,
+    client: Option<&'a Client>,
+    groups: BTreeMap<String, Vec<(String, f64, i64)>>,
+    host_ids: HashMap<String, String>,
+}
+
+impl<'a> ResourceSink<'a> {
+    pub fn new(
+        primary: impl MetricSink + 'a,
+        identify: impl Fn(&str) -> Option<String> + 'a,
+    ) -> ResourceSink<'a> {
+        ResourceSink {
+            primary: Box::new(primary),
+            identify: Box::new(identify),
+            client: None,
+            groups: BTreeMap::new(),
+            host_ids: HashMap::new(),
+        }
+    }
+
+    /// Sets the client of the API, which defaults to the client configured by
+    /// the environment variables.
+    pub fn client(mut self, client: &'a Client) -> ResourceSink<'a> {
+        self.client = Some(client);
+        self
+    }
+
+    /// Returns the values grouped by the custom identifiers in the current run.
+    pub fn groups(&self) -> &BTreeMap<String, Vec<(String, f64, i64)>> {
+        &self.groups
+    }
+
+    fn post_groups(&mut self) -> Result<(), Error> {
+        let groups = std::mem::take(&mut self.groups);
+        if groups.is_empty() {
+            return Ok(());
+        }
+        let owned_client;
+        let client = match self.client {
+            Some(client) => client,
+            None => {
+                owned_client = Client::from_env()?;
+                &owned_client
+            }
+        };
+        let mut result = Ok(());
+        for (custom_identifier, values) in groups {
+            let host_id = match self.host_ids.get(&custom_identifier) {
+                Some(host_id) => host_id.clone(),
+                None => match client.find_host_id(&custom_identifier) {
+                    Ok(Some(host_id)) => {
+                        self.host_ids.insert(custom_identifier, host_id.clone());
+                        host_id
+                    }
+                    Ok(None) => {
+                        let err = format!("host of {} not found", custom_identifier);
+                        result = result.and(Err(err.into()));
+                        continue;
+                    }
+                    Err(err) => {
+                        result = result.and(Err(err));
+                        continue;
+                    }
+                },
+            };
+            let values = values
+                .into_iter()
+                .map(|(name, value, time)| ("custom.".to_owned() + &name, value, time))
+                .collect::<Vec<_>>();
+            result = result.and(client.post_host_metrics(&host_id, &values));
+        }
+        result
+    }
+}
+
+impl MetricSink for ResourceSink<'_> {
+    fn write_value(&mut self, name: &str, value: f64, time: i64) -> Result<(), Error> {
+        match (self.identify)(name) {
+            Some(custom_identifier) => {
+                self.groups.entry(custom_identifier).or_default().push((
+                    name.to_owned(),
+                    value,
+                    time,
+                ));
+                Ok(())
+            }
+            None => self.primary.write_value(name, value, time),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.primary.flush()
+    }
+
+    /// Finishes the primary sink, and posts the grouped values. The posting
+    /// continues on the errors of some resources, and returns the first error.
+    fn finish(&mut self) -> Result<(), Error> {
+        let result = self.primary.finish();
+        result.and(self.post_groups())
+    }
+}
@@ -0,0 +1,106 @@
+//! Conversions between the system time and the RFC 3339 timestamps in UTC,
+//! which are used by the APIs of the cloud providers.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Formats the time as `2023-11-14T22:13:20Z`, truncating the sub-seconds.
+pub(crate) fn format(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Parses the timestamp in UTC, such as `2023-11-14T22:13:20Z` or
+/// `2023-11-14T22:13:20.123Z`.
+#[cfg_attr(
+    not(any(feature = "cloudwatch", feature = "gcp", feature = "tls")),
+    allow(dead_code)
+)]
+pub(crate) fn parse(s: &str) -> Option<SystemTime> {
+    let s = s.strip_suffix('Z')?;
+    let (date, time) = s.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':').map(str::parse::<i64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+    let secs = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<9}", &fraction[..fraction.len().min(9)])
+            .parse()
+            .ok()?
+    };
+    UNIX_EPOCH.checked_add(Duration::new(secs.try_into().ok()?, nanos))
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + (month <= 2) as i64, month, day)
+}
+
+#[cfg_attr(
+    not(any(feature = "cloudwatch", feature = "gcp", feature = "tls")),
+    allow(dead_code)
+)]
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, "1970-01-01T00:00:00Z")]
+    #[case(951782400, "2000-02-29T00:00:00Z")]
+    #[case(1700000000, "2023-11-14T22:13:20Z")]
+    #[case(4102444799, "2099-12-31T23:59:59Z")]
+    fn test_format_parse(#[case] secs: u64, #[case] s: &str) {
+        let time = UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(format(time), s);
+        assert_eq!(parse(s), Some(time));
+    }
+
+    #[rstest]
+    #[case("2023-11-14T22:13:20.5Z", Some(1700000000500))]
+    #[case("2023-11-14T22:13:20.123Z", Some(1700000000123))]
+    #[case("2023-11-14T22:13:20", None)]
+    #[case("2023-13-14T22:13:20Z", None)]
+    #[case("2023-11-14 22:13:20Z", None)]
+    fn test_parse(#[case] s: &str, #[case] millis: Option<u64>) {
+        assert_eq!(
+            parse(s),
+            millis.map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+
+/// A scaffold of a new plugin project.
+///
+/// The project contains the graph definitions, a stub of fetching metrics,
+/// tests, and a Makefile which builds the release archives in the layout
+/// `mkr plugin install` expects.
+///
+/// ```rust
+/// use mackerel_plugin::Scaffold;
+///
+/// let scaffold = Scaffold::new("mackerel-plugin-disk-io").unwrap();
+/// assert_eq!(scaffold.dir_name(), "mackerel-plugin-disk-io");
+/// assert!(scaffold
+///     .files()
+///     .iter()
+///     .any(|(path, content)| path.ends_with("main.rs") && content.contains("DiskIoPlugin")));
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct Scaffold {
+    name: String,
+}
+
+impl Scaffold {
+    /// Creates a scaffold of the plugin name, with or without the
+    /// `mackerel-plugin-` prefix. The name starts with a lowercase alphabet,
+    /// and consists of the words of lowercase alphabets and digits separated
+    /// by hyphens or underscores.
+    pub fn new(name: &str) -> Result<Scaffold, String> {
+        let name = name.strip_prefix("mackerel-plugin-").unwrap_or(name);
+        // the words make the struct name, which starts with an alphabet
+        if !name.starts_with(|c: char| c.is_ascii_lowercase())
+            || !name
+                .chars()
+                .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '-' | '_'))
+            || name.split(['-', '_']).any(str::is_empty)
+        {
+            return Err(format!("invalid plugin name: {}", name));
+        }
+        Ok(Scaffold {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn dir_name(&self) -> String {
+        "mackerel-plugin-".to_owned() + &self.name
+    }
+
+    /// Returns the relative paths and contents of the files.
+    pub fn files(&self) -> Vec<(PathBuf, String)> {
+        let words = self
+            .name
+            .split(['-', '_'])
+            .map(|word| word[..1].to_uppercase() + &word[1..]);
+        let struct_name = words.clone().collect::<String>();
+        let label = words.collect::<Vec<_>>().join(" ");
+        [
+            ("Cargo.toml", include_str!("scaffold/Cargo.toml.tmpl")),
+            ("src/main.rs", include_str!("scaffold/main.rs.tmpl")),
+            ("Makefile", include_str!("scaffold/Makefile.tmpl")),
+            ("README.md", include_str!("scaffold/README.md.tmpl")),
+            (".gitignore", include_str!("scaffold/gitignore.tmpl")),
+        ]
+        .into_iter()
+        .map(|(path, template)| {
+            (
+                PathBuf::from(path),
+                template
+                    .replace("{{name}}", &self.name)
+                    .replace("{{struct}}", &struct_name)
+                    .replace("{{label}}", &label)
+                    .replace("{{version}}", env!("CARGO_PKG_VERSION")),
+            )
+        })
+        .collect()
+    }
+
+    /// Writes the files to the directory, which should not exist.
+    pub fn write_to(&self, dir: &Path) -> Result<(), String> {
+        if dir.exists() {
+            return Err(format!("{} already exists", dir.display()));
+        }
+        for (path, content) in self.files() {
+            let path = dir.join(path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("create {} failed: {}", parent.display(), e))?;
+            }
+            std::fs::write(&path, content)
+                .map_err(|e| format!("write to {} failed: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use crate::clock::Epoch;
+use crate::graph::Graph;
+
+/// The metrics about the plugin itself, which are collected on each run.
+#[derive(Default, Debug)]
+pub(crate) struct SelfMetrics {
+    pub(crate) timestamp: Epoch,
+    pub(crate) metrics: usize,
+    pub(crate) dropped: usize,
+    pub(crate) errors: usize,
+    pub(crate) fetch_duration: Duration,
+    pub(crate) sections: Vec<(String, Duration)>,
+}
+
+impl SelfMetrics {
+    /// Returns the graphs of the metrics about the plugin itself.
+    pub(crate) fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "mackerel_plugin.fetch",
+                label: "Plugin fetch",
+                unit: "integer",
+                metrics: [
+                    { name: "metrics", label: "Metrics" },
+                    { name: "dropped", label: "Dropped" },
+                    { name: "errors", label: "Errors" },
+                ]
+            },
+            crate::graph! {
+                name: "mackerel_plugin.duration",
+                label: "Plugin duration",
+                unit: "seconds",
+                metrics: [
+                    { name: "*", label: "%1" },
+                ]
+            },
+        ]
+    }
+
+    /// Returns the metric values about the plugin itself.
+    pub(crate) fn values(&self) -> Vec<(String, f64)> {
+        let mut values = vec![
+            (
+                "mackerel_plugin.fetch.metrics".to_owned(),
+                self.metrics as f64,
+            ),
+            (
+                "mackerel_plugin.fetch.dropped".to_owned(),
+                self.dropped as f64,
+            ),
+            (
+                "mackerel_plugin.fetch.errors".to_owned(),
+                self.errors as f64,
+            ),
+            (
+                "mackerel_plugin.duration.fetch".to_owned(),
+                self.fetch_duration.as_secs_f64(),
+            ),
+        ];
+        values.extend(self.sections.iter().map(|(name, duration)| {
+            (
+                "mackerel_plugin.duration.".to_owned() + name,
+                duration.as_secs_f64(),
+            )
+        }));
+        values
+    }
+}
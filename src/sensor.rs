@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::graph::Graph;
+#[cfg(target_os = "macos")]
+use crate::smc::{self, Smc};
+use crate::value::Value;
+
+/// A collector of the temperature and the fan speed sensors of the hardware
+/// monitoring chips in `/sys/class/hwmon` on Linux, or the System Management
+/// Controller on macOS.
+///
+/// The metrics are keyed by the chips and the labels of the sensors, such as
+/// `sensors.temperature.coretemp.Package_id_0`. The chips of the same name,
+/// like those of the CPU sockets and the NVMe drives, are distinguished by the
+/// devices, such as `coretemp_coretemp_0` and `nvme_nvme0`. The sensors
+/// without the labels are named by the files, such as `temp1`.
+///
+/// On macOS, the sensors are keyed by the SMC keys, such as
+/// `sensors.temperature.smc.TC0P` and `sensors.fan.smc.F0Ac`. The temperature
+/// sensors out of the range of 0 to 150 °C are ignored, which are those not
+/// connected.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::Sensors;
+///
+/// let sensors = Sensors::new();
+/// let values = sensors.fetch_values().unwrap();
+/// ```
+pub struct Sensors {
+    #[cfg(target_os = "linux")]
+    hwmon_dir: PathBuf,
+}
+
+impl Default for Sensors {
+    fn default() -> Sensors {
+        Sensors::new()
+    }
+}
+
+impl Sensors {
+    pub fn new() -> Sensors {
+        Sensors {
+            #[cfg(target_os = "linux")]
+            hwmon_dir: PathBuf::from("/sys/class/hwmon"),
+        }
+    }
+
+    /// Sets the directory of the hardware monitoring chips.
+    #[cfg(target_os = "linux")]
+    pub fn hwmon_dir(mut self, hwmon_dir: impl Into<PathBuf>) -> Sensors {
+        self.hwmon_dir = hwmon_dir.into();
+        self
+    }
+
+    /// Returns the graphs of the sensors.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "sensors.temperature.#",
+                label: "Sensors temperature (°C)",
+                unit: "float",
+                metrics: [{ name: "*", label: "%2" }],
+            },
+            crate::graph! {
+                name: "sensors.fan.#",
+                label: "Sensors fan speed (RPM)",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%2" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the sensors.
+    #[cfg(target_os = "linux")]
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        let mut chips = std::fs::read_dir(&self.hwmon_dir)
+            .map_err(|e| format!("read {} failed: {}", self.hwmon_dir.display(), e))?
+            .filter_map(|entry| chip(&entry.ok()?.path()))
+            .collect::<Vec<_>>();
+        chips.sort_by(|a, b| (&a.name, &a.device).cmp(&(&b.name, &b.device)));
+        let mut values = HashMap::new();
+        for chip in &chips {
+            let key = if chips.iter().filter(|c| c.name == chip.name).count() > 1 {
+                metric_key(&format!("{}_{}", chip.name, chip.device))
+            } else {
+                metric_key(&chip.name)
+            };
+            for entry in std::fs::read_dir(&chip.dir).into_iter().flatten() {
+                let Ok(entry) = entry else {
+                    continue;
+                };
+                let file_name = entry.file_name();
+                let Some(sensor) = file_name.to_str().and_then(|s| s.strip_suffix("_input")) else {
+                    continue;
+                };
+                let (graph, scale) = if sensor.starts_with("temp") {
+                    ("temperature", 1000.0)
+                } else if sensor.starts_with("fan") {
+                    ("fan", 1.0)
+                } else {
+                    continue;
+                };
+                // the sensors can fail to read, such as those not connected
+                let Some(value) = read(&entry.path()).and_then(|s| s.parse::<f64>().ok()) else {
+                    continue;
+                };
+                if read(&chip.dir.join(format!("{}_fault", sensor))).as_deref() == Some("1") {
+                    continue;
+                }
+                let label = read(&chip.dir.join(format!("{}_label", sensor)))
+                    .filter(|label| !label.is_empty())
+                    .unwrap_or_else(|| sensor.to_owned());
+                values.insert(
+                    format!("sensors.{}.{}.{}", graph, key, metric_key(&label)),
+                    (value / scale).into(),
+                );
+            }
+        }
+        Ok(values)
+    }
+
+    /// Returns the metric values of the sensors.
+    #[cfg(target_os = "macos")]
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        let smc = Smc::open()?;
+        let mut values = HashMap::new();
+        for key in smc.keys()? {
+            let graph = match key {
+                [b'T', ..] => "temperature",
+                [b'F', b'0'..=b'9', b'A', b'c'] => "fan",
+                _ => continue,
+            };
+            // the keys can fail to read, such as those of the other types
+            let Some(value) = smc
+                .read(key)
+                .ok()
+                .and_then(|(data_type, bytes)| smc::decode(data_type, &bytes))
+            else {
+                continue;
+            };
+            if graph == "temperature" && !(0.0..150.0).contains(&value) {
+                continue;
+            }
+            values.insert(
+                format!(
+                    "sensors.{}.smc.{}",
+                    graph,
+                    metric_key(&String::from_utf8_lossy(&key))
+                ),
+                value.into(),
+            );
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(target_os = "linux")]
+struct Chip {
+    name: String,
+    device: String,
+    dir: PathBuf,
+}
+
+/// Reads the chip of the directory, whose attributes are in the device
+/// directory on the old kernels.
+#[cfg(target_os = "linux")]
+fn chip(dir: &Path) -> Option<Chip> {
+    let dir = [dir.to_owned(), dir.join("device")]
+        .into_iter()
+        .find(|dir| dir.join("name").exists())?;
+    let name = read(&dir.join("name"))?;
+    let device = std::fs::read_link(dir.join("device"))
+        .ok()
+        .or_else(|| std::fs::read_link(&dir).ok())
+        .unwrap_or_else(|| dir.clone());
+    let device = device.file_name()?.to_string_lossy().into_owned();
+    Some(Chip { name, device, dir })
+}
+
+#[cfg(target_os = "linux")]
+fn read(path: &Path) -> Option<String> {
+    Some(std::fs::read_to_string(path).ok()?.trim().to_owned())
+}
+
+fn metric_key(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,145 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::graph::Graph;
+use crate::plugin::join_name;
+use crate::state::StateStore;
+use crate::wildcard;
+
+/// The series selected for each wildcard graph, which is saved to the state
+/// store to keep the selection stable across runs.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct SeriesSelection {
+    graphs: HashMap<String, Vec<String>>,
+}
+
+impl SeriesSelection {
+    pub(crate) fn load(state: &dyn StateStore, path: &str) -> SeriesSelection {
+        state
+            .load(&(path.to_owned() + ".series"))
+            .ok()
+            .flatten()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn save(&self, state: &dyn StateStore, path: &str) -> Result<(), String> {
+        let bytes = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+        state.save(&(path.to_owned() + ".series"), &bytes)
+    }
+
+    /// Retains the series of the largest values up to the limit, where the
+    /// values of a series are grouped by the values of the wildcard segments
+    /// of the graph name (or the metric name if the graph name has none), and
+    /// the series are ranked by the sum of the values. The series selected in
+    /// the previous run are kept while they rank within twice the limit, so
+    /// that the graphs do not flap between the series of similar values.
+    /// Returns the values of the series which are not selected.
+    pub(crate) fn select(
+        &mut self,
+        graph: &Graph,
+        values: &mut Vec<(String, f64)>,
+        limit: usize,
+    ) -> Vec<(String, f64)> {
+        let keys = values
+            .iter()
+            .map(|(name, _)| series_key(graph, name))
+            .collect::<Vec<_>>();
+        let mut totals = HashMap::<&str, f64>::new();
+        for (key, (_, value)) in keys.iter().zip(values.iter()) {
+            if let Some(key) = key {
+                *totals.entry(key).or_default() += value;
+            }
+        }
+        let mut ranked = totals.into_iter().collect::<Vec<_>>();
+        ranked.sort_by(|(k1, v1), (k2, v2)| v2.total_cmp(v1).then_with(|| k1.cmp(k2)));
+        let prev = self
+            .graphs
+            .get(&graph.name)
+            .map(|keys| keys.iter().map(String::as_str).collect::<HashSet<_>>())
+            .unwrap_or_default();
+        let mut selected = ranked
+            .iter()
+            .take(limit.saturating_mul(2))
+            .filter(|(key, _)| prev.contains(key))
+            .take(limit)
+            .map(|(key, _)| *key)
+            .collect::<HashSet<_>>();
+        for (key, _) in &ranked {
+            if selected.len() >= limit {
+                break;
+            }
+            selected.insert(key);
+        }
+        let mut names = selected
+            .iter()
+            .map(|key| (*key).to_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        let (kept, dropped) = std::mem::take(values)
+            .into_iter()
+            .zip(keys.iter())
+            .partition::<Vec<_>, _>(|(_, key)| {
+                key.as_deref().is_none_or(|key| selected.contains(key))
+            });
+        *values = kept.into_iter().map(|(value, _)| value).collect();
+        self.graphs.insert(graph.name.clone(), names);
+        dropped.into_iter().map(|(value, _)| value).collect()
+    }
+}
+
+/// Returns the key of the series of the metric name, which consists of the
+/// values of the wildcard segments of the graph name, or the metric name if
+/// the graph name has no wildcard segment.
+fn series_key(graph: &Graph, name: &str) -> Option<String> {
+    let wildcards = graph
+        .name
+        .split('.')
+        .filter(|segment| *segment == "*" || *segment == "#")
+        .count();
+    graph
+        .metrics
+        .iter()
+        .find_map(|metric| wildcard::capture(&join_name(&graph.name, &metric.name), name))
+        .filter(|values| !values.is_empty())
+        .map(|values| match wildcards {
+            0 => values.join("."),
+            _ => values[..wildcards].join("."),
+        })
+}
+
+pub(crate) fn is_wildcard_graph(graph: &Graph) -> bool {
+    let is_wildcard = |name: &str| name.contains('*') || name.contains('#');
+    is_wildcard(&graph.name) || graph.metrics.iter().any(|metric| is_wildcard(&metric.name))
+}
+
+/// Aggregates the series into the `other` series of each metric, which
+/// replaces the wildcard segments of the metric name with `other`. The caller
+/// merges the aggregate into the selected series of the same name.
+pub(crate) fn rollup(graph: &Graph, values: &[(String, f64)]) -> Vec<(String, f64)> {
+    graph
+        .metrics
+        .iter()
+        .filter_map(|metric| {
+            let pattern = join_name(&graph.name, &metric.name);
+            let matched = values
+                .iter()
+                .filter(|(name, _)| wildcard::capture(&pattern, name).is_some())
+                .map(|(_, value)| value)
+                .collect::<Vec<_>>();
+            (!matched.is_empty()).then(|| {
+                (
+                    pattern
+                        .split('.')
+                        .map(|segment| match segment {
+                            "*" | "#" => "other",
+                            segment => segment,
+                        })
+                        .collect::<Vec<_>>()
+                        .join("."),
+                    matched.into_iter().sum(),
+                )
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+use std::str::SplitWhitespace;
+use std::time::Duration;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::http::HttpClient;
+use crate::value::Value;
+
+const SCOREBOARD: [(char, &str, &str); 11] = [
+    ('_', "waiting", "Waiting for connection"),
+    ('S', "starting", "Starting up"),
+    ('R', "reading", "Reading request"),
+    ('W', "sending", "Sending reply"),
+    ('K', "keepalive", "Keepalive"),
+    ('D', "dns", "DNS lookup"),
+    ('C', "closing", "Closing connection"),
+    ('L', "logging", "Logging"),
+    ('G', "finishing", "Gracefully finishing"),
+    ('I', "idle_cleanup", "Idle cleanup"),
+    ('.', "open", "Open slot"),
+];
+
+/// A collector of the stub_status of nginx, whose metric names are
+/// compatible with mackerel-plugin-nginx.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::NginxStatus;
+///
+/// let status = NginxStatus::new("http://localhost/nginx_status");
+/// let values = status.fetch_values().unwrap();
+/// ```
+pub struct NginxStatus {
+    url: String,
+    client: HttpClient,
+}
+
+impl NginxStatus {
+    pub fn new(url: impl Into<String>) -> NginxStatus {
+        NginxStatus {
+            url: url.into(),
+            client: HttpClient::new(),
+        }
+    }
+
+    /// Sets the client for the certificates and the proxy.
+    pub fn client(mut self, client: HttpClient) -> NginxStatus {
+        self.client = client;
+        self
+    }
+
+    /// Returns the graphs of the status.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "nginx.connections",
+                label: "Nginx Connections",
+                unit: "integer",
+                metrics: [{ name: "connections", label: "Active connections" }],
+            },
+            crate::graph! {
+                name: "nginx.requests",
+                label: "Nginx requests",
+                unit: "float",
+                metrics: [
+                    { name: "accepts", label: "Accepted connections", diff: true },
+                    { name: "handled", label: "Handled connections", diff: true },
+                    { name: "requests", label: "Requests", diff: true },
+                ],
+            },
+            crate::graph! {
+                name: "nginx.queue",
+                label: "Nginx connection status",
+                unit: "integer",
+                metrics: [
+                    { name: "reading", label: "Reading" },
+                    { name: "writing", label: "Writing" },
+                    { name: "waiting", label: "Waiting" },
+                ],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the status.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the status, whose request times out on
+    /// the deadline of the fetch.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        NginxStatus::parse(&get(&self.client, ctx, &self.url)?)
+    }
+
+    /// Parses the output of stub_status.
+    ///
+    /// ```text
+    /// Active connections: 291
+    /// server accepts handled requests
+    ///  16630948 16630948 31070465
+    /// Reading: 6 Writing: 179 Waiting: 106
+    /// ```
+    pub fn parse(text: &str) -> Result<HashMap<String, Value>, Error> {
+        let mut words = text.split_whitespace();
+        let mut values = HashMap::new();
+        expect(&mut words, &["Active", "connections:"])?;
+        values.insert(
+            "nginx.connections.connections".to_owned(),
+            number(&mut words)?.into(),
+        );
+        expect(&mut words, &["server", "accepts", "handled", "requests"])?;
+        for key in ["accepts", "handled", "requests"] {
+            values.insert(
+                format!("nginx.requests.{}", key),
+                number(&mut words)?.into(),
+            );
+        }
+        for key in ["Reading", "Writing", "Waiting"] {
+            expect(&mut words, &[&format!("{}:", key)])?;
+            values.insert(
+                format!("nginx.queue.{}", key.to_lowercase()),
+                number(&mut words)?.into(),
+            );
+        }
+        Ok(values)
+    }
+}
+
+fn expect(words: &mut SplitWhitespace, expected: &[&str]) -> Result<(), Error> {
+    for &word in expected {
+        match words.next() {
+            Some(w) if w == word => {}
+            w => {
+                return Err(format!(
+                    "parse the nginx status failed: expected {:?} but got {:?}",
+                    word, w
+                )
+                .into())
+            }
+        }
+    }
+    Ok(())
+}
+
+fn number(words: &mut SplitWhitespace) -> Result<f64, Error> {
+    let word = words.next();
+    word.and_then(|word| word.parse().ok()).ok_or_else(|| {
+        format!(
+            "parse the nginx status failed: expected a number but got {:?}",
+            word
+        )
+        .into()
+    })
+}
+
+/// A collector of the mod_status of Apache HTTP Server, which fetches the
+/// machine-readable output of `?auto`.
+///
+/// The metrics are the workers, the scoreboard, and the requests, the bytes,
+/// and the CPU load, which are only reported with `ExtendedStatus On`. The
+/// metric names of the workers, the requests, the bytes, and the CPU load are
+/// compatible with mackerel-plugin-apache2.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::ApacheStatus;
+///
+/// let status = ApacheStatus::new("http://localhost/server-status?auto");
+/// let values = status.fetch_values().unwrap();
+/// ```
+pub struct ApacheStatus {
+    url: String,
+    client: HttpClient,
+}
+
+impl ApacheStatus {
+    pub fn new(url: impl Into<String>) -> ApacheStatus {
+        ApacheStatus {
+            url: url.into(),
+            client: HttpClient::new(),
+        }
+    }
+
+    /// Sets the client for the certificates and the proxy.
+    pub fn client(mut self, client: HttpClient) -> ApacheStatus {
+        self.client = client;
+        self
+    }
+
+    /// Returns the graphs of the status.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "apache2.workers",
+                label: "Apache Workers",
+                unit: "integer",
+                metrics: [
+                    { name: "busy_workers", label: "Busy Workers", stacked: true },
+                    { name: "idle_workers", label: "Idle Workers", stacked: true },
+                ],
+            },
+            crate::graph! {
+                name: "apache2.bytes",
+                label: "Apache Bytes",
+                unit: "bytes",
+                metrics: [{ name: "bytes_sent", label: "Bytes Sent", diff: true }],
+            },
+            crate::graph! {
+                name: "apache2.cpu",
+                label: "Apache CPU Load",
+                unit: "float",
+                metrics: [{ name: "cpu_load", label: "CPU Load" }],
+            },
+            crate::graph! {
+                name: "apache2.req",
+                label: "Apache Requests",
+                unit: "integer",
+                metrics: [{ name: "requests", label: "Requests", diff: true }],
+            },
+            crate::graph! {
+                name: "apache2.scoreboard",
+                label: "Apache Scoreboard",
+                unit: "integer",
+                metrics: SCOREBOARD.iter().map(|&(_, name, label)| {
+                    crate::metric! { name: name, label: label, stacked: true }
+                }),
+            },
+        ]
+    }
+
+    /// Returns the metric values of the status.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the status, whose request times out on
+    /// the deadline of the fetch.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        ApacheStatus::parse(&get(&self.client, ctx, &self.url)?)
+    }
+
+    /// Parses the output of mod_status with `?auto`, where the fields missing
+    /// in the output are omitted.
+    ///
+    /// ```text
+    /// Total Accesses: 1234
+    /// Total kBytes: 567
+    /// CPULoad: .0123
+    /// BusyWorkers: 2
+    /// IdleWorkers: 48
+    /// Scoreboard: _W_K______....
+    /// ```
+    pub fn parse(text: &str) -> Result<HashMap<String, Value>, Error> {
+        let mut values = HashMap::new();
+        let mut scoreboard = None;
+        for (key, value) in text.lines().filter_map(|line| line.split_once(": ")) {
+            let (name, scale) = match key {
+                "Total Accesses" => ("req.requests", 1.0),
+                "Total kBytes" => ("bytes.bytes_sent", 1024.0),
+                "CPULoad" => ("cpu.cpu_load", 1.0),
+                "BusyWorkers" => ("workers.busy_workers", 1.0),
+                "IdleWorkers" => ("workers.idle_workers", 1.0),
+                "Scoreboard" => {
+                    scoreboard = Some(value.trim());
+                    continue;
+                }
+                _ => continue,
+            };
+            let value = value.trim().parse::<f64>().map_err(|_| {
+                format!(
+                    "parse the Apache status failed: invalid {}: {:?}",
+                    key, value
+                )
+            })?;
+            values.insert(format!("apache2.{}", name), (value * scale).into());
+        }
+        if values.is_empty() && scoreboard.is_none() {
+            return Err("parse the Apache status failed: no status found".into());
+        }
+        if let Some(scoreboard) = scoreboard {
+            for (c, name, _) in SCOREBOARD {
+                let count = scoreboard.chars().filter(|&d| d == c).count();
+                values.insert(
+                    format!("apache2.scoreboard.{}", name),
+                    (count as f64).into(),
+                );
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// A collector of the status page of PHP-FPM, whose metric keys are grouped
+/// by the name of the pool.
+///
+/// The status of each pool is served at its own URL, so the plugins of many
+/// pools merge the values of the collectors, which share the graphs.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::PhpFpmStatus;
+///
+/// let status = PhpFpmStatus::new("http://localhost/fpm-status?json");
+/// let values = status.fetch_values().unwrap();
+/// ```
+pub struct PhpFpmStatus {
+    url: String,
+    client: HttpClient,
+}
+
+impl PhpFpmStatus {
+    pub fn new(url: impl Into<String>) -> PhpFpmStatus {
+        PhpFpmStatus {
+            url: url.into(),
+            client: HttpClient::new(),
+        }
+    }
+
+    /// Sets the client for the certificates and the proxy.
+    pub fn client(mut self, client: HttpClient) -> PhpFpmStatus {
+        self.client = client;
+        self
+    }
+
+    /// Returns the graphs of the status.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "php-fpm.processes.#",
+                label: "PHP-FPM Processes",
+                unit: "integer",
+                metrics: [
+                    { name: "active_processes", label: "Active Processes", stacked: true },
+                    { name: "idle_processes", label: "Idle Processes", stacked: true },
+                ],
+            },
+            crate::graph! {
+                name: "php-fpm.max_active_processes.#",
+                label: "PHP-FPM Max Active Processes",
+                unit: "integer",
+                metrics: [{ name: "max_active_processes", label: "Max Active Processes" }],
+            },
+            crate::graph! {
+                name: "php-fpm.max_children_reached.#",
+                label: "PHP-FPM Max Children Reached",
+                unit: "integer",
+                metrics: [
+                    { name: "max_children_reached", label: "Max Children Reached", diff: true },
+                ],
+            },
+            crate::graph! {
+                name: "php-fpm.queue.#",
+                label: "PHP-FPM Queue",
+                unit: "integer",
+                metrics: [
+                    { name: "listen_queue", label: "Listen Queue" },
+                    { name: "max_listen_queue", label: "Max Listen Queue" },
+                    { name: "listen_queue_len", label: "Listen Queue Length" },
+                ],
+            },
+            crate::graph! {
+                name: "php-fpm.requests.#",
+                label: "PHP-FPM Requests",
+                unit: "integer",
+                metrics: [
+                    { name: "accepted_conn", label: "Accepted Connections", diff: true },
+                    { name: "slow_requests", label: "Slow Requests", diff: true },
+                ],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the status.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the status, whose request times out on
+    /// the deadline of the fetch.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        PhpFpmStatus::parse(&get(&self.client, ctx, &self.url)?)
+    }
+
+    /// Parses the status of the plain text or the JSON (`?json`). The status
+    /// of each process by `?full` is ignored.
+    ///
+    /// ```text
+    /// pool:                 www
+    /// accepted conn:        1234
+    /// listen queue:         0
+    /// idle processes:       4
+    /// active processes:     1
+    /// ```
+    pub fn parse(text: &str) -> Result<HashMap<String, Value>, Error> {
+        let fields = if text.trim_start().starts_with('{') {
+            let status: serde_json::Map<String, serde_json::Value> = serde_json::from_str(text)
+                .map_err(|err| format!("parse the PHP-FPM status failed: {}", err))?;
+            status
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    let value = match value {
+                        serde_json::Value::String(value) => value,
+                        serde_json::Value::Number(value) => value.to_string(),
+                        _ => return None,
+                    };
+                    Some((key, value))
+                })
+                .collect::<HashMap<_, _>>()
+        } else {
+            text.lines()
+                .take_while(|line| !line.starts_with('*'))
+                .filter_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    Some((key.trim().to_owned(), value.trim().to_owned()))
+                })
+                .collect()
+        };
+        let pool = fields
+            .get("pool")
+            .ok_or("parse the PHP-FPM status failed: no pool found")?;
+        let pool = pool
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect::<String>();
+        let mut values = HashMap::new();
+        for (graph, field) in [
+            ("processes", "active processes"),
+            ("processes", "idle processes"),
+            ("max_active_processes", "max active processes"),
+            ("max_children_reached", "max children reached"),
+            ("queue", "listen queue"),
+            ("queue", "max listen queue"),
+            ("queue", "listen queue len"),
+            ("requests", "accepted conn"),
+            ("requests", "slow requests"),
+        ] {
+            let Some(value) = fields.get(field) else {
+                continue;
+            };
+            let value = value.parse::<f64>().map_err(|_| {
+                format!(
+                    "parse the PHP-FPM status failed: invalid {}: {:?}",
+                    field, value
+                )
+            })?;
+            values.insert(
+                format!("php-fpm.{}.{}.{}", graph, pool, field.replace(' ', "_")),
+                value.into(),
+            );
+        }
+        Ok(values)
+    }
+}
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) fn get(client: &HttpClient, ctx: &Context, url: &str) -> Result<String, Error> {
+    let error = |err: &dyn std::fmt::Display| format!("GET {} failed: {}", url, err);
+    ctx.acquire().map_err(|err| error(&err))?;
+    if let Some(result) = client.request_unix("GET", url, &[], ctx.timeout(TIMEOUT)) {
+        let (status, body) = result.map_err(|err| error(&err))?;
+        if status >= 400 {
+            return Err(error(&format!("status code {}", status)).into());
+        }
+        return Ok(String::from_utf8_lossy(&body).into_owned());
+    }
+    let response = client
+        .agent_builder()
+        .map_err(|err| error(&err))?
+        .timeout(TIMEOUT)
+        .build()
+        .get(url)
+        .timeout(ctx.timeout(TIMEOUT))
+        .call()
+        .map_err(|err| error(&err))?;
+    Ok(response.into_string().map_err(|err| error(&err))?)
+}
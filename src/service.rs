@@ -0,0 +1,60 @@
+use crate::api::Client;
+use crate::error::Error;
+use crate::graph::is_valid_graph_name;
+use crate::sink::MetricSink;
+
+/// A sink which posts the values to the service as the service metrics,
+/// which are not tied to a host, such as the total queue depth of a cluster.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{Client, MetricSink, ServiceSink};
+///
+/// let client = Client::from_env().unwrap();
+/// let mut sink = ServiceSink::new(&client, "queue").unwrap();
+/// sink.write_value("queue.depth.total", 42.0, 1700000000).unwrap();
+/// sink.finish().unwrap();
+/// ```
+pub struct ServiceSink<'a> {
+    client: &'a Client,
+    service: String,
+    values: Vec<(String, f64, i64)>,
+}
+
+impl<'a> ServiceSink<'a> {
+    /// Creates a sink of the service, or returns an error if the service name
+    /// is invalid.
+    pub fn new(client: &'a Client, service: impl Into<String>) -> Result<ServiceSink<'a>, Error> {
+        let service = service.into();
+        if !(2..=63).contains(&service.len())
+            || !service
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        {
+            return Err(format!("invalid service name: {}", service).into());
+        }
+        Ok(ServiceSink {
+            client,
+            service,
+            values: Vec::new(),
+        })
+    }
+}
+
+impl MetricSink for ServiceSink<'_> {
+    fn write_value(&mut self, name: &str, value: f64, time: i64) -> Result<(), Error> {
+        if name.is_empty() || !is_valid_graph_name(name) || name.contains(['*', '#']) {
+            return Err(format!("invalid service metric name: {}", name).into());
+        }
+        self.values.push((name.to_owned(), value, time));
+        Ok(())
+    }
+
+    /// Posts the values written in the run.
+    fn finish(&mut self) -> Result<(), Error> {
+        let values = std::mem::take(&mut self.values);
+        if values.is_empty() {
+            return Ok(());
+        }
+        self.client.post_service_metrics(&self.service, &values)
+    }
+}
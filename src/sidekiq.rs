@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::credentials::Secret;
+use crate::error::Error;
+use crate::queue::{QueueBackend, QueueStats};
+use crate::redis::{Connection, Reply};
+
+/// The settings of the connection to Redis shared by the backends.
+struct Redis {
+    addr: String,
+    namespace: String,
+    password: Option<Secret>,
+    database: u32,
+    timeout: Duration,
+}
+
+impl Redis {
+    fn new(addr: String, namespace: &str) -> Redis {
+        Redis {
+            addr,
+            namespace: namespace.to_owned(),
+            password: None,
+            database: 0,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    fn connect(&self) -> Result<Connection, std::io::Error> {
+        let mut conn = Connection::connect(&self.addr, self.timeout)?;
+        if let Some(password) = &self.password {
+            conn.command(&["AUTH", password.expose()])?;
+        }
+        if self.database != 0 {
+            conn.command(&["SELECT", &self.database.to_string()])?;
+        }
+        Ok(conn)
+    }
+
+    /// Returns the key in the namespace like redis-namespace.
+    fn key(&self, key: &str) -> String {
+        if self.namespace.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}:{}", self.namespace, key)
+        }
+    }
+}
+
+/// Counts the jobs in JSON by the `queue` field.
+fn count_by_queue<'a>(jobs: impl IntoIterator<Item = &'a str>) -> HashMap<String, u64> {
+    let mut counts = HashMap::new();
+    for job in jobs {
+        let Ok(job) = serde_json::from_str::<serde_json::Value>(job) else {
+            continue;
+        };
+        if let Some(queue) = job["queue"].as_str() {
+            *counts.entry(queue.to_owned()).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// A backend of the queues of Sidekiq in Redis.
+///
+/// The depth is the number of the enqueued jobs, the in-flight messages are
+/// the jobs being processed, the consumers are the processes fetching from
+/// the queue, and the latency is the time the oldest job has been waiting. The
+/// retries and the dead messages are the jobs in the retry and the dead sets
+/// by the queue.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{Plugin, QueueDepth, Sidekiq};
+///
+/// let plugin = QueueDepth::new(Sidekiq::new("localhost:6379"));
+/// match plugin.run() {
+///     Ok(_) => {}
+///     Err(err) => {
+///         eprintln!("mackerel-plugin-sidekiq: {}", err);
+///         std::process::exit(1);
+///     }
+/// }
+/// ```
+pub struct Sidekiq {
+    redis: Redis,
+}
+
+impl Sidekiq {
+    pub fn new(addr: impl Into<String>) -> Sidekiq {
+        Sidekiq {
+            redis: Redis::new(addr.into(), ""),
+        }
+    }
+
+    /// Sets the namespace of the keys for the applications using
+    /// redis-namespace.
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Sidekiq {
+        self.redis.namespace = namespace.into();
+        self
+    }
+
+    pub fn password(mut self, password: Secret) -> Sidekiq {
+        self.redis.password = Some(password);
+        self
+    }
+
+    pub fn database(mut self, database: u32) -> Sidekiq {
+        self.redis.database = database;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Sidekiq {
+        self.redis.timeout = timeout;
+        self
+    }
+
+    fn fetch(&self, conn: &mut Connection) -> Result<Vec<QueueStats>, std::io::Error> {
+        let redis = &self.redis;
+        let mut names = conn
+            .command(&["SMEMBERS", &redis.key("queues")])?
+            .strings()
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        names.sort();
+
+        let (mut in_flight, mut consumers) = (HashMap::new(), HashMap::<String, u64>::new());
+        let processes = conn.command(&["SMEMBERS", &redis.key("processes")])?;
+        for process in processes.strings() {
+            let work = conn.command(&["HVALS", &redis.key(&format!("{}:work", process))])?;
+            for (queue, count) in count_by_queue(work.strings()) {
+                *in_flight.entry(queue).or_default() += count;
+            }
+            let info = conn.command(&["HGET", &redis.key(process), "info"])?;
+            let Some(Ok(info)) = info.bulk().map(serde_json::from_str::<serde_json::Value>) else {
+                continue;
+            };
+            for queue in info["queues"].as_array().into_iter().flatten() {
+                if let Some(queue) = queue.as_str() {
+                    *consumers.entry(queue.to_owned()).or_default() += 1;
+                }
+            }
+        }
+        let retries = count_by_queue(
+            conn.command(&["ZRANGE", &redis.key("retry"), "0", "-1"])?
+                .strings(),
+        );
+        let dead = count_by_queue(
+            conn.command(&["ZRANGE", &redis.key("dead"), "0", "-1"])?
+                .strings(),
+        );
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let mut queues = Vec::new();
+        for name in names {
+            let key = redis.key(&format!("queue:{}", name));
+            let depth = conn.command(&["LLEN", &key])?.integer().unwrap_or_default();
+            // the jobs are pushed to the head, so the oldest one is the last
+            let oldest = conn.command(&["LINDEX", &key, "-1"])?;
+            let enqueued_at = oldest
+                .bulk()
+                .and_then(|job| serde_json::from_str::<serde_json::Value>(job).ok())
+                .and_then(|job| job["enqueued_at"].as_f64())
+                // in milliseconds since Sidekiq 8
+                .map(|time| if time > 1e11 { time / 1000.0 } else { time });
+            let stat = |counts: &HashMap<String, u64>| counts.get(&name).copied().unwrap_or(0);
+            queues.push(
+                QueueStats::new(&name, depth.max(0) as u64)
+                    .in_flight(stat(&in_flight))
+                    .consumers(stat(&consumers))
+                    .latency(Duration::from_secs_f64(
+                        enqueued_at.map_or(0.0, |time| (now - time).max(0.0)),
+                    ))
+                    .retries(stat(&retries))
+                    .dead(stat(&dead)),
+            );
+        }
+        Ok(queues)
+    }
+}
+
+impl QueueBackend for Sidekiq {
+    fn name(&self) -> &str {
+        "sidekiq"
+    }
+
+    fn queues(&self) -> Result<Vec<QueueStats>, Error> {
+        let error = |err: std::io::Error| format!("sidekiq {} failed: {}", self.redis.addr, err);
+        let mut conn = self.redis.connect().map_err(error)?;
+        Ok(self.fetch(&mut conn).map_err(error)?)
+    }
+}
+
+/// A backend of the queues of Resque in Redis.
+///
+/// The depth is the number of the enqueued jobs, the in-flight messages are
+/// the jobs being processed by the workers, the consumers are the workers
+/// watching the queue, and the dead messages are the failed jobs by the
+/// queue, which are not retried automatically. Resque records no time of
+/// enqueueing the jobs, so the latency is not reported.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{QueueDepth, Resque};
+///
+/// let plugin = QueueDepth::new(Resque::new("localhost:6379"));
+/// ```
+pub struct Resque {
+    redis: Redis,
+}
+
+impl Resque {
+    pub fn new(addr: impl Into<String>) -> Resque {
+        Resque {
+            redis: Redis::new(addr.into(), "resque"),
+        }
+    }
+
+    /// Sets the namespace of the keys, which defaults to `resque`.
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Resque {
+        self.redis.namespace = namespace.into();
+        self
+    }
+
+    pub fn password(mut self, password: Secret) -> Resque {
+        self.redis.password = Some(password);
+        self
+    }
+
+    pub fn database(mut self, database: u32) -> Resque {
+        self.redis.database = database;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Resque {
+        self.redis.timeout = timeout;
+        self
+    }
+
+    fn fetch(&self, conn: &mut Connection) -> Result<Vec<QueueStats>, std::io::Error> {
+        let redis = &self.redis;
+        let mut names = conn
+            .command(&["SMEMBERS", &redis.key("queues")])?
+            .strings()
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        names.sort();
+
+        let (mut jobs, mut consumers) = (Vec::new(), HashMap::<String, u64>::new());
+        let workers = conn.command(&["SMEMBERS", &redis.key("workers")])?;
+        for worker in workers.strings() {
+            // the worker is identified by host:pid:queue,queue
+            let watching = worker.rsplit(':').next().unwrap_or_default();
+            for queue in watching.split(',') {
+                if queue == "*" {
+                    for name in &names {
+                        *consumers.entry(name.clone()).or_default() += 1;
+                    }
+                } else {
+                    *consumers.entry(queue.to_owned()).or_default() += 1;
+                }
+            }
+            if let Reply::Bulk(job) =
+                conn.command(&["GET", &redis.key(&format!("worker:{}", worker))])?
+            {
+                jobs.push(job);
+            }
+        }
+        let in_flight = count_by_queue(jobs.iter().map(String::as_str));
+        let failed = count_by_queue(
+            conn.command(&["LRANGE", &redis.key("failed"), "0", "-1"])?
+                .strings(),
+        );
+
+        let mut queues = Vec::new();
+        for name in names {
+            let depth = conn
+                .command(&["LLEN", &redis.key(&format!("queue:{}", name))])?
+                .integer()
+                .unwrap_or_default();
+            let stat = |counts: &HashMap<String, u64>| counts.get(&name).copied().unwrap_or(0);
+            queues.push(
+                QueueStats::new(&name, depth.max(0) as u64)
+                    .in_flight(stat(&in_flight))
+                    .consumers(stat(&consumers))
+                    .dead(stat(&failed)),
+            );
+        }
+        Ok(queues)
+    }
+}
+
+impl QueueBackend for Resque {
+    fn name(&self) -> &str {
+        "resque"
+    }
+
+    fn queues(&self) -> Result<Vec<QueueStats>, Error> {
+        let error = |err: std::io::Error| format!("resque {} failed: {}", self.redis.addr, err);
+        let mut conn = self.redis.connect().map_err(error)?;
+        Ok(self.fetch(&mut conn).map_err(error)?)
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::format::{format_value, DefaultFormatter, ValueFormatter};
+use crate::json;
+use crate::value::Value;
+
+/// A trait which represents a destination of the metric values output by the
+/// plugin.
+///
+/// ```rust
+/// use mackerel_plugin::{LtsvSink, MetricSink};
+///
+/// let mut out = Vec::new();
+/// let mut sink = LtsvSink::new(&mut out);
+/// sink.write_value("dice.d6", 3.0, 1700000000).unwrap();
+/// sink.finish().unwrap();
+/// assert_eq!(out, b"name:dice.d6\tvalue:3\ttime:1700000000\n");
+/// ```
+pub trait MetricSink {
+    /// Writes the metric value of the name at the time.
+    fn write_value(&mut self, name: &str, value: f64, time: i64) -> Result<(), Error>;
+
+    /// Flushes the values written so far in the middle of a run, so that the
+    /// consumer starts receiving the large output early.
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Finishes the output of a run, and flushes the buffered values.
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<T: MetricSink + ?Sized> MetricSink for &mut T {
+    fn write_value(&mut self, name: &str, value: f64, time: i64) -> Result<(), Error> {
+        (**self).write_value(name, value, time)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        (**self).flush()
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        (**self).finish()
+    }
+}
+
+impl<T: MetricSink + ?Sized> MetricSink for Box<T> {
+    fn write_value(&mut self, name: &str, value: f64, time: i64) -> Result<(), Error> {
+        (**self).write_value(name, value, time)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        (**self).flush()
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        (**self).finish()
+    }
+}
+
+/// The bytes of the values assumed on estimating the output size, which
+/// covers the most values except for the huge or tiny ones.
+const VALUE_WIDTH: usize = 24;
+
+/// Returns the estimated upper bound of the bytes of the values output in the
+/// tab-separated format with the metric key prefix, which the plugins can use
+/// to warn before emitting an insane output.
+///
+/// The width of each value is assumed to be the larger of the value fetched
+/// and 24 bytes, and the timestamp is assumed to take 20 bytes.
+///
+/// ```rust
+/// use mackerel_plugin::{estimate_output_size, Value};
+/// use std::collections::HashMap;
+///
+/// let values = HashMap::from([("dice.d6".to_owned(), Value::from(3.0))]);
+/// assert_eq!(estimate_output_size("game", &values), 59);
+/// ```
+pub fn estimate_output_size(prefix: &str, values: &HashMap<String, Value>) -> usize {
+    values
+        .iter()
+        .map(|(name, value)| {
+            let name = if prefix.is_empty() {
+                name.len()
+            } else {
+                prefix.len() + 1 + name.len()
+            };
+            let value = format_value(value.as_f64()).len().max(VALUE_WIDTH);
+            name + 1 + value + 1 + 20 + 1
+        })
+        .sum()
+}
+
+/// A sink which writes the values in the tab-separated format of
+/// mackerel-agent.
+pub struct TsvSink<W: Write> {
+    out: W,
+    formatter: Box<dyn ValueFormatter>,
+}
+
+impl<W: Write> TsvSink<W> {
+    pub fn new(out: W) -> TsvSink<W> {
+        TsvSink {
+            out,
+            formatter: Box::new(DefaultFormatter),
+        }
+    }
+
+    /// Sets the formatter of the values.
+    pub fn formatter(mut self, formatter: impl ValueFormatter + 'static) -> TsvSink<W> {
+        self.formatter = Box::new(formatter);
+        self
+    }
+}
+
+impl<W: Write> MetricSink for TsvSink<W> {
+    fn write_value(&mut self, name: &str, value: f64, time: i64) -> Result<(), Error> {
+        let value = self.formatter.format(value);
+        writeln!(self.out, "{}\t{}\t{}", name, value, time)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// A sink which writes the values as a JSON array of the objects with `name`,
+/// `value`, and `time`.
+pub struct JsonSink<W: Write> {
+    out: W,
+    formatter: Box<dyn ValueFormatter>,
+    count: usize,
+}
+
+impl<W: Write> JsonSink<W> {
+    pub fn new(out: W) -> JsonSink<W> {
+        JsonSink {
+            out,
+            formatter: Box::new(DefaultFormatter),
+            count: 0,
+        }
+    }
+
+    /// Sets the formatter of the values, which must return a JSON number.
+    pub fn formatter(mut self, formatter: impl ValueFormatter + 'static) -> JsonSink<W> {
+        self.formatter = Box::new(formatter);
+        self
+    }
+}
+
+impl<W: Write> MetricSink for JsonSink<W> {
+    fn write_value(&mut self, name: &str, value: f64, time: i64) -> Result<(), Error> {
+        let mut s = String::from(if self.count == 0 { "[" } else { "," });
+        s.push_str("{\"name\":");
+        json::write_string(&mut s, name);
+        s.push_str(&format!(
+            ",\"value\":{},\"time\":{}}}",
+            self.formatter.format(value),
+            time
+        ));
+        self.out.write_all(s.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if self.count == 0 {
+            self.out.write_all(b"[")?;
+        }
+        self.out.write_all(b"]\n")?;
+        self.out.flush()?;
+        self.count = 0;
+        Ok(())
+    }
+}
+
+/// A sink which writes the values in the LTSV format with the labels `name`,
+/// `value`, and `time`.
+pub struct LtsvSink<W: Write> {
+    out: W,
+    formatter: Box<dyn ValueFormatter>,
+}
+
+impl<W: Write> LtsvSink<W> {
+    pub fn new(out: W) -> LtsvSink<W> {
+        LtsvSink {
+            out,
+            formatter: Box::new(DefaultFormatter),
+        }
+    }
+
+    /// Sets the formatter of the values.
+    pub fn formatter(mut self, formatter: impl ValueFormatter + 'static) -> LtsvSink<W> {
+        self.formatter = Box::new(formatter);
+        self
+    }
+}
+
+impl<W: Write> MetricSink for LtsvSink<W> {
+    fn write_value(&mut self, name: &str, value: f64, time: i64) -> Result<(), Error> {
+        let value = self.formatter.format(value);
+        writeln!(self.out, "name:{}\tvalue:{}\ttime:{}", name, value, time)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// A sink which writes the values to the primary sink and the secondary sinks.
+///
+/// The errors of the primary sink are returned, while the errors of the
+/// secondary sinks are reported to the standard error and the failed sinks are
+/// skipped until the next run, so that a misbehaving secondary sink never
+/// breaks the output for mackerel-agent.
+///
+/// ```rust
+/// use mackerel_plugin::{LtsvSink, MetricSink, TeeSink, TsvSink};
+///
+/// let (mut primary, mut secondary) = (Vec::new(), Vec::new());
+/// let mut sink = TeeSink::new(TsvSink::new(&mut primary)).sink(LtsvSink::new(&mut secondary));
+/// sink.write_value("dice.d6", 3.0, 1700000000).unwrap();
+/// sink.finish().unwrap();
+/// drop(sink);
+/// assert_eq!(primary, b"dice.d6\t3\t1700000000\n");
+/// assert_eq!(secondary, b"name:dice.d6\tvalue:3\ttime:1700000000\n");
+/// ```
+pub struct TeeSink<'a> {
+    primary: Box<dyn MetricSink + 'a>,
+    secondaries: Vec<(Box<dyn MetricSink + 'a>, Option<Error>)>,
+    finished: bool,
+}
+
+impl<'a> TeeSink<'a> {
+    pub fn new(primary: impl MetricSink + 'a) -> TeeSink<'a> {
+        TeeSink {
+            primary: Box::new(primary),
+            secondaries: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Adds a secondary sink.
+    pub fn sink(mut self, sink: impl MetricSink + 'a) -> TeeSink<'a> {
+        self.secondaries.push((Box::new(sink), None));
+        self
+    }
+
+    /// Returns the errors of the secondary sinks in the current (or the last
+    /// finished) run.
+    pub fn errors(&self) -> Vec<&Error> {
+        self.secondaries
+            .iter()
+            .filter_map(|(_, error)| error.as_ref())
+            .collect()
+    }
+
+    fn each_secondary(&mut self, mut f: impl FnMut(&mut dyn MetricSink) -> Result<(), Error>) {
+        for (sink, error) in &mut self.secondaries {
+            if error.is_none() {
+                if let Err(err) = f(sink.as_mut()) {
+                    let _ = writeln!(std::io::stderr(), "secondary sink failed: {}", err);
+                    *error = Some(err);
+                }
+            }
+        }
+    }
+}
+
+impl MetricSink for TeeSink<'_> {
+    fn write_value(&mut self, name: &str, value: f64, time: i64) -> Result<(), Error> {
+        if std::mem::take(&mut self.finished) {
+            for (_, error) in &mut self.secondaries {
+                *error = None;
+            }
+        }
+        let result = self.primary.write_value(name, value, time);
+        self.each_secondary(|sink| sink.write_value(name, value, time));
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        let result = self.primary.flush();
+        self.each_secondary(|sink| sink.flush());
+        result
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        let result = self.primary.finish();
+        self.each_secondary(|sink| sink.finish());
+        self.finished = true;
+        result
+    }
+}
+
+/// A sink which writes the values in the tab-separated format to the files in
+/// the directory, for collecting the values later on the hosts without network.
+///
+/// The files are named with the time of the first value, such as
+/// `mackerel-plugin-dice.1700000000.tsv`, and rotated by the size and the age.
+/// The files rotated by the size within the same time are suffixed with the
+/// sequence number, such as `mackerel-plugin-dice.1700000000.1.tsv`. The
+/// latest file is continued by the next run of the plugin.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::FileSink;
+/// use std::time::Duration;
+///
+/// let sink = FileSink::new("/var/lib/mackerel-plugin", "mackerel-plugin-dice")
+///     .max_size(1 << 20)
+///     .max_age(Duration::from_secs(3600))
+///     .sync(true);
+/// ```
+pub struct FileSink {
+    dir: PathBuf,
+    prefix: String,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+    sync: bool,
+    formatter: Box<dyn ValueFormatter>,
+    current: Option<OutputFile>,
+}
+
+struct OutputFile {
+    file: std::io::BufWriter<std::fs::File>,
+    size: u64,
+    time: i64,
+    seq: u64,
+}
+
+impl FileSink {
+    pub fn new(dir: impl Into<PathBuf>, prefix: impl Into<String>) -> FileSink {
+        FileSink {
+            dir: dir.into(),
+            prefix: prefix.into(),
+            max_size: None,
+            max_age: None,
+            sync: false,
+            formatter: Box::new(DefaultFormatter),
+            current: None,
+        }
+    }
+
+    /// Sets the size of the files in bytes to rotate at.
+    pub fn max_size(mut self, max_size: u64) -> FileSink {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Sets the age of the files to rotate at, which is compared with the
+    /// time of the values.
+    pub fn max_age(mut self, max_age: Duration) -> FileSink {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets whether to synchronize the file to the disk on each run.
+    pub fn sync(mut self, sync: bool) -> FileSink {
+        self.sync = sync;
+        self
+    }
+
+    /// Sets the formatter of the values.
+    pub fn formatter(mut self, formatter: impl ValueFormatter + 'static) -> FileSink {
+        self.formatter = Box::new(formatter);
+        self
+    }
+
+    /// Returns the paths of the files written by the sink, sorted by the time
+    /// and the sequence number.
+    pub fn files(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if let Some(key) = self.file_key(&path) {
+                files.push((key, path));
+            }
+        }
+        files.sort();
+        Ok(files.into_iter().map(|(_, path)| path).collect())
+    }
+
+    /// Returns the time and the sequence number of the file.
+    fn file_key(&self, path: &std::path::Path) -> Option<(i64, u64)> {
+        let key = path
+            .file_name()?
+            .to_str()?
+            .strip_prefix(&self.prefix)?
+            .strip_prefix('.')?
+            .strip_suffix(".tsv")?;
+        match key.split_once('.') {
+            Some((time, seq)) => Some((time.parse().ok()?, seq.parse().ok()?)),
+            None => Some((key.parse().ok()?, 0)),
+        }
+    }
+
+    fn is_expired(&self, size: u64, file_time: i64, time: i64) -> bool {
+        self.max_size.is_some_and(|max_size| size >= max_size)
+            || self.max_age.is_some_and(|max_age| {
+                time.saturating_sub(file_time) >= max_age.as_secs().try_into().unwrap_or(i64::MAX)
+            })
+    }
+
+    fn open(&self, time: i64, seq: u64) -> Result<OutputFile, Error> {
+        let path = self.dir.join(if seq == 0 {
+            format!("{}.{}.tsv", self.prefix, time)
+        } else {
+            format!("{}.{}.{}.tsv", self.prefix, time, seq)
+        });
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("open {} failed: {}", path.display(), e))?;
+        let size = file.metadata()?.len();
+        Ok(OutputFile {
+            file: std::io::BufWriter::new(file),
+            size,
+            time,
+            seq,
+        })
+    }
+
+    /// Opens the file following the expired file, which is named with the
+    /// time of the value, or the next sequence number if the time does not
+    /// advance, so that the files keep the order of the values written.
+    fn open_next(&self, expired: &OutputFile, time: i64) -> Result<OutputFile, Error> {
+        if expired.time < time {
+            self.open(time, 0)
+        } else {
+            self.open(expired.time, expired.seq + 1)
+        }
+    }
+
+    fn output_file(&mut self, time: i64) -> Result<&mut OutputFile, Error> {
+        let current = match self.current.take() {
+            Some(current) if !self.is_expired(current.size, current.time, time) => current,
+            Some(mut current) => {
+                current.file.flush()?;
+                self.open_next(&current, time)?
+            }
+            None => match self.files()?.last().and_then(|path| self.file_key(path)) {
+                Some((file_time, seq)) => {
+                    let current = self.open(file_time, seq)?;
+                    if self.is_expired(current.size, current.time, time) {
+                        self.open_next(&current, time)?
+                    } else {
+                        current
+                    }
+                }
+                None => self.open(time, 0)?,
+            },
+        };
+        Ok(self.current.insert(current))
+    }
+}
+
+impl MetricSink for FileSink {
+    fn write_value(&mut self, name: &str, value: f64, time: i64) -> Result<(), Error> {
+        let line = format!("{}\t{}\t{}\n", name, self.formatter.format(value), time);
+        let current = self.output_file(time)?;
+        current.file.write_all(line.as_bytes())?;
+        current.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if let Some(current) = &mut self.current {
+            current.file.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if let Some(current) = &mut self.current {
+            current.file.flush()?;
+            if self.sync {
+                current.file.get_ref().sync_all()?;
+            }
+        }
+        Ok(())
+    }
+}
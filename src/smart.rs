@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Command;
+
+use crate::check::{CheckResult, CheckStatus};
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// The ATA attributes of the remaining life of the SSDs, which are the
+/// normalized values decreasing from 100.
+const WEAR_ATTRIBUTES: [u64; 3] = [177, 231, 233];
+
+/// A collector of the S.M.A.R.T. health of the disks by `smartctl` of
+/// smartmontools 7.0 or later, which reports the attributes in JSON.
+///
+/// The metrics are keyed by the device names; the reallocated and the pending
+/// sectors of the ATA disks, the media errors of the NVMe disks, and the
+/// temperature and the wear (the percentage of the life used) of both. The
+/// disks default to the block devices with the underlying devices in
+/// `/sys/block`, which excludes the loop, RAM and device-mapper devices.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::SmartDisks;
+///
+/// let disks = SmartDisks::new().device("/dev/sda").device("/dev/nvme0n1");
+/// let values = disks.fetch_values().unwrap();
+/// let result = disks.check();
+/// println!("{}", result);
+/// ```
+pub struct SmartDisks {
+    devices: Vec<String>,
+    command: OsString,
+}
+
+impl Default for SmartDisks {
+    fn default() -> SmartDisks {
+        SmartDisks::new()
+    }
+}
+
+impl SmartDisks {
+    pub fn new() -> SmartDisks {
+        SmartDisks {
+            devices: Vec::new(),
+            command: "smartctl".into(),
+        }
+    }
+
+    /// Adds the device to collect the health of, such as `/dev/sda`.
+    pub fn device(mut self, device: impl Into<String>) -> SmartDisks {
+        self.devices.push(device.into());
+        self
+    }
+
+    /// Sets the command of `smartctl`, which is required to be run by root.
+    pub fn command(mut self, command: impl Into<OsString>) -> SmartDisks {
+        self.command = command.into();
+        self
+    }
+
+    /// Returns the graphs of the disks.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "smart.reallocated_sectors",
+                label: "S.M.A.R.T. reallocated sectors",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "smart.pending_sectors",
+                label: "S.M.A.R.T. pending sectors",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "smart.media_errors",
+                label: "S.M.A.R.T. media errors",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "smart.temperature",
+                label: "S.M.A.R.T. temperature (°C)",
+                unit: "float",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "smart.wear",
+                label: "S.M.A.R.T. wear",
+                unit: "percentage",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the disks.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the disks, killing `smartctl` when the
+    /// fetch is cancelled.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        let mut values = HashMap::new();
+        for (disk, report) in self.reports(ctx)? {
+            let mut insert = |name: &str, value: Option<f64>| {
+                if let Some(value) = value {
+                    values.insert(format!("smart.{}.{}", name, disk), value.into());
+                }
+            };
+            let attributes = attributes(&report);
+            let raw = |id: u64| {
+                let attribute = attributes.iter().find(|a| a["id"].as_u64() == Some(id))?;
+                attribute["raw"]["value"].as_f64()
+            };
+            let nvme = &report["nvme_smart_health_information_log"];
+            insert("reallocated_sectors", raw(5));
+            insert("pending_sectors", raw(197));
+            insert("media_errors", nvme["media_errors"].as_f64());
+            insert(
+                "temperature",
+                report["temperature"]["current"]
+                    .as_f64()
+                    .or_else(|| nvme["temperature"].as_f64()),
+            );
+            insert(
+                "wear",
+                nvme["percentage_used"].as_f64().or_else(|| {
+                    WEAR_ATTRIBUTES.iter().find_map(|&id| {
+                        let attribute = attributes.iter().find(|a| a["id"].as_u64() == Some(id))?;
+                        Some(100.0 - attribute["value"].as_f64()?)
+                    })
+                }),
+            );
+        }
+        Ok(values)
+    }
+
+    /// Checks the overall health and the attributes of the disks, which is
+    /// critical if any disk fails the self-assessment, has the attributes
+    /// failing now, or has the critical warnings of NVMe, and warning if any
+    /// disk has the attributes failed in the past.
+    pub fn check(&self) -> CheckResult {
+        let reports = match self.reports(&Context::new()) {
+            Ok(reports) => reports,
+            Err(err) => return CheckResult::new(CheckStatus::Unknown, err.to_string()),
+        };
+        let mut status = CheckStatus::Ok;
+        let mut messages = Vec::new();
+        for (disk, report) in &reports {
+            let mut failed = |s: CheckStatus, message: String| {
+                status = status.max(s);
+                messages.push(format!("{}: {}", disk, message));
+            };
+            if report["smart_status"]["passed"].as_bool() == Some(false) {
+                failed(
+                    CheckStatus::Critical,
+                    "the overall-health self-assessment failed".to_owned(),
+                );
+            }
+            for attribute in attributes(report) {
+                let name = attribute["name"].as_str().unwrap_or_default();
+                match attribute["when_failed"].as_str().unwrap_or_default() {
+                    "" => {}
+                    "past" | "In_the_past" => {
+                        failed(CheckStatus::Warning, format!("{} failed in the past", name))
+                    }
+                    _ => failed(CheckStatus::Critical, format!("{} is failing now", name)),
+                }
+            }
+            let warning = report["nvme_smart_health_information_log"]["critical_warning"]
+                .as_u64()
+                .unwrap_or_default();
+            if warning != 0 {
+                failed(
+                    CheckStatus::Critical,
+                    format!("the critical warning 0x{:02x}", warning),
+                );
+            }
+        }
+        if messages.is_empty() {
+            messages.push(format!("{} disks passed", reports.len()));
+        }
+        CheckResult::new(status, messages.join(", "))
+    }
+
+    /// Returns the reports of `smartctl` keyed by the device names.
+    fn reports(&self, ctx: &Context) -> Result<Vec<(String, serde_json::Value)>, Error> {
+        let devices = if self.devices.is_empty() {
+            block_devices(Path::new("/sys/block"))
+        } else {
+            self.devices.clone()
+        };
+        let mut reports = Vec::new();
+        for device in devices {
+            let output = ctx
+                .output(
+                    Command::new(&self.command)
+                        .args(["--json", "--health", "--attributes"])
+                        .arg(&device),
+                )
+                .map_err(|e| format!("smartctl {} failed: {}", device, e))?;
+            let report: serde_json::Value = serde_json::from_slice(&output.stdout)
+                .map_err(|e| format!("smartctl {} failed: {}", device, e))?;
+            // the lowest two bits of the exit status are the errors of the
+            // command line and the device open, and the other bits are
+            // reported as the health
+            if output.status.code().is_none_or(|code| code & 0b11 != 0) {
+                let message = report["smartctl"]["messages"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|message| message["string"].as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Err(format!("smartctl {} failed: {}", device, message).into());
+            }
+            let name = device.rsplit('/').next().unwrap_or(&device);
+            reports.push((metric_key(name), report));
+        }
+        Ok(reports)
+    }
+}
+
+fn attributes(report: &serde_json::Value) -> &[serde_json::Value] {
+    report["ata_smart_attributes"]["table"]
+        .as_array()
+        .map_or(&[], |table| &table[..])
+}
+
+/// Lists the block devices with the underlying devices.
+fn block_devices(root: &Path) -> Vec<String> {
+    let mut devices = std::fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            entry
+                .path()
+                .join("device")
+                .exists()
+                .then(|| format!("/dev/{}", entry.file_name().to_string_lossy()))
+        })
+        .collect::<Vec<_>>();
+    devices.sort();
+    devices
+}
+
+fn metric_key(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
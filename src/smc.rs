@@ -0,0 +1,199 @@
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::mem::size_of;
+
+type MachPort = u32;
+type KernReturn = c_int;
+
+/// The selector of the method of the SMC user client, and the commands.
+const KERNEL_INDEX_SMC: u32 = 2;
+const SMC_CMD_READ_BYTES: u8 = 5;
+const SMC_CMD_READ_INDEX: u8 = 8;
+const SMC_CMD_READ_KEYINFO: u8 = 9;
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct KeyInfo {
+    data_size: u32,
+    data_type: u32,
+    data_attributes: u8,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct KeyData {
+    key: u32,
+    // the version and the power limits, which are not read
+    vers: [u16; 3],
+    p_limit_data: [u32; 4],
+    key_info: KeyInfo,
+    result: u8,
+    status: u8,
+    data8: u8,
+    data32: u32,
+    bytes: [u8; 32],
+}
+
+const _: () = assert!(size_of::<KeyData>() == 80);
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IOServiceMatching(name: *const c_char) -> *mut c_void;
+    fn IOServiceGetMatchingService(main_port: MachPort, matching: *mut c_void) -> MachPort;
+    fn IOServiceOpen(
+        service: MachPort,
+        owning_task: MachPort,
+        r#type: u32,
+        connect: *mut MachPort,
+    ) -> KernReturn;
+    fn IOServiceClose(connect: MachPort) -> KernReturn;
+    fn IOObjectRelease(object: MachPort) -> KernReturn;
+    fn IOConnectCallStructMethod(
+        connection: MachPort,
+        selector: u32,
+        input: *const c_void,
+        input_size: usize,
+        output: *mut c_void,
+        output_size: *mut usize,
+    ) -> KernReturn;
+}
+
+extern "C" {
+    static mach_task_self_: MachPort;
+}
+
+/// A connection to the System Management Controller by the `AppleSMC` service
+/// of IOKit, whose values are read by the four-character keys.
+pub(crate) struct Smc {
+    connection: MachPort,
+}
+
+impl Smc {
+    pub(crate) fn open() -> Result<Smc, String> {
+        let name = CString::new("AppleSMC").unwrap();
+        // SAFETY: the matching dictionary is consumed by the lookup, and the
+        // service is released after the connection is opened
+        unsafe {
+            let service = IOServiceGetMatchingService(0, IOServiceMatching(name.as_ptr()));
+            if service == 0 {
+                return Err("AppleSMC service not found".to_owned());
+            }
+            let mut connection = 0;
+            let ret = IOServiceOpen(service, mach_task_self_, 0, &mut connection);
+            IOObjectRelease(service);
+            if ret != 0 {
+                return Err(format!("open AppleSMC failed: {:#x}", ret));
+            }
+            Ok(Smc { connection })
+        }
+    }
+
+    /// Returns the keys of the SMC, in the order of the indices.
+    pub(crate) fn keys(&self) -> Result<Vec<[u8; 4]>, String> {
+        let count = match self.read(*b"#KEY")? {
+            (_, bytes) if bytes.len() == 4 => u32::from_be_bytes(bytes.try_into().unwrap()),
+            _ => return Err("read AppleSMC keys failed".to_owned()),
+        };
+        (0..count)
+            .map(|index| {
+                let output = self.call(&KeyData {
+                    data8: SMC_CMD_READ_INDEX,
+                    data32: index,
+                    ..KeyData::default()
+                })?;
+                Ok(output.key.to_be_bytes())
+            })
+            .collect()
+    }
+
+    /// Reads the data type and the bytes of the value of the key.
+    pub(crate) fn read(&self, key: [u8; 4]) -> Result<([u8; 4], Vec<u8>), String> {
+        let key = u32::from_be_bytes(key);
+        let key_info = self
+            .call(&KeyData {
+                key,
+                data8: SMC_CMD_READ_KEYINFO,
+                ..KeyData::default()
+            })?
+            .key_info;
+        let output = self.call(&KeyData {
+            key,
+            key_info,
+            data8: SMC_CMD_READ_BYTES,
+            ..KeyData::default()
+        })?;
+        let size = (key_info.data_size as usize).min(output.bytes.len());
+        Ok((
+            key_info.data_type.to_be_bytes(),
+            output.bytes[..size].to_vec(),
+        ))
+    }
+
+    fn call(&self, input: &KeyData) -> Result<KeyData, String> {
+        let mut output = KeyData::default();
+        let mut output_size = size_of::<KeyData>();
+        // SAFETY: the input and the output are the structures of the size
+        let ret = unsafe {
+            IOConnectCallStructMethod(
+                self.connection,
+                KERNEL_INDEX_SMC,
+                input as *const KeyData as *const c_void,
+                size_of::<KeyData>(),
+                &mut output as *mut KeyData as *mut c_void,
+                &mut output_size,
+            )
+        };
+        if ret != 0 {
+            return Err(format!("call AppleSMC failed: {:#x}", ret));
+        }
+        if output.result != 0 {
+            return Err(format!("call AppleSMC failed: result {}", output.result));
+        }
+        Ok(output)
+    }
+}
+
+impl Drop for Smc {
+    fn drop(&mut self) {
+        // SAFETY: the connection is opened by `Smc::open`
+        unsafe {
+            IOServiceClose(self.connection);
+        }
+    }
+}
+
+/// Decodes the value of the data type, such as `sp78` of the temperatures and
+/// `fpe2` of the fan speeds on the Intel Macs, and `flt ` of those on the
+/// Apple silicon Macs.
+pub(crate) fn decode(data_type: [u8; 4], bytes: &[u8]) -> Option<f64> {
+    match (&data_type, bytes) {
+        (b"sp78", &[x, y]) => Some(i16::from_be_bytes([x, y]) as f64 / 256.0),
+        (b"fpe2", &[x, y]) => Some(u16::from_be_bytes([x, y]) as f64 / 4.0),
+        (b"flt ", &[a, b, c, d]) => Some(f32::from_le_bytes([a, b, c, d]) as f64),
+        (b"ui8 ", &[x]) => Some(x as f64),
+        (b"ui16", &[x, y]) => Some(u16::from_be_bytes([x, y]) as f64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(*b"sp78", &[0x2d, 0x80], Some(45.5))]
+    #[case(*b"sp78", &[0xff, 0x00], Some(-1.0))]
+    #[case(*b"fpe2", &[0x13, 0x88], Some(1250.0))]
+    #[case(*b"flt ", &[0x00, 0x00, 0x36, 0x42], Some(45.5))]
+    #[case(*b"ui8 ", &[0x02], Some(2.0))]
+    #[case(*b"ui16", &[0x04, 0xb0], Some(1200.0))]
+    #[case(*b"sp78", &[0x2d], None)]
+    #[case(*b"flag", &[0x01], None)]
+    fn test_decode(
+        #[case] data_type: [u8; 4],
+        #[case] bytes: &[u8],
+        #[case] expected: Option<f64>,
+    ) {
+        assert_eq!(decode(data_type, bytes), expected);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::aws::{Client, Credentials};
+use crate::error::Error;
+use crate::queue::{QueueBackend, QueueStats};
+
+/// A backend of the queues of Amazon SQS. The depth is the approximate number
+/// of the visible messages, and the in-flight messages are those not visible.
+pub struct Sqs {
+    client: Client,
+    queue_name_prefix: String,
+}
+
+impl Sqs {
+    pub fn new(region: impl Into<String>, credentials: Credentials) -> Sqs {
+        Sqs {
+            client: Client::new(region.into(), "sqs", credentials),
+            queue_name_prefix: String::new(),
+        }
+    }
+
+    /// Creates a client with the region of `AWS_REGION` (or
+    /// `AWS_DEFAULT_REGION`) and the credentials of the environment variables.
+    pub fn from_env() -> Result<Sqs, Error> {
+        Ok(Sqs::new(
+            Client::region_from_env()?,
+            Credentials::from_env()?,
+        ))
+    }
+
+    /// Sets the endpoint of the API.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Sqs {
+        self.client.endpoint = endpoint.into().trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Limits the queues to those whose names start with the prefix.
+    pub fn queue_name_prefix(mut self, prefix: impl Into<String>) -> Sqs {
+        self.queue_name_prefix = prefix.into();
+        self
+    }
+
+    fn queue_urls(&self) -> Result<Vec<String>, Error> {
+        let mut urls = Vec::new();
+        let mut next_token = None;
+        loop {
+            let mut params = vec![
+                ("Action".to_owned(), "ListQueues".to_owned()),
+                ("Version".to_owned(), "2012-11-05".to_owned()),
+                ("MaxResults".to_owned(), "1000".to_owned()),
+            ];
+            if !self.queue_name_prefix.is_empty() {
+                params.push(("QueueNamePrefix".to_owned(), self.queue_name_prefix.clone()));
+            }
+            if let Some(next_token) = next_token.take() {
+                params.push(("NextToken".to_owned(), next_token));
+            }
+            let response = self.client.request("ListQueues", &params)?;
+            let result = response
+                .child("ListQueuesResult")
+                .ok_or("ListQueues failed: invalid response")?;
+            urls.extend(
+                result
+                    .children
+                    .iter()
+                    .filter(|child| child.name == "QueueUrl")
+                    .map(|child| child.text.clone()),
+            );
+            match result.child_text("NextToken") {
+                Some(token) if !token.is_empty() => next_token = Some(token.to_owned()),
+                _ => break,
+            }
+        }
+        Ok(urls)
+    }
+}
+
+impl QueueBackend for Sqs {
+    fn name(&self) -> &str {
+        "sqs"
+    }
+
+    fn queues(&self) -> Result<Vec<QueueStats>, Error> {
+        let mut queues = Vec::new();
+        for url in self.queue_urls()? {
+            let params = [
+                ("Action", "GetQueueAttributes"),
+                ("Version", "2012-11-05"),
+                ("QueueUrl", &url),
+                ("AttributeName.1", "ApproximateNumberOfMessages"),
+                ("AttributeName.2", "ApproximateNumberOfMessagesNotVisible"),
+            ]
+            .map(|(key, value)| (key.to_owned(), value.to_owned()));
+            let response = self.client.request("GetQueueAttributes", &params)?;
+            let attribute = |name: &str| {
+                response
+                    .child("GetQueueAttributesResult")?
+                    .children
+                    .iter()
+                    .find(|attribute| attribute.child_text("Name") == Some(name))?
+                    .child_text("Value")?
+                    .parse::<u64>()
+                    .ok()
+            };
+            let name = url.rsplit('/').next().unwrap_or(&url);
+            let mut queue = QueueStats::new(
+                name,
+                attribute("ApproximateNumberOfMessages").unwrap_or_default(),
+            );
+            if let Some(in_flight) = attribute("ApproximateNumberOfMessagesNotVisible") {
+                queue = queue.in_flight(in_flight);
+            }
+            queues.push(queue);
+        }
+        Ok(queues)
+    }
+}
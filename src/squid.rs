@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::process::Command;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+/// The lines of the hit ratios in `mgr:info` and their metric names.
+const HIT_RATIOS: [(&str, &str); 4] = [
+    ("Hits as % of all requests", "request_ratio"),
+    ("Hits as % of bytes sent", "byte_ratio"),
+    ("Memory hits as % of hit requests", "memory_ratio"),
+    ("Disk hits as % of hit requests", "disk_ratio"),
+];
+
+/// A collector of the statistics of Squid by `squidclient mgr:info`.
+///
+/// The metrics are the client requests and the clients, the hit ratios of the
+/// requests and the bytes in the last 5 minutes reported by Squid, and the
+/// storage sizes.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::SquidStats;
+///
+/// let stats = SquidStats::new().address("localhost", 3128);
+/// let values = stats.fetch_values().unwrap();
+/// ```
+pub struct SquidStats {
+    command: OsString,
+    address: Option<(String, u16)>,
+}
+
+impl Default for SquidStats {
+    fn default() -> SquidStats {
+        SquidStats::new()
+    }
+}
+
+impl SquidStats {
+    pub fn new() -> SquidStats {
+        SquidStats {
+            command: "squidclient".into(),
+            address: None,
+        }
+    }
+
+    /// Sets the command of `squidclient`.
+    pub fn command(mut self, command: impl Into<OsString>) -> SquidStats {
+        self.command = command.into();
+        self
+    }
+
+    /// Sets the host and the port of Squid.
+    pub fn address(mut self, host: impl Into<String>, port: u16) -> SquidStats {
+        self.address = Some((host.into(), port));
+        self
+    }
+
+    /// Returns the graphs of the statistics.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "squid.requests",
+                label: "Squid Client Requests",
+                unit: "integer",
+                metrics: [{ name: "requests", label: "Requests", diff: true }],
+            },
+            crate::graph! {
+                name: "squid.clients",
+                label: "Squid Clients",
+                unit: "integer",
+                metrics: [{ name: "clients", label: "Clients" }],
+            },
+            crate::graph! {
+                name: "squid.cache_hit_ratio.5min",
+                label: "Squid Cache Hit Ratio (5min)",
+                unit: "percentage",
+                metrics: [
+                    { name: "request_ratio", label: "Request" },
+                    { name: "byte_ratio", label: "Byte" },
+                    { name: "memory_ratio", label: "Memory" },
+                    { name: "disk_ratio", label: "Disk" },
+                ],
+            },
+            crate::graph! {
+                name: "squid.storage",
+                label: "Squid Storage",
+                unit: "bytes",
+                metrics: [
+                    { name: "swap_size", label: "Swap" },
+                    { name: "mem_size", label: "Memory" },
+                ],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the statistics.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the statistics, killing `squidclient`
+    /// when the fetch is cancelled.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        let mut command = Command::new(&self.command);
+        if let Some((host, port)) = &self.address {
+            command.arg("-h").arg(host).arg("-p").arg(port.to_string());
+        }
+        let output = ctx
+            .output(command.arg("mgr:info"))
+            .map_err(|err| format!("squidclient mgr:info failed: {}", err))?;
+        if !output.status.success() {
+            return Err(format!(
+                "squidclient mgr:info failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        SquidStats::parse(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Parses the output of `mgr:info`, which may be preceded by the headers
+    /// of the response.
+    ///
+    /// ```text
+    /// Connection information for squid:
+    ///     Number of clients accessing cache:  3
+    ///     Number of HTTP requests received:   1234
+    /// Cache information for squid:
+    ///     Hits as % of all requests:  5min: 20.5%, 60min: 18.5%
+    ///     Storage Mem size:   512 KB
+    /// ```
+    pub fn parse(text: &str) -> Result<HashMap<String, Value>, Error> {
+        let mut values = HashMap::new();
+        let number = |key: &str, value: &str| {
+            value.parse::<f64>().map_err(|_| {
+                format!(
+                    "parse the squid info failed: invalid {}: {:?}",
+                    key.to_lowercase(),
+                    value
+                )
+            })
+        };
+        for line in text.lines() {
+            let Some((key, value)) = line.trim().split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key {
+                "Number of HTTP requests received" => {
+                    values.insert(
+                        "squid.requests.requests".to_owned(),
+                        number(key, value)?.into(),
+                    );
+                }
+                "Number of clients accessing cache" => {
+                    values.insert(
+                        "squid.clients.clients".to_owned(),
+                        number(key, value)?.into(),
+                    );
+                }
+                "Storage Swap size" | "Storage Mem size" => {
+                    let size = value.strip_suffix(" KB").unwrap_or(value);
+                    let name = if key == "Storage Swap size" {
+                        "swap_size"
+                    } else {
+                        "mem_size"
+                    };
+                    values.insert(
+                        format!("squid.storage.{}", name),
+                        (number(key, size)? * 1024.0).into(),
+                    );
+                }
+                _ => {
+                    let Some(&(_, name)) = HIT_RATIOS.iter().find(|&&(line, _)| line == key) else {
+                        continue;
+                    };
+                    // 5min: 20.5%, 60min: 18.5%
+                    let ratio = value
+                        .strip_prefix("5min:")
+                        .and_then(|value| value.split('%').next())
+                        .unwrap_or(value)
+                        .trim();
+                    values.insert(
+                        format!("squid.cache_hit_ratio.5min.{}", name),
+                        number(key, ratio)?.into(),
+                    );
+                }
+            }
+        }
+        if values.is_empty() {
+            return Err(format!("parse the squid info failed: {:?}", text.trim()).into());
+        }
+        Ok(values)
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::{Duration, SystemTime};
+
+/// A policy for the metric values observed by the source long ago.
+///
+/// The values attached the observed time (`Value::Observed`) older than the
+/// maximum age are dropped, or emitted with a warning to the standard error,
+/// which is logged by mackerel-agent.
+///
+/// ```rust
+/// use mackerel_plugin::Staleness;
+/// use std::time::Duration;
+///
+/// let staleness = Staleness::new(Duration::from_secs(300)).warn();
+/// ```
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Staleness {
+    pub max_age: Duration,
+    pub action: StaleAction,
+}
+
+/// An action for the stale metric values.
+#[derive(Default, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum StaleAction {
+    /// Drops the stale values.
+    #[default]
+    Drop,
+    /// Emits the stale values with a warning.
+    Warn,
+}
+
+impl Staleness {
+    pub fn new(max_age: Duration) -> Staleness {
+        Staleness {
+            max_age,
+            action: StaleAction::Drop,
+        }
+    }
+
+    pub fn warn(mut self) -> Staleness {
+        self.action = StaleAction::Warn;
+        self
+    }
+
+    /// Returns the age of the value observed at the time if it is stale.
+    pub(crate) fn stale_age(&self, observed: SystemTime, now: SystemTime) -> Option<Duration> {
+        now.duration_since(observed)
+            .ok()
+            .filter(|&age| age > self.max_age)
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// A trait which represents a store of the plugin state between runs.
+///
+/// The plugin state is used for calculating the difference of metric values.
+pub trait StateStore {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, String>;
+
+    fn save(&self, key: &str, bytes: &[u8]) -> Result<(), String>;
+}
+
+impl<T: StateStore + ?Sized> StateStore for &T {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        (**self).load(key)
+    }
+
+    fn save(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        (**self).save(key, bytes)
+    }
+}
+
+/// A state store which saves the state to the file of the key path.
+///
+/// When the file cannot be written due to a read-only filesystem or the lack
+/// of permission, the state is kept in the process-global memory with a
+/// warning, which is still useful for the plugins running in a loop.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct FileStateStore;
+
+impl StateStore for FileStateStore {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        if let Some(bytes) = fallback_store().load(key)? {
+            return Ok(Some(bytes));
+        }
+        match std::fs::read(key) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("read {} failed: {}", key, e)),
+        }
+    }
+
+    fn save(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        match atomic_write(key, bytes) {
+            Ok(()) => {
+                let mut states = fallback_store().states.lock().map_err(|e| e.to_string())?;
+                states.remove(key);
+                Ok(())
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::ReadOnlyFilesystem | std::io::ErrorKind::PermissionDenied
+                ) =>
+            {
+                warn_fallback(&e.to_string());
+                fallback_store().save(key, bytes)
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Returns the process-global state store used when the files cannot be
+/// written.
+pub(crate) fn fallback_store() -> &'static MemoryStateStore {
+    static STORE: OnceLock<MemoryStateStore> = OnceLock::new();
+    STORE.get_or_init(MemoryStateStore::new)
+}
+
+/// Warns that the state is kept in memory, only once in the process.
+pub(crate) fn warn_fallback(reason: &str) {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        let _ = writeln!(
+            std::io::stderr(),
+            "{}, keeping the plugin state in memory",
+            reason
+        );
+    }
+}
+
+/// A state store which keeps the state in memory.
+#[derive(Default, Debug)]
+pub struct MemoryStateStore {
+    states: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryStateStore {
+    pub fn new() -> MemoryStateStore {
+        MemoryStateStore::default()
+    }
+}
+
+impl StateStore for MemoryStateStore {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        let states = self.states.lock().map_err(|e| e.to_string())?;
+        Ok(states.get(key).cloned())
+    }
+
+    fn save(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        let mut states = self.states.lock().map_err(|e| e.to_string())?;
+        states.insert(key.to_owned(), bytes.to_vec());
+        Ok(())
+    }
+}
+
+/// Returns the working directory of the plugin state, which is specified by
+/// `MACKEREL_PLUGIN_WORKDIR` or defaults to the temporary directory.
+///
+/// The temporary directory respects `TMPDIR`, so the plugins built statically
+/// do not depend on `/tmp` existing in minimal containers.
+pub fn workdir() -> PathBuf {
+    std::env::var_os("MACKEREL_PLUGIN_WORKDIR")
+        .filter(|path| !path.is_empty())
+        .map_or_else(std::env::temp_dir, PathBuf::from)
+}
+
+/// Returns whether a file can be created in the directory, by creating and
+/// removing a probe file.
+pub(crate) fn is_writable(dir: &Path) -> bool {
+    let path = dir.join(format!(".mackerel-plugin-probe.{}", std::process::id()));
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+    {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&path);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+fn atomic_write(path: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let with_context = |e: std::io::Error, context: String| {
+        std::io::Error::new(e.kind(), format!("{} failed: {}", context, e))
+    };
+    let tmp_path = &format!(
+        "{}.{}",
+        path,
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(std::io::Error::other)?
+            .as_secs_f64()
+    );
+    let mut file = std::fs::File::create(tmp_path)
+        .map_err(|e| with_context(e, format!("open {}", tmp_path)))?;
+    file.write_all(bytes)
+        .and_then(|()| file.sync_all())
+        .map_err(|e| {
+            let _ = std::fs::remove_file(tmp_path);
+            with_context(e, format!("write to {}", tmp_path))
+        })?;
+    drop(file);
+    std::fs::rename(tmp_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(tmp_path);
+        with_context(e, format!("rename {} to {}", tmp_path, path))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_writable() {
+        assert!(is_writable(&std::env::temp_dir()));
+        assert!(!is_writable(
+            &std::env::temp_dir().join("mackerel-plugin-not-found")
+        ));
+    }
+}
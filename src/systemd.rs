@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::process::Command;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+const PROPERTIES: &str = "ActiveState,NRestarts,MemoryCurrent,CPUUsageNSec,TasksCurrent";
+
+/// A collector of the status of the systemd units, which runs `systemctl show`
+/// for the configured units.
+///
+/// The metrics are keyed by the unit names without the `.service` suffix;
+/// the state (active or failed), the restarts per minute, and the memory, CPU,
+/// and tasks accounting. The accounting values are omitted when they are not
+/// enabled for the unit.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{Graph, Plugin, SystemdUnits, Value};
+/// use std::collections::HashMap;
+///
+/// struct UnitsPlugin {
+///     units: SystemdUnits,
+/// }
+///
+/// impl Plugin for UnitsPlugin {
+///     fn fetch_values(&self) -> Result<HashMap<String, Value>, String> {
+///         self.units.fetch_values().map_err(|err| err.to_string())
+///     }
+///
+///     fn graph_definition(&self) -> Vec<Graph> {
+///         SystemdUnits::graphs()
+///     }
+/// }
+///
+/// let plugin = UnitsPlugin {
+///     units: SystemdUnits::new(["nginx.service", "mackerel-agent.service"]),
+/// };
+/// ```
+pub struct SystemdUnits {
+    units: Vec<String>,
+    command: OsString,
+}
+
+impl SystemdUnits {
+    pub fn new<I>(units: I) -> SystemdUnits
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        SystemdUnits {
+            units: units.into_iter().map(Into::into).collect(),
+            command: "systemctl".into(),
+        }
+    }
+
+    /// Sets the command of `systemctl`.
+    pub fn command(mut self, command: impl Into<OsString>) -> SystemdUnits {
+        self.command = command.into();
+        self
+    }
+
+    /// Returns the graphs of the systemd units.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "systemd.active",
+                label: "Systemd unit active",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "systemd.failed",
+                label: "Systemd unit failed",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "systemd.restarts",
+                label: "Systemd unit restarts",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1", diff: true }],
+            },
+            crate::graph! {
+                name: "systemd.memory",
+                label: "Systemd unit memory",
+                unit: "bytes",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+            crate::graph! {
+                name: "systemd.cpu",
+                label: "Systemd unit CPU",
+                unit: "percentage",
+                metrics: [{ name: "*", label: "%1", diff: true }],
+            },
+            crate::graph! {
+                name: "systemd.tasks",
+                label: "Systemd unit tasks",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the units.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the units, killing `systemctl` when the
+    /// fetch is cancelled.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        let mut values = HashMap::new();
+        if self.units.is_empty() {
+            return Ok(values);
+        }
+        let output = ctx
+            .output(
+                Command::new(&self.command)
+                    .arg("show")
+                    .arg(format!("--property={}", PROPERTIES))
+                    .arg("--")
+                    .args(&self.units),
+            )
+            .map_err(|e| format!("systemctl show failed: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "systemctl show failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // the properties of the units are separated by empty lines
+        for (unit, block) in self.units.iter().zip(stdout.split("\n\n")) {
+            let properties = block
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .collect::<HashMap<_, _>>();
+            let key = unit_key(unit);
+            let state = properties.get("ActiveState").copied().unwrap_or_default();
+            values.insert(
+                format!("systemd.active.{}", key),
+                (state == "active").into(),
+            );
+            values.insert(
+                format!("systemd.failed.{}", key),
+                (state == "failed").into(),
+            );
+            let mut insert = |name: &str, property: &str, scale: f64| {
+                // the accounting values are [not set] or UINT64_MAX when disabled
+                if let Some(value) = properties
+                    .get(property)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .filter(|&value| value != u64::MAX)
+                {
+                    values.insert(
+                        format!("systemd.{}.{}", name, key),
+                        Value::Float(value as f64 * scale),
+                    );
+                }
+            };
+            insert("restarts", "NRestarts", 1.0);
+            insert("memory", "MemoryCurrent", 1.0);
+            // the CPU nanoseconds differentiated per minute make the percentage
+            insert("cpu", "CPUUsageNSec", 100.0 / 60e9);
+            insert("tasks", "TasksCurrent", 1.0);
+        }
+        Ok(values)
+    }
+}
+
+fn unit_key(unit: &str) -> String {
+    unit.strip_suffix(".service")
+        .unwrap_or(unit)
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,80 @@
+use crate::graph::{is_valid_graph_name, Graph};
+use crate::plugin::join_name;
+
+/// A template of the graph, which is instantiated with the prefix of the name
+/// and the parameters substituted for the placeholders (`{role}`) in the
+/// labels of the graph and the metrics. The braces are escaped by doubling
+/// them (`{{` and `}}`).
+///
+/// This is useful for building many similar graphs of the sharded services.
+///
+/// ```rust
+/// use mackerel_plugin::{graph, GraphTemplate};
+///
+/// let template = GraphTemplate::new(graph! {
+///     name: "connections",
+///     label: "{role} connections",
+///     unit: "integer",
+///     metrics: [{ name: "active", label: "{role} active" }],
+/// });
+/// let graph = template
+///     .instantiate("mysql.shard1", &[("role", "Primary")])
+///     .unwrap();
+/// assert_eq!(graph.name, "mysql.shard1.connections");
+/// assert_eq!(graph.label, "Primary connections");
+/// assert_eq!(graph.metrics[0].label, "Primary active");
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct GraphTemplate {
+    graph: Graph,
+}
+
+impl GraphTemplate {
+    pub fn new(graph: Graph) -> GraphTemplate {
+        GraphTemplate { graph }
+    }
+
+    /// Instantiates the graph with the prefix and the parameters, or returns
+    /// an error if the name is invalid or a placeholder has no parameter.
+    pub fn instantiate(&self, prefix: &str, params: &[(&str, &str)]) -> Result<Graph, String> {
+        let name = join_name(prefix, &self.graph.name);
+        if !is_valid_graph_name(&name) {
+            return Err(format!("invalid graph name: {}", name));
+        }
+        let mut graph = self.graph.clone();
+        graph.name = name;
+        graph.label = substitute(&graph.label, params)?;
+        for metric in &mut graph.metrics {
+            metric.label = substitute(&metric.label, params)?;
+        }
+        Ok(graph)
+    }
+}
+
+fn substitute(text: &str, params: &[(&str, &str)]) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find(['{', '}']) {
+        result.push_str(&rest[..i]);
+        let (c, after) = (&rest[i..i + 1], &rest[i + 1..]);
+        if let Some(after) = after.strip_prefix(c) {
+            result.push_str(c);
+            rest = after;
+        } else if c == "{" {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unclosed placeholder in label: {}", text))?;
+            let key = &after[..end];
+            let &(_, value) = params
+                .iter()
+                .find(|&&(name, _)| name == key)
+                .ok_or_else(|| format!("unknown parameter {{{}}} in label: {}", key, text))?;
+            result.push_str(value);
+            rest = &after[end + 1..];
+        } else {
+            return Err(format!("unmatched brace in label: {}", text));
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
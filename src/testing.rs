@@ -0,0 +1,279 @@
+//! Utilities for testing plugins.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+use crate::error::Error;
+use crate::output::{parse_values, META_HEADER};
+use crate::plugin::Plugin;
+use crate::state::MemoryStateStore;
+
+#[cfg(feature = "proptest")]
+pub mod strategy;
+
+/// A simulator of mackerel-agent, which runs the plugin repeatedly like the
+/// agent does, and returns the parsed outputs.
+///
+/// The simulator drives a plugin in-process with a controlled clock and an
+/// in-memory state store, or executes a compiled plugin binary with a
+/// dedicated working directory.
+///
+/// ```rust
+/// use mackerel_plugin::testing::AgentSimulator;
+/// use mackerel_plugin::{graph, Graph, Plugin};
+/// use std::collections::HashMap;
+///
+/// struct UptimePlugin {}
+///
+/// impl Plugin for UptimePlugin {
+///     fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+///         Ok(HashMap::from([("uptime.seconds".to_owned(), 100.0)]))
+///     }
+///
+///     fn graph_definition(&self) -> Vec<Graph> {
+///         vec![graph! {
+///             name: "uptime",
+///             label: "Uptime",
+///             unit: "seconds",
+///             metrics: [{ name: "seconds", label: "Seconds" }],
+///         }]
+///     }
+/// }
+///
+/// let plugin = UptimePlugin {};
+/// let mut agent = AgentSimulator::new(&plugin);
+/// let values = agent.run_values().unwrap();
+/// assert_eq!(values, vec![("uptime.seconds".to_owned(), 100.0, 1700000000)]);
+/// let values = agent.run_values().unwrap();
+/// assert_eq!(values, vec![("uptime.seconds".to_owned(), 100.0, 1700000060)]);
+/// ```
+pub struct AgentSimulator<'a> {
+    target: Target<'a>,
+    state: MemoryStateStore,
+    time: SystemTime,
+    interval: Duration,
+    workdir: PathBuf,
+    runs: usize,
+}
+
+enum Target<'a> {
+    Plugin(&'a dyn Plugin),
+    Command(PathBuf),
+}
+
+impl<'a> AgentSimulator<'a> {
+    /// Creates a simulator which drives the plugin in-process. The clock starts
+    /// at 1700000000 (2023-11-14T22:13:20Z) and advances by a minute per run.
+    pub fn new(plugin: &'a dyn Plugin) -> AgentSimulator<'a> {
+        AgentSimulator::with_target(Target::Plugin(plugin))
+    }
+
+    /// Creates a simulator which executes the plugin binary. The binary uses
+    /// the system clock, so the interval is used for sleeping between runs.
+    pub fn command(path: impl Into<PathBuf>) -> AgentSimulator<'a> {
+        AgentSimulator::with_target(Target::Command(path.into()))
+    }
+
+    fn with_target(target: Target<'a>) -> AgentSimulator<'a> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        AgentSimulator {
+            target,
+            state: MemoryStateStore::new(),
+            time: std::time::UNIX_EPOCH + Duration::from_secs(1700000000),
+            interval: Duration::from_secs(60),
+            workdir: std::env::temp_dir().join(format!(
+                "mackerel-plugin-simulator-{}-{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            )),
+            runs: 0,
+        }
+    }
+
+    /// Sets the time of the next run.
+    pub fn at(mut self, time: SystemTime) -> AgentSimulator<'a> {
+        self.time = time;
+        self
+    }
+
+    /// Sets the interval between runs.
+    pub fn interval(mut self, interval: Duration) -> AgentSimulator<'a> {
+        self.interval = interval;
+        self
+    }
+
+    /// Runs the plugin for the metric values, and advances the clock.
+    pub fn run_values(&mut self) -> Result<Vec<(String, f64, i64)>, Error> {
+        self.runs += 1;
+        let output = match &self.target {
+            Target::Plugin(plugin) => {
+                let mut out = Vec::new();
+                plugin.output_values_with(&mut out, &self.state, &self.time)?;
+                self.time += self.interval;
+                out
+            }
+            Target::Command(path) => {
+                if self.runs > 1 {
+                    std::thread::sleep(self.interval);
+                }
+                std::fs::create_dir_all(&self.workdir).map_err(|e| e.to_string())?;
+                execute(
+                    std::process::Command::new(path)
+                        .env("MACKEREL_PLUGIN_WORKDIR", &self.workdir)
+                        .env_remove("MACKEREL_AGENT_PLUGIN_META")
+                        .env_remove("MACKEREL_PLUGIN_OUTPUT_FORMAT"),
+                )?
+            }
+        };
+        parse_values(&String::from_utf8_lossy(&output))
+    }
+
+    /// Runs the plugin for the graph definitions.
+    pub fn run_meta(&self) -> Result<serde_json::Value, Error> {
+        let output = match &self.target {
+            Target::Plugin(plugin) => {
+                let mut out = Vec::new();
+                plugin.output_definitions(&mut out)?;
+                out
+            }
+            Target::Command(path) => execute(
+                std::process::Command::new(path)
+                    .env("MACKEREL_PLUGIN_WORKDIR", &self.workdir)
+                    .env("MACKEREL_AGENT_PLUGIN_META", "1"),
+            )?,
+        };
+        let output = String::from_utf8_lossy(&output);
+        let json = output
+            .strip_prefix(META_HEADER)
+            .filter(|rest| rest.starts_with(['\n', '\r']))
+            .ok_or("meta output should start with the header")?;
+        Ok(serde_json::from_str(json).map_err(|e| e.to_string())?)
+    }
+}
+
+impl Drop for AgentSimulator<'_> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.workdir);
+    }
+}
+
+/// Returns the pretty-printed JSON with the keys of objects sorted, which is
+/// stable regardless of the construction order of the graph definitions.
+pub fn canonical_json(value: &serde_json::Value) -> String {
+    fn sort_keys(value: &serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut entries = map.iter().collect::<Vec<_>>();
+                entries.sort_by_key(|&(key, _)| key);
+                serde_json::Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key.clone(), sort_keys(value)))
+                        .collect(),
+                )
+            }
+            serde_json::Value::Array(values) => {
+                serde_json::Value::Array(values.iter().map(sort_keys).collect())
+            }
+            value => value.clone(),
+        }
+    }
+    serde_json::to_string_pretty(&sort_keys(value)).unwrap_or_default() + "\n"
+}
+
+/// Asserts that the content equals to the golden file, showing the line diff
+/// on mismatch. When the environment variable `MACKEREL_PLUGIN_UPDATE_GOLDEN`
+/// is set, the golden file is updated with the content instead.
+#[track_caller]
+pub fn assert_golden(path: impl AsRef<Path>, actual: &str) {
+    let path = path.as_ref();
+    if std::env::var("MACKEREL_PLUGIN_UPDATE_GOLDEN").is_ok_and(|value| !value.is_empty()) {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "read {} failed: {} (set MACKEREL_PLUGIN_UPDATE_GOLDEN=1 to create it)",
+            path.display(),
+            e
+        )
+    });
+    if expected != actual {
+        panic!(
+            "{} does not match (set MACKEREL_PLUGIN_UPDATE_GOLDEN=1 to update it):\n{}",
+            path.display(),
+            line_diff(&expected, actual)
+        );
+    }
+}
+
+/// Asserts that the graph definitions of the plugin equal to the golden file.
+///
+/// ```rust,no_run
+/// # use mackerel_plugin::{Graph, Plugin};
+/// # use std::collections::HashMap;
+/// # struct DicePlugin {}
+/// # impl Plugin for DicePlugin {
+/// #     fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> { unimplemented!() }
+/// #     fn graph_definition(&self) -> Vec<Graph> { unimplemented!() }
+/// # }
+/// use mackerel_plugin::testing::assert_meta_golden;
+///
+/// assert_meta_golden(&DicePlugin {}, "tests/golden/meta.json");
+/// ```
+#[track_caller]
+pub fn assert_meta_golden(plugin: &dyn Plugin, path: impl AsRef<Path>) {
+    let meta = AgentSimulator::new(plugin).run_meta().unwrap();
+    assert_golden(path, &canonical_json(&meta));
+}
+
+fn line_diff(expected: &str, actual: &str) -> String {
+    let (xs, ys) = (
+        expected.lines().collect::<Vec<_>>(),
+        actual.lines().collect::<Vec<_>>(),
+    );
+    let mut lcs = vec![vec![0; ys.len() + 1]; xs.len() + 1];
+    for i in (0..xs.len()).rev() {
+        for j in (0..ys.len()).rev() {
+            lcs[i][j] = if xs[i] == ys[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j, mut diff) = (0, 0, String::new());
+    while i < xs.len() || j < ys.len() {
+        if i < xs.len() && j < ys.len() && xs[i] == ys[j] {
+            diff += &format!("  {}\n", xs[i]);
+            i += 1;
+            j += 1;
+        } else if i < xs.len() && (j == ys.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff += &format!("- {}\n", xs[i]);
+            i += 1;
+        } else {
+            diff += &format!("+ {}\n", ys[j]);
+            j += 1;
+        }
+    }
+    diff
+}
+
+fn execute(command: &mut std::process::Command) -> Result<Vec<u8>, Error> {
+    let output = command
+        .output()
+        .map_err(|e| format!("execute {:?} failed: {}", command.get_program(), e))?;
+    if !output.status.success() {
+        return Err(Error::Other(format!(
+            "{:?} exited with {}: {}",
+            command.get_program(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
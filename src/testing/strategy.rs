@@ -0,0 +1,64 @@
+//! Strategies of [proptest](https://docs.rs/proptest) for fuzzing the naming
+//! schemes of plugins against [`matches_metric`](crate::matches_metric).
+//!
+//! ```rust
+//! use mackerel_plugin::matches_metric;
+//! use mackerel_plugin::testing::strategy::pattern_and_name;
+//! use proptest::prelude::*;
+//!
+//! proptest!(|((pattern, name) in pattern_and_name())| {
+//!     prop_assert!(matches_metric(&pattern, &name));
+//! });
+//! ```
+
+use proptest::prelude::*;
+
+/// A strategy of metric name segments which wildcards match.
+pub fn segment() -> impl Strategy<Value = String> {
+    "[a-zA-Z0-9_-]{1,8}"
+}
+
+/// A strategy of metric name segments which wildcards do not match.
+pub fn invalid_segment() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        "[a-z0-9]{0,4}[^a-zA-Z0-9_.-][a-z0-9]{0,4}",
+    ]
+}
+
+/// A strategy of metric names without wildcards.
+pub fn metric_name() -> impl Strategy<Value = String> {
+    prop::collection::vec(segment(), 1..=5).prop_map(|segments| segments.join("."))
+}
+
+/// A strategy of metric name patterns, which consist of segments and wildcards.
+pub fn pattern() -> impl Strategy<Value = String> {
+    prop::collection::vec(
+        prop_oneof![segment(), Just("*".to_owned()), Just("#".to_owned())],
+        1..=5,
+    )
+    .prop_map(|segments| segments.join("."))
+}
+
+/// A strategy of metric names which match to the pattern.
+pub fn matching_name(pattern: &str) -> impl Strategy<Value = String> {
+    pattern
+        .split('.')
+        .map(|segment| {
+            if segment == "*" || segment == "#" {
+                self::segment().boxed()
+            } else {
+                Just(segment.to_owned()).boxed()
+            }
+        })
+        .collect::<Vec<_>>()
+        .prop_map(|segments| segments.join("."))
+}
+
+/// A strategy of metric name patterns and the names matching to them.
+pub fn pattern_and_name() -> impl Strategy<Value = (String, String)> {
+    pattern().prop_flat_map(|pattern| {
+        let name = matching_name(&pattern);
+        (Just(pattern), name)
+    })
+}
@@ -0,0 +1,108 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use strum::{Display, EnumString};
+
+use crate::plugin::{join_name, Plugin};
+
+/// An advisory threshold of a metric.
+///
+/// The graph definitions of Mackerel do not carry the thresholds, so they are
+/// exported as a separate document for generating the monitors from the same
+/// source as the plugin.
+///
+/// ```rust
+/// use mackerel_plugin::{metric, Threshold};
+///
+/// let metric = metric! {
+///     name: "used",
+///     label: "Used",
+///     threshold: Threshold::above().warning(80.0).critical(90.0),
+/// };
+/// ```
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Threshold {
+    pub operator: Operator,
+    pub warning: Option<f64>,
+    pub critical: Option<f64>,
+}
+
+/// An operator of the threshold, which compares the metric value with the
+/// threshold value.
+#[derive(
+    PartialEq, Eq, Clone, Copy, Debug, Display, EnumString, SerializeDisplay, DeserializeFromStr,
+)]
+pub enum Operator {
+    #[strum(serialize = ">")]
+    Greater,
+    #[strum(serialize = "<")]
+    Less,
+}
+
+impl Threshold {
+    /// Returns a threshold alerting on the values greater than the threshold.
+    pub fn above() -> Threshold {
+        Threshold {
+            operator: Operator::Greater,
+            warning: None,
+            critical: None,
+        }
+    }
+
+    /// Returns a threshold alerting on the values less than the threshold.
+    pub fn below() -> Threshold {
+        Threshold {
+            operator: Operator::Less,
+            warning: None,
+            critical: None,
+        }
+    }
+
+    pub fn warning(mut self, warning: f64) -> Threshold {
+        self.warning = Some(warning);
+        self
+    }
+
+    pub fn critical(mut self, critical: f64) -> Threshold {
+        self.critical = Some(critical);
+        self
+    }
+}
+
+/// A threshold of the metric with the name posted to Mackerel, such as
+/// `custom.dice.d6`.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct MetricThreshold {
+    pub metric: String,
+    #[serde(flatten)]
+    pub threshold: Threshold,
+}
+
+/// Returns the thresholds of the metrics in the graph definitions of the
+/// plugin.
+pub fn metric_thresholds<P: Plugin + ?Sized>(plugin: &P) -> Vec<MetricThreshold> {
+    let prefix = plugin.metric_key_prefix();
+    plugin
+        .graph_definition_ref()
+        .iter()
+        .flat_map(|graph| {
+            let graph_name = join_name(&prefix, &graph.name);
+            graph.metrics.iter().filter_map(move |metric| {
+                Some(MetricThreshold {
+                    metric: "custom.".to_owned() + &join_name(&graph_name, &metric.name),
+                    threshold: metric.threshold?,
+                })
+            })
+        })
+        .collect()
+}
+
+/// Returns the JSON document of the thresholds of the metrics in the graph
+/// definitions of the plugin, which is also output by the plugin with the
+/// environment variable `MACKEREL_PLUGIN_THRESHOLDS`.
+#[cfg(feature = "json")]
+pub fn thresholds_json<P: Plugin + ?Sized>(plugin: &P) -> String {
+    serde_json::to_string_pretty(&serde_json::json!({
+        "thresholds": metric_thresholds(plugin),
+    }))
+    .unwrap_or_default()
+}
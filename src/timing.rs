@@ -0,0 +1,71 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static SECTIONS: Mutex<Vec<(String, Duration)>> = Mutex::new(Vec::new());
+
+/// A guard which measures the duration of a section until it is dropped.
+///
+/// The durations are accumulated by the name in the process during a run of
+/// the plugin, and emitted in the graph of the plugin duration when
+/// `emit_self_metrics` is enabled.
+///
+/// ```rust
+/// use mackerel_plugin::{section_durations, SectionTimer};
+///
+/// {
+///     let _t = SectionTimer::start("db_query");
+///     // query the database
+/// }
+/// assert_eq!(section_durations()[0].0, "db_query");
+/// ```
+#[must_use = "the section is measured until the timer is dropped"]
+pub struct SectionTimer {
+    name: String,
+    started: Instant,
+}
+
+impl SectionTimer {
+    /// Starts measuring the section. The characters which are not allowed in
+    /// the metric names are replaced with `_`.
+    pub fn start(name: &str) -> SectionTimer {
+        SectionTimer {
+            name: name
+                .chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                    _ => '_',
+                })
+                .collect(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for SectionTimer {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        if let Ok(mut sections) = SECTIONS.lock() {
+            match sections.iter_mut().find(|(name, _)| *name == self.name) {
+                Some((_, duration)) => *duration += elapsed,
+                None => sections.push((std::mem::take(&mut self.name), elapsed)),
+            }
+        }
+    }
+}
+
+/// Returns the durations of the sections measured in the current run, in the
+/// order of the first measurement.
+pub fn section_durations() -> Vec<(String, Duration)> {
+    SECTIONS
+        .lock()
+        .map(|sections| sections.clone())
+        .unwrap_or_default()
+}
+
+/// Clears the durations of the sections, and returns them.
+pub(crate) fn take_section_durations() -> Vec<(String, Duration)> {
+    SECTIONS
+        .lock()
+        .map(|mut sections| std::mem::take(&mut *sections))
+        .unwrap_or_default()
+}
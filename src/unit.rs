@@ -2,7 +2,9 @@ use serde_with::{DeserializeFromStr, SerializeDisplay};
 use strum::{Display, EnumString};
 
 /// A metric unit.
-#[derive(PartialEq, Clone, Debug, Display, EnumString, SerializeDisplay, DeserializeFromStr)]
+#[derive(
+    PartialEq, Clone, Copy, Debug, Display, EnumString, SerializeDisplay, DeserializeFromStr,
+)]
 #[strum(serialize_all = "lowercase")]
 pub enum Unit {
     Float,
@@ -18,6 +20,105 @@ pub enum Unit {
     IOPS,
 }
 
+impl Unit {
+    /// Returns the unit of the name, which is used to validate the literal
+    /// units of the [`graph!`](crate::graph!) and [`metric!`](crate::metric!)
+    /// macros at compile time.
+    #[doc(hidden)]
+    pub const fn from_name(name: &str) -> Option<Unit> {
+        const UNITS: [(&str, Unit); 9] = [
+            ("float", Unit::Float),
+            ("integer", Unit::Integer),
+            ("percentage", Unit::Percentage),
+            ("seconds", Unit::Seconds),
+            ("milliseconds", Unit::Milliseconds),
+            ("bytes", Unit::Bytes),
+            ("bytes/sec", Unit::BytesPerSec),
+            ("bits/sec", Unit::BitsPerSec),
+            ("iops", Unit::IOPS),
+        ];
+        let mut i = 0;
+        while i < UNITS.len() {
+            if eq_bytes(UNITS[i].0.as_bytes(), name.as_bytes()) {
+                return Some(UNITS[i].1);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Returns the recommended number of the decimal places of the values of
+    /// the unit, which the plugins opt in by `Plugin::precision`. The
+    /// percentages are rounded to 2 decimal places, and the values of the
+    /// other units are output as they are.
+    pub fn recommended_precision(&self) -> Option<u32> {
+        match self {
+            Unit::Percentage => Some(2),
+            _ => None,
+        }
+    }
+}
+
+/// Converts the unit in [`graph!`](crate::graph!) and
+/// [`metric!`](crate::metric!), where the strings are parsed at runtime.
+#[doc(hidden)]
+pub trait IntoUnit {
+    fn into_unit(self) -> Unit;
+}
+
+impl IntoUnit for Unit {
+    fn into_unit(self) -> Unit {
+        self
+    }
+}
+
+impl IntoUnit for &str {
+    fn into_unit(self) -> Unit {
+        self.parse()
+            .unwrap_or_else(|_| panic!("invalid unit: {}", self))
+    }
+}
+
+impl IntoUnit for &String {
+    fn into_unit(self) -> Unit {
+        self.as_str().into_unit()
+    }
+}
+
+impl IntoUnit for String {
+    fn into_unit(self) -> Unit {
+        self.as_str().into_unit()
+    }
+}
+
+const fn eq_bytes(xs: &[u8], ys: &[u8]) -> bool {
+    if xs.len() != ys.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < xs.len() {
+        if xs[i] != ys[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Rounds the value to the decimal places, where the ties are rounded to the
+/// even digit (`0.125` to `0.12`, `0.375` to `0.38`). The ties are decided on
+/// the value scaled in the floating-point arithmetic, which may differ from
+/// the decimal arithmetic in the last place. The values too large to have the
+/// decimal places are returned as they are.
+pub(crate) fn round_half_even(value: f64, precision: u32) -> f64 {
+    let scale = 10f64.powi(precision.min(15) as i32);
+    let scaled = value * scale;
+    if !scaled.is_finite() || scaled.abs() >= (1u64 << 52) as f64 {
+        return value;
+    }
+    scaled.round_ties_even() / scale
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -36,7 +137,28 @@ mod tests {
     fn test_unit(#[case] unit: Unit, #[case] unit_str: &str) {
         assert_eq!(unit.to_string(), unit_str);
         assert_eq!(unit, unit_str.parse().unwrap());
+        assert_eq!(Some(unit), Unit::from_name(unit_str));
         assert_eq!(unit, serde_json::from_value(unit_str.into()).unwrap());
         assert_eq!(serde_json::to_value(unit).unwrap(), unit_str);
     }
+
+    #[test]
+    fn test_unit_from_name() {
+        assert_eq!(Unit::from_name("liters"), None);
+        assert_eq!(Unit::from_name("Bytes"), None);
+    }
+
+    #[rstest]
+    #[case(12.345678, 2, 12.35)]
+    #[case(0.125, 2, 0.12)]
+    #[case(0.375, 2, 0.38)]
+    #[case(2.5, 0, 2.0)]
+    #[case(3.5, 0, 4.0)]
+    #[case(-2.5, 0, -2.0)]
+    #[case(2.665, 2, 2.66)]
+    #[case(1e300, 2, 1e300)]
+    #[case(33.333333333333336, 1, 33.3)]
+    fn test_round_half_even(#[case] value: f64, #[case] precision: u32, #[case] expected: f64) {
+        assert_eq!(round_half_even(value, precision), expected);
+    }
 }
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::value::Value;
+
+const WORKER_STATUSES: [(&str, &str); 5] = [
+    ("busy", "Busy"),
+    ("idle", "Idle"),
+    ("cheap", "Cheap"),
+    ("pause", "Pause"),
+    ("sig", "Signal"),
+];
+
+/// A collector of the stats server of uWSGI, which reads the JSON of the
+/// address of the `stats` option.
+///
+/// The metrics are the workers by the status, the requests, the exceptions,
+/// and the memory of the workers, the listen queue, and the requests of each
+/// worker. The metric keys are grouped by the name of the instance, so the
+/// plugins of many instances, such as the vassals of the emperor, merge the
+/// values of the collectors, which share the graphs.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::UwsgiStats;
+///
+/// let stats = UwsgiStats::new("app", "127.0.0.1:1717");
+/// let values = stats.fetch_values().unwrap();
+/// ```
+pub struct UwsgiStats {
+    name: String,
+    addr: String,
+    timeout: Duration,
+}
+
+impl UwsgiStats {
+    /// Creates a collector of the address, which is the host and the port,
+    /// or the path of the UNIX domain socket on Unix.
+    pub fn new(name: &str, addr: impl Into<String>) -> UwsgiStats {
+        UwsgiStats {
+            name: metric_key(name),
+            addr: addr.into(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> UwsgiStats {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the graphs of the stats.
+    pub fn graphs() -> Vec<Graph> {
+        vec![
+            crate::graph! {
+                name: "uwsgi.workers.#",
+                label: "uWSGI Workers",
+                unit: "integer",
+                metrics: WORKER_STATUSES.iter().map(|&(name, label)| {
+                    crate::metric! { name: name, label: label, stacked: true }
+                }),
+            },
+            crate::graph! {
+                name: "uwsgi.requests.#",
+                label: "uWSGI Requests",
+                unit: "integer",
+                metrics: [
+                    { name: "requests", label: "Requests", diff: true },
+                    { name: "exceptions", label: "Exceptions", diff: true },
+                ],
+            },
+            crate::graph! {
+                name: "uwsgi.queue.#",
+                label: "uWSGI Listen Queue",
+                unit: "integer",
+                metrics: [
+                    { name: "listen_queue", label: "Listen Queue" },
+                    { name: "listen_queue_errors", label: "Listen Queue Errors", diff: true },
+                ],
+            },
+            crate::graph! {
+                name: "uwsgi.memory.#",
+                label: "uWSGI Memory",
+                unit: "bytes",
+                metrics: [
+                    { name: "rss", label: "RSS" },
+                    { name: "vsz", label: "VSZ" },
+                ],
+            },
+            crate::graph! {
+                name: "uwsgi.worker_requests.#",
+                label: "uWSGI Requests by worker",
+                unit: "integer",
+                metrics: [{ name: "*", label: "Worker %2", diff: true }],
+            },
+        ]
+    }
+
+    /// Returns the metric values of the stats.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        let text = self
+            .read()
+            .map_err(|err| format!("uWSGI stats {} failed: {}", self.addr, err))?;
+        self.parse(&text)
+    }
+
+    fn read(&self) -> Result<String, std::io::Error> {
+        let mut text = String::new();
+        #[cfg(unix)]
+        if self.addr.contains('/') {
+            let mut stream = std::os::unix::net::UnixStream::connect(&self.addr)?;
+            stream.set_read_timeout(Some(self.timeout))?;
+            stream.read_to_string(&mut text)?;
+            return Ok(text);
+        }
+        let addr =
+            self.addr.to_socket_addrs()?.next().ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::NotFound, "cannot resolve")
+            })?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    /// Parses the JSON of the stats server.
+    pub fn parse(&self, text: &str) -> Result<HashMap<String, Value>, Error> {
+        let stats: serde_json::Value = serde_json::from_str(text)
+            .map_err(|err| format!("parse the uWSGI stats failed: {}", err))?;
+        let workers = stats["workers"]
+            .as_array()
+            .ok_or("parse the uWSGI stats failed: no workers found")?;
+        let mut values = HashMap::new();
+        let mut insert = |graph: &str, name: &str, value: f64| {
+            values.insert(
+                format!("uwsgi.{}.{}.{}", graph, self.name, name),
+                value.into(),
+            );
+        };
+        let mut statuses = WORKER_STATUSES
+            .iter()
+            .map(|&(status, _)| (status, 0.0))
+            .collect::<HashMap<_, _>>();
+        let (mut requests, mut exceptions, mut rss, mut vsz) = (0.0, 0.0, 0.0, 0.0);
+        for worker in workers {
+            let status = worker["status"].as_str().unwrap_or_default();
+            // the status of a worker being signaled is sig0, sig1, and so on
+            let status = if status.starts_with("sig") {
+                "sig"
+            } else {
+                status
+            };
+            if let Some(count) = statuses.get_mut(status) {
+                *count += 1.0;
+            }
+            let worker_requests = worker["requests"].as_f64().unwrap_or_default();
+            if let Some(id) = worker["id"].as_u64() {
+                insert("worker_requests", &id.to_string(), worker_requests);
+            }
+            requests += worker_requests;
+            exceptions += worker["exceptions"].as_f64().unwrap_or_default();
+            rss += worker["rss"].as_f64().unwrap_or_default();
+            vsz += worker["vsz"].as_f64().unwrap_or_default();
+        }
+        for (status, count) in statuses {
+            insert("workers", status, count);
+        }
+        insert("requests", "requests", requests);
+        insert("requests", "exceptions", exceptions);
+        insert("memory", "rss", rss);
+        insert("memory", "vsz", vsz);
+        for name in ["listen_queue", "listen_queue_errors"] {
+            if let Some(value) = stats[name].as_f64() {
+                insert("queue", name, value);
+            }
+        }
+        Ok(values)
+    }
+}
+
+fn metric_key(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,92 @@
+use serde_derive::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// A metric value fetched by the plugin.
+///
+/// The values are normalized to `f64` on output; booleans are mapped to 0 or 1,
+/// and durations are mapped to seconds. The integers are converted to the
+/// floats, and the counters are made explicitly by [`Value::counter32`] and
+/// [`Value::counter`], which are handled specially on calculating the
+/// difference; a 32-bit counter which decreased by more than the half of the
+/// range is considered to have wrapped around, while the other counters which
+/// decreased are considered to have been reset.
+///
+/// A value served by a source which caches the statistics can be attached the
+/// time the source observed it, so that the stale values are detected by the
+/// staleness policy of the plugin.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Value {
+    Float(f64),
+    Counter32(u32),
+    Counter(u64),
+    Bool(bool),
+    Duration(Duration),
+    Observed(f64, SystemTime),
+}
+
+impl Value {
+    /// Returns the value of the 32-bit counter, which is considered to have
+    /// wrapped around when it decreased.
+    pub fn counter32(value: u32) -> Value {
+        Value::Counter32(value)
+    }
+
+    /// Returns the value of the 64-bit counter, which is considered to have
+    /// been reset when it decreased.
+    pub fn counter(value: u64) -> Value {
+        Value::Counter(value)
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            Value::Float(value) => value,
+            Value::Counter32(value) => value as f64,
+            Value::Counter(value) => value as f64,
+            Value::Bool(value) => value as u8 as f64,
+            Value::Duration(value) => value.as_secs_f64(),
+            Value::Observed(value, _) => value,
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Value {
+        Value::Float(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Value {
+        Value::Float(value as f64)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Value {
+        Value::Float(value as f64)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Value {
+        Value::Float(value as f64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Value {
+        Value::Bool(value)
+    }
+}
+
+impl From<Duration> for Value {
+    fn from(value: Duration) -> Value {
+        Value::Duration(value)
+    }
+}
+
+impl From<(f64, SystemTime)> for Value {
+    fn from((value, time): (f64, SystemTime)) -> Value {
+        Value::Observed(value, time)
+    }
+}
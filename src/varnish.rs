@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::process::Command;
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::unit::Unit;
+use crate::value::Value;
+
+// (graph, label, unit, [(metric, label, counter)])
+type GraphSpec = (
+    &'static str,
+    &'static str,
+    Unit,
+    &'static [(&'static str, &'static str, &'static str)],
+);
+
+const GRAPHS: [GraphSpec; 3] = [
+    (
+        "requests",
+        "Varnish Client Requests",
+        Unit::Integer,
+        &[
+            ("requests", "Requests", "MAIN.client_req"),
+            ("cache_hits", "Hits", "MAIN.cache_hit"),
+            ("cache_misses", "Misses", "MAIN.cache_miss"),
+        ],
+    ),
+    (
+        "backend",
+        "Varnish Backend",
+        Unit::Integer,
+        &[
+            ("backend_req", "Requests", "MAIN.backend_req"),
+            ("backend_conn", "Connections", "MAIN.backend_conn"),
+            ("backend_fail", "Failures", "MAIN.backend_fail"),
+        ],
+    ),
+    (
+        "transfer",
+        "Varnish Transfer",
+        Unit::Bytes,
+        &[
+            ("header_bytes", "Header", "MAIN.s_resp_hdrbytes"),
+            ("body_bytes", "Body", "MAIN.s_resp_bodybytes"),
+        ],
+    ),
+];
+
+/// A collector of the statistics of Varnish Cache by `varnishstat -j`.
+///
+/// The metrics are the client requests, the cache hits and misses, the
+/// backend requests, and the bytes of the responses, which are counters to be
+/// differentiated. The hit ratio of the interval is derived from the hits and
+/// the misses of the previous run in the context, so it is emitted from the
+/// second run.
+///
+/// ```rust,no_run
+/// use mackerel_plugin::{Context, VarnishStats};
+///
+/// let stats = VarnishStats::new();
+/// let values = stats.fetch_values_ctx(&Context::from_env()).unwrap();
+/// ```
+pub struct VarnishStats {
+    command: OsString,
+    instance: Option<String>,
+}
+
+impl Default for VarnishStats {
+    fn default() -> VarnishStats {
+        VarnishStats::new()
+    }
+}
+
+impl VarnishStats {
+    pub fn new() -> VarnishStats {
+        VarnishStats {
+            command: "varnishstat".into(),
+            instance: None,
+        }
+    }
+
+    /// Sets the command of `varnishstat`.
+    pub fn command(mut self, command: impl Into<OsString>) -> VarnishStats {
+        self.command = command.into();
+        self
+    }
+
+    /// Sets the instance name of varnishd (`-n`).
+    pub fn instance(mut self, instance: impl Into<String>) -> VarnishStats {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Returns the graphs of the statistics.
+    pub fn graphs() -> Vec<Graph> {
+        let mut graphs = GRAPHS
+            .iter()
+            .map(|&(name, label, unit, metrics)| {
+                crate::graph! {
+                    name: &format!("varnish.{}", name),
+                    label: label,
+                    unit: unit,
+                    metrics: metrics.iter().map(|&(name, label, _)| {
+                        crate::metric! { name: name, label: label, diff: true }
+                    }),
+                }
+            })
+            .collect::<Vec<_>>();
+        graphs.push(crate::graph! {
+            name: "varnish.hit_ratio",
+            label: "Varnish Cache Hit Ratio",
+            unit: "percentage",
+            metrics: [{ name: "hit_ratio", label: "Hit ratio" }],
+        });
+        graphs
+    }
+
+    /// Returns the metric values of the statistics.
+    pub fn fetch_values(&self) -> Result<HashMap<String, Value>, Error> {
+        self.fetch_values_ctx(&Context::new())
+    }
+
+    /// Returns the metric values of the statistics with the hit ratio since
+    /// the previous run, killing `varnishstat` when the fetch is cancelled.
+    pub fn fetch_values_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, Error> {
+        let mut command = Command::new(&self.command);
+        command.arg("-j");
+        if let Some(instance) = &self.instance {
+            command.arg("-n").arg(instance);
+        }
+        let output = ctx
+            .output(&mut command)
+            .map_err(|err| format!("varnishstat -j failed: {}", err))?;
+        if !output.status.success() {
+            return Err(format!(
+                "varnishstat -j failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        let mut values = VarnishStats::parse(&String::from_utf8_lossy(&output.stdout))?;
+        if let Some(ratio) = hit_ratio(ctx, &values) {
+            values.insert("varnish.hit_ratio.hit_ratio".to_owned(), ratio.into());
+        }
+        Ok(values)
+    }
+
+    /// Parses the JSON of `varnishstat -j`, where the counters are in
+    /// `counters` since Varnish 6.5, or at the top level before.
+    pub fn parse(text: &str) -> Result<HashMap<String, Value>, Error> {
+        let stats: serde_json::Value = serde_json::from_str(text)
+            .map_err(|err| format!("parse the varnishstat output failed: {}", err))?;
+        let counters = stats.get("counters").unwrap_or(&stats);
+        let mut values = HashMap::new();
+        for (graph, _, _, metrics) in GRAPHS {
+            for (name, _, counter) in metrics {
+                if let Some(value) = counters[counter]["value"].as_u64() {
+                    values.insert(format!("varnish.{}.{}", graph, name), Value::counter(value));
+                }
+            }
+        }
+        if values.is_empty() {
+            return Err("parse the varnishstat output failed: no counters found".into());
+        }
+        Ok(values)
+    }
+}
+
+/// Returns the percentage of the hits in the requests looked up since the
+/// previous run, or `None` on the first run or after the restart.
+fn hit_ratio(ctx: &Context, values: &HashMap<String, Value>) -> Option<f64> {
+    let delta = |name: &str| {
+        let value = values.get(name)?.as_f64() - ctx.previous_value(name)?;
+        Some(value).filter(|&value| value >= 0.0)
+    };
+    let hits = delta("varnish.requests.cache_hits")?;
+    let misses = delta("varnish.requests.cache_misses")?;
+    Some(hits * 100.0 / (hits + misses)).filter(|ratio| ratio.is_finite())
+}
@@ -0,0 +1,114 @@
+/// Returns whether the metric name matches to the pattern of the metric in the
+/// graph definition, in the same way as the values are collected for output.
+///
+/// The pattern and the name are split by dots, and each segment of the pattern
+/// should be equal to the corresponding segment of the name. A wildcard
+/// segment (`*` or `#`) matches a non-empty segment consisting of alphanumeric
+/// characters, hyphens, and underscores, and does not match across dots.
+///
+/// ```rust
+/// use mackerel_plugin::matches_metric;
+///
+/// assert!(matches_metric("disk.*.read", "disk.sda.read"));
+/// assert!(matches_metric("disk.#.read", "disk.nvme0n1.read"));
+/// assert!(!matches_metric("disk.*.read", "disk.sda.sda1.read"));
+/// assert!(!matches_metric("disk.*.read", "disk..read"));
+/// assert!(!matches_metric("disk.*", "disk.sda:1"));
+/// ```
+pub fn matches_metric(pattern: &str, name: &str) -> bool {
+    capture(pattern, name).is_some()
+}
+
+/// Escapes the characters which a wildcard segment does not match, including
+/// dots, with underscores, so that the name can be used as a segment of the
+/// metric name, such as the names of the queues containing dots.
+///
+/// ```rust
+/// use mackerel_plugin::{escape_segment, matches_metric};
+///
+/// assert_eq!(escape_segment("jobs.high:1"), "jobs_high_1");
+/// assert!(matches_metric("queue.*.depth", &format!("queue.{}.depth", escape_segment("jobs.high"))));
+/// ```
+pub fn escape_segment(segment: &str) -> String {
+    segment.replace(|c: char| !is_segment_char(c), "_")
+}
+
+/// Escapes the characters which a wildcard segment does not match in each
+/// segment of the metric name with the replacement, keeping the dots between
+/// the segments.
+pub(crate) fn escape_name(name: &str, replacement: char) -> String {
+    name.replace(
+        |c: char| c != '.' && !is_segment_char(c),
+        &replacement.to_string(),
+    )
+}
+
+/// Escapes the metric name to match the pattern, where a wildcard segment of
+/// the pattern takes one or more segments of the name, and the dots and the
+/// other characters which a wildcard segment does not match are replaced with
+/// the replacement. Returns `None` if the name cannot match the pattern.
+pub(crate) fn escape_to_pattern(pattern: &str, name: &str, replacement: char) -> Option<String> {
+    fn escape(
+        patterns: &[&str],
+        segments: &[&str],
+        replacement: char,
+        escaped: &mut Vec<String>,
+    ) -> bool {
+        let Some((&pattern, patterns)) = patterns.split_first() else {
+            return segments.is_empty();
+        };
+        if pattern != "*" && pattern != "#" {
+            if segments.first() != Some(&pattern) {
+                return false;
+            }
+            escaped.push(pattern.to_owned());
+            if escape(patterns, &segments[1..], replacement, escaped) {
+                return true;
+            }
+            escaped.pop();
+            return false;
+        }
+        // the wildcard segment takes the segments as few as possible
+        for n in 1..=segments.len().saturating_sub(patterns.len()) {
+            let segment = segments[..n].join(".");
+            if segment.is_empty() {
+                return false;
+            }
+            escaped.push(segment.replace(|c: char| !is_segment_char(c), &replacement.to_string()));
+            if escape(patterns, &segments[n..], replacement, escaped) {
+                return true;
+            }
+            escaped.pop();
+        }
+        false
+    }
+    let (patterns, segments) = (
+        pattern.split('.').collect::<Vec<_>>(),
+        name.split('.').collect::<Vec<_>>(),
+    );
+    let mut escaped = Vec::new();
+    escape(&patterns, &segments, replacement, &mut escaped).then(|| escaped.join("."))
+}
+
+pub(crate) fn is_segment_char(c: char) -> bool {
+    matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_')
+}
+
+/// Captures the values of wildcard segments (`*` and `#`) of the metric name
+/// matching to the pattern, or returns `None` if the name does not match.
+pub(crate) fn capture<'a>(pattern: &str, name: &'a str) -> Option<Vec<&'a str>> {
+    let mut values = Vec::new();
+    let mut segments = name.split('.');
+    for cs in pattern.split('.') {
+        let ds = segments.next()?;
+        if cs == "*" || cs == "#" {
+            if ds.is_empty() || !ds.chars().all(is_segment_char) {
+                return None;
+            }
+            values.push(ds);
+        } else if cs != ds {
+            return None;
+        }
+    }
+    segments.next().is_none().then_some(values)
+}
@@ -0,0 +1,109 @@
+#![cfg(all(unix, feature = "json"))]
+
+use std::io::Cursor;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+use mackerel_plugin::{graph, Aggregator, Context, Error};
+
+fn fake_plugin(name: &str, script: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "mackerel-plugin-aggregator-test-{}-{}.sh",
+        name,
+        std::process::id()
+    ));
+    std::fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+fn plugin_script(graph: &str, value: u32) -> String {
+    format!(
+        r#"if [ -n "$MACKEREL_AGENT_PLUGIN_META" ]; then
+  echo '# mackerel-agent-plugin'
+  echo '{{"graphs":{{"{graph}":{{"label":"Foo","unit":"integer","metrics":[{{"name":"bar","label":"Bar"}}]}}}}}}'
+else
+  printf '{graph}.bar\t{value}\t1700000000\n'
+fi
+"#
+    )
+}
+
+#[test]
+fn aggregator_output_values() {
+    let foo = fake_plugin("values-foo", &plugin_script("foo", 1));
+    let bar = fake_plugin("values-bar", &plugin_script("foo", 2));
+    let fail = fake_plugin("values-fail", "echo 'connection refused' >&2\nexit 1\n");
+    let aggregator = Aggregator::new()
+        .command([&foo])
+        .command([&fail])
+        .command_with_prefix([&bar], "shop")
+        .command([&bar]);
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(aggregator.output_values(&Context::new(), &mut out), Ok(()));
+    assert_eq!(
+        String::from_utf8(out.into_inner()).unwrap(),
+        "foo.bar\t1\t1700000000\nshop.foo.bar\t2\t1700000000\nfoo.bar\t2\t1700000000\n"
+    );
+
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(
+        aggregator.dedup().output_values(&Context::new(), &mut out),
+        Ok(())
+    );
+    assert_eq!(
+        String::from_utf8(out.into_inner()).unwrap(),
+        "foo.bar\t1\t1700000000\nshop.foo.bar\t2\t1700000000\n"
+    );
+
+    assert_eq!(
+        Aggregator::new()
+            .command([&fail])
+            .fetch_values(&Context::new()),
+        Err(Error::Other(format!(
+            "{} failed: exit status: 1: connection refused",
+            fail.display()
+        )))
+    );
+    for path in [foo, bar, fail] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn aggregator_fetch_definitions() {
+    let foo = fake_plugin("meta-foo", &plugin_script("foo", 1));
+    let broken = fake_plugin("meta-broken", "echo '{}'\n");
+    let aggregator = Aggregator::new()
+        .command([&foo])
+        .command([&broken])
+        .command_with_prefix([&foo], "shop")
+        .command([&foo]);
+    let graph = graph! {
+        name: "foo",
+        label: "Foo",
+        unit: "integer",
+        metrics: [{ name: "bar", label: "Bar" }]
+    };
+    assert_eq!(
+        aggregator.fetch_definitions(&Context::new()),
+        Ok(vec![
+            graph.clone(),
+            mackerel_plugin::Graph {
+                name: "shop.foo".to_owned(),
+                ..graph
+            },
+        ])
+    );
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(
+        aggregator.output_definitions(&Context::new(), &mut out),
+        Ok(())
+    );
+    assert!(String::from_utf8(out.into_inner())
+        .unwrap()
+        .starts_with("# mackerel-agent-plugin\n{\"graphs\":{\"foo\":"));
+    for path in [foo, broken] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
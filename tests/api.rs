@@ -0,0 +1,298 @@
+#![cfg(feature = "api")]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+use mackerel_plugin::{
+    graph, Client, Graph, MemoryStateStore, MetricSink, Plugin, Replay, ReplayStats, ResourceSink,
+    ServiceSink, TsvSink,
+};
+
+/// Serializes the tests setting the environment variables.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+struct Request {
+    path: String,
+    api_key: String,
+    body: serde_json::Value,
+}
+
+fn mock_server(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<Request>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for (status, response) in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let path = line.split(' ').nth(1).unwrap().to_owned();
+            let (mut api_key, mut length) = (String::new(), 0);
+            loop {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                match line.trim_end().split_once(": ") {
+                    Some((key, value)) if key.eq_ignore_ascii_case("x-api-key") => {
+                        api_key = value.to_owned()
+                    }
+                    Some((key, value)) if key.eq_ignore_ascii_case("content-length") => {
+                        length = value.parse().unwrap()
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {} Status\r\nRetry-After: 0\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response.len(),
+                response
+            )
+            .unwrap();
+            let body = serde_json::from_slice(&body).unwrap_or_default();
+            tx.send(Request {
+                path,
+                api_key,
+                body,
+            })
+            .unwrap();
+        }
+    });
+    (url, rx)
+}
+
+#[test]
+fn client_post_host_metrics() {
+    let (url, rx) = mock_server(vec![(429, ""), (200, "")]);
+    let client = Client::new("apikey").base_url(url);
+    assert_eq!(
+        client.post_host_metrics("host", &[("custom.dice.d6".to_owned(), 3.0, 1700000000)]),
+        Ok(())
+    );
+    for _ in 0..2 {
+        let request = rx.recv().unwrap();
+        assert_eq!(request.path, "/api/v0/tsdb");
+        assert_eq!(request.api_key, "apikey");
+        assert_eq!(
+            request.body,
+            serde_json::json!([
+                { "hostId": "host", "name": "custom.dice.d6", "time": 1700000000, "value": 3.0 }
+            ])
+        );
+    }
+}
+
+#[test]
+fn client_post_error() {
+    let (url, _rx) = mock_server(vec![(400, "")]);
+    let client = Client::new("apikey").base_url(url);
+    assert_eq!(
+        client.post_host_metrics("host", &[]),
+        Err("POST /api/v0/tsdb failed: 400 ".into())
+    );
+}
+
+fn replay_files(name: &str) -> Vec<PathBuf> {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-replay-{}-{}",
+        std::process::id(),
+        name
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let files = vec![
+        dir.join("mackerel-plugin-dice.1700000000.tsv"),
+        dir.join("mackerel-plugin-dice.1700086400.tsv"),
+    ];
+    std::fs::write(
+        &files[0],
+        "dice.d6\t3\t1700000000\ndice.d6\t4\t1700086340\ndice.d6\t5\t1700086400\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &files[1],
+        "dice.d6\t6\t1700086460\ninvalid\ndice.d6\t1\t1700086520\n",
+    )
+    .unwrap();
+    files
+}
+
+#[test]
+fn replay_run() {
+    let files = replay_files("run");
+    let (url, rx) = mock_server(vec![(200, ""), (500, ""), (500, ""), (500, ""), (500, "")]);
+    let client = Client::new("apikey").base_url(url);
+    let state = MemoryStateStore::new();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700086560);
+    let replay = Replay::new(&client, "host", &state, "replay")
+        .batch_size(2)
+        .interval(Duration::ZERO)
+        .clock(&now);
+    assert_eq!(
+        replay.run(&files),
+        Err("POST /api/v0/tsdb failed: 500 ".into())
+    );
+    let times = |request: Request| {
+        request
+            .body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value["time"].as_i64().unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(times(rx.recv().unwrap()), vec![1700086340, 1700086400]);
+    for _ in 0..4 {
+        assert_eq!(times(rx.recv().unwrap()), vec![1700086460, 1700086520]);
+    }
+
+    let (url, rx) = mock_server(vec![(200, "")]);
+    let client = Client::new("apikey").base_url(url);
+    let replay = Replay::new(&client, "host", &state, "replay")
+        .batch_size(2)
+        .interval(Duration::ZERO)
+        .clock(&now);
+    assert_eq!(
+        replay.run(&files),
+        Ok(ReplayStats {
+            posted: 2,
+            skipped: 1
+        })
+    );
+    assert_eq!(times(rx.recv().unwrap()), vec![1700086460, 1700086520]);
+    assert_eq!(replay.run(&files), Ok(ReplayStats::default()));
+    std::fs::remove_dir_all(files[0].parent().unwrap()).unwrap();
+}
+
+struct QueuePlugin {}
+
+impl Plugin for QueuePlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([("queue.depth.total".to_owned(), 42.0)]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "queue.depth",
+            label: "Queue depth",
+            unit: "integer",
+            metrics: [
+                { name: "total", label: "Total" },
+            ]
+        }]
+    }
+
+    fn service_name(&self) -> Option<String> {
+        Some("queue".to_owned())
+    }
+}
+
+#[test]
+fn service_sink() {
+    let (url, rx) = mock_server(vec![(200, "")]);
+    let client = Client::new("apikey").base_url(url);
+    let plugin = QueuePlugin {};
+    let mut sink = ServiceSink::new(&client, plugin.service_name().unwrap()).unwrap();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.output_values_to(&mut sink, &MemoryStateStore::new(), &now),
+        Ok(())
+    );
+    let request = rx.recv().unwrap();
+    assert_eq!(request.path, "/api/v0/services/queue/tsdb");
+    assert_eq!(
+        request.body,
+        serde_json::json!([{ "name": "queue.depth.total", "time": 1700000000, "value": 42.0 }])
+    );
+    assert_eq!(
+        sink.write_value("queue.depth.*", 1.0, 1700000000),
+        Err("invalid service metric name: queue.depth.*".into())
+    );
+}
+
+#[test]
+fn service_plugin_run_with() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (url, rx) = mock_server(vec![(200, "")]);
+    std::env::set_var("MACKEREL_APIKEY", "apikey");
+    std::env::set_var("MACKEREL_APIBASE", url);
+    let mut out = Vec::new();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let result = QueuePlugin {}.run_with(&mut out, MemoryStateStore::new(), now);
+    std::env::remove_var("MACKEREL_APIKEY");
+    std::env::remove_var("MACKEREL_APIBASE");
+    assert_eq!(result, Ok(()));
+    assert!(out.is_empty());
+    let request = rx.recv().unwrap();
+    assert_eq!(request.path, "/api/v0/services/queue/tsdb");
+    assert_eq!(request.api_key, "apikey");
+    assert_eq!(
+        request.body,
+        serde_json::json!([{ "name": "queue.depth.total", "time": 1700000000, "value": 42.0 }])
+    );
+}
+
+#[test]
+fn service_plugin_run_with_meta() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    std::env::set_var("MACKEREL_AGENT_PLUGIN_META", "1");
+    let mut out = Vec::new();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let result = QueuePlugin {}.run_with(&mut out, MemoryStateStore::new(), now);
+    std::env::remove_var("MACKEREL_AGENT_PLUGIN_META");
+    assert_eq!(result, Ok(()));
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("# mackerel-agent-plugin\n"));
+    assert!(out.contains(r#""queue.depth":"#));
+}
+
+#[test]
+fn service_sink_invalid_name() {
+    let client = Client::new("apikey");
+    assert!(ServiceSink::new(&client, "q").is_err());
+    assert!(ServiceSink::new(&client, "queue/depth").is_err());
+}
+
+#[test]
+fn resource_sink() {
+    let (url, rx) = mock_server(vec![
+        (200, r#"{"hosts":[{"id":"host1"}]}"#),
+        (200, ""),
+        (200, r#"{"hosts":[]}"#),
+    ]);
+    let client = Client::new("apikey").base_url(url);
+    let mut out = Vec::new();
+    let mut sink = ResourceSink::new(TsvSink::new(&mut out), |name: &str| {
+        name.strip_prefix("lb.requests.")
+            .map(|id| format!("lb-{}", id))
+    })
+    .client(&client);
+    for (name, value) in [
+        ("lb.requests.a", 1.0),
+        ("lb.total", 3.0),
+        ("lb.requests.b", 2.0),
+    ] {
+        assert_eq!(sink.write_value(name, value, 1700000000), Ok(()));
+    }
+    assert_eq!(sink.groups().len(), 2);
+    assert_eq!(sink.finish(), Err("host of lb-b not found".into()));
+    drop(sink);
+    assert_eq!(String::from_utf8(out).unwrap(), "lb.total\t3\t1700000000\n");
+    let request = rx.recv().unwrap();
+    assert_eq!(request.path, "/api/v0/hosts?customIdentifier=lb-a");
+    let request = rx.recv().unwrap();
+    assert_eq!(
+        request.body,
+        serde_json::json!([
+            { "hostId": "host1", "name": "custom.lb.requests.a", "time": 1700000000, "value": 1.0 }
+        ])
+    );
+    let request = rx.recv().unwrap();
+    assert_eq!(request.path, "/api/v0/hosts?customIdentifier=lb-b");
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use mackerel_plugin::{BpfMap, Error, PerCpu, Value};
+
+/// The map of the bytes and packets by the interface index on each CPU.
+struct TrafficMap(Vec<(u32, Vec<(u64, u64)>)>);
+
+impl BpfMap for TrafficMap {
+    type Key = u32;
+    type Value = PerCpu<(u64, u64)>;
+
+    fn entries(&self) -> Result<Vec<(u32, PerCpu<(u64, u64)>)>, Error> {
+        if self.0.is_empty() {
+            return Err("lookup the map failed".into());
+        }
+        Ok(self
+            .0
+            .iter()
+            .map(|(key, values)| (*key, values.clone().into()))
+            .collect())
+    }
+}
+
+#[test]
+fn bpf_map_metrics() {
+    let map = TrafficMap(vec![
+        (1, vec![(100, 1), (200, 2)]),
+        (2, vec![(0, 0), (50, 1)]),
+        (3, vec![(0, 0), (0, 0)]),
+    ]);
+    let metrics = map
+        .metrics(|ifindex, values| {
+            let (bytes, _) =
+                values.fold((0, 0), |(b, p), (bytes, packets)| (b + bytes, p + packets));
+            (bytes > 0).then(|| {
+                (
+                    format!("traffic.bytes.if{}", ifindex),
+                    Value::counter(bytes),
+                )
+            })
+        })
+        .unwrap();
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics.get("traffic.bytes.if1"), Some(&Value::Counter(300)));
+    assert_eq!(metrics.get("traffic.bytes.if2"), Some(&Value::Counter(50)));
+
+    let err = TrafficMap(Vec::new())
+        .metrics(|_, _| Some((String::new(), 0.0)))
+        .unwrap_err();
+    assert_eq!(err.to_string(), "lookup the map failed");
+}
+
+#[test]
+fn bpf_hash_map() {
+    let map = HashMap::from([
+        ("read", PerCpu::new(vec![3_u64, 4])),
+        ("write", vec![1, 0].into()),
+    ]);
+    let metrics = map
+        .metrics(|name, counts| Some((format!("syscall.{}", name), counts.max().unwrap())))
+        .unwrap();
+    assert_eq!(metrics.get("syscall.read"), Some(&Value::Float(4.0)));
+    assert_eq!(metrics.get("syscall.write"), Some(&Value::Float(1.0)));
+    assert_eq!(map["read"].sum(), 7);
+    assert_eq!(map["read"].get(1), Some(&4));
+    assert_eq!(map["write"].iter().count(), 2);
+}
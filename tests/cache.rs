@@ -0,0 +1,63 @@
+#![cfg(feature = "json")]
+
+use std::cell::Cell;
+use std::time::{Duration, UNIX_EPOCH};
+
+use mackerel_plugin::{Cache, MemoryStateStore};
+
+#[test]
+fn cache_get_or_refresh() {
+    let state = MemoryStateStore::new();
+    let count = Cell::new(0);
+    let refresh = || {
+        count.set(count.get() + 1);
+        Ok(vec![format!("db{}", count.get())])
+    };
+    let now = UNIX_EPOCH + Duration::from_secs(1700000000);
+    let ttl = Duration::from_secs(600);
+    let cache = Cache::new(&state, "cache").clock(&now);
+    assert_eq!(
+        cache.get_or_refresh("databases", ttl, refresh),
+        Ok(vec!["db1".to_owned()])
+    );
+    assert_eq!(
+        cache.get_or_refresh("databases", ttl, refresh),
+        Ok(vec!["db1".to_owned()])
+    );
+
+    let now = now + Duration::from_secs(599);
+    let cache = Cache::new(&state, "cache").clock(&now);
+    assert_eq!(
+        cache.get_or_refresh("databases", ttl, refresh),
+        Ok(vec!["db1".to_owned()])
+    );
+
+    let now = now + Duration::from_secs(1);
+    let cache = Cache::new(&state, "cache").clock(&now);
+    assert_eq!(
+        cache.get_or_refresh("databases", ttl, refresh),
+        Ok(vec!["db2".to_owned()])
+    );
+    assert_eq!(cache.invalidate("databases"), Ok(()));
+    assert_eq!(
+        cache.get_or_refresh("databases", ttl, refresh),
+        Ok(vec!["db3".to_owned()])
+    );
+    assert_eq!(count.get(), 3);
+}
+
+#[test]
+fn cache_refresh_error() {
+    let state = MemoryStateStore::new();
+    let cache = Cache::new(&state, "cache");
+    assert_eq!(
+        cache.get_or_refresh::<Vec<String>>("databases", Duration::from_secs(600), || Err(
+            "connection refused".to_owned()
+        )),
+        Err("connection refused".into())
+    );
+    assert_eq!(
+        cache.get_or_refresh("databases", Duration::from_secs(600), || Ok(1)),
+        Ok(1)
+    );
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "tls")]
+
+use std::io::Read;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+use mackerel_plugin::{CertificateExpiry, CheckStatus, Value};
+
+const CERTIFICATE: &str = "tests/testdata/localhost.pem";
+const KEY: &str = "tests/testdata/localhost.key";
+const NOT_AFTER: u64 = 2107527244; // 2036-10-13T16:14:04Z
+
+fn tls_server() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(
+        vec![CertificateDer::from_pem_file(CERTIFICATE).unwrap()],
+        PrivateKeyDer::from_pem_file(KEY).unwrap(),
+    )
+    .unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut connection = rustls::ServerConnection::new(Arc::new(config)).unwrap();
+        while connection.is_handshaking() {
+            connection.complete_io(&mut stream).unwrap();
+        }
+        let _ = connection.complete_io(&mut stream);
+        let _ = connection.reader().read_to_end(&mut Vec::new());
+    });
+    address
+}
+
+#[test]
+fn certificate_expiry_file() {
+    let expiry = CertificateExpiry::file("localhost", CERTIFICATE);
+    assert_eq!(
+        expiry.not_after(),
+        Ok(UNIX_EPOCH + Duration::from_secs(NOT_AFTER))
+    );
+    assert_eq!(
+        expiry.days_remaining(UNIX_EPOCH + Duration::from_secs(NOT_AFTER - 86400 * 3 / 2)),
+        Ok(1.5)
+    );
+    let values = expiry.fetch_values().unwrap();
+    assert!(matches!(
+        values["certificate.expiry.localhost"],
+        Value::Float(days) if days > 0.0
+    ));
+    assert_eq!(expiry.check(30.0, 7.0).status, CheckStatus::Ok);
+    assert_eq!(expiry.check(365.0 * 20.0, 7.0).status, CheckStatus::Warning);
+    assert_eq!(
+        expiry.check(365.0 * 30.0, 365.0 * 20.0).status,
+        CheckStatus::Critical
+    );
+}
+
+#[test]
+fn certificate_expiry_file_error() {
+    let result = CertificateExpiry::file("localhost", KEY).check(30.0, 7.0);
+    assert_eq!(result.status, CheckStatus::Unknown);
+    assert_eq!(
+        result.to_string(),
+        format!("UNKNOWN: certificate not found in {}", KEY)
+    );
+}
+
+#[test]
+fn certificate_expiry_connect() {
+    let address = tls_server();
+    let expiry = CertificateExpiry::connect("localhost", address).server_name("localhost");
+    assert_eq!(
+        expiry.not_after(),
+        Ok(UNIX_EPOCH + Duration::from_secs(NOT_AFTER))
+    );
+}
+
+#[test]
+fn certificate_expiry_connect_error() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let result = CertificateExpiry::connect("localhost", &address[..]).check(30.0, 7.0);
+    assert_eq!(result.status, CheckStatus::Unknown);
+    assert!(result
+        .message
+        .starts_with(&format!("connect to {} failed: ", address)));
+}
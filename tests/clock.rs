@@ -0,0 +1,81 @@
+use rstest::rstest;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mackerel_plugin::{graph, Epoch, Graph, MemoryStateStore, Plugin};
+
+struct DicePlugin {}
+
+impl Plugin for DicePlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([("dice.d6".to_owned(), 3.0)]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "dice",
+            label: "My Dice",
+            unit: "integer",
+            metrics: [{ name: "d6", label: "Die 6" }],
+        }]
+    }
+}
+
+#[rstest]
+#[case(UNIX_EPOCH + Duration::from_secs(1700000000), 1700000000)]
+#[case(UNIX_EPOCH + Duration::from_millis(1700000000999), 1700000000)]
+#[case(UNIX_EPOCH, 0)]
+#[case(UNIX_EPOCH - Duration::from_millis(500), -1)]
+#[case(UNIX_EPOCH - Duration::from_secs(60), -60)]
+fn epoch_from_system_time(#[case] time: SystemTime, #[case] expected: i64) {
+    let epoch = Epoch::from(time);
+    assert_eq!(epoch.as_secs(), expected);
+    assert_eq!(Epoch::from(SystemTime::from(epoch)), epoch);
+}
+
+#[rstest]
+#[case(1700000000, 1699999980)]
+#[case(1699999980, 1699999980)]
+#[case(-1, -60)]
+fn epoch_floor(#[case] secs: i64, #[case] expected: i64) {
+    assert_eq!(Epoch::new(secs).floor(60), Epoch::new(expected));
+}
+
+#[test]
+fn epoch_clock() {
+    let mut out = Vec::new();
+    let now = Epoch::new(1700000000);
+    assert_eq!(
+        DicePlugin {}.run_with(&mut out, MemoryStateStore::new(), now),
+        Ok(())
+    );
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        format!("dice.d6\t3\t{}\n", now)
+    );
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn epoch_chrono() {
+    use chrono::{DateTime, Utc};
+
+    let time = DateTime::parse_from_rfc3339("2023-11-15T07:13:20.5+09:00").unwrap();
+    let epoch = Epoch::from(time);
+    assert_eq!(epoch, Epoch::new(1700000000));
+    assert_eq!(
+        DateTime::<Utc>::try_from(epoch).unwrap(),
+        time.with_timezone(&Utc) - chrono::Duration::milliseconds(500)
+    );
+    assert_eq!(
+        DateTime::<Utc>::try_from(Epoch::new(i64::MAX)),
+        Err(format!("timestamp out of range: {}", i64::MAX))
+    );
+
+    let mut out = Vec::new();
+    assert_eq!(
+        DicePlugin {}.run_with(&mut out, MemoryStateStore::new(), time),
+        Ok(())
+    );
+    assert_eq!(String::from_utf8(out).unwrap(), "dice.d6\t3\t1700000000\n");
+}
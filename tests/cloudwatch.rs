@@ -0,0 +1,220 @@
+#![cfg(feature = "cloudwatch")]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mackerel_plugin::{CloudWatch, Credentials, MetricQuery, Value};
+
+struct Request {
+    authorization: String,
+    body: String,
+}
+
+fn mock_server(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<Request>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for (status, response) in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let (mut authorization, mut length) = (String::new(), 0);
+            loop {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                match line.trim_end().split_once(": ") {
+                    Some((key, value)) if key.eq_ignore_ascii_case("authorization") => {
+                        authorization = value.to_owned()
+                    }
+                    Some((key, value)) if key.eq_ignore_ascii_case("content-length") => {
+                        length = value.parse().unwrap()
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {} Status\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response.len(),
+                response
+            )
+            .unwrap();
+            tx.send(Request {
+                authorization,
+                body: String::from_utf8(body).unwrap(),
+            })
+            .unwrap();
+        }
+    });
+    (url, rx)
+}
+
+fn cloudwatch(url: String) -> CloudWatch {
+    let credentials = Credentials {
+        access_key_id: "AKIDEXAMPLE".to_owned(),
+        secret_access_key: "secret".to_owned(),
+        session_token: None,
+    };
+    CloudWatch::new("ap-northeast-1", credentials).endpoint(url)
+}
+
+fn queries() -> Vec<MetricQuery> {
+    vec![
+        MetricQuery::new("rds.cpu.db1", "AWS/RDS", "CPUUtilization")
+            .dimension("DBInstanceIdentifier", "db1"),
+        MetricQuery::new("sqs.messages.jobs", "AWS/SQS", "NumberOfMessagesSent")
+            .dimension("QueueName", "jobs")
+            .stat("Sum")
+            .period(300),
+    ]
+}
+
+#[test]
+fn cloudwatch_get_metric_data() {
+    let (url, rx) = mock_server(vec![
+        (
+            200,
+            r#"<GetMetricDataResponse xmlns="http://monitoring.amazonaws.com/doc/2010-08-01/">
+  <GetMetricDataResult>
+    <MetricDataResults>
+      <member>
+        <Id>m0</Id>
+        <Timestamps>
+          <member>2023-11-14T22:13:00Z</member>
+          <member>2023-11-14T22:12:00Z</member>
+        </Timestamps>
+        <Values>
+          <member>12.5</member>
+          <member>10.0</member>
+        </Values>
+        <StatusCode>PartialData</StatusCode>
+      </member>
+    </MetricDataResults>
+    <NextToken>token/1</NextToken>
+  </GetMetricDataResult>
+</GetMetricDataResponse>"#,
+        ),
+        (
+            200,
+            r#"<GetMetricDataResponse xmlns="http://monitoring.amazonaws.com/doc/2010-08-01/">
+  <GetMetricDataResult>
+    <MetricDataResults>
+      <member>
+        <Id>m0</Id>
+        <Timestamps><member>2023-11-14T22:11:00Z</member></Timestamps>
+        <Values><member>8.0</member></Values>
+      </member>
+      <member>
+        <Id>m1</Id>
+        <Timestamps/>
+        <Values/>
+      </member>
+    </MetricDataResults>
+  </GetMetricDataResult>
+</GetMetricDataResponse>"#,
+        ),
+    ]);
+    let time = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+    assert_eq!(
+        cloudwatch(url).get_metric_data(&queries(), time(1699999400), time(1700000000)),
+        Ok(HashMap::from([
+            (
+                "rds.cpu.db1".to_owned(),
+                vec![
+                    (time(1699999980), 12.5),
+                    (time(1699999920), 10.0),
+                    (time(1699999860), 8.0)
+                ]
+            ),
+            ("sqs.messages.jobs".to_owned(), vec![]),
+        ]))
+    );
+    let request = rx.recv().unwrap();
+    assert!(request
+        .authorization
+        .starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20"));
+    assert!(request
+        .authorization
+        .contains("/ap-northeast-1/monitoring/aws4_request, "));
+    assert_eq!(
+        request.body.split('&').collect::<Vec<_>>(),
+        vec![
+            "Action=GetMetricData",
+            "Version=2010-08-01",
+            "StartTime=2023-11-14T22%3A03%3A20Z",
+            "EndTime=2023-11-14T22%3A13%3A20Z",
+            "ScanBy=TimestampDescending",
+            "MetricDataQueries.member.1.Id=m0",
+            "MetricDataQueries.member.1.MetricStat.Metric.Namespace=AWS%2FRDS",
+            "MetricDataQueries.member.1.MetricStat.Metric.MetricName=CPUUtilization",
+            "MetricDataQueries.member.1.MetricStat.Metric.Dimensions.member.1.Name=DBInstanceIdentifier",
+            "MetricDataQueries.member.1.MetricStat.Metric.Dimensions.member.1.Value=db1",
+            "MetricDataQueries.member.1.MetricStat.Period=60",
+            "MetricDataQueries.member.1.MetricStat.Stat=Average",
+            "MetricDataQueries.member.2.Id=m1",
+            "MetricDataQueries.member.2.MetricStat.Metric.Namespace=AWS%2FSQS",
+            "MetricDataQueries.member.2.MetricStat.Metric.MetricName=NumberOfMessagesSent",
+            "MetricDataQueries.member.2.MetricStat.Metric.Dimensions.member.1.Name=QueueName",
+            "MetricDataQueries.member.2.MetricStat.Metric.Dimensions.member.1.Value=jobs",
+            "MetricDataQueries.member.2.MetricStat.Period=300",
+            "MetricDataQueries.member.2.MetricStat.Stat=Sum",
+        ]
+    );
+    let request = rx.recv().unwrap();
+    assert!(request.body.ends_with("&NextToken=token%2F1"));
+}
+
+#[test]
+fn cloudwatch_fetch_values() {
+    let (url, _rx) = mock_server(vec![(
+        200,
+        r#"<GetMetricDataResponse>
+  <GetMetricDataResult>
+    <MetricDataResults>
+      <member>
+        <Id>m1</Id>
+        <Timestamps>
+          <member>2023-11-14T22:10:00Z</member>
+          <member>2023-11-14T22:05:00Z</member>
+        </Timestamps>
+        <Values>
+          <member>42</member>
+          <member>40</member>
+        </Values>
+      </member>
+    </MetricDataResults>
+  </GetMetricDataResult>
+</GetMetricDataResponse>"#,
+    )]);
+    assert_eq!(
+        cloudwatch(url).fetch_values(&queries()),
+        Ok(HashMap::from([(
+            "sqs.messages.jobs".to_owned(),
+            Value::Observed(42.0, UNIX_EPOCH + Duration::from_secs(1699999800))
+        )]))
+    );
+}
+
+#[test]
+fn cloudwatch_error() {
+    let (url, _rx) = mock_server(vec![(
+        400,
+        r#"<ErrorResponse>
+  <Error><Type>Sender</Type><Code>InvalidParameterValue</Code></Error>
+</ErrorResponse>"#,
+    )]);
+    assert_eq!(
+        cloudwatch(url)
+            .get_metric_data(&queries(), SystemTime::now(), SystemTime::now())
+            .map_err(|err| err.to_string()),
+        Err("GetMetricData failed: 400 InvalidParameterValue".to_owned())
+    );
+}
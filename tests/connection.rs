@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use mackerel_plugin::{Connections, Context, Value};
+
+fn fake_proc(name: &str, tcp6: bool) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-connection-test.{}.{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("net")).unwrap();
+    let header = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n";
+    std::fs::write(
+        dir.join("net/tcp"),
+        header.to_owned()
+            + "   0: 00000000:0050 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 100 1\n"
+            + "   1: 0100007F:0050 0100007F:C350 01 00000000:00000000 00:00000000 00000000     0        0 101 1\n"
+            + "   2: 0100007F:C350 0100007F:0050 01 00000000:00000000 00:00000000 00000000     0        0 102 1\n"
+            + "   3: 0100007F:0050 0100007F:C351 06 00000000:00000000 00:00000000 00000000     0        0 0 1\n",
+    )
+    .unwrap();
+    if tcp6 {
+        std::fs::write(
+            dir.join("net/tcp6"),
+            header.to_owned()
+                + "   0: 00000000000000000000000000000000:01BB 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 200 1\n"
+                + "   1: 0000000000000000FFFF00000100007F:01BB 0000000000000000FFFF00000100007F:D000 08 00000000:00000000 00:00000000 00000000     0        0 201 1\n",
+        )
+        .unwrap();
+    }
+    dir
+}
+
+fn states(counts: &[(&str, f64)]) -> HashMap<String, f64> {
+    [
+        "established",
+        "syn_sent",
+        "syn_recv",
+        "fin_wait1",
+        "fin_wait2",
+        "time_wait",
+        "close",
+        "close_wait",
+        "last_ack",
+        "listen",
+        "closing",
+    ]
+    .iter()
+    .map(|&state| {
+        let count = counts
+            .iter()
+            .find(|(name, _)| *name == state)
+            .map_or(0.0, |(_, count)| *count);
+        (state.to_owned(), count)
+    })
+    .collect()
+}
+
+#[test]
+fn connections_fetch_values() {
+    let dir = fake_proc("values", true);
+    let values = Connections::new()
+        .port(80)
+        .port(443)
+        .port(8080)
+        .proc_dir(&dir)
+        .fetch_values()
+        .unwrap();
+    let expected = [
+        (
+            "tcp.state",
+            states(&[
+                ("established", 2.0),
+                ("time_wait", 1.0),
+                ("close_wait", 1.0),
+                ("listen", 2.0),
+            ]),
+        ),
+        (
+            "tcp.port.80",
+            states(&[("established", 1.0), ("time_wait", 1.0), ("listen", 1.0)]),
+        ),
+        (
+            "tcp.port.443",
+            states(&[("close_wait", 1.0), ("listen", 1.0)]),
+        ),
+        ("tcp.port.8080", states(&[])),
+    ]
+    .into_iter()
+    .flat_map(|(prefix, states)| {
+        states
+            .into_iter()
+            .map(move |(state, count)| (format!("{}.{}", prefix, state), Value::Float(count)))
+    })
+    .collect::<HashMap<_, _>>();
+    assert_eq!(values, expected);
+}
+
+#[test]
+fn connections_fetch_values_without_tcp6() {
+    let dir = fake_proc("without_tcp6", false);
+    let values = Connections::new().proc_dir(&dir).fetch_values().unwrap();
+    assert_eq!(values["tcp.state.listen"], Value::Float(1.0));
+    assert_eq!(values.len(), 11);
+}
+
+#[cfg(unix)]
+#[test]
+fn connections_fetch_values_netstat() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let command = std::env::temp_dir().join(format!(
+        "mackerel-plugin-netstat-test-{}.sh",
+        std::process::id()
+    ));
+    std::fs::write(
+        &command,
+        "#!/bin/sh\n[ \"$*\" = \"-an -p tcp\" ] || exit 1\ncat <<'EOF'\n\
+         Active Internet connections (including servers)\n\
+         Proto Recv-Q Send-Q  Local Address          Foreign Address        (state)\n\
+         tcp4       0      0  127.0.0.1.80           127.0.0.1.50000        ESTABLISHED\n\
+         tcp4       0      0  127.0.0.1.50000        127.0.0.1.80           ESTABLISHED\n\
+         tcp4       0      0  127.0.0.1.80           127.0.0.1.50001        TIME_WAIT\n\
+         tcp6       0      0  ::1.443                ::1.50002              CLOSE_WAIT\n\
+         tcp46      0      0  *.80                   *.*                    LISTEN\n\
+         tcp6       0      0  fe80::1%lo0.443        *.*                    LISTEN\n\
+         EOF\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let values = Connections::new()
+        .port(80)
+        .port(443)
+        .netstat(&command)
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values["tcp.state.established"], Value::Float(2.0));
+    assert_eq!(values["tcp.state.listen"], Value::Float(2.0));
+    assert_eq!(values["tcp.port.80.time_wait"], Value::Float(1.0));
+    assert_eq!(values["tcp.port.80.listen"], Value::Float(1.0));
+    assert_eq!(values["tcp.port.443.close_wait"], Value::Float(1.0));
+    assert_eq!(values["tcp.port.443.listen"], Value::Float(1.0));
+    assert_eq!(values.len(), 33);
+
+    let command = std::env::temp_dir().join("mackerel-plugin-netstat-test.none");
+    assert!(Connections::new().netstat(&command).fetch_values().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn connections_fetch_values_ctx() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, Instant};
+
+    let command = std::env::temp_dir().join(format!(
+        "mackerel-plugin-netstat-test-ctx-{}.sh",
+        std::process::id()
+    ));
+    std::fs::write(
+        &command,
+        "#!/bin/sh
+exec sleep 10
+",
+    )
+    .unwrap();
+    std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let started = Instant::now();
+    let ctx = Context::new().with_deadline(started + Duration::from_millis(100));
+    assert_eq!(
+        Connections::new()
+            .netstat(&command)
+            .fetch_values_ctx(&ctx)
+            .map_err(|err| err.to_string()),
+        Err("netstat -an -p tcp failed: fetch deadline exceeded".to_owned())
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn connections_fetch_values_error() {
+    let dir = std::env::temp_dir().join("mackerel-plugin-connection-test.none");
+    assert!(Connections::new().proc_dir(&dir).fetch_values().is_err());
+}
+
+#[test]
+fn connections_graphs() {
+    let graphs = Connections::graphs();
+    assert_eq!(
+        graphs
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec!["tcp.state", "tcp.port.#"]
+    );
+    assert_eq!(graphs[0].metrics.len(), 11);
+}
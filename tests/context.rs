@@ -0,0 +1,135 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use mackerel_plugin::{CancellationToken, Context};
+
+#[test]
+fn context_option() {
+    let ctx = Context::new().with_args([
+        "-host",
+        "db1",
+        "--port=3306",
+        "--verbose",
+        "-hostname",
+        "ignored",
+        "--host",
+        "db2",
+        "-tempfile",
+    ]);
+    assert_eq!(ctx.option("host"), Some("db2"));
+    assert_eq!(ctx.option("port"), Some("3306"));
+    assert_eq!(ctx.option("user"), None);
+    assert_eq!(ctx.option("tempfile"), None);
+    assert!(ctx.flag("verbose"));
+    assert!(ctx.flag("tempfile"));
+    assert!(!ctx.flag("user"));
+    assert!(!ctx.flag("port"));
+    assert_eq!(ctx.args().len(), 9);
+}
+
+#[test]
+fn context_env() {
+    let ctx = Context::new().with_env("MACKEREL_PLUGIN_INCLUDE", "sd*");
+    assert_eq!(ctx.env("MACKEREL_PLUGIN_INCLUDE"), Some("sd*"));
+    assert_eq!(ctx.env("MACKEREL_PLUGIN_EXCLUDE"), None);
+}
+
+#[test]
+fn context_deadline() {
+    let ctx = Context::new();
+    assert_eq!(ctx.deadline(), None);
+    assert_eq!(ctx.remaining(), None);
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let ctx = Context::new().with_deadline(deadline);
+    assert_eq!(ctx.deadline(), Some(deadline));
+    assert!(ctx.remaining().unwrap() > Duration::from_secs(50));
+    let ctx = Context::new().with_deadline(Instant::now());
+    assert_eq!(ctx.remaining(), Some(Duration::ZERO));
+}
+
+#[test]
+fn context_log() {
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let ctx = Context::new().with_logger({
+        let messages = messages.clone();
+        move |message| messages.lock().unwrap().push(message.to_owned())
+    });
+    ctx.log("fetching the values");
+    ctx.log("done");
+    assert_eq!(*messages.lock().unwrap(), ["fetching the values", "done"]);
+}
+
+#[test]
+fn context_cancellation() {
+    let token = CancellationToken::new();
+    let ctx = Context::new().with_cancellation(token.clone());
+    assert!(!ctx.is_cancelled());
+    assert_eq!(ctx.check(), Ok(()));
+    ctx.cancellation().cancel();
+    assert!(token.is_cancelled());
+    assert!(ctx.is_cancelled());
+    assert_eq!(ctx.check().unwrap_err().to_string(), "fetch cancelled");
+
+    let ctx = Context::new().with_deadline(Instant::now());
+    assert!(ctx.is_cancelled());
+    assert_eq!(
+        ctx.check().unwrap_err().to_string(),
+        "fetch deadline exceeded"
+    );
+}
+
+#[test]
+fn context_timeout() {
+    let timeout = Duration::from_secs(10);
+    assert_eq!(Context::new().timeout(timeout), timeout);
+    let ctx = Context::new().with_deadline(Instant::now() + Duration::from_secs(3));
+    assert!(ctx.timeout(timeout) <= Duration::from_secs(3));
+    let ctx = Context::new().with_deadline(Instant::now() + Duration::from_secs(60));
+    assert_eq!(ctx.timeout(timeout), timeout);
+}
+
+#[cfg(unix)]
+#[test]
+fn context_output() {
+    let output = Context::new()
+        .output(std::process::Command::new("sh").args(["-c", "echo out; echo err >&2; exit 3"]))
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(output.stdout, b"out\n");
+    assert_eq!(output.stderr, b"err\n");
+
+    let started = Instant::now();
+    let ctx = Context::new().with_deadline(Instant::now() + Duration::from_millis(100));
+    let err = ctx
+        .output(std::process::Command::new("sleep").arg("10"))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    assert_eq!(err.to_string(), "fetch deadline exceeded");
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn context_run() {
+    assert_eq!(Context::new().run(|| 42), Ok(42));
+
+    let started = Instant::now();
+    let ctx = Context::new().with_deadline(Instant::now() + Duration::from_millis(100));
+    let err = ctx
+        .run(|| std::thread::sleep(Duration::from_secs(10)))
+        .unwrap_err();
+    assert_eq!(err.to_string(), "fetch deadline exceeded");
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn context_sleep_jitter() {
+    assert_eq!(Context::new().sleep_jitter(Duration::ZERO), Duration::ZERO);
+    let started = Instant::now();
+    let jitter = Context::new().sleep_jitter(Duration::from_millis(50));
+    assert!(jitter <= Duration::from_millis(50));
+    assert!(started.elapsed() >= jitter);
+
+    let ctx = Context::new().with_deadline(Instant::now() + Duration::from_millis(200));
+    assert!(ctx.sleep_jitter(Duration::from_secs(60)) <= Duration::from_millis(100));
+    assert!(!ctx.is_cancelled());
+}
@@ -0,0 +1,82 @@
+#![cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+
+use mackerel_plugin::{CpuStats, Value};
+
+#[cfg(target_os = "linux")]
+fn proc_dir(name: &str, stat: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-cpu-test.{}.{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("stat"), stat).unwrap();
+    dir
+}
+
+#[test]
+fn cpu_graphs() {
+    let graphs = CpuStats::graphs();
+    assert_eq!(graphs.len(), 1);
+    assert_eq!(graphs[0].name, "cpu");
+    assert!(graphs[0]
+        .metrics
+        .iter()
+        .all(|metric| metric.stacked && metric.diff));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn cpu_fetch_values() {
+    let dir = proc_dir(
+        "fetch",
+        "cpu  6000 300 1200 90000 600 0 60 0 0 0\n\
+         cpu0 3000 150 600 45000 300 0 30 0 0 0\n\
+         intr 12345\n",
+    );
+    let mut values = CpuStats::new()
+        .proc_dir(&dir)
+        .fetch_values()
+        .unwrap()
+        .into_iter()
+        .collect::<Vec<_>>();
+    values.sort_by(|(x, _), (y, _)| x.cmp(y));
+    assert_eq!(
+        values,
+        [
+            ("cpu.idle", 1500.0),
+            ("cpu.iowait", 10.0),
+            ("cpu.irq", 0.0),
+            ("cpu.nice", 5.0),
+            ("cpu.softirq", 1.0),
+            ("cpu.steal", 0.0),
+            ("cpu.system", 20.0),
+            ("cpu.user", 100.0),
+        ]
+        .map(|(key, value)| (key.to_owned(), Value::Float(value)))
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn cpu_fetch_values_no_cpu_line() {
+    let dir = proc_dir("no-cpu", "intr 12345\n");
+    assert_eq!(
+        CpuStats::new()
+            .proc_dir(&dir)
+            .fetch_values()
+            .unwrap_err()
+            .to_string(),
+        format!("read {} failed: no cpu line", dir.join("stat").display())
+    );
+}
+
+#[test]
+fn cpu_fetch_values_host() {
+    let values = CpuStats::new().fetch_values().unwrap();
+    assert!(matches!(values["cpu.idle"], Value::Float(idle) if idle > 0.0));
+    assert!(values.keys().all(|key| key.starts_with("cpu.")));
+}
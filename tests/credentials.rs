@@ -0,0 +1,84 @@
+use mackerel_plugin::{Error, Secret, SecretSource};
+
+#[test]
+fn secret_source_parse() {
+    assert_eq!(
+        "env:MYSQL_PASSWORD".parse(),
+        Ok(SecretSource::Env("MYSQL_PASSWORD".to_owned()))
+    );
+    assert_eq!(
+        "file:/etc/mackerel-agent/mysql-password".parse(),
+        Ok(SecretSource::File(
+            "/etc/mackerel-agent/mysql-password".into()
+        ))
+    );
+    assert_eq!(
+        "keyring:mysql/root".parse(),
+        Ok(SecretSource::Keyring {
+            service: "mysql".to_owned(),
+            account: "root".to_owned(),
+        })
+    );
+    for source in ["", "env:", "password", "keyring:mysql", "keyring:/root"] {
+        assert_eq!(
+            source.parse::<SecretSource>(),
+            Err(Error::Other(format!("invalid secret source: {}", source)))
+        );
+    }
+    let source = SecretSource::Keyring {
+        service: "mysql".to_owned(),
+        account: "root".to_owned(),
+    };
+    assert_eq!(source.to_string(), "keyring:mysql/root");
+}
+
+#[test]
+fn secret_resolve() {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-credentials-test-{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("password");
+    std::fs::write(&path, "p@ssw0rd\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    }
+    std::env::set_var("MACKEREL_PLUGIN_CREDENTIALS_TEST", "s3cr3t");
+
+    let env = SecretSource::Env("MACKEREL_PLUGIN_CREDENTIALS_TEST".to_owned());
+    let file = SecretSource::File(path.clone());
+    let missing = SecretSource::File(dir.join("missing"));
+    assert_eq!(
+        Secret::resolve(&[missing.clone(), file.clone(), env.clone()]),
+        Ok(Secret::new("p@ssw0rd"))
+    );
+    assert_eq!(
+        Secret::resolve(&[missing.clone(), env.clone(), file.clone()]),
+        Ok(Secret::new("s3cr3t"))
+    );
+    assert_eq!(
+        Secret::resolve(&[missing]),
+        Err(Error::Other(format!(
+            "secret not found in file:{}",
+            dir.join("missing").display()
+        )))
+    );
+    assert_eq!(format!("{:?}", Secret::new("s3cr3t")), "Secret(****)");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(
+            Secret::resolve(&[file, env]),
+            Err(Error::Other(format!(
+                "permissions 0644 of {} are too open",
+                path.display()
+            )))
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,158 @@
+use mackerel_plugin::{
+    definitions_diff, graph, Definitions, Graph, GraphDiff, MetricDiff, Threshold, Unit,
+};
+
+#[test]
+fn definitions_diff_no_changes() {
+    let graphs = vec![graph! {
+        name: "dice",
+        label: "My Dice",
+        unit: "integer",
+        metrics: [
+            { name: "d6", label: "Die 6" },
+            { name: "d20", label: "Die 20" },
+        ]
+    }];
+    let diff = definitions_diff(&graphs, &graphs);
+    assert!(diff.is_empty());
+}
+
+#[test]
+fn definitions_diff_changes() {
+    let old = vec![
+        graph! {
+            name: "dice",
+            label: "My Dice",
+            unit: "integer",
+            metrics: [
+                { name: "d6", label: "Die 6" },
+                { name: "d20", label: "Die 20" },
+            ]
+        },
+        graph! {
+            name: "coin",
+            label: "My Coin",
+            unit: "integer",
+            metrics: [
+                { name: "head", label: "Head" },
+            ]
+        },
+    ];
+    let new = vec![
+        graph! {
+            name: "dice",
+            label: "Dice",
+            unit: "float",
+            metrics: [
+                { name: "d6", label: "Die 6", stacked: true },
+                { name: "d12", label: "Die 12" },
+            ]
+        },
+        graph! {
+            name: "cards",
+            label: "My Cards",
+            unit: "integer",
+            metrics: [
+                { name: "*", label: "%1" },
+            ]
+        },
+    ];
+    let diff = definitions_diff(&old, &new);
+    assert!(!diff.is_empty());
+    assert_eq!(diff.added_graphs, vec!["cards"]);
+    assert_eq!(diff.removed_graphs, vec!["coin"]);
+    assert_eq!(
+        diff.changed_graphs,
+        vec![GraphDiff {
+            name: "dice".to_owned(),
+            label: Some(("My Dice".to_owned(), "Dice".to_owned())),
+            unit: Some((Unit::Integer, Unit::Float)),
+            added_metrics: vec!["d12".to_owned()],
+            removed_metrics: vec!["d20".to_owned()],
+            changed_metrics: vec![MetricDiff {
+                name: "d6".to_owned(),
+                stacked: Some((false, true)),
+                ..Default::default()
+            }],
+        }]
+    );
+}
+
+fn dice_graphs() -> Vec<Graph> {
+    vec![
+        graph! {
+            name: "dice",
+            label: "My Dice",
+            unit: "integer",
+            metrics: [
+                { name: "d6", label: "Die 6", diff: true },
+                { name: "d20", label: "Die 20", threshold: Threshold::above().warning(18.0) },
+            ]
+        },
+        graph! {
+            name: "coin",
+            label: "My Coin",
+            unit: "integer",
+            metrics: [{ name: "head", label: "Head", stacked: true }]
+        },
+    ]
+}
+
+#[test]
+fn definitions_canonical() {
+    let mut graphs = dice_graphs();
+    graphs.reverse();
+    graphs[1].metrics.reverse();
+    graphs[1].label = "My\tDice ".to_owned();
+    let (old, new) = (Definitions::new(dice_graphs()), Definitions::from(graphs));
+    assert_ne!(old, new);
+    assert_eq!(old.canonical(), new.canonical());
+    assert_eq!(
+        old.canonical()
+            .graphs
+            .iter()
+            .map(|graph| &graph.name)
+            .collect::<Vec<_>>(),
+        vec!["coin", "dice"]
+    );
+    assert_eq!(old.canonical().graphs[1].metrics[0].name, "d20");
+    assert_eq!(old.canonical().canonical(), old.canonical());
+    assert_eq!(
+        old.diff(&new).changed_graphs,
+        vec![GraphDiff {
+            name: "dice".to_owned(),
+            label: Some(("My Dice".to_owned(), "My\tDice ".to_owned())),
+            ..GraphDiff::default()
+        }]
+    );
+
+    let mut graphs = dice_graphs();
+    graphs[0].metrics[0].stacked = true;
+    assert_ne!(old.canonical(), Definitions::new(graphs).canonical());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn definitions_round_trip() {
+    use mackerel_plugin::{parse_meta_output, Plugin};
+    use std::collections::HashMap;
+
+    struct DicePlugin {}
+
+    impl Plugin for DicePlugin {
+        fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+            Ok(HashMap::new())
+        }
+
+        fn graph_definition(&self) -> Vec<Graph> {
+            dice_graphs()
+        }
+    }
+
+    let mut out = Vec::new();
+    DicePlugin {}.output_definitions(&mut out).unwrap();
+    let parsed = Definitions::new(parse_meta_output(&String::from_utf8(out).unwrap()).unwrap());
+    let definitions = Definitions::new(DicePlugin {}.graph_definition());
+    assert_ne!(parsed.canonical(), definitions.canonical());
+    assert_eq!(parsed.canonical(), definitions.published().canonical());
+}
@@ -0,0 +1,134 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use mackerel_plugin::{
+    expand_label, graph, workdir, Context, Discovery, FileStateStore, Graph, MemoryStateStore,
+    Plugin, SystemClock, Transform, Value,
+};
+
+#[test]
+fn discovery_targets() {
+    let discovery = Discovery::new()
+        .target("db_2", "Orders")
+        .target("db_1", "Users");
+    assert!(!discovery.is_empty());
+    assert_eq!(discovery.names().collect::<Vec<_>>(), ["db_1", "db_2"]);
+    assert_eq!(discovery.display_name("db_1"), Some("Users"));
+    assert_eq!(discovery.display_name("db_3"), None);
+    assert_eq!(
+        expand_label("%1 size", &["db_1"], &[discovery.transform()]),
+        "Users size"
+    );
+    assert_eq!(
+        expand_label("%1 size", &["db_3"], &[discovery.transform()]),
+        "db_3 size"
+    );
+    assert!(Discovery::new().is_empty());
+}
+
+struct DatabasePlugin {
+    name: &'static str,
+    discoveries: Cell<usize>,
+}
+
+impl Plugin for DatabasePlugin {
+    fn discover(&self) -> Result<Discovery, String> {
+        self.discoveries.set(self.discoveries.get() + 1);
+        Ok(Discovery::new().target("db_1", "Users"))
+    }
+
+    fn fetch_metrics_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, String> {
+        let discovery = self.discovered(ctx).map_err(|e| e.to_string())?;
+        Ok(discovery
+            .names()
+            .map(|name| (format!("database.size.{}", name), 100.0.into()))
+            .collect())
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "database.size",
+            label: "Database Size",
+            unit: "bytes",
+            metrics: [{ name: "*", label: "%1" }]
+        }]
+    }
+
+    fn metric_key_prefix(&self) -> String {
+        format!("discovery-{}-{}", self.name, std::process::id())
+    }
+
+    #[cfg(feature = "json")]
+    fn fetch_cache_ttl(&self) -> Option<std::time::Duration> {
+        Some(std::time::Duration::from_secs(60))
+    }
+
+    fn label_transforms(&self) -> Vec<Transform> {
+        self.discovered(&Context::new())
+            .map(|discovery| vec![discovery.transform()])
+            .unwrap_or_default()
+    }
+}
+
+#[test]
+fn plugin_discovered() {
+    let plugin = DatabasePlugin {
+        name: "test",
+        discoveries: Cell::new(0),
+    };
+    let prefix = plugin.metric_key_prefix();
+    assert_eq!(
+        plugin.series_labels(&FileStateStore, &SystemClock),
+        Ok(HashMap::from([(
+            format!("{}.database.size.db_1", prefix),
+            "Users".to_owned()
+        )]))
+    );
+    assert_eq!(
+        plugin.discovered(&Context::new()),
+        Ok(Discovery::new().target("db_1", "Users"))
+    );
+    #[cfg(feature = "json")]
+    {
+        assert_eq!(plugin.discoveries.get(), 1);
+        let path = workdir().join(format!("mackerel-plugin-{}", prefix));
+        std::fs::remove_file(path.with_extension("cache")).unwrap();
+        std::fs::remove_file(path.with_extension("values")).unwrap();
+    }
+}
+
+#[test]
+fn plugin_discovered_run_with() {
+    let plugin = DatabasePlugin {
+        name: "run-with",
+        discoveries: Cell::new(0),
+    };
+    let path = workdir().join(format!("mackerel-plugin-{}", plugin.metric_key_prefix()));
+    let state = MemoryStateStore::new();
+    let now = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1700000000);
+    for _ in 0..2 {
+        let mut out = Vec::new();
+        assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "{}.database.size.db_1\t100\t1700000000\n",
+                plugin.metric_key_prefix()
+            )
+        );
+    }
+    #[cfg(feature = "json")]
+    {
+        use mackerel_plugin::StateStore;
+        assert_eq!(plugin.discoveries.get(), 1);
+        let path = path.to_str().unwrap();
+        assert!(state.load(&(path.to_owned() + ".cache")).unwrap().is_some());
+        assert!(state
+            .load(&(path.to_owned() + ".values"))
+            .unwrap()
+            .is_some());
+    }
+    for extension in ["", "cache", "values"] {
+        assert!(!path.with_extension(extension).exists());
+    }
+}
@@ -0,0 +1,100 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use mackerel_plugin::{DnsProbe, RecordType, Value};
+
+/// Starts the resolver which answers the queries of the domains in the
+/// records, and responds NXDOMAIN to others.
+fn mock_resolver(records: &'static [&'static str]) -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    std::thread::spawn(move || loop {
+        let mut request = [0; 512];
+        let (len, peer) = socket.recv_from(&mut request).unwrap();
+        let request = &request[..len];
+        let (mut domain, mut i) = (Vec::new(), 12);
+        while request[i] != 0 {
+            let len = request[i] as usize;
+            domain.push(String::from_utf8_lossy(&request[i + 1..i + 1 + len]).into_owned());
+            i += len + 1;
+        }
+        let found = records.contains(&&domain.join(".")[..]);
+        let question = &request[12..i + 5];
+        let mut response = request[..2].to_vec();
+        response.extend([0x81, if found { 0x80 } else { 0x83 }, 0, 1]);
+        response.extend([0, found as u8, 0, 0, 0, 0]);
+        response.extend(question);
+        if found {
+            response.extend([0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 0, 2, 1]);
+        }
+        socket.send_to(&response, peer).unwrap();
+    });
+    addr
+}
+
+#[test]
+fn dns_probe_fetch_values() {
+    let resolver = mock_resolver(&["www.example.com"]);
+    let values = DnsProbe::new()
+        .record("www", "www.example.com.", RecordType::A)
+        .record("api", "api.example.com", RecordType::AAAA)
+        .resolver(resolver)
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values.len(), 3);
+    assert!(matches!(
+        values["dns.latency.www.127_0_0_1"],
+        Value::Duration(latency) if latency < Duration::from_secs(2)
+    ));
+    assert_eq!(values["dns.success.www.127_0_0_1"], Value::Bool(true));
+    assert_eq!(values["dns.success.api.127_0_0_1"], Value::Bool(false));
+}
+
+#[test]
+fn dns_probe_check() {
+    let resolver = mock_resolver(&["www.example.com"]);
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let silent = socket.local_addr().unwrap();
+    let probe = DnsProbe::new()
+        .resolver(resolver)
+        .timeout(Duration::from_millis(100));
+    let result = probe
+        .record("www", "www.example.com", RecordType::A)
+        .check();
+    assert_eq!(result.to_string(), "OK: 1 records resolved");
+
+    let probe = DnsProbe::new()
+        .resolver(resolver)
+        .resolver(silent)
+        .timeout(Duration::from_millis(100));
+    let result = probe
+        .record("www", "www.example.com", RecordType::A)
+        .check();
+    assert_eq!(
+        result.to_string(),
+        format!("WARNING: www.example.com A via {}: timed out", silent)
+    );
+
+    let probe = DnsProbe::new()
+        .resolver(resolver)
+        .timeout(Duration::from_millis(100));
+    let result = probe
+        .record("api", "api.example.com", RecordType::MX)
+        .check();
+    assert_eq!(
+        result.to_string(),
+        format!("CRITICAL: api.example.com MX via {}: NXDOMAIN", resolver)
+    );
+    drop(socket);
+}
+
+#[test]
+fn dns_probe_graphs() {
+    assert_eq!(
+        DnsProbe::graphs()
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec!["dns.latency.#", "dns.success.#"]
+    );
+}
@@ -0,0 +1,158 @@
+#![cfg(feature = "elasticsearch")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::sync::mpsc;
+
+use mackerel_plugin::{matches_metric, ElasticsearchStats, Value};
+
+const HEALTH: &str = r#"{
+  "cluster_name": "logs",
+  "status": "yellow",
+  "number_of_nodes": 2,
+  "number_of_data_nodes": 2,
+  "active_primary_shards": 10,
+  "active_shards": 18,
+  "relocating_shards": 0,
+  "initializing_shards": 1,
+  "unassigned_shards": 1,
+  "number_of_pending_tasks": 0
+}"#;
+
+const NODES_STATS: &str = r#"{
+  "nodes": {
+    "aBcD": {
+      "name": "es-1.internal",
+      "jvm": {
+        "mem": {"heap_used_in_bytes": 536870912, "heap_committed_in_bytes": 1073741824, "heap_max_in_bytes": 1073741824},
+        "gc": {"collectors": {
+          "young": {"collection_count": 120, "collection_time_in_millis": 3400},
+          "old": {"collection_count": 2, "collection_time_in_millis": 150}
+        }}
+      },
+      "indices": {
+        "docs": {"count": 100000},
+        "indexing": {"index_total": 5000, "index_time_in_millis": 12000, "delete_total": 20},
+        "search": {"query_total": 800, "query_time_in_millis": 4000, "fetch_total": 700, "fetch_time_in_millis": 300}
+      }
+    },
+    "eFgH": {
+      "jvm": {"mem": {"heap_used_in_bytes": 268435456}}
+    }
+  }
+}"#;
+
+#[test]
+fn elasticsearch_stats_parse() {
+    let values = ElasticsearchStats::parse_health(HEALTH).unwrap();
+    assert_eq!(
+        values["elasticsearch.cluster.status.yellow"],
+        Value::from(1.0)
+    );
+    assert_eq!(
+        values["elasticsearch.cluster.status.green"],
+        Value::from(0.0)
+    );
+    assert_eq!(
+        values["elasticsearch.cluster.nodes.data_nodes"],
+        Value::from(2.0)
+    );
+    assert_eq!(
+        values["elasticsearch.cluster.shards.unassigned"],
+        Value::from(1.0)
+    );
+    assert_eq!(values.len(), 3 + 2 + 5 + 1);
+
+    let values = ElasticsearchStats::parse_nodes_stats(NODES_STATS).unwrap();
+    assert_eq!(
+        values["elasticsearch.node.heap.es-1_internal.used"],
+        Value::from(536870912.0)
+    );
+    assert_eq!(
+        values["elasticsearch.node.gc_count.es-1_internal.young"],
+        Value::Counter(120)
+    );
+    assert_eq!(
+        values["elasticsearch.node.time.es-1_internal.query"],
+        Value::Counter(4000)
+    );
+    assert_eq!(
+        values["elasticsearch.node.docs.es-1_internal.count"],
+        Value::from(100000.0)
+    );
+    assert_eq!(
+        values["elasticsearch.node.heap.eFgH.used"],
+        Value::from(268435456.0)
+    );
+    assert_eq!(values.len(), 15 + 1);
+
+    let graphs = ElasticsearchStats::graphs();
+    for key in values.keys() {
+        let graph = graphs
+            .iter()
+            .find(|graph| {
+                graph
+                    .metrics
+                    .iter()
+                    .any(|metric| matches_metric(&format!("{}.{}", graph.name, metric.name), key))
+            })
+            .unwrap_or_else(|| panic!("{}", key));
+        let metric = key.rsplit('.').next().unwrap();
+        let diff = graph
+            .metrics
+            .iter()
+            .find(|m| m.name == metric)
+            .unwrap()
+            .diff;
+        assert_eq!(diff, matches!(values[key], Value::Counter(_)), "{}", key);
+    }
+
+    assert_eq!(
+        ElasticsearchStats::parse_health("{}")
+            .unwrap_err()
+            .to_string(),
+        "parse the Elasticsearch cluster health failed: no status found"
+    );
+    assert_eq!(
+        ElasticsearchStats::parse_nodes_stats(r#"{"error":"forbidden"}"#)
+            .unwrap_err()
+            .to_string(),
+        "parse the Elasticsearch nodes stats failed: no nodes found"
+    );
+}
+
+#[test]
+fn elasticsearch_stats_fetch_values() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().take(2) {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let path = line.split(' ').nth(1).unwrap().to_owned();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let body = if path == "/_cluster/health" {
+                HEALTH
+            } else {
+                NODES_STATS
+            };
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            tx.send(path).unwrap();
+        }
+    });
+    let values = ElasticsearchStats::new(url).fetch_values().unwrap();
+    assert_eq!(values.len(), 11 + 16);
+    assert_eq!(
+        rx.iter().collect::<Vec<_>>(),
+        ["/_cluster/health", "/_nodes/stats/jvm,indices"]
+    );
+}
@@ -0,0 +1,165 @@
+#![cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+
+use mackerel_plugin::{Filesystems, Filter, Value};
+
+#[cfg(target_os = "linux")]
+fn mountinfo(name: &str, lines: &[String]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-filesystem-test.{}.{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(dir.join("data dir")).unwrap();
+    let path = dir.join("mountinfo");
+    std::fs::write(
+        &path,
+        lines
+            .join("\n")
+            .replace("{dir}", &dir.display().to_string()),
+    )
+    .unwrap();
+    path
+}
+
+#[cfg(target_os = "linux")]
+fn keys(filesystems: &Filesystems) -> Vec<String> {
+    let mut keys = filesystems
+        .fetch_values()
+        .unwrap()
+        .into_keys()
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn filesystems_fetch_values() {
+    let path = mountinfo(
+        "fetch",
+        &[
+            "22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw".to_owned(),
+            "23 22 8:1 /srv /mnt/bind\\040srv rw,relatime shared:1 - ext4 /dev/sda1 rw".to_owned(),
+            "24 22 0:21 / /proc rw,nosuid - proc proc rw".to_owned(),
+            "25 22 0:25 / /run rw,nosuid - tmpfs tmpfs rw,mode=755".to_owned(),
+            "26 22 253:0 /data {dir}/data\\040dir rw - xfs /dev/mapper/vg0-lv.data rw".to_owned(),
+            "27 22 8:17 / /nonexistent rw - xfs /dev/sdb1 rw".to_owned(),
+            "28 22 0:50 / /var/lib/docker/overlay2/abc/merged rw - overlay overlay rw".to_owned(),
+        ],
+    );
+    let filesystems = Filesystems::new().mountinfo(&path);
+    let values = filesystems.fetch_values().unwrap();
+    for key in ["sda1", "mapper_vg0-lv_data"] {
+        let used = values[&format!("disk.usage.{}.used", key)];
+        let available = values[&format!("disk.usage.{}.available", key)];
+        let (Value::Float(used), Value::Float(available)) = (used, available) else {
+            panic!("{:?}", values);
+        };
+        assert!(used + available > 0.0);
+        assert_eq!(
+            values[&format!("disk.percentage.{}.used", key)],
+            Value::Float(used / (used + available) * 100.0)
+        );
+    }
+    assert!(values
+        .keys()
+        .all(|key| !key.contains("sdb1") && !key.contains("proc") && !key.contains("run")));
+
+    assert_eq!(
+        keys(
+            &Filesystems::new()
+                .mountinfo(&path)
+                .mount_points(Filter::new().exclude("/"))
+        )
+        .iter()
+        .filter(|key| key.starts_with("disk.usage."))
+        .collect::<Vec<_>>(),
+        vec![
+            "disk.usage.mapper_vg0-lv_data.available",
+            "disk.usage.mapper_vg0-lv_data.used",
+        ]
+    );
+    assert_eq!(
+        keys(
+            &Filesystems::new()
+                .mountinfo(&path)
+                .fs_types(Filter::new().include("tmpfs"))
+        )
+        .iter()
+        .filter(|key| key.starts_with("disk.usage."))
+        .collect::<Vec<_>>(),
+        vec!["disk.usage.run.available", "disk.usage.run.used"]
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn filesystems_fetch_values_bind_mount() {
+    // the bind mount, which is inaccessible here, precedes the mount of the
+    // whole filesystem
+    let path = mountinfo(
+        "bind",
+        &[
+            "26 22 253:0 /data /nonexistent\\040bind rw - xfs /dev/dm-0 rw".to_owned(),
+            "27 22 253:0 / / rw - xfs /dev/dm-0 rw".to_owned(),
+        ],
+    );
+    let values = Filesystems::new().mountinfo(&path).fetch_values().unwrap();
+    assert!(values.contains_key("disk.usage.dm-0.used"));
+    let values = Filesystems::new()
+        .mountinfo(&path)
+        .mount_points(Filter::new().exclude("/"))
+        .fetch_values()
+        .unwrap();
+    assert!(values.is_empty());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn filesystems_fetch_values_error() {
+    assert_eq!(
+        Filesystems::new()
+            .mountinfo("/nonexistent/mountinfo")
+            .fetch_values()
+            .unwrap_err()
+            .to_string(),
+        "read /nonexistent/mountinfo failed: No such file or directory (os error 2)"
+    );
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+#[test]
+fn filesystems_fetch_values_mntinfo() {
+    let values = Filesystems::new()
+        .mount_points(Filter::new().include("/"))
+        .fetch_values()
+        .unwrap();
+    assert!(
+        values
+            .iter()
+            .any(|(key, value)| key.starts_with("disk.usage.")
+                && key.ends_with(".used")
+                && matches!(value, Value::Float(used) if *used > 0.0)),
+        "{:?}",
+        values
+    );
+}
+
+#[test]
+fn filesystems_graphs() {
+    assert_eq!(
+        Filesystems::graphs()
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec![
+            "disk.usage.#",
+            "disk.percentage.#",
+            "inode.count.#",
+            "inode.percentage.#",
+        ]
+    );
+}
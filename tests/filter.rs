@@ -0,0 +1,39 @@
+use rstest::rstest;
+
+use mackerel_plugin::Filter;
+
+#[rstest]
+#[case(Filter::new(), "sda", true)]
+#[case(Filter::new().include("sda"), "sda", true)]
+#[case(Filter::new().include("sda"), "sdb", false)]
+#[case(Filter::new().include("sd*"), "sd", true)]
+#[case(Filter::new().include("sd*"), "sdb1", true)]
+#[case(Filter::new().include("sd?"), "sdb", true)]
+#[case(Filter::new().include("sd?"), "sdb1", false)]
+#[case(Filter::new().include("*1"), "sdb1", true)]
+#[case(Filter::new().include("*d*1"), "sdb1", true)]
+#[case(Filter::new().include("*d*2"), "sdb1", false)]
+#[case(Filter::new().include("sda").include("nvme*"), "nvme0n1", true)]
+#[case(Filter::new().exclude("veth*"), "eth0", true)]
+#[case(Filter::new().exclude("veth*"), "veth1a2b", false)]
+#[case(Filter::new().include("*").exclude("lo"), "lo", false)]
+fn filter_matches(#[case] filter: Filter, #[case] value: &str, #[case] expected: bool) {
+    assert_eq!(filter.matches(value), expected);
+}
+
+#[test]
+fn filter_from_env_for() {
+    std::env::set_var("MACKEREL_PLUGIN_INCLUDE_DISK", "sd*");
+    std::env::set_var("MACKEREL_PLUGIN_EXCLUDE", "lo");
+    assert_eq!(
+        Filter::from_env_for("disk.#"),
+        Filter::new().include("sd*").exclude("lo")
+    );
+    assert_eq!(
+        Filter::from_env_for("interface.#"),
+        Filter::new().exclude("lo")
+    );
+    assert_eq!(Filter::from_env_for("#"), Filter::new().exclude("lo"));
+    std::env::remove_var("MACKEREL_PLUGIN_INCLUDE_DISK");
+    std::env::remove_var("MACKEREL_PLUGIN_EXCLUDE");
+}
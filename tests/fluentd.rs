@@ -0,0 +1,86 @@
+#![cfg(feature = "http")]
+
+use std::io::{BufRead, BufReader, Write};
+
+use mackerel_plugin::{FluentdStats, Value};
+
+const PLUGINS: &str = r#"{"plugins":[
+  {"plugin_id":"object:3fc","plugin_category":"input","type":"monitor_agent","output_plugin":false,"retry_count":null},
+  {"plugin_id":"in_forward","plugin_category":"input","type":"forward","output_plugin":false,"emit_records":1200,"emit_count":300},
+  {"plugin_id":"out.s3","plugin_category":"output","type":"s3","output_plugin":true,
+   "buffer_queue_length":2,"buffer_total_queued_size":4096,"retry_count":3,
+   "emit_records":1000,"emit_count":250,"write_count":20,"rollback_count":1,
+   "slow_flush_count":0,"flush_time_count":1530,"buffer_stage_length":1,
+   "buffer_stage_byte_size":512,"buffer_queue_byte_size":3584,
+   "buffer_available_buffer_space_ratios":99.5}
+]}"#;
+
+#[test]
+fn fluentd_stats_parse() {
+    let values = FluentdStats::parse(PLUGINS).unwrap();
+    assert_eq!(values.len(), 2 + 13);
+    assert_eq!(
+        values["fluentd.emit_records.in_forward"],
+        Value::Counter(1200)
+    );
+    assert_eq!(values["fluentd.retry_count.out_s3"], Value::Counter(3));
+    assert_eq!(
+        values["fluentd.buffer_queue_length.out_s3"],
+        Value::from(2.0)
+    );
+    assert_eq!(
+        values["fluentd.buffer_available_buffer_space_ratios.out_s3"],
+        Value::from(99.5)
+    );
+    for key in values.keys() {
+        assert!(
+            FluentdStats::graphs()
+                .iter()
+                .any(|graph| graph
+                    .metrics
+                    .iter()
+                    .any(|metric| mackerel_plugin::matches_metric(
+                        &format!("{}.{}", graph.name, metric.name),
+                        key
+                    ))),
+            "{}",
+            key
+        );
+    }
+
+    assert_eq!(
+        FluentdStats::parse("{}").unwrap_err().to_string(),
+        "parse the Fluentd plugins failed: no plugins found"
+    );
+}
+
+#[test]
+fn fluentd_stats_fetch_values() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        reader.read_line(&mut request).unwrap();
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            PLUGINS.len(),
+            PLUGINS
+        )
+        .unwrap();
+        request
+    });
+    let values = FluentdStats::new(endpoint)
+        .plugin_type("s3")
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values.len(), 13);
+    assert!(values.keys().all(|key| key.ends_with(".out_s3")));
+    assert_eq!(handle.join().unwrap(), "GET /api/plugins.json HTTP/1.1\r\n");
+}
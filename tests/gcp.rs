@@ -0,0 +1,185 @@
+#![cfg(feature = "gcp")]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mackerel_plugin::{CloudMonitoring, TimeSeriesQuery, Value};
+
+struct Request {
+    path: String,
+    authorization: String,
+}
+
+fn mock_server(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<Request>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for (status, response) in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let path = line.split(' ').nth(1).unwrap().to_owned();
+            let mut authorization = String::new();
+            loop {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                match line.trim_end().split_once(": ") {
+                    Some((key, value)) if key.eq_ignore_ascii_case("authorization") => {
+                        authorization = value.to_owned()
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {} Status\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response.len(),
+                response
+            )
+            .unwrap();
+            tx.send(Request {
+                path,
+                authorization,
+            })
+            .unwrap();
+        }
+    });
+    (url, rx)
+}
+
+fn query() -> TimeSeriesQuery {
+    TimeSeriesQuery::new(
+        "gce.cpu.{zone}.{instance_name}",
+        r#"metric.type = "compute.googleapis.com/instance/cpu/utilization""#,
+    )
+    .aligner("ALIGN_MAX")
+    .alignment_period(Duration::from_secs(90))
+}
+
+#[test]
+fn cloud_monitoring_list_time_series() {
+    let (url, rx) = mock_server(vec![
+        (
+            200,
+            r#"{
+  "timeSeries": [
+    {
+      "metric": { "labels": { "instance_name": "web-1" } },
+      "resource": { "labels": { "zone": "asia-northeast1-a" } },
+      "points": [
+        { "interval": { "endTime": "2023-11-14T22:13:00Z" }, "value": { "doubleValue": 0.25 } },
+        { "interval": { "endTime": "2023-11-14T22:11:00Z" }, "value": { "doubleValue": 0.5 } }
+      ]
+    }
+  ],
+  "nextPageToken": "token"
+}"#,
+        ),
+        (
+            200,
+            r#"{
+  "timeSeries": [
+    {
+      "metric": { "labels": { "instance_name": "web.2" } },
+      "resource": { "labels": { "zone": "asia-northeast1-b" } },
+      "points": [
+        { "interval": { "endTime": "2023-11-14T22:13:00Z" }, "value": { "int64Value": "3" } }
+      ]
+    }
+  ]
+}"#,
+        ),
+    ]);
+    let time = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+    assert_eq!(
+        CloudMonitoring::new("project-1", "token-1")
+            .endpoint(url)
+            .list_time_series(&query(), time(1699999680), time(1699999980)),
+        Ok(HashMap::from([
+            (
+                "gce.cpu.asia-northeast1-a.web-1".to_owned(),
+                vec![(time(1699999980), 0.25), (time(1699999860), 0.5)]
+            ),
+            (
+                "gce.cpu.asia-northeast1-b.web_2".to_owned(),
+                vec![(time(1699999980), 3.0)]
+            ),
+        ]))
+    );
+    let request = rx.recv().unwrap();
+    assert_eq!(request.authorization, "Bearer token-1");
+    assert_eq!(
+        request.path,
+        "/v3/projects/project-1/timeSeries\
+         ?filter=metric.type+%3D+%22compute.googleapis.com%2Finstance%2Fcpu%2Futilization%22\
+         &interval.startTime=2023-11-14T22%3A08%3A00Z\
+         &interval.endTime=2023-11-14T22%3A13%3A00Z\
+         &aggregation.alignmentPeriod=120s\
+         &aggregation.perSeriesAligner=ALIGN_MAX"
+    );
+    let request = rx.recv().unwrap();
+    assert!(request.path.ends_with("&pageToken=token"));
+}
+
+#[test]
+fn cloud_monitoring_fetch_values() {
+    let (url, rx) = mock_server(vec![(
+        200,
+        r#"{
+  "timeSeries": [
+    {
+      "metric": { "labels": { "instance_name": "web-1" } },
+      "resource": { "labels": { "zone": "asia-northeast1-a" } },
+      "points": [
+        { "interval": { "endTime": "2023-11-14T22:13:00Z" }, "value": { "doubleValue": 0.25 } },
+        { "interval": { "endTime": "2023-11-14T22:11:00Z" }, "value": { "doubleValue": 0.5 } }
+      ]
+    }
+  ]
+}"#,
+    )]);
+    assert_eq!(
+        CloudMonitoring::new("project-1", "token-1")
+            .endpoint(url)
+            .window(Duration::from_secs(600))
+            .fetch_values(&[query()
+                .reducer("REDUCE_MAX")
+                .group_by("resource.label.zone")]),
+        Ok(HashMap::from([(
+            "gce.cpu.asia-northeast1-a.web-1".to_owned(),
+            Value::Observed(0.25, UNIX_EPOCH + Duration::from_secs(1699999980))
+        )]))
+    );
+    let request = rx.recv().unwrap();
+    assert!(request.path.ends_with(
+        "&aggregation.crossSeriesReducer=REDUCE_MAX\
+         &aggregation.groupByFields=resource.label.zone"
+    ));
+    let end = request
+        .path
+        .split('&')
+        .find_map(|param| param.strip_prefix("interval.endTime="))
+        .unwrap();
+    assert!(end.ends_with("%3A00Z"));
+}
+
+#[test]
+fn cloud_monitoring_error() {
+    let (url, _rx) = mock_server(vec![(
+        403,
+        r#"{ "error": { "code": 403, "message": "Permission denied." } }"#,
+    )]);
+    assert_eq!(
+        CloudMonitoring::new("project-1", "token-1")
+            .endpoint(url)
+            .list_time_series(&query(), SystemTime::now(), SystemTime::now())
+            .map_err(|err| err.to_string()),
+        Err("GET /v3/projects/project-1/timeSeries failed: 403 Permission denied.".to_owned())
+    );
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "nvml")]
+
+use mackerel_plugin::{GpuDevices, Value};
+
+#[test]
+fn gpu_devices_fetch_values() {
+    // the hosts without the NVIDIA driver cannot load the NVML library
+    let devices = match GpuDevices::new() {
+        Ok(devices) => devices,
+        Err(err) => {
+            assert!(err.to_string().starts_with("initialize NVML failed: "));
+            return;
+        }
+    };
+    for (name, value) in devices.fetch_values().unwrap() {
+        assert!(name.starts_with("gpu."), "{}", name);
+        assert!(matches!(value, Value::Float(value) if value >= 0.0));
+    }
+}
+
+#[test]
+fn gpu_devices_graphs() {
+    assert_eq!(
+        GpuDevices::graphs()
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec![
+            "gpu.utilization.#",
+            "gpu.memory.#",
+            "gpu.temperature",
+            "gpu.power"
+        ]
+    );
+}
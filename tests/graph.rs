@@ -1,6 +1,6 @@
 use serde_json::json;
 
-use mackerel_plugin::graph;
+use mackerel_plugin::{graph, metric, Graph, Metric, Unit};
 
 #[test]
 fn graph() {
@@ -56,3 +56,216 @@ fn graph_has_diff() {
     };
     assert!(!graph2.has_diff());
 }
+
+#[test]
+fn graph_expr_name() {
+    for (name, valid) in [
+        ("", true),
+        ("foo.bar", true),
+        ("foo.*.#", true),
+        (".foo", false),
+        ("foo.", false),
+        ("foo/bar", false),
+    ] {
+        let name = name.to_owned();
+        let result = std::panic::catch_unwind(|| {
+            graph! {
+                name: &name,
+                label: "Foo",
+                unit: "integer",
+                metrics: [{ name: "foo", label: "Foo" }],
+            }
+        });
+        assert_eq!(result.is_ok(), valid, "{}", name);
+    }
+}
+
+#[test]
+fn graph_expr_unit() {
+    for (unit, expected) in [
+        ("bytes", Some(Unit::Bytes)),
+        ("bytes/sec", Some(Unit::BytesPerSec)),
+        ("liters", None),
+    ] {
+        let unit = unit.to_owned();
+        let result = std::panic::catch_unwind(|| {
+            graph! {
+                name: "foo",
+                label: "Foo",
+                unit: &unit,
+                metrics: [{ name: "foo", label: "Foo", unit: unit.clone() }],
+            }
+        });
+        assert_eq!(
+            result.ok().map(|graph| (graph.unit, graph.metrics[0].unit)),
+            expected.map(|unit| (unit, Some(unit))),
+            "{}",
+            unit
+        );
+    }
+}
+
+#[test]
+fn graph_expr_metrics() {
+    let devices = ["sda", "sdb"];
+    let graph = graph! {
+        name: "disk.io",
+        label: format!("Disk IO ({} devices)", devices.len()),
+        unit: "iops",
+        metrics: devices.iter().map(|&device| metric! {
+            name: device,
+            label: device.to_uppercase(),
+            diff: true,
+        }),
+    };
+    assert_eq!(
+        graph,
+        graph! {
+            name: "disk.io",
+            label: "Disk IO (2 devices)",
+            unit: "iops",
+            metrics: [
+                { name: "sda", label: "SDA", diff: true },
+                { name: "sdb", label: "SDB", diff: true },
+            ],
+        }
+    );
+    assert_eq!(
+        graph! {
+            name: "disk.io",
+            label: "Disk IO",
+            unit: "iops",
+            metrics: Vec::new(),
+        }
+        .metrics,
+        Vec::new()
+    );
+}
+
+#[test]
+fn graph_metric_units() {
+    let graph = graph! {
+        name: "memory",
+        label: "Memory",
+        unit: "bytes",
+        metrics: [
+            { name: "used", label: "Used" },
+            { name: "cached", label: "Cached", unit: "bytes" },
+            { name: "usage", label: "Usage", unit: "percentage" },
+        ]
+    };
+    assert_eq!(graph.unit_of(&graph.metrics[0]), &Unit::Bytes);
+    assert_eq!(graph.unit_of(&graph.metrics[2]), &Unit::Percentage);
+    assert_eq!(
+        graph.warnings(),
+        vec!["unit of metric usage (percentage) conflicts with unit of graph memory (bytes)"]
+    );
+    assert_eq!(
+        serde_json::to_value(&graph).unwrap()["metrics"][2],
+        json!({ "name": "usage", "label": "Usage", "stacked": false })
+    );
+}
+
+#[test]
+fn graph_semantic_warnings() {
+    let graph = graph! {
+        name: "loadavg",
+        label: "Load Average",
+        unit: "float",
+        metrics: [
+            { name: "loadavg1", label: "1min", stacked: true },
+            { name: "loadavg5", label: "5min" },
+            { name: "usage", label: "Usage", unit: "percentage", diff: true },
+            { name: "requests", label: "Requests", stacked: true, diff: true },
+        ]
+    };
+    assert_eq!(
+        graph.warnings(),
+        vec![
+            "graph loadavg mixes the stacked and unstacked metrics",
+            "metric loadavg1 of graph loadavg stacks the float gauge, which may not be additive",
+            "unit of metric usage (percentage) conflicts with unit of graph loadavg (float)",
+            "metric usage of graph loadavg takes the difference of the percentage",
+        ]
+    );
+
+    let graph = graph! {
+        name: "cpu",
+        label: "CPU",
+        unit: "percentage",
+        metrics: [
+            { name: "user", label: "User", stacked: true },
+            { name: "system", label: "System", stacked: true },
+        ]
+    };
+    assert!(graph.warnings().is_empty());
+}
+
+#[test]
+fn graph_new() {
+    assert_eq!(
+        Graph::new(
+            "disk.#",
+            "Disk",
+            "iops",
+            vec![Metric::new("read", "Read").unwrap()]
+        ),
+        Ok(graph! {
+            name: "disk.#",
+            label: "Disk",
+            unit: "iops",
+            metrics: [{ name: "read", label: "Read" }],
+        })
+    );
+    assert_eq!(
+        Graph::new("disk.", "Disk", "iops", Vec::new()),
+        Err("invalid graph name: disk.".to_owned())
+    );
+    assert_eq!(
+        Graph::new("disk", "Disk", "liters", Vec::new()),
+        Err("invalid unit: liters".to_owned())
+    );
+}
+
+#[test]
+fn graph_deserialize() {
+    let graph = serde_json::from_value::<Graph>(json!({
+        "name": "disk.#",
+        "label": "Disk",
+        "unit": "iops",
+        "metrics": [{ "name": "read", "label": "Read", "stacked": false, "diff": true }],
+    }))
+    .unwrap();
+    assert_eq!(
+        graph,
+        graph! {
+            name: "disk.#",
+            label: "Disk",
+            unit: "iops",
+            metrics: [{ name: "read", label: "Read", diff: true }],
+        }
+    );
+
+    for (value, expected) in [
+        (
+            json!({ "name": "disk io", "label": "Disk", "unit": "iops", "metrics": [] }),
+            "invalid graph name: disk io",
+        ),
+        (
+            json!({
+                "name": "disk",
+                "label": "Disk",
+                "unit": "iops",
+                "metrics": [{ "name": "read.bytes", "label": "Read", "stacked": false, "diff": false }],
+            }),
+            "invalid metric name: read.bytes",
+        ),
+    ] {
+        assert_eq!(
+            serde_json::from_value::<Graph>(value)
+                .unwrap_err()
+                .to_string(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,138 @@
+#![cfg(feature = "http")]
+
+use std::io::{BufRead, BufReader, Write};
+
+use mackerel_plugin::{HaproxyStats, Value};
+
+const STATS: &str = "# pxname,svname,qcur,qmax,scur,smax,slim,stot,bin,bout,dreq,dresp,ereq,econ,eresp,wretr,wredis,status,
+stats,FRONTEND,,,1,2,2000,10,1000,2000,0,0,0,,,,,OPEN,
+api.v1,web-1,0,3,5,20,,120,4096,8192,,0,,2,1,0,0,UP,
+api.v1,web-2,1,4,3,18,,80,2048,4096,,0,,0,0,0,0,UP,
+api.v1,BACKEND,1,5,8,30,200,200,6144,12288,0,0,,2,1,0,0,UP,
+static,BACKEND,0,0,0,1,200,50,100,200,0,0,,1,0,0,0,UP,
+";
+
+#[test]
+fn haproxy_stats_parse() {
+    let values = HaproxyStats::parse(STATS).unwrap();
+    let value = |key: &str| values.get(key).copied();
+    assert_eq!(
+        value("haproxy.total.sessions.sessions"),
+        Some(Value::from(250.0))
+    );
+    assert_eq!(
+        value("haproxy.total.bytes.bytes_in"),
+        Some(Value::from(6244.0))
+    );
+    assert_eq!(
+        value("haproxy.total.bytes.bytes_out"),
+        Some(Value::from(12488.0))
+    );
+    assert_eq!(
+        value("haproxy.total.connection_errors.connection_errors"),
+        Some(Value::from(3.0))
+    );
+    assert_eq!(
+        value("haproxy.backend.sessions.api_v1.current"),
+        Some(Value::from(8.0))
+    );
+    assert_eq!(
+        value("haproxy.backend.queue.static.current"),
+        Some(Value::from(0.0))
+    );
+    assert_eq!(
+        value("haproxy.server.sessions.api_v1.web-1.total"),
+        Some(Value::from(120.0))
+    );
+    assert_eq!(
+        value("haproxy.server.errors.api_v1.web-1.response"),
+        Some(Value::from(1.0))
+    );
+    assert_eq!(
+        value("haproxy.server.bytes.api_v1.web-2.out"),
+        Some(Value::from(4096.0))
+    );
+    assert!(values.keys().all(|key| !key.contains("stats")));
+    assert_eq!(values.len(), 4 + 7 * 4);
+    for key in values.keys() {
+        assert!(
+            HaproxyStats::graphs()
+                .iter()
+                .any(|graph| graph
+                    .metrics
+                    .iter()
+                    .any(|metric| mackerel_plugin::matches_metric(
+                        &format!("{}.{}", graph.name, metric.name),
+                        key
+                    ))),
+            "{}",
+            key
+        );
+    }
+
+    assert_eq!(
+        HaproxyStats::parse("<html></html>")
+            .unwrap_err()
+            .to_string(),
+        "parse the HAProxy stats failed: no header found"
+    );
+    assert_eq!(
+        HaproxyStats::parse("# pxname,svname,scur\napi,BACKEND,many\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the HAProxy stats failed: invalid scur: \"many\""
+    );
+}
+
+#[test]
+fn haproxy_stats_fetch_values() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://{}/haproxy?stats;csv",
+        listener.local_addr().unwrap()
+    );
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            STATS.len(),
+            STATS
+        )
+        .unwrap();
+    });
+    assert_eq!(
+        HaproxyStats::new(url).fetch_values(),
+        HaproxyStats::parse(STATS)
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn haproxy_stats_socket() {
+    let path = std::env::temp_dir().join(format!(
+        "mackerel-plugin-haproxy-test-{}.sock",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut command = String::new();
+        reader.read_line(&mut command).unwrap();
+        reader.get_mut().write_all(STATS.as_bytes()).unwrap();
+        command
+    });
+    assert_eq!(
+        HaproxyStats::socket(path.to_str().unwrap()).fetch_values(),
+        HaproxyStats::parse(STATS)
+    );
+    assert_eq!(handle.join().unwrap(), "show stat\n");
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,220 @@
+#![cfg(feature = "http")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::sync::Arc;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+
+use mackerel_plugin::{HttpClient, HttpProbe, NginxStatus, Value};
+
+const CA: &str = "tests/testdata/ca.pem";
+const NGINX_STATUS: &str = "Active connections: 291
+server accepts handled requests
+ 16630948 16630948 31070465
+Reading: 6 Writing: 179 Waiting: 106
+";
+
+fn mtls_server() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "https://localhost:{}/status",
+        listener.local_addr().unwrap().port()
+    );
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut roots = rustls::RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_file(CA).unwrap())
+        .unwrap();
+    let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .unwrap();
+    let config = Arc::new(
+        rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(
+                vec![CertificateDer::from_pem_file("tests/testdata/server.pem").unwrap()],
+                PrivateKeyDer::from_pem_file("tests/testdata/server.key").unwrap(),
+            )
+            .unwrap(),
+    );
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let connection = rustls::ServerConnection::new(config.clone()).unwrap();
+            let mut reader = BufReader::new(rustls::StreamOwned::new(connection, stream.unwrap()));
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                line.clear();
+            }
+            let _ = write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                NGINX_STATUS.len(),
+                NGINX_STATUS
+            );
+            let stream = reader.get_mut();
+            stream.conn.send_close_notify();
+            let _ = stream.flush();
+        }
+    });
+    url
+}
+
+#[test]
+fn http_client_mtls() {
+    let url = mtls_server();
+    let client = HttpClient::new()
+        .ca_file(CA)
+        .client_certificate("tests/testdata/client.pem", "tests/testdata/client.key");
+    let values = NginxStatus::new(&url)
+        .client(client)
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values["nginx.connections.connections"], Value::from(291.0));
+
+    let err = NginxStatus::new(&url)
+        .client(HttpClient::new().ca_file(CA))
+        .fetch_values()
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with(&format!("GET {} failed: ", url)), "{}", err);
+
+    let err = NginxStatus::new(&url)
+        .fetch_values()
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("UnknownIssuer"), "{}", err);
+}
+
+#[test]
+fn http_client_load_error() {
+    let client = HttpClient::new().ca_file("tests/testdata/missing.pem");
+    assert_eq!(
+        NginxStatus::new("https://localhost/status")
+            .client(client)
+            .fetch_values()
+            .unwrap_err()
+            .to_string(),
+        "GET https://localhost/status failed: load tests/testdata/missing.pem failed: I/O error: \
+         No such file or directory (os error 2)"
+    );
+
+    let client = HttpClient::new()
+        .client_certificate("tests/testdata/client.key", "tests/testdata/client.key");
+    assert_eq!(
+        NginxStatus::new("https://localhost/status")
+            .client(client)
+            .fetch_values()
+            .unwrap_err()
+            .to_string(),
+        "GET https://localhost/status failed: load tests/testdata/client.key failed: \
+         no certificate"
+    );
+}
+
+#[test]
+fn http_client_proxy() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        reader.read_line(&mut request).unwrap();
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            NGINX_STATUS.len(),
+            NGINX_STATUS
+        )
+        .unwrap();
+        request
+    });
+    let values = NginxStatus::new("http://status.internal/nginx_status")
+        .client(HttpClient::new().proxy(proxy))
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values.len(), 7);
+    assert_eq!(
+        handle.join().unwrap(),
+        "GET http://status.internal/nginx_status HTTP/1.1\r\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn http_client_unix_socket() {
+    let path = std::env::temp_dir().join(format!(
+        "mackerel-plugin-http-test-{}.sock",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    let handle = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (i, stream) in listener.incoming().take(3).enumerate() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut request = String::new();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                request += &line;
+                line.clear();
+            }
+            requests.push(request);
+            let stream = reader.get_mut();
+            match i {
+                0 => {
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"
+                    )
+                    .unwrap();
+                    for chunk in NGINX_STATUS.as_bytes().chunks(40) {
+                        write!(stream, "{:x}\r\n", chunk.len()).unwrap();
+                        stream.write_all(chunk).unwrap();
+                        write!(stream, "\r\n").unwrap();
+                    }
+                    write!(stream, "0\r\n\r\n").unwrap();
+                }
+                _ => write!(stream, "HTTP/1.0 503 Unavailable\r\n\r\nunavailable").unwrap(),
+            }
+        }
+        requests
+    });
+    let client = HttpClient::new().unix_socket(&path);
+    let values = NginxStatus::new("http://localhost/nginx_status")
+        .client(client.clone())
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values.len(), 7);
+    assert_eq!(
+        NginxStatus::new("http://localhost/nginx_status")
+            .client(client.clone())
+            .fetch_values()
+            .unwrap_err()
+            .to_string(),
+        "GET http://localhost/nginx_status failed: status code 503"
+    );
+    let values = HttpProbe::new("docker", "http://docker/_ping")
+        .count(1)
+        .client(client)
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values["http.status.docker.5xx"], Value::from(1.0));
+    assert_eq!(
+        handle.join().unwrap(),
+        [
+            "GET /nginx_status HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n",
+            "GET /nginx_status HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n",
+            "GET /_ping HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n",
+        ]
+    );
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "http")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use mackerel_plugin::{CheckStatus, HttpProbe, Value};
+
+fn mock_server(statuses: Vec<u16>) -> (String, mpsc::Receiver<Vec<String>>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/health", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for status in statuses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut lines = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim_end().is_empty() {
+                    break;
+                }
+                lines.push(line.trim_end().to_owned());
+            }
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {} Status\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                status
+            )
+            .unwrap();
+            tx.send(lines).unwrap();
+        }
+    });
+    (url, rx)
+}
+
+#[test]
+fn http_probe_fetch_values() {
+    let (url, rx) = mock_server(vec![200, 200, 301, 404, 503]);
+    let probe = HttpProbe::new("api.health", url)
+        .method("HEAD")
+        .header("X-Probe", "mackerel")
+        .count(5);
+    let values = probe.fetch_values().unwrap();
+    for (name, count) in [
+        ("2xx", 2.0),
+        ("3xx", 1.0),
+        ("4xx", 1.0),
+        ("5xx", 1.0),
+        ("error", 0.0),
+    ] {
+        assert_eq!(
+            values[&format!("http.status.api_health.{}", name)],
+            Value::Float(count)
+        );
+    }
+    assert_eq!(values["http.availability.api_health"], Value::Float(60.0));
+    let latency = |name: &str| values[&format!("http.latency.api_health.{}", name)].as_f64();
+    assert!(0.0 < latency("p50") && latency("p50") <= latency("p90"));
+    assert!(latency("p90") <= latency("p99") && latency("p99") <= latency("max"));
+    let request = rx.recv().unwrap();
+    assert_eq!(request[0], "HEAD /health HTTP/1.1");
+    assert!(request.contains(&"X-Probe: mackerel".to_owned()));
+}
+
+#[test]
+fn http_probe_fetch_values_error() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    drop(listener);
+    let values = HttpProbe::new("down", url).fetch_values().unwrap();
+    assert_eq!(values["http.status.down.error"], Value::Float(3.0));
+    assert_eq!(values["http.availability.down"], Value::Float(0.0));
+    assert!(!values.contains_key("http.latency.down.max"));
+}
+
+#[test]
+fn http_probe_check() {
+    let (url, _rx) = mock_server(vec![200, 200, 200, 200, 500, 200, 200, 200, 200]);
+    let probe = HttpProbe::new("api", &url[..]);
+    let result = probe.check(Duration::from_secs(5), Duration::from_secs(10));
+    assert_eq!(result.status, CheckStatus::Ok);
+    assert!(result.message.starts_with(&format!(
+        "3 of 3 requests to {} succeeded, max latency ",
+        url
+    )));
+    let result = probe.check(Duration::from_secs(5), Duration::from_secs(10));
+    assert_eq!(result.status, CheckStatus::Warning);
+    let result = probe.check(Duration::ZERO, Duration::ZERO);
+    assert_eq!(result.status, CheckStatus::Critical);
+    let result = probe.check(Duration::from_secs(5), Duration::from_secs(10));
+    assert_eq!(result.status, CheckStatus::Critical);
+    assert!(result
+        .message
+        .starts_with(&format!("0 of 3 requests to {} succeeded (", url)));
+}
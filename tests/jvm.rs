@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use mackerel_plugin::{JvmStats, Value};
+
+const JSTAT_GC: &str = " S0C    S1C    S0U    S1U      EC       EU        OC         OU       MC     MU    CCSC   CCSU   YGC     YGCT    FGC    FGCT    CGC    CGCT     GCT
+ 0.0   2048.0  0.0   2048.0 26624.0   4096.0   233472.0    6214.5  11776.0 11468.5 1280.0 1125.5      3    0.012   0      0.000   2      0.003    0.015
+";
+
+fn values(values: &[(&str, f64)]) -> HashMap<String, Value> {
+    values
+        .iter()
+        .map(|&(key, value)| (key.to_owned(), value.into()))
+        .collect()
+}
+
+#[test]
+fn jvm_stats_parse_jstat() {
+    assert_eq!(
+        JvmStats::new("app.server").parse_jstat(JSTAT_GC),
+        Ok(values(&[
+            ("jvm.app_server.new_space.S0C", 0.0),
+            ("jvm.app_server.new_space.S1C", 2048.0 * 1024.0),
+            ("jvm.app_server.new_space.S0U", 0.0),
+            ("jvm.app_server.new_space.S1U", 2048.0 * 1024.0),
+            ("jvm.app_server.new_space.EC", 26624.0 * 1024.0),
+            ("jvm.app_server.new_space.EU", 4096.0 * 1024.0),
+            ("jvm.app_server.old_space.OC", 233472.0 * 1024.0),
+            ("jvm.app_server.old_space.OU", 6214.5 * 1024.0),
+            ("jvm.app_server.metaspace.MC", 11776.0 * 1024.0),
+            ("jvm.app_server.metaspace.MU", 11468.5 * 1024.0),
+            ("jvm.app_server.metaspace.CCSC", 1280.0 * 1024.0),
+            ("jvm.app_server.metaspace.CCSU", 1125.5 * 1024.0),
+            ("jvm.app_server.gc_events.YGC", 3.0),
+            ("jvm.app_server.gc_time.YGCT", 0.012),
+            ("jvm.app_server.gc_events.FGC", 0.0),
+            ("jvm.app_server.gc_time.FGCT", 0.0),
+            ("jvm.app_server.gc_events.CGC", 2.0),
+            ("jvm.app_server.gc_time.CGCT", 0.003),
+            (
+                "jvm.app_server.heap.used",
+                (2048.0 + 4096.0 + 6214.5) * 1024.0
+            ),
+            (
+                "jvm.app_server.heap.committed",
+                (2048.0 + 26624.0 + 233472.0) * 1024.0
+            ),
+        ]))
+    );
+    // the last sample of jstat -gc with the interval
+    let values = JvmStats::new("app")
+        .parse_jstat(&format!(
+            "{}{}",
+            JSTAT_GC,
+            JSTAT_GC.lines().nth(1).unwrap().replace(" 3 ", " 4 ")
+        ))
+        .unwrap();
+    assert_eq!(values["jvm.app.gc_events.YGC"], Value::Float(4.0));
+    // the columns unavailable in the JVM
+    let values = JvmStats::new("app")
+        .parse_jstat("YGC YGCT CGC CGCT\n1 0.5 - -\n")
+        .unwrap();
+    assert_eq!(values.get("jvm.app.gc_events.CGC"), None);
+    assert_eq!(values["jvm.app.gc_time.YGCT"], Value::Float(0.5));
+}
+
+#[test]
+fn jvm_stats_parse_jstat_error() {
+    assert_eq!(
+        JvmStats::new("app")
+            .parse_jstat("12345 not found\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the jstat output failed: \"12345 not found\""
+    );
+    assert_eq!(
+        JvmStats::new("app")
+            .parse_jstat("YGC YGCT\n1 x\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the jstat output failed: invalid YGCT: \"x\""
+    );
+}
+
+#[test]
+fn jvm_stats_parse_gc_log() {
+    let log = "\
+[0.012s][info][gc] Using G1
+[0.210s][info][gc] GC(0) Pause Young (Normal) (G1 Evacuation Pause) 24M->4M(256M) 3.500ms
+[1.234s][info][gc] GC(1) Pause Young (Concurrent Start) (G1 Humongous Allocation) 30M->6M(256M) 2.500ms
+[1.240s][info][gc] GC(2) Concurrent Mark Cycle
+[1.250s][info][gc] GC(2) Pause Remark 8M->8M(256M) 1.000ms
+[1.260s][info][gc] GC(2) Pause Cleanup 8M->8M(256M) 0.250ms
+[1.270s][info][gc] GC(2) Concurrent Mark Cycle 30.000ms
+[2.000s][info][gc] GC(3) Pause Full (System.gc()) 20M->3M(14M) 12.000ms
+";
+    assert_eq!(
+        JvmStats::new("app").parse_gc_log(log),
+        values(&[
+            ("jvm.app.gc_events.YGC", 2.0),
+            ("jvm.app.gc_time.YGCT", 0.006),
+            ("jvm.app.gc_events.FGC", 1.0),
+            ("jvm.app.gc_time.FGCT", 0.012),
+            ("jvm.app.gc_events.CGC", 2.0),
+            ("jvm.app.gc_time.CGCT", 0.00125),
+            ("jvm.app.heap.used", 3.0 * 1024.0 * 1024.0),
+            ("jvm.app.heap.committed", 14.0 * 1024.0 * 1024.0),
+        ])
+    );
+
+    let log = "\
+2.345: [GC (Allocation Failure) [PSYoungGen: 33280K->5104K(38400K)] 33280K->5120K(125952K), 0.0625000 secs] [Times: user=0.01 sys=0.00, real=0.01 secs]
+3.456: [GC (Allocation Failure) [ParNew: 10K->1K(20K), 0.0010000 secs] 30K->20K(100K), 0.1250000 secs] [Times: user=0.00 sys=0.00, real=0.00 secs]
+4.567: [Full GC (Ergonomics) [PSYoungGen: 5104K->0K(38400K)] [ParOldGen: 16K->4951K(87552K)] 5120K->4951K(125952K), [Metaspace: 2745K->2745K(1056768K)], 0.0250000 secs] [Times: user=0.05 sys=0.00, real=0.03 secs]
+";
+    assert_eq!(
+        JvmStats::new("app").parse_gc_log(log),
+        values(&[
+            ("jvm.app.gc_events.YGC", 2.0),
+            ("jvm.app.gc_time.YGCT", 0.1875),
+            ("jvm.app.gc_events.FGC", 1.0),
+            ("jvm.app.gc_time.FGCT", 0.025),
+            ("jvm.app.gc_events.CGC", 0.0),
+            ("jvm.app.gc_time.CGCT", 0.0),
+            ("jvm.app.heap.used", 4951.0 * 1024.0),
+            ("jvm.app.heap.committed", 125952.0 * 1024.0),
+        ])
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn jvm_stats_fetch_values() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("mackerel-plugin-jvm-test.{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let command = dir.join("jstat");
+    std::fs::write(
+        &command,
+        format!(
+            "#!/bin/sh\n[ \"$*\" = \"-gc 12345\" ] || {{ echo \"$2 not found\"; exit 1; }}\ncat <<EOF\n{}EOF\n",
+            JSTAT_GC
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let stats = JvmStats::new("app").command(&command);
+    assert_eq!(
+        stats.fetch_values(12345),
+        JvmStats::new("app").parse_jstat(JSTAT_GC)
+    );
+    assert_eq!(
+        stats.fetch_values(1).unwrap_err().to_string(),
+        "jstat -gc failed: 1 not found"
+    );
+}
+
+#[test]
+fn jvm_stats_graphs() {
+    let graphs = JvmStats::graphs();
+    assert_eq!(
+        graphs
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec![
+            "jvm.#.gc_events",
+            "jvm.#.gc_time",
+            "jvm.#.heap",
+            "jvm.#.new_space",
+            "jvm.#.old_space",
+            "jvm.#.metaspace",
+        ]
+    );
+    assert!(graphs[0].metrics.iter().all(|metric| metric.diff));
+}
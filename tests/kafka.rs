@@ -0,0 +1,149 @@
+#![cfg(feature = "kafka")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::sync::mpsc;
+
+use mackerel_plugin::{KafkaLag, Value};
+
+fn mock_server(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<String>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for (status, response) in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let path = line.split(' ').nth(1).unwrap().to_owned();
+            while line.trim_end() != "" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {} Status\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response.len(),
+                response
+            )
+            .unwrap();
+            tx.send(path).unwrap();
+        }
+    });
+    (url, rx)
+}
+
+const ORDERS_LAG: &str = r#"{
+  "error": false,
+  "message": "consumer status returned",
+  "status": {
+    "cluster": "local",
+    "group": "orders",
+    "status": "WARN",
+    "partitions": [
+      { "topic": "orders", "partition": 0, "status": "OK", "current_lag": 10 },
+      { "topic": "orders", "partition": 1, "status": "WARN", "current_lag": 25 },
+      { "topic": "orders.retry", "partition": 0, "status": "OK", "current_lag": 0 },
+      { "topic": "orders.retry", "partition": 1, "status": "OK", "current_lag": null }
+    ],
+    "totallag": 35
+  }
+}"#;
+
+#[test]
+fn kafka_lag_fetch_values() {
+    let (url, rx) = mock_server(vec![
+        (
+            200,
+            r#"{"error":false,"consumers":["orders","billing.v2"]}"#,
+        ),
+        (200, ORDERS_LAG),
+        (200, r#"{"error":false,"status":{"partitions":[]}}"#),
+    ]);
+    let values = KafkaLag::new(url, "local")
+        .partitions(true)
+        .fetch_values()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), "/v3/kafka/local/consumer");
+    assert_eq!(rx.recv().unwrap(), "/v3/kafka/local/consumer/orders/lag");
+    assert_eq!(
+        rx.recv().unwrap(),
+        "/v3/kafka/local/consumer/billing.v2/lag"
+    );
+    let mut values = values.into_iter().collect::<Vec<_>>();
+    values.sort_by(|(x, _), (y, _)| x.cmp(y));
+    assert_eq!(
+        values,
+        vec![
+            ("kafka.group_lag.billing_v2".to_owned(), Value::Float(0.0)),
+            ("kafka.group_lag.orders".to_owned(), Value::Float(35.0)),
+            ("kafka.lag.orders.orders".to_owned(), Value::Float(35.0)),
+            (
+                "kafka.lag.orders.orders_retry".to_owned(),
+                Value::Float(0.0)
+            ),
+            ("kafka.max_lag.orders.orders".to_owned(), Value::Float(25.0)),
+            (
+                "kafka.max_lag.orders.orders_retry".to_owned(),
+                Value::Float(0.0)
+            ),
+            (
+                "kafka.partition_lag.orders.orders.0".to_owned(),
+                Value::Float(10.0)
+            ),
+            (
+                "kafka.partition_lag.orders.orders.1".to_owned(),
+                Value::Float(25.0)
+            ),
+            (
+                "kafka.partition_lag.orders.orders_retry.0".to_owned(),
+                Value::Float(0.0)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn kafka_lag_fetch_values_group() {
+    let (url, rx) = mock_server(vec![(200, ORDERS_LAG)]);
+    let values = KafkaLag::new(url, "local")
+        .group("orders")
+        .fetch_values()
+        .unwrap();
+    assert_eq!(rx.recv().unwrap(), "/v3/kafka/local/consumer/orders/lag");
+    assert_eq!(values.len(), 5);
+    assert!(!values.contains_key("kafka.partition_lag.orders.orders.0"));
+}
+
+#[test]
+fn kafka_lag_fetch_values_error() {
+    let (url, _rx) = mock_server(vec![(
+        404,
+        r#"{"error":true,"message":"cluster or consumer not found"}"#,
+    )]);
+    let err = KafkaLag::new(url, "local")
+        .group("unknown")
+        .fetch_values()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "GET /v3/kafka/local/consumer/unknown/lag failed: 404 cluster or consumer not found"
+    );
+}
+
+#[test]
+fn kafka_lag_graphs() {
+    assert_eq!(
+        KafkaLag::graphs()
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec![
+            "kafka.group_lag",
+            "kafka.lag.#",
+            "kafka.max_lag.#",
+            "kafka.partition_lag.#.#"
+        ]
+    );
+}
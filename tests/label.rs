@@ -0,0 +1,43 @@
+use rstest::rstest;
+use std::collections::HashMap;
+
+use mackerel_plugin::{expand_label, Transform};
+
+#[rstest]
+#[case("%1", &["sda"], &[], "sda")]
+#[case("Disk %1", &["sda"], &[], "Disk sda")]
+#[case("%1 on %2", &["sda", "host1"], &[], "sda on host1")]
+#[case("%2 on %1", &["sda", "host1"], &[], "host1 on sda")]
+#[case("%3 100%", &["sda"], &[], "%3 100%")]
+#[case("%0 %", &["sda"], &[], "%0 %")]
+#[case("%1", &["sda"], &[Transform::Uppercase], "SDA")]
+#[case("%1", &["SDA"], &[Transform::Lowercase], "sda")]
+#[case("%1", &["dev_sda"], &[Transform::StripPrefix("dev_".to_owned())], "sda")]
+#[case("%1", &["sda"], &[Transform::StripPrefix("dev_".to_owned())], "sda")]
+#[case(
+    "%1",
+    &["dev_sda"],
+    &[Transform::StripPrefix("dev_".to_owned()), Transform::Uppercase],
+    "SDA"
+)]
+fn label_expand(
+    #[case] label: &str,
+    #[case] values: &[&str],
+    #[case] transforms: &[Transform],
+    #[case] expected: &str,
+) {
+    assert_eq!(expand_label(label, values, transforms), expected);
+}
+
+#[test]
+fn label_expand_map() {
+    let transforms = [Transform::Map(HashMap::from([(
+        "db1".to_owned(),
+        "Primary database".to_owned(),
+    )]))];
+    assert_eq!(
+        expand_label("%1", &["db1"], &transforms),
+        "Primary database"
+    );
+    assert_eq!(expand_label("%1", &["db2"], &transforms), "db2");
+}
@@ -0,0 +1,78 @@
+#![cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+
+use mackerel_plugin::{MemoryStats, Value};
+
+#[cfg(target_os = "linux")]
+fn proc_dir(name: &str, meminfo: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-memory-test.{}.{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("meminfo"), meminfo).unwrap();
+    dir
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn memory_fetch_values() {
+    let dir = proc_dir(
+        "fetch",
+        "MemTotal:        8000000 kB\n\
+         MemFree:         1000000 kB\n\
+         MemAvailable:    5000000 kB\n\
+         Buffers:          200000 kB\n\
+         Cached:          3000000 kB\n\
+         SwapTotal:       2000000 kB\n\
+         SwapFree:        1500000 kB\n\
+         HugePages_Total:       0\n",
+    );
+    let values = MemoryStats::new().proc_dir(&dir).fetch_values().unwrap();
+    assert_eq!(values["memory.used"], Value::Float(3000000.0 * 1024.0));
+    assert_eq!(values["memory.available"], Value::Float(5000000.0 * 1024.0));
+    assert_eq!(values["swap.used"], Value::Float(500000.0 * 1024.0));
+    assert_eq!(values["swap.free"], Value::Float(1500000.0 * 1024.0));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn memory_fetch_values_no_mem_available() {
+    let dir = proc_dir(
+        "no-available",
+        "MemTotal:        8000000 kB\n\
+         MemFree:         1000000 kB\n\
+         Buffers:          200000 kB\n\
+         Cached:          3000000 kB\n\
+         SwapTotal:             0 kB\n\
+         SwapFree:              0 kB\n",
+    );
+    let values = MemoryStats::new().proc_dir(&dir).fetch_values().unwrap();
+    assert_eq!(values["memory.available"], Value::Float(4200000.0 * 1024.0));
+    assert_eq!(values["swap.used"], Value::Float(0.0));
+
+    let dir = proc_dir("no-buffers", "MemFree: 1000000 kB\n");
+    assert_eq!(
+        MemoryStats::new()
+            .proc_dir(&dir)
+            .fetch_values()
+            .unwrap_err()
+            .to_string(),
+        format!("read {} failed: no Buffers", dir.join("meminfo").display())
+    );
+}
+
+#[test]
+fn memory_fetch_values_host() {
+    let values = MemoryStats::new().fetch_values().unwrap();
+    let (Value::Float(used), Value::Float(available)) =
+        (values["memory.used"], values["memory.available"])
+    else {
+        panic!("{:?}", values);
+    };
+    assert!(used > 0.0 && available > 0.0);
+    assert_eq!(values.len(), 4);
+}
@@ -1,4 +1,4 @@
-use mackerel_plugin::{metric, Metric};
+use mackerel_plugin::{metric, Metric, Unit};
 
 #[test]
 fn metric_macro() {
@@ -8,6 +8,9 @@ fn metric_macro() {
             label: label.to_owned(),
             stacked,
             diff,
+            unit: None,
+            threshold: None,
+            precision: None,
         }
     }
 
@@ -56,3 +59,80 @@ fn metric_macro() {
         metric("foo", "Foo metric", true, false)
     );
 }
+
+#[test]
+fn metric_macro_expr_name() {
+    let name = "foo".to_owned() + "_bar";
+    assert_eq!(metric! { name: &name, label: "Foo" }.name, "foo_bar");
+}
+
+#[test]
+#[should_panic(expected = "invalid metric name: foo.bar")]
+fn metric_macro_invalid_expr_name() {
+    let name = "foo".to_owned() + ".bar";
+    metric! { name: &name, label: "Foo" };
+}
+
+#[test]
+fn metric_macro_precision() {
+    assert_eq!(metric! { name: "foo", label: "Foo" }.precision, None);
+    assert_eq!(
+        metric! { name: "foo", label: "Foo", precision: 3 }.precision,
+        Some(3)
+    );
+}
+
+#[test]
+fn metric_macro_unit() {
+    assert_eq!(metric! { name: "foo", label: "Foo" }.unit, None);
+    assert_eq!(
+        metric! { name: "foo", label: "Foo", unit: "bytes/sec" }.unit,
+        Some(Unit::BytesPerSec)
+    );
+    assert_eq!(
+        metric! { name: "foo", label: "Foo", unit: Unit::Seconds }.unit,
+        Some(Unit::Seconds)
+    );
+    assert_eq!(metric! { name: "foo", label: "Foo", unit: None }.unit, None);
+    let metric = metric! { name: "foo", label: "Foo", unit: "bytes", diff: true, };
+    assert_eq!((metric.unit, metric.diff), (Some(Unit::Bytes), true));
+}
+
+#[test]
+fn metric_new() {
+    assert_eq!(
+        Metric::new("foo", "Foo metric"),
+        Ok(metric! { name: "foo", label: "Foo metric" })
+    );
+    assert_eq!(
+        Metric::new("*", "%1"),
+        Ok(metric! { name: "*", label: "%1" })
+    );
+    assert_eq!(
+        Metric::new("foo.bar", "Foo"),
+        Err("invalid metric name: foo.bar".to_owned())
+    );
+    assert_eq!(
+        Metric::new("", "Foo"),
+        Err("invalid metric name: ".to_owned())
+    );
+}
+
+#[test]
+fn metric_deserialize() {
+    assert_eq!(
+        serde_json::from_str::<Metric>(
+            r#"{"name": "foo", "label": "Foo", "stacked": true, "diff": true, "precision": 3}"#
+        )
+        .unwrap(),
+        metric! { name: "foo", label: "Foo", stacked: true, diff: true, precision: 3 }
+    );
+    assert_eq!(
+        serde_json::from_str::<Metric>(
+            r#"{"name": "foo.bar", "label": "Foo", "stacked": false, "diff": false}"#
+        )
+        .unwrap_err()
+        .to_string(),
+        "invalid metric name: foo.bar"
+    );
+}
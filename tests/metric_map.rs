@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use mackerel_plugin::{MetricMap, Value};
+
+#[test]
+fn metric_map_with_prefix() {
+    let mut metrics = MetricMap::new();
+    metrics.insert("uptime", 123.0).insert("up", true);
+    {
+        let mut queue = metrics.with_prefix("queue");
+        queue.insert("size", 10_u64);
+        let mut default = queue.with_prefix("default");
+        default.insert("latency", 0.5).insert("", 1.0);
+    }
+    metrics.with_prefix("").insert("empty", 2.0);
+    assert_eq!(
+        HashMap::from(metrics),
+        HashMap::from([
+            ("uptime".to_owned(), Value::Float(123.0)),
+            ("up".to_owned(), Value::Bool(true)),
+            ("queue.size".to_owned(), Value::Float(10.0)),
+            ("queue.default.latency".to_owned(), Value::Float(0.5)),
+            ("queue.default".to_owned(), Value::Float(1.0)),
+            ("empty".to_owned(), Value::Float(2.0)),
+        ])
+    );
+}
+
+#[test]
+fn metric_map_merge() {
+    let mut metrics = MetricMap::new();
+    metrics.insert("foo", 1.0).insert("bar", 2.0);
+    let mut other = MetricMap::new();
+    other.insert("bar", 3.0).insert("baz", 4.0);
+    metrics.merge(other.clone());
+    metrics.with_prefix("other").merge(other);
+    assert_eq!(metrics.len(), 5);
+    assert_eq!(
+        HashMap::<String, f64>::from(metrics),
+        HashMap::from([
+            ("foo".to_owned(), 1.0),
+            ("bar".to_owned(), 3.0),
+            ("baz".to_owned(), 4.0),
+            ("other.bar".to_owned(), 3.0),
+            ("other.baz".to_owned(), 4.0),
+        ])
+    );
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "json")]
+
+use mackerel_plugin::{matches_metric, MongoDbStats, Value};
+
+const SERVER_STATUS: &str = r#"{
+  "host": "mongo-1",
+  "version": "7.0.5",
+  "connections": {"current": 12, "available": 838848, "totalCreated": 240},
+  "opcounters": {"insert": 100, "query": 2500, "update": 40, "delete": 3, "getmore": 0, "command": 8000},
+  "wiredTiger": {
+    "cache": {
+      "bytes currently in the cache": 104857600,
+      "tracked dirty bytes in the cache": 1048576,
+      "maximum bytes configured": 1073741824,
+      "pages read into cache": 3000,
+      "pages written from cache": 1200
+    }
+  },
+  "ok": 1
+}"#;
+
+#[test]
+fn mongodb_stats_parse() {
+    let values = MongoDbStats::parse(SERVER_STATUS).unwrap();
+    assert_eq!(
+        values["mongodb.connections.connections_current"],
+        Value::from(12.0)
+    );
+    assert_eq!(
+        values["mongodb.opcounters.opcounters_query"],
+        Value::Counter(2500)
+    );
+    assert_eq!(
+        values["mongodb.wiredtiger_cache.bytes_in_cache"],
+        Value::from(104857600.0)
+    );
+    assert_eq!(
+        values["mongodb.wiredtiger_cache_pages.pages_written"],
+        Value::Counter(1200)
+    );
+    assert_eq!(values.len(), 12);
+    let graphs = MongoDbStats::graphs();
+    for (key, value) in &values {
+        let metric = graphs
+            .iter()
+            .flat_map(|graph| {
+                graph.metrics.iter().filter(move |metric| {
+                    matches_metric(&format!("{}.{}", graph.name, metric.name), key)
+                })
+            })
+            .next()
+            .unwrap_or_else(|| panic!("{}", key));
+        assert_eq!(metric.diff, matches!(value, Value::Counter(_)), "{}", key);
+    }
+
+    // the storage engines other than WiredTiger
+    let values = MongoDbStats::parse(r#"{"connections":{"current":1},"ok":1}"#).unwrap();
+    assert_eq!(values.len(), 1);
+
+    assert_eq!(
+        MongoDbStats::parse(r#"{"ok":0,"errmsg":"command serverStatus requires authentication"}"#)
+            .unwrap_err()
+            .to_string(),
+        "parse the MongoDB status failed: command serverStatus requires authentication"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn mongodb_stats_fetch_values() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let command = std::env::temp_dir().join(format!(
+        "mackerel-plugin-mongosh-test-{}.sh",
+        std::process::id()
+    ));
+    std::fs::write(
+        &command,
+        format!(
+            "#!/bin/sh\n[ \"$1\" = mongodb://localhost:27017 ] || {{ echo \"invalid uri: $1\" >&2; exit 1; }}\ncat <<'EOF'\n{}\nEOF\n",
+            SERVER_STATUS
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(
+        MongoDbStats::new("mongodb://localhost:27017")
+            .command(&command)
+            .fetch_values(),
+        MongoDbStats::parse(SERVER_STATUS)
+    );
+    assert_eq!(
+        MongoDbStats::new("mongodb://localhost:27018")
+            .command(&command)
+            .fetch_values()
+            .unwrap_err()
+            .to_string(),
+        "mongosh serverStatus failed: invalid uri: mongodb://localhost:27018"
+    );
+    std::fs::remove_file(&command).unwrap();
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "mysql")]
+
+use std::time::{Duration, Instant};
+
+use mackerel_plugin::{Context, MySqlStats, Value};
+
+/// Returns the URL of the server for the tests, which are skipped when it is
+/// not set.
+fn url() -> Option<String> {
+    std::env::var("MACKEREL_PLUGIN_TEST_MYSQL").ok()
+}
+
+#[test]
+fn mysql_stats_fetch_values() {
+    let Some(url) = url() else {
+        return;
+    };
+    let values = MySqlStats::new(url).fetch_values().unwrap();
+    for key in [
+        "mysql.cmd.Com_select",
+        "mysql.cmd.Questions",
+        "mysql.join.Select_scan",
+        "mysql.threads.Threads_connected",
+        "mysql.connections.Connections",
+        "mysql.table_locks.Slow_queries",
+        "mysql.traffic.Bytes_sent",
+        "mysql.innodb_rows.Innodb_rows_read",
+        "mysql.innodb_buffer_pool.Innodb_buffer_pool_pages_total",
+    ] {
+        assert!(
+            matches!(values.get(key), Some(Value::Float(value)) if *value >= 0.0),
+            "{}",
+            key
+        );
+    }
+    // the connection of this collector is connected
+    assert!(values["mysql.threads.Threads_connected"].as_f64() >= 1.0);
+    let keys = MySqlStats::graphs()
+        .iter()
+        .flat_map(|graph| {
+            graph
+                .metrics
+                .iter()
+                .map(|metric| format!("{}.{}", graph.name, metric.name))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    for key in values.keys() {
+        assert!(keys.contains(key), "{}", key);
+    }
+}
+
+#[test]
+fn mysql_stats_fetch_values_error() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    let err = MySqlStats::new(format!("mysql://root@127.0.0.1:{}", port))
+        .fetch_values()
+        .unwrap_err();
+    assert!(err.to_string().starts_with("connect to MySQL failed: "));
+}
+
+#[test]
+fn mysql_stats_fetch_values_ctx() {
+    // the server accepting the connections but never responding
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let started = Instant::now();
+    let ctx = Context::new().with_deadline(started + Duration::from_millis(200));
+    let err = MySqlStats::new(format!("mysql://root@127.0.0.1:{}", port))
+        .fetch_values_ctx(&ctx)
+        .unwrap_err();
+    assert!(err.to_string().starts_with("connect to MySQL failed: "));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn mysql_stats_graphs() {
+    let graphs = MySqlStats::graphs();
+    assert_eq!(
+        graphs
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec![
+            "mysql.cmd",
+            "mysql.join",
+            "mysql.threads",
+            "mysql.connections",
+            "mysql.seconds_behind_master",
+            "mysql.table_locks",
+            "mysql.traffic",
+            "mysql.innodb_rows",
+            "mysql.innodb_row_lock_time",
+            "mysql.innodb_row_lock_waits",
+            "mysql.innodb_buffer_pool_read",
+            "mysql.innodb_buffer_pool_write",
+            "mysql.innodb_buffer_pool",
+            "mysql.innodb_io",
+        ]
+    );
+    let questions = graphs[0]
+        .metrics
+        .iter()
+        .find(|metric| metric.name == "Questions")
+        .unwrap();
+    assert!(questions.diff && !questions.stacked);
+}
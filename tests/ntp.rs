@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use mackerel_plugin::{NtpOffset, Value};
+
+const CHRONY_TRACKING: &str = "A9FEA97B,169.254.169.123,4,1791766800.123456789,0.000001234,-0.000125000,0.000250000,-12.345,0.001,0.012,0.000500000,0.000250000,64.1,Normal\n";
+
+const NTPQ_RV: &str = r#"associd=0 status=0615 leap_none, sync_ntp, 1 event, clock_sync,
+version="ntpd 4.2.8p15@1.3728-o Wed Feb 16 17:13:02 UTC 2022 (1)",
+processor="x86_64", system="Linux/6.1.0", leap=00, stratum=3,
+precision=-24, rootdelay=12.345, rootdisp=23.456, refid=10.0.0.1,
+reftime=ea1b2c3d.12345678  Fri, Oct 16 2026 10:00:00.071,
+clock=ea1b2c4e.87654321  Fri, Oct 16 2026 10:00:17.530, peer=1234, tc=10,
+mintc=3, offset=-0.123456, frequency=-12.345, sys_jitter=0.234567,
+clk_jitter=0.123, clk_wander=0.005
+"#;
+
+fn values(values: &[(&str, f64)]) -> HashMap<String, Value> {
+    values
+        .iter()
+        .map(|&(key, value)| (key.to_owned(), value.into()))
+        .collect()
+}
+
+#[test]
+fn ntp_offset_parse_chrony() {
+    assert_eq!(
+        NtpOffset::parse_chrony(CHRONY_TRACKING),
+        Ok(values(&[
+            ("ntp.offset.offset", -0.125),
+            ("ntp.offset.jitter", 0.25),
+            ("ntp.root.delay", 0.5),
+            ("ntp.root.dispersion", 0.25),
+            ("ntp.stratum.stratum", 4.0),
+            ("ntp.sync.synchronized", 1.0),
+        ]))
+    );
+    let values = NtpOffset::parse_chrony(
+        "00000000,,0,0.000000000,0.000000000,0.000000000,0.000000000,0.000,0.000,0.000,1.000000000,1.000000000,0.0,Not synchronised\n",
+    )
+    .unwrap();
+    assert_eq!(values["ntp.sync.synchronized"], Value::Float(0.0));
+    assert_eq!(
+        NtpOffset::parse_chrony("506 Cannot talk to daemon\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the chronyc tracking failed: \"506 Cannot talk to daemon\""
+    );
+}
+
+#[test]
+fn ntp_offset_parse_ntpq() {
+    assert_eq!(
+        NtpOffset::parse_ntpq(NTPQ_RV),
+        Ok(values(&[
+            ("ntp.offset.offset", -0.123456),
+            ("ntp.offset.jitter", 0.234567),
+            ("ntp.root.delay", 12.345),
+            ("ntp.root.dispersion", 23.456),
+            ("ntp.stratum.stratum", 3.0),
+            ("ntp.sync.synchronized", 1.0),
+        ]))
+    );
+    let values = NtpOffset::parse_ntpq(
+        "associd=0 status=c016 leap_alarm, sync_unspec, 1 event, restart,\nleap=11, stratum=16, offset=0.000000, sys_jitter=0.000000\n",
+    )
+    .unwrap();
+    assert_eq!(values["ntp.sync.synchronized"], Value::Float(0.0));
+    assert_eq!(
+        NtpOffset::parse_ntpq("ntpq: read: Connection refused\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the ntpq output failed: \"ntpq: read: Connection refused\""
+    );
+    assert_eq!(
+        NtpOffset::parse_ntpq("leap=00, stratum=2, offset=x\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the ntpq output failed: invalid offset: \"x\""
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn ntp_offset_fetch_values() {
+    use mackerel_plugin::NtpDaemon;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("mackerel-plugin-ntp-test.{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, script) in [
+        (
+            "chronyc",
+            format!(
+                "#!/bin/sh\n[ \"$*\" = \"-c tracking\" ] || exit 1\ncat <<EOF\n{}EOF\n",
+                CHRONY_TRACKING
+            ),
+        ),
+        (
+            "ntpq",
+            "#!/bin/sh\necho 'ntpq: read: Connection refused' >&2\nexit 1\n".to_owned(),
+        ),
+    ] {
+        let command = dir.join(name);
+        std::fs::write(&command, script).unwrap();
+        std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    assert_eq!(
+        NtpOffset::new().command(dir.join("chronyc")).fetch_values(),
+        NtpOffset::parse_chrony(CHRONY_TRACKING)
+    );
+    assert_eq!(
+        NtpOffset::new()
+            .daemon(NtpDaemon::Ntpd)
+            .command(dir.join("ntpq"))
+            .fetch_values()
+            .unwrap_err()
+            .to_string(),
+        "ntpq -c rv failed: ntpq: read: Connection refused"
+    );
+}
+
+#[test]
+fn ntp_offset_graphs() {
+    assert_eq!(
+        NtpOffset::graphs()
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec!["ntp.offset", "ntp.root", "ntp.stratum", "ntp.sync"]
+    );
+}
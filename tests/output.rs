@@ -0,0 +1,153 @@
+#![cfg(feature = "json")]
+
+use std::collections::HashMap;
+
+use mackerel_plugin::{
+    graph, parse_definitions, parse_meta_output, parse_values, Error, Graph, Plugin, META_HEADER,
+};
+
+struct TemperaturePlugin {}
+
+impl Plugin for TemperaturePlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::new())
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![
+            graph! {
+                name: "temperature.#",
+                label: "温度 ℃",
+                unit: "float",
+                metrics: [{ name: "*", label: "%1 \"温度\"", stacked: true }]
+            },
+            graph! {
+                name: "fan",
+                label: "Fan",
+                unit: "integer",
+                metrics: [{ name: "speed", label: "Speed" }]
+            },
+        ]
+    }
+}
+
+#[test]
+fn output_parse_meta_output() {
+    let plugin = TemperaturePlugin {};
+    let mut out = Vec::new();
+    plugin.output_definitions(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let mut graphs = plugin.graph_definition();
+    graphs.sort_by(|g1, g2| g1.name.cmp(&g2.name));
+    assert_eq!(parse_meta_output(&out), Ok(graphs.clone()));
+    assert_eq!(
+        parse_meta_output(&format!("\u{feff}{}", out.replace('\n', "\r\n"))),
+        Ok(graphs)
+    );
+}
+
+#[test]
+fn output_parse_meta_output_error() {
+    let header_error = Err(Error::Other(format!(
+        "meta output should start with {:?}",
+        META_HEADER
+    )));
+    assert_eq!(parse_meta_output(""), header_error);
+    assert_eq!(parse_meta_output("{\"graphs\":{}}"), header_error);
+    assert_eq!(
+        parse_meta_output(&format!("{}-extra\n{{\"graphs\":{{}}}}", META_HEADER)),
+        header_error
+    );
+    assert_eq!(
+        parse_meta_output(&format!("{}\n{{\"graphs\":{{}}}}", META_HEADER)),
+        Ok(Vec::new())
+    );
+    assert_eq!(
+        parse_meta_output(&format!("{}\n{{}}", META_HEADER)),
+        Err(Error::Other(
+            "invalid graph definitions: graphs not found".to_owned()
+        ))
+    );
+    assert_eq!(
+        parse_meta_output(&format!(
+            "{}\n{{\"graphs\":{{\"foo\":{{\"label\":\"Foo\",\"unit\":\"meters\",\"metrics\":[]}}}}}}",
+            META_HEADER
+        )),
+        Err(Error::Other(
+            "invalid graph definitions: invalid unit: meters".to_owned()
+        ))
+    );
+    assert_eq!(
+        parse_meta_output(&format!(
+            "{}\n{{\"graphs\":{{\"温度\":{{\"label\":\"温度\",\"unit\":\"float\",\"metrics\":[]}}}}}}",
+            META_HEADER
+        )),
+        Err(Error::Other(
+            "invalid graph definitions: invalid graph name: 温度".to_owned()
+        ))
+    );
+    assert_eq!(
+        parse_meta_output(&format!(
+            "{}\n{{\"graphs\":{{\"foo\":{{\"label\":\"Foo\",\"unit\":\"float\",\"metrics\":[{{\"name\":\"a:b\",\"label\":\"A\"}}]}}}}}}",
+            META_HEADER
+        )),
+        Err(Error::Other(
+            "invalid graph definitions: invalid metric name: a:b".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn output_parse_definitions() {
+    assert_eq!(
+        parse_definitions(
+            "{\"graphs\":{\"fan\":{\"label\":\"Fan\",\"unit\":\"integer\",\"metrics\":[{\"name\":\"speed\",\"label\":\"Speed\"}]}}}"
+        ),
+        Ok(vec![graph! {
+            name: "fan",
+            label: "Fan",
+            unit: "integer",
+            metrics: [{ name: "speed", label: "Speed" }]
+        }])
+    );
+    assert_eq!(
+        parse_definitions("{\"graphs\":{\"fan\":{\"label\":\"Fan\",\"unit\":\"integer\"}}}"),
+        Err(Error::Other(
+            "invalid graph definitions: metrics not found".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn output_parse_values() {
+    let plugin = TemperaturePlugin {};
+    assert_eq!(
+        parse_values("temperature.cpu.core0\t45.5\t1700000000\r\n\nfan.speed\t1200\t1700000000"),
+        Ok(vec![
+            ("temperature.cpu.core0".to_owned(), 45.5, 1700000000),
+            ("fan.speed".to_owned(), 1200.0, 1700000000),
+        ])
+    );
+    let mut out = Vec::new();
+    plugin.output_values(&mut out).unwrap();
+    assert_eq!(
+        parse_values(&String::from_utf8(out).unwrap()),
+        Ok(Vec::new())
+    );
+    assert_eq!(
+        parse_values("fan.speed\t1200"),
+        Err(Error::Other("invalid line: fan.speed\t1200".to_owned()))
+    );
+    assert_eq!(
+        parse_values("fan.speed\tfast\t1700000000"),
+        Err(Error::Other(
+            "invalid value: fan.speed\tfast\t1700000000".to_owned()
+        ))
+    );
+    assert_eq!(
+        parse_values("fan.speed\t1200\tnow"),
+        Err(Error::Other(
+            "invalid timestamp: fan.speed\t1200\tnow".to_owned()
+        ))
+    );
+}
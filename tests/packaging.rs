@@ -0,0 +1,79 @@
+#![cfg(feature = "json")]
+
+use rstest::rstest;
+use serde_json::json;
+
+use mackerel_plugin::Package;
+
+#[rstest]
+#[case(
+    "x86_64-unknown-linux-musl",
+    Ok("mackerel-plugin-dice_linux_amd64.zip")
+)]
+#[case(
+    "aarch64-unknown-linux-gnu",
+    Ok("mackerel-plugin-dice_linux_arm64.zip")
+)]
+#[case(
+    "armv7-unknown-linux-gnueabihf",
+    Ok("mackerel-plugin-dice_linux_arm.zip")
+)]
+#[case("i686-unknown-linux-gnu", Ok("mackerel-plugin-dice_linux_386.zip"))]
+#[case("x86_64-apple-darwin", Ok("mackerel-plugin-dice_darwin_amd64.zip"))]
+#[case("aarch64-apple-darwin", Ok("mackerel-plugin-dice_darwin_arm64.zip"))]
+#[case("x86_64-pc-windows-msvc", Ok("mackerel-plugin-dice_windows_amd64.zip"))]
+#[case("x86_64-unknown-freebsd", Ok("mackerel-plugin-dice_freebsd_amd64.zip"))]
+#[case(
+    "wasm32-unknown-unknown",
+    Err("unsupported target: wasm32-unknown-unknown")
+)]
+#[case("x86_64-unknown-none", Err("unsupported target: x86_64-unknown-none"))]
+fn package_archive_name(#[case] target: &str, #[case] expected: Result<&str, &str>) {
+    let package = Package::new(
+        "mackerel-plugin-dice",
+        "0.1.0",
+        "itchyny/mackerel-plugin-dice",
+    );
+    assert_eq!(
+        package.archive_name(target),
+        expected.map(str::to_owned).map_err(str::to_owned)
+    );
+}
+
+#[test]
+fn package_metadata_json() {
+    let package = Package::new(
+        "mackerel-plugin-dice",
+        "0.1.0",
+        "itchyny/mackerel-plugin-dice",
+    )
+    .description("Dice plugin");
+    assert_eq!(
+        package.registry_json(),
+        json!({ "description": "Dice plugin", "source": "itchyny/mackerel-plugin-dice" })
+    );
+    assert_eq!(
+        package
+            .metadata_json(&["x86_64-unknown-linux-musl", "x86_64-pc-windows-gnu"])
+            .unwrap(),
+        json!({
+            "name": "mackerel-plugin-dice",
+            "version": "0.1.0",
+            "install": "mkr plugin install itchyny/mackerel-plugin-dice@v0.1.0",
+            "registry": { "description": "Dice plugin", "source": "itchyny/mackerel-plugin-dice" },
+            "archives": [
+                {
+                    "target": "x86_64-unknown-linux-musl",
+                    "file": "mackerel-plugin-dice_linux_amd64.zip",
+                    "entries": ["mackerel-plugin-dice_linux_amd64/mackerel-plugin-dice"],
+                },
+                {
+                    "target": "x86_64-pc-windows-gnu",
+                    "file": "mackerel-plugin-dice_windows_amd64.zip",
+                    "entries": ["mackerel-plugin-dice_windows_amd64/mackerel-plugin-dice.exe"],
+                },
+            ],
+        })
+    );
+    assert!(package.metadata_json(&["wasm32-wasi"]).is_err());
+}
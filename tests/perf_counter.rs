@@ -0,0 +1,29 @@
+#![cfg(windows)]
+
+use std::time::Duration;
+
+use mackerel_plugin::{PerfCounters, Value};
+
+#[test]
+fn perf_counters_fetch_values() {
+    let values = PerfCounters::new()
+        .counter("cpu.total", r"\Processor(_Total)\% Processor Time")
+        .counter("cpu.processor", r"\Processor(*)\% Processor Time")
+        .interval(Duration::from_millis(100))
+        .fetch_values()
+        .unwrap();
+    assert!(matches!(values["cpu.total"], Value::Float(value) if value >= 0.0));
+    assert!(values.contains_key("cpu.processor._Total"));
+    assert!(values.contains_key("cpu.processor.0"));
+}
+
+#[test]
+fn perf_counters_fetch_values_error() {
+    let result = PerfCounters::new()
+        .counter("unknown", r"\Unknown Object\Unknown Counter")
+        .fetch_values();
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .starts_with(r"add the counter \Unknown Object\Unknown Counter failed: 0x"));
+}
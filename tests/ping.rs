@@ -0,0 +1,45 @@
+#![cfg(all(feature = "ping", target_os = "linux"))]
+
+use mackerel_plugin::{PingProbe, PingProtocol, Value};
+
+#[test]
+fn ping_probe_udp() {
+    let values = PingProbe::new()
+        .target("localhost", "127.0.0.1")
+        .protocol(PingProtocol::Udp)
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values.len(), 4);
+    assert_eq!(values["ping.loss.localhost"], Value::Float(0.0));
+    let rtt = |stat: &str| values[&format!("ping.rtt.localhost.{}", stat)].as_f64();
+    assert!(0.0 < rtt("min") && rtt("min") <= rtt("avg") && rtt("avg") <= rtt("max"));
+}
+
+#[test]
+fn ping_probe_icmp() {
+    // the ICMP sockets may not be permitted in the environment
+    match PingProbe::new()
+        .target("localhost", "127.0.0.1")
+        .fetch_values()
+    {
+        Ok(values) => assert_eq!(values["ping.loss.localhost"], Value::Float(0.0)),
+        Err(err) => assert!(err.to_string().contains("net.ipv4.ping_group_range")),
+    }
+    let values = PingProbe::new()
+        .target("localhost", "127.0.0.1")
+        .udp_fallback(true)
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values["ping.loss.localhost"], Value::Float(0.0));
+}
+
+#[test]
+fn ping_probe_resolve_error() {
+    assert_eq!(
+        PingProbe::new()
+            .target("unknown", "unknown.invalid")
+            .fetch_values()
+            .map_err(|err| err.to_string()),
+        Err("ping unknown.invalid failed: cannot resolve the host".to_owned())
+    );
+}
@@ -1,8 +1,17 @@
+use rstest::rstest;
 use serde_json::json;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use mackerel_plugin::{graph, Graph, Plugin};
+#[cfg(feature = "json")]
+use mackerel_plugin::PrefixMigration;
+use mackerel_plugin::{
+    graph, Clock, Context, Error, Filter, Graph, LtsvSink, MemoryStateStore, MetricSink, Plugin,
+    Rename, StaleAction, Staleness, SyncPlugin, Timestamping, Transform, Unit, Value, META_HEADER,
+};
 
 struct DicePlugin {}
 
@@ -57,18 +66,34 @@ fn plugin_output_values() {
     );
 }
 
+#[test]
+fn plugin_output_values_json() {
+    let plugin = DicePlugin {};
+    let mut out = Cursor::new(Vec::new());
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.output_values_json(&mut out, &MemoryStateStore::new(), &now),
+        Ok(())
+    );
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&out.into_inner()).unwrap(),
+        json!([
+            { "name": "dice.d6", "value": 3, "time": 1700000000 },
+            { "name": "dice.d20", "value": 17, "time": 1700000000 }
+        ])
+    );
+}
+
 #[test]
 fn plugin_output_definitions() {
     let plugin = DicePlugin {};
     let mut out = Cursor::new(Vec::new());
     assert!(plugin.output_definitions(&mut out).is_ok());
     let out_str = String::from_utf8(out.into_inner()).unwrap();
-    assert!(out_str.starts_with("# mackerel-agent-plugin\n"));
+    assert!(out_str.starts_with(&format!("{}\n", META_HEADER)));
     assert_eq!(
-        serde_json::from_str::<serde_json::Value>(
-            out_str.chars().skip(24).collect::<String>().as_ref()
-        )
-        .unwrap(),
+        serde_json::from_str::<serde_json::Value>(out_str.strip_prefix(META_HEADER).unwrap())
+            .unwrap(),
         json!({
             "graphs": {
                 "dice": {
@@ -156,12 +181,10 @@ fn wildcard_plugin_output_definitions() {
     let mut out = Cursor::new(Vec::new());
     assert!(plugin.output_definitions(&mut out).is_ok());
     let out_str = String::from_utf8(out.into_inner()).unwrap();
-    assert!(out_str.starts_with("# mackerel-agent-plugin\n"));
+    assert!(out_str.starts_with(&format!("{}\n", META_HEADER)));
     assert_eq!(
-        serde_json::from_str::<serde_json::Value>(
-            out_str.chars().skip(24).collect::<String>().as_ref()
-        )
-        .unwrap(),
+        serde_json::from_str::<serde_json::Value>(out_str.strip_prefix(META_HEADER).unwrap())
+            .unwrap(),
         json!({
             "graphs": {
                 "inode.percentage.#": {
@@ -239,12 +262,10 @@ fn prefix_plugin_output_definitions() {
     let mut out = Cursor::new(Vec::new());
     assert!(plugin.output_definitions(&mut out).is_ok());
     let out_str = String::from_utf8(out.into_inner()).unwrap();
-    assert!(out_str.starts_with("# mackerel-agent-plugin\n"));
+    assert!(out_str.starts_with(&format!("{}\n", META_HEADER)));
     assert_eq!(
-        serde_json::from_str::<serde_json::Value>(
-            out_str.chars().skip(24).collect::<String>().as_ref()
-        )
-        .unwrap(),
+        serde_json::from_str::<serde_json::Value>(out_str.strip_prefix(META_HEADER).unwrap())
+            .unwrap(),
         json!({
             "graphs": {
                 "inode.percentage.#": {
@@ -302,12 +323,10 @@ fn empty_graph_name_plugin_output_definitions() {
     let mut out = Cursor::new(Vec::new());
     assert!(plugin.output_definitions(&mut out).is_ok());
     let out_str = String::from_utf8(out.into_inner()).unwrap();
-    assert!(out_str.starts_with("# mackerel-agent-plugin\n"));
+    assert!(out_str.starts_with(&format!("{}\n", META_HEADER)));
     assert_eq!(
-        serde_json::from_str::<serde_json::Value>(
-            out_str.chars().skip(24).collect::<String>().as_ref()
-        )
-        .unwrap(),
+        serde_json::from_str::<serde_json::Value>(out_str.strip_prefix(META_HEADER).unwrap())
+            .unwrap(),
         json!({
             "graphs": {
                 "uptime": {
@@ -322,6 +341,127 @@ fn empty_graph_name_plugin_output_definitions() {
     );
 }
 
+struct BorrowedGraphsPlugin(Vec<Graph>);
+
+impl Plugin for BorrowedGraphsPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([("dice.d6".to_owned(), 3.0)]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        unreachable!("the graphs should be borrowed")
+    }
+
+    fn graph_definition_ref(&self) -> Cow<'_, [Graph]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn metric_key_prefix(&self) -> String {
+        "borrowed".to_owned()
+    }
+}
+
+#[test]
+fn borrowed_graphs_plugin_output() {
+    let plugin = BorrowedGraphsPlugin(DicePlugin {}.graph_definition());
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(plugin.output_values(&mut out), Ok(()));
+    let out_str = String::from_utf8(out.into_inner()).unwrap();
+    assert!(out_str.starts_with("borrowed.dice.d6\t3\t"));
+
+    let mut out = Cursor::new(Vec::new());
+    assert!(plugin.output_definitions(&mut out).is_ok());
+    let out_str = String::from_utf8(out.into_inner()).unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(out_str.strip_prefix(META_HEADER).unwrap())
+            .unwrap()["graphs"]["borrowed.dice"]["label"],
+        "My Dice"
+    );
+    assert_eq!(
+        plugin.resolve("dice.d6"),
+        Some(("borrowed.dice".to_owned(), "d6".to_owned()))
+    );
+}
+
+struct PrecisionPlugin {
+    unit_precision: bool,
+}
+
+impl Plugin for PrecisionPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([
+            ("cpu.user".to_owned(), 100.0 / 3.0),
+            ("cpu.idle".to_owned(), 0.125),
+            ("cpu.steal".to_owned(), 0.375),
+            ("load.avg".to_owned(), 2.0 / 3.0),
+            ("load.procs".to_owned(), 12.5),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![
+            graph! {
+                name: "cpu",
+                label: "CPU",
+                unit: "percentage",
+                metrics: [
+                    { name: "user", label: "User" },
+                    { name: "idle", label: "Idle" },
+                    { name: "steal", label: "Steal", precision: 0 },
+                ]
+            },
+            graph! {
+                name: "load",
+                label: "Load",
+                unit: "float",
+                metrics: [
+                    { name: "avg", label: "Average" },
+                    { name: "procs", label: "Processes", unit: "integer" },
+                ]
+            },
+        ]
+    }
+
+    fn precision(&self, unit: &Unit) -> Option<u32> {
+        match unit {
+            _ if !self.unit_precision => None,
+            Unit::Integer => Some(0),
+            unit => unit.recommended_precision(),
+        }
+    }
+}
+
+#[rstest]
+#[case(true, vec![
+    "cpu.idle\t0.12\t1700000000",
+    "cpu.steal\t0\t1700000000",
+    "cpu.user\t33.33\t1700000000",
+    "load.avg\t0.6666666666666666\t1700000000",
+    "load.procs\t12\t1700000000",
+])]
+#[case(false, vec![
+    "cpu.idle\t0.125\t1700000000",
+    "cpu.steal\t0\t1700000000",
+    "cpu.user\t33.333333333333336\t1700000000",
+    "load.avg\t0.6666666666666666\t1700000000",
+    "load.procs\t12.5\t1700000000",
+])]
+fn precision_plugin_output_values(#[case] unit_precision: bool, #[case] expected: Vec<&str>) {
+    let mut out = Cursor::new(Vec::new());
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        PrecisionPlugin { unit_precision }.run_with(&mut out, MemoryStateStore::new(), now),
+        Ok(())
+    );
+    let mut lines = String::from_utf8(out.into_inner())
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    lines.sort();
+    assert_eq!(lines, expected);
+}
+
 struct DiffMetricPlugin {}
 
 impl Plugin for DiffMetricPlugin {
@@ -388,3 +528,1528 @@ fn diff_metric_plugin_output_values() {
     }
     let _ = std::fs::remove_file(plugin.tempfile_path("").unwrap());
 }
+
+struct CounterPlugin {
+    count: std::cell::Cell<f64>,
+}
+
+impl Plugin for CounterPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        self.count.set(self.count.get() + 120.0);
+        Ok(HashMap::from([(
+            "counter.value".to_owned(),
+            self.count.get(),
+        )]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "counter",
+            label: "Counter",
+            unit: "integer",
+            metrics: [
+                { name: "value", label: "value", diff: true },
+            ]
+        }]
+    }
+}
+
+#[test]
+fn plugin_run_with() {
+    let plugin = CounterPlugin {
+        count: std::cell::Cell::new(0.0),
+    };
+    let state = MemoryStateStore::new();
+    let now = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1700000000);
+    {
+        let mut out = Cursor::new(Vec::new());
+        assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+        assert_eq!(String::from_utf8(out.into_inner()).unwrap(), "");
+    }
+    {
+        let mut out = Cursor::new(Vec::new());
+        let now = now + std::time::Duration::from_secs(60);
+        assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+        assert_eq!(
+            String::from_utf8(out.into_inner()).unwrap(),
+            "counter.value\t120\t1700000060\n"
+        );
+    }
+}
+
+struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+struct AuditPlugin {
+    audit: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Plugin for AuditPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        DicePlugin {}.fetch_metrics()
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        DicePlugin {}.graph_definition()
+    }
+
+    fn secondary_sinks(&self) -> Vec<Box<dyn MetricSink>> {
+        vec![Box::new(LtsvSink::new(SharedWriter(self.audit.clone())))]
+    }
+}
+
+#[test]
+fn plugin_secondary_sinks() {
+    let plugin = AuditPlugin {
+        audit: Arc::new(Mutex::new(Vec::new())),
+    };
+    let mut out = Cursor::new(Vec::new());
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.run_with(&mut out, MemoryStateStore::new(), now),
+        Ok(())
+    );
+    assert_eq!(
+        String::from_utf8(out.into_inner()).unwrap(),
+        "dice.d6\t3\t1700000000\ndice.d20\t17\t1700000000\n"
+    );
+    assert_eq!(
+        String::from_utf8(plugin.audit.lock().unwrap().clone()).unwrap(),
+        "name:dice.d6\tvalue:3\ttime:1700000000\nname:dice.d20\tvalue:17\ttime:1700000000\n"
+    );
+}
+
+struct SelfMetricsPlugin {
+    fail: bool,
+}
+
+impl Plugin for SelfMetricsPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        if self.fail {
+            return Err("connection refused".to_owned());
+        }
+        Ok(HashMap::from([
+            ("dice.d6".to_owned(), 3.0),
+            ("dice.d20".to_owned(), f64::NAN),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        DicePlugin {}.graph_definition()
+    }
+
+    fn metric_key_prefix(&self) -> String {
+        "dice".to_owned()
+    }
+
+    fn emit_self_metrics(&self) -> bool {
+        true
+    }
+}
+
+#[rstest]
+#[case(false, Ok(()), "dice.dice.d6\t3\t1700000000\ndice.mackerel_plugin.fetch.metrics\t2\t1700000000\ndice.mackerel_plugin.fetch.dropped\t1\t1700000000\ndice.mackerel_plugin.fetch.errors\t0\t1700000000\n")]
+#[case(true, Err(Error::Other("connection refused".to_owned())), "dice.mackerel_plugin.fetch.metrics\t0\t1700000000\ndice.mackerel_plugin.fetch.dropped\t0\t1700000000\ndice.mackerel_plugin.fetch.errors\t1\t1700000000\n")]
+fn self_metrics_plugin_output_values(
+    #[case] fail: bool,
+    #[case] result: Result<(), Error>,
+    #[case] expected: &str,
+) {
+    let plugin = SelfMetricsPlugin { fail };
+    let mut out = Cursor::new(Vec::new());
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.output_values_with(&mut out, &MemoryStateStore::new(), &now),
+        result
+    );
+    let out = String::from_utf8(out.into_inner()).unwrap();
+    let (out, duration) = out.split_at(out.find("dice.mackerel_plugin.duration.fetch\t").unwrap());
+    assert_eq!(out, expected);
+    assert!(duration.ends_with("\t1700000000\n"));
+}
+
+#[test]
+fn self_metrics_plugin_output_definitions() {
+    let plugin = SelfMetricsPlugin { fail: false };
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(plugin.output_definitions(&mut out), Ok(()));
+    let out = String::from_utf8(out.into_inner()).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(out.strip_prefix(META_HEADER).unwrap()).unwrap();
+    assert_eq!(
+        json["graphs"]["dice.mackerel_plugin.duration"],
+        json!({
+            "label": "Plugin duration",
+            "unit": "seconds",
+            "metrics": [{ "name": "*", "label": "%1", "stacked": false }]
+        })
+    );
+    assert_eq!(json["graphs"].as_object().unwrap().len(), 3);
+}
+
+struct AlignedCounterPlugin {
+    count: std::cell::Cell<f64>,
+}
+
+impl Plugin for AlignedCounterPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        self.count.set(self.count.get() + 120.0);
+        Ok(HashMap::from([(
+            "counter.value".to_owned(),
+            self.count.get(),
+        )]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        CounterPlugin {
+            count: std::cell::Cell::new(0.0),
+        }
+        .graph_definition()
+    }
+
+    fn align_timestamps(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn aligned_plugin_output_values() {
+    let plugin = AlignedCounterPlugin {
+        count: std::cell::Cell::new(0.0),
+    };
+    let state = MemoryStateStore::new();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000035);
+    {
+        let mut out = Cursor::new(Vec::new());
+        assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+        assert_eq!(String::from_utf8(out.into_inner()).unwrap(), "");
+    }
+    {
+        let mut out = Cursor::new(Vec::new());
+        let now = now + Duration::from_secs(45);
+        assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+        assert_eq!(
+            String::from_utf8(out.into_inner()).unwrap(),
+            "counter.value\t160\t1700000040\n"
+        );
+    }
+}
+
+#[rstest]
+#[case(b"not a json".as_slice())]
+#[case(br#"{"timestamp":-9223372036854775808,"values":{"counter.value":1e308}}"#.as_slice())]
+#[case(br#"{"timestamp":9223372036854775807,"values":{"counter.value":-1e308}}"#.as_slice())]
+fn plugin_corrupt_state(#[case] bytes: &[u8]) {
+    use mackerel_plugin::StateStore;
+    let plugin = CounterPlugin {
+        count: std::cell::Cell::new(0.0),
+    };
+    let state = MemoryStateStore::new();
+    let path = plugin.tempfile_path("").unwrap();
+    assert_eq!(state.save(&path, bytes), Ok(()));
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+    assert_eq!(String::from_utf8(out.into_inner()).unwrap(), "");
+}
+
+struct FailingWriter {
+    write_error: Option<std::io::ErrorKind>,
+    flush_error: Option<std::io::ErrorKind>,
+}
+
+impl std::io::Write for FailingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_error
+            .map_or(Ok(buf.len()), |kind| Err(kind.into()))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flush_error.map_or(Ok(()), |kind| Err(kind.into()))
+    }
+}
+
+#[test]
+fn plugin_output_write_errors() {
+    let plugin = DicePlugin {};
+    for (kind, err, exit_code) in [
+        (std::io::ErrorKind::BrokenPipe, Error::BrokenPipe, 141),
+        (std::io::ErrorKind::StorageFull, Error::StorageFull, 1),
+        (
+            std::io::ErrorKind::PermissionDenied,
+            Error::Write("permission denied".to_owned()),
+            1,
+        ),
+    ] {
+        let mut out = FailingWriter {
+            write_error: Some(kind),
+            flush_error: None,
+        };
+        assert_eq!(plugin.output_values(&mut out), Err(err.clone()));
+        assert_eq!(plugin.output_definitions(&mut out), Err(err.clone()));
+        let out = FailingWriter {
+            write_error: None,
+            flush_error: Some(kind),
+        };
+        let state = MemoryStateStore::new();
+        assert_eq!(
+            plugin.run_with(out, &state, std::time::SystemTime::now()),
+            Err(err.clone())
+        );
+        assert_eq!(err.exit_code(), exit_code);
+    }
+}
+
+struct FilteredPlugin {}
+
+impl Plugin for FilteredPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([
+            ("interface.eth0.rx".to_owned(), 100.0),
+            ("interface.eth1.rx".to_owned(), 200.0),
+            ("interface.veth1a2b.rx".to_owned(), 300.0),
+            ("interface.lo.rx".to_owned(), 400.0),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "interface.#",
+            label: "Interface",
+            unit: "bytes/sec",
+            metrics: [
+                { name: "rx", label: "rx" },
+            ]
+        }]
+    }
+
+    fn wildcard_filter(&self, _: &Graph) -> Filter {
+        Filter::new().include("*eth*").exclude("veth*")
+    }
+}
+
+#[test]
+fn filtered_plugin_output_values() {
+    let plugin = FilteredPlugin {};
+    let mut out = Cursor::new(Vec::new());
+    let now = current_epoch();
+    assert_eq!(plugin.output_values(&mut out), Ok(()));
+    let out_str = String::from_utf8(out.into_inner()).unwrap();
+    assert!(out_str.contains(&format!("{}\t{}\t{}\n", "interface.eth0.rx", 100.0, now)));
+    assert!(out_str.contains(&format!("{}\t{}\t{}\n", "interface.eth1.rx", 200.0, now)));
+    assert!(!out_str.contains("interface.veth1a2b.rx"));
+    assert!(!out_str.contains("interface.lo.rx"));
+}
+
+struct DeviceFilteredPlugin {}
+
+impl Plugin for DeviceFilteredPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([
+            ("disk.sda.read".to_owned(), 100.0),
+            ("disk.nvme0n1.read".to_owned(), 200.0),
+            ("interface.eth0.rx".to_owned(), 300.0),
+            ("interface.veth1a2b.rx".to_owned(), 400.0),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![
+            graph! {
+                name: "disk.#",
+                label: "Disk",
+                unit: "integer",
+                metrics: [{ name: "read", label: "read" }]
+            },
+            graph! {
+                name: "interface.#",
+                label: "Interface",
+                unit: "bytes/sec",
+                metrics: [{ name: "rx", label: "rx" }]
+            },
+        ]
+    }
+
+    fn wildcard_filter(&self, graph: &Graph) -> Filter {
+        match graph.name.as_str() {
+            "disk.#" => Filter::new().include("sd*"),
+            _ => Filter::new().exclude("veth*"),
+        }
+    }
+}
+
+#[test]
+fn device_filtered_plugin_output_values() {
+    let plugin = DeviceFilteredPlugin {};
+    let mut out = Vec::new();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.output_values_with(&mut out, &MemoryStateStore::new(), &now),
+        Ok(())
+    );
+    let mut lines = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    lines.sort();
+    assert_eq!(
+        lines,
+        vec![
+            "disk.sda.read\t100\t1700000000",
+            "interface.eth0.rx\t300\t1700000000",
+        ]
+    );
+}
+
+#[test]
+fn plugin_resolve() {
+    let plugin = InodePlugin {};
+    assert_eq!(
+        plugin.resolve("inode.percentage.sda1.used"),
+        Some(("inode.percentage.#".to_owned(), "used".to_owned()))
+    );
+    assert_eq!(
+        plugin.resolve("inode.count.sda1.used"),
+        Some(("inode.count.sda1".to_owned(), "*".to_owned()))
+    );
+    assert_eq!(plugin.resolve("inode.percentage.sda1.free"), None);
+    assert_eq!(plugin.resolve("inode.percentage..used"), None);
+
+    let plugin = PrefixPlugin {};
+    assert_eq!(
+        plugin.resolve("percentage.sda1.used"),
+        Some(("inode.percentage.#".to_owned(), "used".to_owned()))
+    );
+    assert_eq!(plugin.resolve("count.sda1.used"), None);
+
+    let plugin = FilteredPlugin {};
+    assert_eq!(
+        plugin.resolve("interface.eth0.rx"),
+        Some(("interface.#".to_owned(), "rx".to_owned()))
+    );
+    assert_eq!(plugin.resolve("interface.veth1a2b.rx"), None);
+}
+
+struct LabelPlugin {}
+
+impl Plugin for LabelPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([
+            ("disk.dev_sda.read".to_owned(), 100.0),
+            ("disk.dev_sdb.read".to_owned(), 200.0),
+            ("disk.dev_sdb.write".to_owned(), 300.0),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "disk.#",
+            label: "Disk",
+            unit: "integer",
+            metrics: [
+                { name: "read", label: "%1 read" },
+            ]
+        }]
+    }
+
+    fn metric_key_prefix(&self) -> String {
+        "custom".to_owned()
+    }
+
+    fn label_transforms(&self) -> Vec<Transform> {
+        vec![
+            Transform::StripPrefix("dev_".to_owned()),
+            Transform::Uppercase,
+        ]
+    }
+}
+
+#[test]
+fn plugin_series_labels() {
+    let plugin = LabelPlugin {};
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.series_labels(&MemoryStateStore::new(), &now),
+        Ok(HashMap::from([
+            ("custom.disk.dev_sda.read".to_owned(), "SDA read".to_owned()),
+            ("custom.disk.dev_sdb.read".to_owned(), "SDB read".to_owned()),
+        ]))
+    );
+}
+
+struct ExpandedPlugin {}
+
+impl Plugin for ExpandedPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([
+            ("disk.dev_sdb.read".to_owned(), 200.0),
+            ("disk.dev_sda.read".to_owned(), 100.0),
+            ("queue.mail".to_owned(), 3.0),
+            ("uptime".to_owned(), 3600.0),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![
+            graph! {
+                name: "disk.#",
+                label: "Disk %1",
+                unit: "integer",
+                metrics: [{ name: "read", label: "%1 read", diff: true }]
+            },
+            graph! {
+                name: "queue",
+                label: "Queue",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1 jobs" }]
+            },
+            graph! {
+                name: "",
+                label: "Uptime",
+                unit: "seconds",
+                metrics: [{ name: "uptime", label: "Uptime" }]
+            },
+        ]
+    }
+
+    fn label_transforms(&self) -> Vec<Transform> {
+        vec![Transform::StripPrefix("dev_".to_owned())]
+    }
+
+    fn expand_definitions(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn plugin_expanded_graph_definition() {
+    let plugin = ExpandedPlugin {};
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.expanded_graph_definition(&MemoryStateStore::new(), &now),
+        Ok(vec![
+            graph! {
+                name: "disk.dev_sda",
+                label: "Disk sda",
+                unit: "integer",
+                metrics: [{ name: "read", label: "sda read", diff: true }]
+            },
+            graph! {
+                name: "disk.dev_sdb",
+                label: "Disk sdb",
+                unit: "integer",
+                metrics: [{ name: "read", label: "sdb read", diff: true }]
+            },
+            graph! {
+                name: "queue",
+                label: "Queue",
+                unit: "integer",
+                metrics: [{ name: "mail", label: "mail jobs" }]
+            },
+            graph! {
+                name: "",
+                label: "Uptime",
+                unit: "seconds",
+                metrics: [{ name: "uptime", label: "Uptime" }]
+            },
+        ])
+    );
+
+    let mut out = Cursor::new(Vec::new());
+    assert!(plugin.output_definitions(&mut out).is_ok());
+    let out_str = String::from_utf8(out.into_inner()).unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(out_str.strip_prefix(META_HEADER).unwrap())
+            .unwrap()["graphs"]["disk.dev_sda"],
+        json!({
+            "label": "Disk sda",
+            "metrics": [{ "name": "read", "label": "sda read", "stacked": false }],
+            "unit": "integer"
+        })
+    );
+}
+
+struct RenamePlugin {}
+
+impl Plugin for RenamePlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([
+            ("old.foo".to_owned(), 100.0),
+            ("legacy.bar".to_owned(), 200.0),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![
+            graph! {
+                name: "new",
+                label: "New",
+                unit: "integer",
+                metrics: [
+                    { name: "foo", label: "foo" },
+                ]
+            },
+            graph! {
+                name: "*",
+                label: "Bar",
+                unit: "integer",
+                metrics: [
+                    { name: "bar", label: "bar" },
+                ]
+            },
+        ]
+    }
+
+    fn metric_renames(&self) -> Result<Vec<Rename>, String> {
+        Ok(vec![
+            Rename::new("old", "new"),
+            Rename::new("legacy", "modern").keep_old(),
+        ])
+    }
+}
+
+#[test]
+fn rename_plugin_output_values() {
+    let plugin = RenamePlugin {};
+    let mut out = Cursor::new(Vec::new());
+    let now = current_epoch();
+    assert_eq!(plugin.output_values(&mut out), Ok(()));
+    let out_str = String::from_utf8(out.into_inner()).unwrap();
+    assert!(out_str.contains(&format!("{}\t{}\t{}\n", "new.foo", 100.0, now)));
+    assert!(!out_str.contains("old.foo"));
+    assert!(out_str.contains(&format!("{}\t{}\t{}\n", "modern.bar", 200.0, now)));
+    assert!(out_str.contains(&format!("{}\t{}\t{}\n", "legacy.bar", 200.0, now)));
+}
+
+#[cfg(feature = "json")]
+struct MigrationPlugin {}
+
+#[cfg(feature = "json")]
+impl Plugin for MigrationPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([("foo.bar".to_owned(), 100.0)]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "foo",
+            label: "Foo",
+            unit: "integer",
+            metrics: [
+                { name: "bar", label: "bar" },
+            ]
+        }]
+    }
+
+    fn metric_key_prefix(&self) -> String {
+        "new".to_owned()
+    }
+
+    fn prefix_migration(&self) -> Option<PrefixMigration> {
+        Some(PrefixMigration::new(
+            "old",
+            std::time::Duration::from_secs(3600),
+        ))
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn migration_plugin_output_values() {
+    let plugin = MigrationPlugin {};
+    let state = MemoryStateStore::new();
+    let now = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1700000000);
+    for (elapsed, expected) in [
+        (
+            0,
+            "new.foo.bar\t100\t1700000000\nold.foo.bar\t100\t1700000000\n",
+        ),
+        (
+            3599,
+            "new.foo.bar\t100\t1700003599\nold.foo.bar\t100\t1700003599\n",
+        ),
+        (3600, "new.foo.bar\t100\t1700003600\n"),
+    ] {
+        let mut out = Cursor::new(Vec::new());
+        let now = now + std::time::Duration::from_secs(elapsed);
+        assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+        assert_eq!(String::from_utf8(out.into_inner()).unwrap(), expected);
+    }
+}
+
+struct ValuePlugin {
+    counter: std::cell::Cell<u32>,
+}
+
+impl Plugin for ValuePlugin {
+    fn fetch_values(&self) -> Result<HashMap<String, Value>, String> {
+        self.counter.set(self.counter.get().wrapping_add(600));
+        Ok(HashMap::from([
+            ("value.up".to_owned(), true.into()),
+            (
+                "value.latency".to_owned(),
+                Duration::from_millis(250).into(),
+            ),
+            (
+                "value.counter32".to_owned(),
+                Value::counter32(self.counter.get()),
+            ),
+            (
+                "value.counter".to_owned(),
+                Value::counter(self.counter.get() as u64),
+            ),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "value",
+            label: "Value",
+            unit: "float",
+            metrics: [
+                { name: "up", label: "up" },
+                { name: "latency", label: "latency" },
+                { name: "counter32", label: "counter32", diff: true },
+                { name: "counter", label: "counter", diff: true },
+            ]
+        }]
+    }
+}
+
+#[test]
+fn value_plugin_output_values() {
+    let plugin = ValuePlugin {
+        counter: std::cell::Cell::new(u32::MAX - 899),
+    };
+    let state = MemoryStateStore::new();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    {
+        let mut out = Cursor::new(Vec::new());
+        assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+        assert_eq!(
+            String::from_utf8(out.into_inner()).unwrap(),
+            "value.up\t1\t1700000000\nvalue.latency\t0.25\t1700000000\n"
+        );
+    }
+    {
+        let mut out = Cursor::new(Vec::new());
+        let now = now + Duration::from_secs(60);
+        assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+        assert_eq!(
+            String::from_utf8(out.into_inner()).unwrap(),
+            "value.up\t1\t1700000060\nvalue.latency\t0.25\t1700000060\nvalue.counter32\t600\t1700000060\n"
+        );
+    }
+    // the counters decreased by the restart of the source are not reported
+    for (elapsed, counter, expected) in [
+        (
+            120,
+            1_000_000,
+            "value.up\t1\t1700000120\nvalue.latency\t0.25\t1700000120\nvalue.counter32\t1000300\t1700000120\nvalue.counter\t1000300\t1700000120\n",
+        ),
+        (
+            180,
+            0,
+            "value.up\t1\t1700000180\nvalue.latency\t0.25\t1700000180\n",
+        ),
+    ] {
+        plugin.counter.set(counter);
+        let mut out = Cursor::new(Vec::new());
+        let now = now + Duration::from_secs(elapsed);
+        assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+        assert_eq!(String::from_utf8(out.into_inner()).unwrap(), expected);
+    }
+}
+
+struct TickingClock {
+    time: Mutex<std::time::SystemTime>,
+}
+
+impl Clock for TickingClock {
+    fn now(&self) -> std::time::SystemTime {
+        let mut time = self.time.lock().unwrap();
+        let now = *time;
+        *time = now + Duration::from_secs(30);
+        now
+    }
+}
+
+struct TimestampPlugin {
+    timestamping: Timestamping,
+}
+
+impl Plugin for TimestampPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([
+            ("foo.bar".to_owned(), 1.0),
+            ("foo.baz".to_owned(), 2.0),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "foo",
+            label: "Foo",
+            unit: "integer",
+            metrics: [
+                { name: "bar", label: "bar" },
+                { name: "baz", label: "baz" },
+            ]
+        }]
+    }
+
+    fn timestamping(&self) -> Timestamping {
+        self.timestamping
+    }
+
+    fn source_time(&self) -> Option<std::time::SystemTime> {
+        Some(std::time::UNIX_EPOCH + Duration::from_secs(1699999990))
+    }
+}
+
+#[rstest]
+#[case(Timestamping::BeforeFetch, 1700000000)]
+#[case(Timestamping::AfterFetch, 1700000030)]
+#[case(Timestamping::Source, 1699999990)]
+fn timestamp_plugin_output_values(#[case] timestamping: Timestamping, #[case] timestamp: i64) {
+    let plugin = TimestampPlugin { timestamping };
+    let clock = TickingClock {
+        time: Mutex::new(std::time::UNIX_EPOCH + Duration::from_secs(1700000000)),
+    };
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(
+        plugin.run_with(&mut out, MemoryStateStore::new(), &clock),
+        Ok(())
+    );
+    assert_eq!(
+        String::from_utf8(out.into_inner()).unwrap(),
+        format!("foo.bar\t1\t{}\nfoo.baz\t2\t{}\n", timestamp, timestamp)
+    );
+}
+
+struct StalePlugin {
+    action: StaleAction,
+}
+
+impl Plugin for StalePlugin {
+    fn fetch_values(&self) -> Result<HashMap<String, Value>, String> {
+        let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+        Ok(HashMap::from([
+            ("stats.fresh".to_owned(), Value::from((1.0, now))),
+            (
+                "stats.stale".to_owned(),
+                Value::from((2.0, now - Duration::from_secs(301))),
+            ),
+            ("stats.local".to_owned(), Value::from(3.0)),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "stats",
+            label: "Stats",
+            unit: "integer",
+            metrics: [
+                { name: "fresh", label: "fresh" },
+                { name: "stale", label: "stale" },
+                { name: "local", label: "local" },
+            ]
+        }]
+    }
+
+    fn staleness(&self) -> Option<Staleness> {
+        Some(Staleness {
+            max_age: Duration::from_secs(300),
+            action: self.action,
+        })
+    }
+}
+
+#[rstest]
+#[case(
+    StaleAction::Drop,
+    "stats.fresh\t1\t1700000000\nstats.local\t3\t1700000000\n"
+)]
+#[case(
+    StaleAction::Warn,
+    "stats.fresh\t1\t1700000000\nstats.stale\t2\t1700000000\nstats.local\t3\t1700000000\n"
+)]
+fn stale_plugin_output_values(#[case] action: StaleAction, #[case] expected: &str) {
+    let plugin = StalePlugin { action };
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(
+        plugin.run_with(&mut out, MemoryStateStore::new(), now),
+        Ok(())
+    );
+    assert_eq!(String::from_utf8(out.into_inner()).unwrap(), expected);
+}
+
+#[cfg(feature = "json")]
+struct TopPlugin {
+    runs: std::cell::RefCell<Vec<Vec<(&'static str, f64)>>>,
+    rollup: bool,
+}
+
+#[cfg(feature = "json")]
+impl Plugin for TopPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(self
+            .runs
+            .borrow_mut()
+            .remove(0)
+            .into_iter()
+            .map(|(name, value)| ("process.".to_owned() + name + ".cpu", value))
+            .chain([("total.cpu".to_owned(), 100.0)])
+            .collect())
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![
+            graph! {
+                name: "process.#",
+                label: "Process",
+                unit: "percentage",
+                metrics: [
+                    { name: "cpu", label: "cpu" },
+                ]
+            },
+            graph! {
+                name: "total",
+                label: "Total",
+                unit: "percentage",
+                metrics: [
+                    { name: "cpu", label: "cpu" },
+                ]
+            },
+        ]
+    }
+
+    fn series_limit(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn series_rollup(&self) -> bool {
+        self.rollup
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn top_plugin_output_values() {
+    let plugin = TopPlugin {
+        runs: std::cell::RefCell::new(vec![
+            vec![("a", 10.0), ("b", 9.0), ("c", 8.0), ("d", 2.0), ("e", 1.0)],
+            vec![("a", 10.0), ("b", 8.0), ("c", 9.0), ("d", 2.0), ("e", 1.0)],
+            vec![("a", 10.0), ("b", 0.5), ("c", 9.0), ("d", 2.0), ("e", 1.0)],
+        ]),
+        rollup: false,
+    };
+    let state = MemoryStateStore::new();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    for expected in [
+        ["process.a.cpu", "process.b.cpu", "total.cpu"],
+        ["process.a.cpu", "process.b.cpu", "total.cpu"],
+        ["process.a.cpu", "process.c.cpu", "total.cpu"],
+    ] {
+        let mut out = Cursor::new(Vec::new());
+        assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+        let out_str = String::from_utf8(out.into_inner()).unwrap();
+        let mut names = out_str
+            .lines()
+            .map(|line| line.split('\t').next().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, expected);
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn top_plugin_rollup_output_values() {
+    let plugin = TopPlugin {
+        runs: std::cell::RefCell::new(vec![vec![
+            ("a", 10.0),
+            ("b", 9.0),
+            ("c", 8.0),
+            ("d", 2.0),
+            ("e", 1.0),
+        ]]),
+        rollup: true,
+    };
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(
+        plugin.run_with(&mut out, MemoryStateStore::new(), now),
+        Ok(())
+    );
+    let out_str = String::from_utf8(out.into_inner()).unwrap();
+    let mut lines = out_str.lines().collect::<Vec<_>>();
+    lines.sort();
+    assert_eq!(
+        lines,
+        [
+            "process.a.cpu\t10\t1700000000",
+            "process.b.cpu\t9\t1700000000",
+            "process.other.cpu\t11\t1700000000",
+            "total.cpu\t100\t1700000000",
+        ]
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn top_plugin_rollup_other_output_values() {
+    let plugin = TopPlugin {
+        runs: std::cell::RefCell::new(vec![vec![
+            ("a", 10.0),
+            ("other", 9.5),
+            ("b", 9.0),
+            ("c", 8.0),
+            ("d", 2.0),
+        ]]),
+        rollup: true,
+    };
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(
+        plugin.run_with(&mut out, MemoryStateStore::new(), now),
+        Ok(())
+    );
+    let out_str = String::from_utf8(out.into_inner()).unwrap();
+    let mut lines = out_str.lines().collect::<Vec<_>>();
+    lines.sort();
+    assert_eq!(
+        lines,
+        [
+            "process.a.cpu\t10\t1700000000",
+            "process.other.cpu\t28.5\t1700000000",
+            "total.cpu\t100\t1700000000",
+        ]
+    );
+}
+
+#[cfg(feature = "json")]
+struct RollupCollisionPlugin {}
+
+#[cfg(feature = "json")]
+impl Plugin for RollupCollisionPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([
+            ("process.other.cpu".to_owned(), 1.0),
+            ("process.a.cpu".to_owned(), 10.0),
+            ("process.b.cpu".to_owned(), 9.0),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![
+            graph! {
+                name: "process.other",
+                label: "Other process",
+                unit: "percentage",
+                metrics: [{ name: "cpu", label: "cpu" }]
+            },
+            graph! {
+                name: "process.#",
+                label: "Process",
+                unit: "percentage",
+                metrics: [{ name: "cpu", label: "cpu" }]
+            },
+        ]
+    }
+
+    fn wildcard_filter(&self, _: &Graph) -> Filter {
+        Filter::new().exclude("other")
+    }
+
+    fn series_limit(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn series_rollup(&self) -> bool {
+        true
+    }
+
+    fn strict(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn rollup_collision_plugin_output_values() {
+    let plugin = RollupCollisionPlugin {};
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.run_with(Vec::new(), MemoryStateStore::new(), now),
+        Err(
+            "duplicate metric names: process.other.cpu matches graphs process.other and process.#"
+                .into()
+        )
+    );
+}
+
+#[cfg(feature = "json")]
+struct DiskTopPlugin {}
+
+#[cfg(feature = "json")]
+impl Plugin for DiskTopPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok([
+            ("sda", 10.0, 0.0),
+            ("sdb", 1.0, 8.0),
+            ("sdc", 5.0, 5.0),
+            ("sdd", 9.0, 9.0),
+        ]
+        .into_iter()
+        .flat_map(|(device, read, write)| {
+            [
+                (format!("disk.{}.read", device), read),
+                (format!("disk.{}.write", device), write),
+            ]
+        })
+        .collect())
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "disk.#",
+            label: "Disk",
+            unit: "integer",
+            metrics: [
+                { name: "read", label: "read" },
+                { name: "write", label: "write" },
+            ]
+        }]
+    }
+
+    fn series_limit(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn series_rollup(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn disk_top_plugin_output_values() {
+    let plugin = DiskTopPlugin {};
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let mut out = Vec::new();
+    assert_eq!(
+        plugin.run_with(&mut out, MemoryStateStore::new(), now),
+        Ok(())
+    );
+    let out_str = String::from_utf8(out).unwrap();
+    let mut lines = out_str.lines().collect::<Vec<_>>();
+    lines.sort();
+    assert_eq!(
+        lines,
+        [
+            "disk.other.read\t6\t1700000000",
+            "disk.other.write\t13\t1700000000",
+            "disk.sda.read\t10\t1700000000",
+            "disk.sda.write\t0\t1700000000",
+            "disk.sdd.read\t9\t1700000000",
+            "disk.sdd.write\t9\t1700000000",
+        ]
+    );
+}
+
+struct ContextPlugin {
+    previous: std::cell::RefCell<Vec<(Option<i64>, Option<f64>)>>,
+}
+
+impl Plugin for ContextPlugin {
+    fn fetch_metrics_ctx(&self, ctx: &Context) -> Result<HashMap<String, Value>, String> {
+        self.previous.borrow_mut().push((
+            ctx.previous_timestamp(),
+            ctx.previous_value("counter.value"),
+        ));
+        let value = ctx.previous_value("counter.value").unwrap_or_default() + 120.0;
+        Ok(HashMap::from([("counter.value".to_owned(), value.into())]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        CounterPlugin {
+            count: std::cell::Cell::new(0.0),
+        }
+        .graph_definition()
+    }
+}
+
+#[test]
+fn context_plugin_output_values() {
+    let plugin = ContextPlugin {
+        previous: std::cell::RefCell::new(Vec::new()),
+    };
+    let state = MemoryStateStore::new();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+    let now = now + Duration::from_secs(60);
+    assert_eq!(plugin.run_with(&mut out, &state, now), Ok(()));
+    assert_eq!(
+        String::from_utf8(out.into_inner()).unwrap(),
+        "counter.value\t120\t1700000060\n"
+    );
+    assert_eq!(
+        plugin.previous.into_inner(),
+        vec![(None, None), (Some(1700000000), Some(120.0))]
+    );
+}
+
+struct PartitionedPlugin {
+    parallel: bool,
+    failing: &'static [&'static str],
+}
+
+impl Plugin for PartitionedPlugin {
+    fn fetch_per_graph(&self) -> bool {
+        true
+    }
+
+    fn fetch_metrics_for(&self, graph: &Graph) -> Result<HashMap<String, Value>, String> {
+        if self.failing.contains(&&graph.name[..]) {
+            return Err(format!("{} unavailable", graph.name));
+        }
+        Ok(HashMap::from([(
+            graph.name.clone() + ".d6",
+            Value::Float(graph.name.len() as f64),
+        )]))
+    }
+
+    fn parallel_fetch(&self) -> Option<&dyn SyncPlugin> {
+        if self.parallel {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        ["dice", "coin"]
+            .into_iter()
+            .map(|name| {
+                graph! {
+                    name: name,
+                    label: "Dice",
+                    unit: "integer",
+                    metrics: [{ name: "d6", label: "Die 6" }]
+                }
+            })
+            .collect()
+    }
+
+    fn emit_self_metrics(&self) -> bool {
+        true
+    }
+}
+
+#[rstest]
+#[case(false, &[], Ok(()), "dice.d6\t4\t1700000000\ncoin.d6\t4\t1700000000\nmackerel_plugin.fetch.metrics\t2\t1700000000\nmackerel_plugin.fetch.dropped\t0\t1700000000\nmackerel_plugin.fetch.errors\t0\t1700000000\n")]
+#[case(true, &[], Ok(()), "dice.d6\t4\t1700000000\ncoin.d6\t4\t1700000000\nmackerel_plugin.fetch.metrics\t2\t1700000000\nmackerel_plugin.fetch.dropped\t0\t1700000000\nmackerel_plugin.fetch.errors\t0\t1700000000\n")]
+#[case(false, &["dice"], Ok(()), "coin.d6\t4\t1700000000\nmackerel_plugin.fetch.metrics\t1\t1700000000\nmackerel_plugin.fetch.dropped\t0\t1700000000\nmackerel_plugin.fetch.errors\t1\t1700000000\n")]
+#[case(true, &["coin"], Ok(()), "dice.d6\t4\t1700000000\nmackerel_plugin.fetch.metrics\t1\t1700000000\nmackerel_plugin.fetch.dropped\t0\t1700000000\nmackerel_plugin.fetch.errors\t1\t1700000000\n")]
+#[case(true, &["dice", "coin"], Err(Error::Other("dice unavailable".to_owned())), "mackerel_plugin.fetch.metrics\t0\t1700000000\nmackerel_plugin.fetch.dropped\t0\t1700000000\nmackerel_plugin.fetch.errors\t1\t1700000000\n")]
+fn partitioned_plugin_output_values(
+    #[case] parallel: bool,
+    #[case] failing: &'static [&'static str],
+    #[case] result: Result<(), Error>,
+    #[case] expected: &str,
+) {
+    let plugin = PartitionedPlugin { parallel, failing };
+    let mut out = Cursor::new(Vec::new());
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.output_values_with(&mut out, &MemoryStateStore::new(), &now),
+        result
+    );
+    let out = String::from_utf8(out.into_inner()).unwrap();
+    let (out, _) = out.split_at(out.find("mackerel_plugin.duration.fetch\t").unwrap());
+    assert_eq!(out, expected);
+}
+
+#[cfg(feature = "json")]
+struct CachedPlugin {
+    fetches: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(feature = "json")]
+impl Plugin for CachedPlugin {
+    fn fetch_values(&self) -> Result<HashMap<String, Value>, String> {
+        let count = self
+            .fetches
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Ok(HashMap::from([
+            ("dice.d6".to_owned(), Value::Float(count as f64)),
+            ("dice.d20".to_owned(), Value::Counter32(17)),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        DicePlugin {}.graph_definition()
+    }
+
+    fn metric_key_prefix(&self) -> String {
+        "cached".to_owned()
+    }
+
+    fn fetch_cache_ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(30))
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn cached_plugin_output_values() {
+    let plugin = CachedPlugin {
+        fetches: Default::default(),
+    };
+    let state = MemoryStateStore::new();
+    for (secs, expected) in [
+        (
+            1700000000,
+            "cached.dice.d6\t0\t1700000000\ncached.dice.d20\t17\t1700000000\n",
+        ),
+        (
+            1700000020,
+            "cached.dice.d6\t0\t1700000020\ncached.dice.d20\t17\t1700000020\n",
+        ),
+        (
+            1700000060,
+            "cached.dice.d6\t1\t1700000060\ncached.dice.d20\t17\t1700000060\n",
+        ),
+    ] {
+        let mut out = Cursor::new(Vec::new());
+        let now = std::time::UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(plugin.output_values_with(&mut out, &state, &now), Ok(()));
+        assert_eq!(String::from_utf8(out.into_inner()).unwrap(), expected);
+    }
+    assert_eq!(plugin.fetches.load(std::sync::atomic::Ordering::Relaxed), 2);
+}
+
+struct StrictPlugin {
+    names: &'static [&'static str],
+}
+
+impl Plugin for StrictPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(self
+            .names
+            .iter()
+            .map(|&name| (name.to_owned(), 1.0))
+            .collect())
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "disk.#",
+            label: "Disk",
+            unit: "integer",
+            metrics: [{ name: "read", label: "Read" }]
+        }]
+    }
+
+    fn strict(&self) -> bool {
+        true
+    }
+}
+
+#[rstest]
+#[case(&["disk.sda.read"], Ok(()))]
+#[case(&["disk.sda.read", "disk.sda.raed", "dsk.sdb.read"], Err(Error::Other("unmatched metric names: disk.sda.raed, dsk.sdb.read".to_owned())))]
+fn strict_plugin_output_values(
+    #[case] names: &'static [&'static str],
+    #[case] result: Result<(), Error>,
+) {
+    let plugin = StrictPlugin { names };
+    let mut out = Cursor::new(Vec::new());
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.output_values_with(&mut out, &MemoryStateStore::new(), &now),
+        result
+    );
+}
+
+struct DuplicatePlugin {
+    strict: bool,
+}
+
+impl Plugin for DuplicatePlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([
+            ("inode.count.sda1.used".to_owned(), 100.0),
+            ("inode.count.sdb1.used".to_owned(), 200.0),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![
+            graph! {
+                name: "inode.count.sda1",
+                label: "Inode sda1",
+                unit: "integer",
+                metrics: [{ name: "*", label: "%1" }]
+            },
+            graph! {
+                name: "inode.count.#",
+                label: "Inode",
+                unit: "integer",
+                metrics: [{ name: "used", label: "Used" }]
+            },
+        ]
+    }
+
+    fn strict(&self) -> bool {
+        self.strict
+    }
+
+    fn emit_self_metrics(&self) -> bool {
+        true
+    }
+}
+
+#[rstest]
+#[case(false, Ok(()), "inode.count.sda1.used\t100\t1700000000\ninode.count.sdb1.used\t200\t1700000000\nmackerel_plugin.fetch.metrics\t2\t1700000000\nmackerel_plugin.fetch.dropped\t1\t1700000000\nmackerel_plugin.fetch.errors\t0\t1700000000\n")]
+#[case(true, Err(Error::Other("duplicate metric names: inode.count.sda1.used matches graphs inode.count.sda1 and inode.count.#".to_owned())), "mackerel_plugin.fetch.metrics\t2\t1700000000\nmackerel_plugin.fetch.dropped\t0\t1700000000\nmackerel_plugin.fetch.errors\t1\t1700000000\n")]
+fn duplicate_plugin_output_values(
+    #[case] strict: bool,
+    #[case] result: Result<(), Error>,
+    #[case] expected: &str,
+) {
+    let plugin = DuplicatePlugin { strict };
+    let mut out = Cursor::new(Vec::new());
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    assert_eq!(
+        plugin.output_values_with(&mut out, &MemoryStateStore::new(), &now),
+        result
+    );
+    let out = String::from_utf8(out.into_inner()).unwrap();
+    let (out, _) = out.split_at(out.find("mackerel_plugin.duration.fetch\t").unwrap());
+    assert_eq!(out, expected);
+}
+
+struct MixedStackingPlugin {
+    strict: bool,
+}
+
+impl Plugin for MixedStackingPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::new())
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "memory",
+            label: "Memory",
+            unit: "bytes",
+            metrics: [
+                { name: "used", label: "Used", stacked: true },
+                { name: "total", label: "Total" },
+            ]
+        }]
+    }
+
+    fn strict(&self) -> bool {
+        self.strict
+    }
+}
+
+#[test]
+fn mixed_stacking_plugin_output_definitions() {
+    let plugin = MixedStackingPlugin { strict: false };
+    assert_eq!(
+        plugin.graph_warnings(),
+        vec!["graph memory mixes the stacked and unstacked metrics"]
+    );
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(plugin.output_definitions(&mut out), Ok(()));
+
+    let plugin = MixedStackingPlugin { strict: true };
+    let mut out = Cursor::new(Vec::new());
+    assert_eq!(
+        plugin.output_definitions(&mut out),
+        Err(Error::Other(
+            "graph memory mixes the stacked and unstacked metrics".to_owned()
+        ))
+    );
+    assert!(out.into_inner().is_empty());
+}
+
+struct QueuePlugin {
+    escape: char,
+    strict: bool,
+}
+
+impl Plugin for QueuePlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([
+            ("queue.jobs.high.depth".to_owned(), 3.0),
+            ("queue.mail:retry.depth".to_owned(), 5.0),
+            ("queue.a_b.depth".to_owned(), 1.0),
+            ("queue.a:b.depth".to_owned(), 2.0),
+        ]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "queue.#",
+            label: "Queue",
+            unit: "integer",
+            metrics: [{ name: "depth", label: "Depth" }]
+        }]
+    }
+
+    fn escape_metric_names(&self) -> Option<char> {
+        Some(self.escape)
+    }
+
+    fn strict(&self) -> bool {
+        self.strict
+    }
+}
+
+#[rstest]
+#[case('_', false, Ok(vec![
+    "queue.a_b.depth\t1\t1700000000",
+    "queue.jobs_high.depth\t3\t1700000000",
+    "queue.mail_retry.depth\t5\t1700000000",
+]))]
+#[case('-', false, Ok(vec![
+    "queue.a-b.depth\t2\t1700000000",
+    "queue.a_b.depth\t1\t1700000000",
+    "queue.jobs-high.depth\t3\t1700000000",
+    "queue.mail-retry.depth\t5\t1700000000",
+]))]
+#[case('_', true, Err(Error::Other(
+    "colliding metric names: queue.a:b.depth is escaped to queue.a_b.depth".to_owned()
+)))]
+#[case('.', false, Err(Error::Other("invalid escape character: '.'".to_owned())))]
+fn queue_plugin_output_values(
+    #[case] escape: char,
+    #[case] strict: bool,
+    #[case] expected: Result<Vec<&str>, Error>,
+) {
+    let plugin = QueuePlugin { escape, strict };
+    let mut out = Cursor::new(Vec::new());
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let result = plugin.output_values_with(&mut out, &MemoryStateStore::new(), &now);
+    let out = String::from_utf8(out.into_inner()).unwrap();
+    let mut lines = out.lines().collect::<Vec<_>>();
+    lines.sort_unstable();
+    assert_eq!(result.map(|_| lines), expected);
+}
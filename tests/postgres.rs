@@ -0,0 +1,132 @@
+#![cfg(feature = "postgres")]
+
+use std::time::{Duration, Instant};
+
+use mackerel_plugin::{Context, PostgresStats, Value};
+
+/// Returns the connection string of the server for the tests, which are
+/// skipped when it is not set.
+fn config() -> Option<String> {
+    std::env::var("MACKEREL_PLUGIN_TEST_POSTGRES").ok()
+}
+
+#[test]
+fn postgres_stats_fetch_values() {
+    let Some(config) = config() else {
+        return;
+    };
+    let values = PostgresStats::new(config).fetch_values().unwrap();
+    for key in [
+        "postgres.connections.active",
+        "postgres.connections.idle_in_transaction_aborted",
+        "postgres.commits.xact_commit",
+        "postgres.blocks.blks_hit",
+        "postgres.rows.tup_returned",
+        "postgres.size.total_size",
+        "postgres.deadlocks.deadlocks",
+        "postgres.iotime.blk_read_time",
+        "postgres.tempfile.temp_bytes",
+        "postgres.xlog_location.xlog_location_bytes",
+        "postgres.bgwriter.checkpoints_timed",
+        "postgres.bgwriter.buffers_alloc",
+    ] {
+        assert!(
+            matches!(values.get(key), Some(Value::Float(value)) if *value >= 0.0),
+            "{}",
+            key
+        );
+    }
+    // the connection of this collector is active
+    assert!(values["postgres.connections.active"].as_f64() >= 1.0);
+    assert!(values["postgres.size.total_size"].as_f64() > 0.0);
+    let keys = PostgresStats::graphs()
+        .iter()
+        .flat_map(|graph| {
+            graph
+                .metrics
+                .iter()
+                .map(|metric| format!("{}.{}", graph.name, metric.name))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    for key in values.keys() {
+        assert!(
+            keys.contains(key) || key.starts_with("postgres.replication_lag."),
+            "{}",
+            key
+        );
+    }
+}
+
+#[test]
+fn postgres_stats_fetch_values_database() {
+    let Some(config) = config() else {
+        return;
+    };
+    let values = PostgresStats::new(config)
+        .database("unknown")
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values["postgres.size.total_size"], Value::Float(0.0));
+    assert_eq!(values["postgres.commits.xact_commit"], Value::Float(0.0));
+}
+
+#[test]
+fn postgres_stats_fetch_values_error() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    let err = PostgresStats::new(format!("host=127.0.0.1 port={} user=postgres", port))
+        .fetch_values()
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("connect to PostgreSQL failed: "));
+}
+
+#[test]
+fn postgres_stats_fetch_values_ctx() {
+    // the server accepting the connections but never responding
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let started = Instant::now();
+    let ctx = Context::new().with_deadline(started + Duration::from_millis(200));
+    let err = PostgresStats::new(format!("host=127.0.0.1 port={} user=postgres", port))
+        .fetch_values_ctx(&ctx)
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("connect to PostgreSQL failed: "));
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let Some(config) = config() else {
+        return;
+    };
+    let ctx = Context::new().with_deadline(Instant::now() + Duration::from_secs(10));
+    let values = PostgresStats::new(config).fetch_values_ctx(&ctx).unwrap();
+    assert!(values.contains_key("postgres.connections.active"));
+}
+
+#[test]
+fn postgres_stats_graphs() {
+    assert_eq!(
+        PostgresStats::graphs()
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec![
+            "postgres.connections",
+            "postgres.commits",
+            "postgres.blocks",
+            "postgres.rows",
+            "postgres.size",
+            "postgres.deadlocks",
+            "postgres.iotime",
+            "postgres.tempfile",
+            "postgres.xlog_location",
+            "postgres.bgwriter",
+            "postgres.replication_lag",
+            "postgres.replay_delay",
+        ]
+    );
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use mackerel_plugin::{Context, Processes, Value};
+
+fn fake_proc(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-process-test.{}.{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let process = |pid: &str, comm: &str, ticks: (u64, u64), rss: u64, cmdline: &str, cgroup| {
+        let dir = dir.join(pid);
+        std::fs::create_dir_all(dir.join("fd")).unwrap();
+        std::fs::write(
+            dir.join("stat"),
+            format!(
+                "{} ({}) S 1 {} {} 0 -1 4194560 100 0 0 0 {} {} 0 0 20 0 1 0 100 1000 10\n",
+                pid, comm, pid, pid, ticks.0, ticks.1
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("status"),
+            format!("Name:\t{}\nVmRSS:\t    {} kB\nThreads:\t1\n", comm, rss),
+        )
+        .unwrap();
+        std::fs::write(dir.join("cmdline"), cmdline.replace(' ', "\0") + "\0").unwrap();
+        std::fs::write(dir.join("cgroup"), cgroup).unwrap();
+        for fd in 0..3 {
+            std::fs::write(dir.join("fd").join(fd.to_string()), "").unwrap();
+        }
+    };
+    process(
+        "100",
+        "nginx",
+        (300, 60),
+        2048,
+        "nginx: master process /usr/sbin/nginx",
+        "0::/system.slice/nginx.service\n",
+    );
+    process(
+        "101",
+        "nginx",
+        (1200, 240),
+        4096,
+        "nginx: worker process",
+        "0::/system.slice/nginx.service\n",
+    );
+    process(
+        "200",
+        "app (worker)",
+        (60, 0),
+        1024,
+        "/usr/bin/app --worker",
+        "0::/system.slice/app.service/worker\n",
+    );
+    std::fs::create_dir_all(dir.join("self")).unwrap();
+    std::fs::write(dir.join("uptime"), "").unwrap();
+    dir
+}
+
+fn values(dir: &Path, processes: Processes) -> HashMap<String, Value> {
+    processes.proc_dir(dir).fetch_values().unwrap()
+}
+
+#[test]
+fn processes_fetch_values() {
+    let dir = fake_proc("values");
+    assert_eq!(
+        values(
+            &dir,
+            Processes::new()
+                .name("nginx", "nginx")
+                .name("app worker", "app (worker)")
+                .cgroup("app", "/system.slice/app.service/")
+                .cgroup("none", "/system.slice/app")
+        ),
+        HashMap::from([
+            ("process.count.nginx".to_owned(), Value::Float(2.0)),
+            ("process.cpu.nginx".to_owned(), Value::Float(30.0)),
+            ("process.rss.nginx".to_owned(), Value::Float(6291456.0)),
+            ("process.fds.nginx".to_owned(), Value::Float(6.0)),
+            ("process.count.app_worker".to_owned(), Value::Float(1.0)),
+            ("process.cpu.app_worker".to_owned(), Value::Float(1.0)),
+            ("process.rss.app_worker".to_owned(), Value::Float(1048576.0)),
+            ("process.fds.app_worker".to_owned(), Value::Float(3.0)),
+            ("process.count.app".to_owned(), Value::Float(1.0)),
+            ("process.cpu.app".to_owned(), Value::Float(1.0)),
+            ("process.rss.app".to_owned(), Value::Float(1048576.0)),
+            ("process.fds.app".to_owned(), Value::Float(3.0)),
+            ("process.count.none".to_owned(), Value::Float(0.0)),
+            ("process.cpu.none".to_owned(), Value::Float(0.0)),
+            ("process.rss.none".to_owned(), Value::Float(0.0)),
+            ("process.fds.none".to_owned(), Value::Float(0.0)),
+        ])
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn processes_fetch_values_regex() {
+    let dir = fake_proc("regex");
+    let values = values(
+        &dir,
+        Processes::new().regex("workers", regex::Regex::new(r"\bworker\b").unwrap()),
+    );
+    assert_eq!(values["process.count.workers"], Value::Float(2.0));
+    assert_eq!(values["process.rss.workers"], Value::Float(5242880.0));
+}
+
+#[cfg(unix)]
+#[test]
+fn processes_fetch_values_ps() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let command =
+        std::env::temp_dir().join(format!("mackerel-plugin-ps-test-{}.sh", std::process::id()));
+    std::fs::write(
+        &command,
+        "#!/bin/sh\ncase \"$*\" in\n\
+         '-axww -o pid=,rss=,time=,comm=') cat <<'EOF'\n\
+         \x20 100  2048   0:03.60 /usr/sbin/nginx\n\
+         \x20 101  4096   0:14.40 /usr/sbin/nginx\n\
+         \x20 200  1024   0:00.60 /opt/app/bin/app (worker)\n\
+         \x20 300   512 1-00:00:00.00 launchd\n\
+         EOF\n\
+         ;;\n\
+         '-axww -o pid=,command=') cat <<'EOF'\n\
+         \x20 100 nginx: master process /usr/sbin/nginx\n\
+         \x20 101 nginx: worker process\n\
+         \x20 200 /opt/app/bin/app --worker\n\
+         EOF\n\
+         ;;\n\
+         *) exit 1 ;;\n\
+         esac\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(
+        Processes::new()
+            .name("nginx", "nginx")
+            .name("app worker", "app (worker)")
+            .name("launchd", "launchd")
+            .cgroup("app", "/system.slice/app.service")
+            .ps(&command)
+            .fetch_values()
+            .unwrap(),
+        HashMap::from([
+            ("process.count.nginx".to_owned(), Value::Float(2.0)),
+            ("process.cpu.nginx".to_owned(), Value::Float(30.0)),
+            ("process.rss.nginx".to_owned(), Value::Float(6291456.0)),
+            ("process.count.app_worker".to_owned(), Value::Float(1.0)),
+            ("process.cpu.app_worker".to_owned(), Value::Float(1.0)),
+            ("process.rss.app_worker".to_owned(), Value::Float(1048576.0)),
+            ("process.count.launchd".to_owned(), Value::Float(1.0)),
+            ("process.cpu.launchd".to_owned(), Value::Float(144000.0)),
+            ("process.rss.launchd".to_owned(), Value::Float(524288.0)),
+            ("process.count.app".to_owned(), Value::Float(0.0)),
+            ("process.cpu.app".to_owned(), Value::Float(0.0)),
+            ("process.rss.app".to_owned(), Value::Float(0.0)),
+        ])
+    );
+    #[cfg(feature = "regex")]
+    {
+        let values = Processes::new()
+            .regex("workers", regex::Regex::new(r"\bworker\b").unwrap())
+            .ps(&command)
+            .fetch_values()
+            .unwrap();
+        assert_eq!(values["process.count.workers"], Value::Float(2.0));
+        assert_eq!(values["process.rss.workers"], Value::Float(5242880.0));
+    }
+
+    let command = std::env::temp_dir().join("mackerel-plugin-ps-test.none");
+    assert!(Processes::new().ps(&command).fetch_values().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn processes_fetch_values_ctx() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, Instant};
+
+    let command = std::env::temp_dir().join(format!(
+        "mackerel-plugin-ps-test-ctx-{}.sh",
+        std::process::id()
+    ));
+    std::fs::write(
+        &command,
+        "#!/bin/sh
+exec sleep 10
+",
+    )
+    .unwrap();
+    std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let started = Instant::now();
+    let ctx = Context::new().with_deadline(started + Duration::from_millis(100));
+    let err = Processes::new()
+        .name("nginx", "nginx")
+        .ps(&command)
+        .fetch_values_ctx(&ctx)
+        .unwrap_err()
+        .to_string();
+    assert!(err.ends_with(" failed: fetch deadline exceeded"), "{}", err);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn processes_fetch_values_procfs() {
+    let values = Processes::new().cgroup("all", "/").fetch_values().unwrap();
+    assert!(values["process.count.all"].as_f64() >= 1.0);
+    assert!(values["process.rss.all"].as_f64() > 0.0);
+}
+
+#[test]
+fn processes_graphs() {
+    assert_eq!(
+        Processes::graphs()
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec!["process.count", "process.cpu", "process.rss", "process.fds"]
+    );
+}
@@ -0,0 +1,128 @@
+use std::io::{BufRead, BufReader, Cursor, Write};
+use std::time::Duration;
+
+use mackerel_plugin::*;
+
+struct StaticBackend(Vec<QueueStats>);
+
+impl QueueBackend for StaticBackend {
+    fn name(&self) -> &str {
+        "static"
+    }
+
+    fn queues(&self) -> Result<Vec<QueueStats>, Error> {
+        Ok(self.0.clone())
+    }
+}
+
+fn output_lines(plugin: &impl Plugin) -> Vec<String> {
+    let state = MemoryStateStore::new();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let mut out = Cursor::new(Vec::new());
+    plugin.run_with(&mut out, &state, now).unwrap();
+    let mut lines = String::from_utf8(out.into_inner())
+        .unwrap()
+        .lines()
+        .map(|line| line.rsplit_once('\t').unwrap().0.to_owned())
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines
+}
+
+#[test]
+fn queue_depth_output_values() {
+    let backend = StaticBackend(vec![
+        QueueStats::new("orders", 10).in_flight(2).consumers(3),
+        QueueStats::new("mail.low", 5)
+            .latency(Duration::from_millis(1500))
+            .retries(4)
+            .dead(1),
+    ]);
+    assert_eq!(
+        output_lines(&QueueDepth::new(backend)),
+        vec![
+            "static.consumers.orders\t3",
+            "static.dead.mail_low\t1",
+            "static.depth.mail_low\t5",
+            "static.depth.orders\t10",
+            "static.in_flight.orders\t2",
+            "static.latency.mail_low\t1.5",
+            "static.retries.mail_low\t4",
+            "static.total.depth\t15",
+            "static.total.in_flight\t2",
+            "static.total.queues\t2",
+        ]
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn queue_depth_top() {
+    let backend = StaticBackend(vec![
+        QueueStats::new("a", 10),
+        QueueStats::new("b", 1),
+        QueueStats::new("c", 5),
+        QueueStats::new("d", 2),
+    ]);
+    let plugin = QueueDepth::new(backend).prefix("jobs").top(2);
+    assert_eq!(
+        output_lines(&plugin),
+        vec![
+            "jobs.depth.a\t10",
+            "jobs.depth.c\t5",
+            "jobs.depth.other\t3",
+            "jobs.total.depth\t18",
+            "jobs.total.in_flight\t0",
+            "jobs.total.queues\t4",
+        ]
+    );
+}
+
+#[test]
+fn beanstalkd_queues() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 {
+            let data = match line.trim_end() {
+                "list-tubes" => "---\n- default\n- emails\n- gone\n",
+                "stats-tube default" => {
+                    "---\nname: default\ncurrent-jobs-ready: 3\n\
+                     current-jobs-reserved: 1\ncurrent-watching: 2\n"
+                }
+                "stats-tube emails" => {
+                    "---\nname: emails\ncurrent-jobs-ready: 0\n\
+                     current-jobs-reserved: 0\ncurrent-watching: 0\n"
+                }
+                _ => {
+                    reader.get_mut().write_all(b"NOT_FOUND\r\n").unwrap();
+                    line.clear();
+                    continue;
+                }
+            };
+            write!(reader.get_mut(), "OK {}\r\n{}\r\n", data.len(), data).unwrap();
+            line.clear();
+        }
+    });
+    assert_eq!(
+        Beanstalkd::new(addr.to_string()).queues().unwrap(),
+        vec![
+            QueueStats::new("default", 3).in_flight(1).consumers(2),
+            QueueStats::new("emails", 0).in_flight(0).consumers(0),
+        ]
+    );
+}
+
+#[test]
+fn beanstalkd_queues_error() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    let err = Beanstalkd::new(addr.to_string()).queues().unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with(&format!("beanstalkd {} failed: ", addr)));
+}
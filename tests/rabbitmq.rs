@@ -0,0 +1,102 @@
+#![cfg(feature = "rabbitmq")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::sync::mpsc;
+
+use mackerel_plugin::{QueueBackend, QueueStats, RabbitMq};
+
+struct Request {
+    path: String,
+    authorization: String,
+}
+
+fn mock_server(status: u16, response: &'static str) -> (String, mpsc::Receiver<Request>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://guest:guest@{}/", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let path = line.split(' ').nth(1).unwrap().to_owned();
+        let mut authorization = String::new();
+        loop {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            match line.trim_end().split_once(": ") {
+                Some((key, value)) if key.eq_ignore_ascii_case("authorization") => {
+                    authorization = value.to_owned()
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 {} Status\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            response.len(),
+            response
+        )
+        .unwrap();
+        tx.send(Request {
+            path,
+            authorization,
+        })
+        .unwrap();
+    });
+    (url, rx)
+}
+
+#[test]
+fn rabbitmq_queues() {
+    let (url, rx) = mock_server(
+        200,
+        r#"[
+  {"name":"orders","vhost":"/","messages_ready":10,"messages_unacknowledged":2,"consumers":3},
+  {"name":"mails","vhost":"app","messages_ready":1,"messages_unacknowledged":0,"consumers":1},
+  {"name":"idle","vhost":"/"}
+]"#,
+    );
+    assert_eq!(
+        RabbitMq::new(url).queues(),
+        Ok(vec![
+            QueueStats::new("orders", 10).in_flight(2).consumers(3),
+            QueueStats::new("app/mails", 1).in_flight(0).consumers(1),
+            QueueStats::new("idle", 0).in_flight(0).consumers(0),
+        ])
+    );
+    let request = rx.recv().unwrap();
+    assert_eq!(
+        request.path,
+        "/api/queues?columns=name%2Cvhost%2Cmessages_ready%2Cmessages_unacknowledged%2Cconsumers"
+    );
+    assert_eq!(request.authorization, "Basic Z3Vlc3Q6Z3Vlc3Q=");
+}
+
+#[test]
+fn rabbitmq_queues_vhost() {
+    let (url, rx) = mock_server(
+        200,
+        r#"[{"name":"mails","vhost":"app/v2","messages_ready":1}]"#,
+    );
+    assert_eq!(
+        RabbitMq::new(url).vhost("app/v2").queues(),
+        Ok(vec![QueueStats::new("mails", 1).in_flight(0).consumers(0)])
+    );
+    assert!(rx
+        .recv()
+        .unwrap()
+        .path
+        .starts_with("/api/queues/app%2Fv2?columns="));
+}
+
+#[test]
+fn rabbitmq_queues_error() {
+    let (url, _rx) = mock_server(401, r#"{"error":"not_authorized","reason":"Login failed"}"#);
+    assert_eq!(
+        RabbitMq::new(url).queues().unwrap_err().to_string(),
+        "GET /api/queues failed: 401 Login failed"
+    );
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "json")]
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use mackerel_plugin::{Context, Error, MemoryStateStore, RateLimiter};
+
+#[test]
+fn rate_limiter_try_acquire() {
+    let state = MemoryStateStore::new();
+    let interval = Duration::from_secs(60);
+    let now = UNIX_EPOCH + Duration::from_secs(1700000000);
+    let limiter = RateLimiter::new(&state, "ratelimit", 3, interval).clock(&now);
+    assert_eq!(limiter.available(), Ok(3.0));
+    for _ in 0..3 {
+        assert_eq!(limiter.try_acquire(), Ok(true));
+    }
+    assert_eq!(limiter.try_acquire(), Ok(false));
+
+    // the budget is persisted across the runs
+    let now = now + Duration::from_secs(20);
+    let limiter = RateLimiter::new(&state, "ratelimit", 3, interval).clock(&now);
+    assert_eq!(limiter.available(), Ok(1.0));
+    assert_eq!(limiter.try_acquire(), Ok(true));
+    assert_eq!(limiter.try_acquire(), Ok(false));
+
+    let now = now + Duration::from_secs(3600);
+    let limiter = RateLimiter::new(&state, "ratelimit", 3, interval).clock(&now);
+    assert_eq!(limiter.available(), Ok(3.0));
+}
+
+#[test]
+fn rate_limiter_acquire() {
+    let state = MemoryStateStore::new();
+    let limiter = RateLimiter::new(&state, "ratelimit", 20, Duration::from_secs(1));
+    let ctx = Context::new();
+    let started = Instant::now();
+    for _ in 0..22 {
+        assert_eq!(limiter.acquire(&ctx), Ok(()));
+    }
+    assert!(started.elapsed() >= Duration::from_millis(50));
+
+    let limiter = RateLimiter::new(&state, "ratelimit2", 1, Duration::from_secs(3600));
+    let ctx = Context::new().with_deadline(Instant::now() + Duration::from_secs(10));
+    assert_eq!(limiter.acquire(&ctx), Ok(()));
+    assert_eq!(
+        limiter.acquire(&ctx),
+        Err(Error::Other("rate limit exceeded".to_owned()))
+    );
+}
+
+#[test]
+fn context_acquire() {
+    let state: &'static MemoryStateStore = Box::leak(Box::new(MemoryStateStore::new()));
+    let limiter = RateLimiter::new(state, "ratelimit", 1, Duration::from_secs(3600));
+    let ctx = Context::new()
+        .with_deadline(Instant::now() + Duration::from_secs(10))
+        .with_rate_limiter(Arc::new(limiter));
+    assert_eq!(ctx.acquire(), Ok(()));
+    assert_eq!(
+        ctx.acquire(),
+        Err(Error::Other("rate limit exceeded".to_owned()))
+    );
+    assert_eq!(Context::new().acquire(), Ok(()));
+}
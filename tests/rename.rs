@@ -0,0 +1,37 @@
+use rstest::rstest;
+
+use mackerel_plugin::Rename;
+
+#[rstest]
+#[case(Rename::new("disk", "storage"), "disk", Some("storage"))]
+#[case(
+    Rename::new("disk", "storage"),
+    "disk.sda.read",
+    Some("storage.sda.read")
+)]
+#[case(Rename::new("disk", "storage"), "diskio.sda.read", None)]
+#[case(
+    Rename::new("disk.io", "disk.iops"),
+    "disk.io.sda",
+    Some("disk.iops.sda")
+)]
+#[case(Rename::new("disk.io", "disk.iops"), "disk.iowait", None)]
+#[case(Rename::new("disk.io", "disk.iops"), "net.disk.io", None)]
+fn rename_apply(#[case] rename: Rename, #[case] name: &str, #[case] expected: Option<&str>) {
+    assert_eq!(rename.apply(name).as_deref(), expected);
+}
+
+#[test]
+fn rename_deserialize() {
+    let renames: Vec<Rename> = serde_json::from_str(
+        r#"[{ "from": "foo", "to": "bar" }, { "from": "baz", "to": "qux", "keep_old": true }]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        renames,
+        vec![
+            Rename::new("foo", "bar"),
+            Rename::new("baz", "qux").keep_old()
+        ]
+    );
+}
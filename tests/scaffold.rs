@@ -0,0 +1,69 @@
+#![cfg(feature = "scaffold")]
+
+use std::path::PathBuf;
+
+use mackerel_plugin::Scaffold;
+
+#[test]
+fn scaffold_new() {
+    assert_eq!(Scaffold::new("foo"), Scaffold::new("mackerel-plugin-foo"));
+    assert!(Scaffold::new("foo-bar_2").is_ok());
+    assert!(Scaffold::new("").is_err());
+    assert!(Scaffold::new("mackerel-plugin-").is_err());
+    assert!(Scaffold::new("-foo").is_err());
+    assert!(Scaffold::new("Foo").is_err());
+    assert!(Scaffold::new("foo.bar").is_err());
+    assert!(Scaffold::new("9gag").is_err());
+    assert!(Scaffold::new("_").is_err());
+    assert!(Scaffold::new("foo-").is_err());
+    assert!(Scaffold::new("foo__bar").is_err());
+}
+
+#[test]
+fn scaffold_files() {
+    let scaffold = Scaffold::new("disk-io").unwrap();
+    assert_eq!(scaffold.dir_name(), "mackerel-plugin-disk-io");
+    let files = scaffold.files();
+    assert_eq!(
+        files
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>(),
+        [
+            "Cargo.toml",
+            "src/main.rs",
+            "Makefile",
+            "README.md",
+            ".gitignore"
+        ]
+        .map(PathBuf::from)
+        .to_vec()
+    );
+    for (_, content) in &files {
+        assert!(!content.contains("{{"), "{}", content);
+    }
+    let cargo_toml = &files[0].1;
+    assert!(cargo_toml.contains("name = \"mackerel-plugin-disk-io\""));
+    assert!(cargo_toml.contains(&format!(
+        "mackerel_plugin = \"{}\"",
+        env!("CARGO_PKG_VERSION")
+    )));
+    let main_rs = &files[1].1;
+    assert!(main_rs.contains("struct DiskIoPlugin {}"));
+    assert!(main_rs.contains("label: \"Disk Io sample\""));
+    assert!(main_rs.contains("\"disk-io\".to_owned()"));
+    let makefile = &files[2].1;
+    assert!(makefile.contains("BIN := mackerel-plugin-disk-io"));
+    assert!(makefile.contains("\tcargo build --release\n"));
+}
+
+#[test]
+fn scaffold_write_to() {
+    let dir = std::env::temp_dir().join(format!("mackerel-plugin-scaffold-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let scaffold = Scaffold::new("foo").unwrap();
+    assert_eq!(scaffold.write_to(&dir), Ok(()));
+    assert!(dir.join("src/main.rs").is_file());
+    assert!(scaffold.write_to(&dir).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
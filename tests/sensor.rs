@@ -0,0 +1,107 @@
+#![cfg(target_os = "linux")]
+
+use std::collections::HashMap;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use mackerel_plugin::{Sensors, Value};
+
+fn write_files(dir: &Path, files: &[(&str, &str)]) {
+    std::fs::create_dir_all(dir).unwrap();
+    for (name, content) in files {
+        std::fs::write(dir.join(name), format!("{}\n", content)).unwrap();
+    }
+}
+
+fn hwmon_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-sensor-test.{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let devices = dir.join("devices");
+    let hwmon = dir.join("hwmon");
+    for (i, socket) in ["coretemp.0", "coretemp.1"].iter().enumerate() {
+        std::fs::create_dir_all(devices.join(socket)).unwrap();
+        let chip = hwmon.join(format!("hwmon{}", i));
+        write_files(
+            &chip,
+            &[
+                ("name", "coretemp"),
+                ("temp1_input", &format!("{}000", 50 + i)),
+                ("temp1_label", "Package id 0"),
+                ("temp2_input", "45500"),
+                ("temp2_label", "Core 0"),
+                ("temp2_max", "100000"),
+            ],
+        );
+        symlink(devices.join(socket), chip.join("device")).unwrap();
+    }
+    write_files(
+        &hwmon.join("hwmon2"),
+        &[
+            ("name", "nct6775"),
+            ("fan1_input", "1250"),
+            ("fan2_input", "0"),
+            ("fan3_input", "900"),
+            ("fan3_fault", "1"),
+            ("temp7_input", "-1000"),
+            ("temp7_label", ""),
+            ("in0_input", "1040"),
+        ],
+    );
+    // the attributes in the device directory on the old kernels
+    write_files(
+        &devices.join("0-0048"),
+        &[("name", "lm75"), ("temp1_input", "38250")],
+    );
+    std::fs::create_dir_all(hwmon.join("hwmon3")).unwrap();
+    symlink(devices.join("0-0048"), hwmon.join("hwmon3").join("device")).unwrap();
+    // the directory without the name is ignored
+    write_files(&hwmon.join("hwmon4"), &[("temp1_input", "1000")]);
+    hwmon
+}
+
+#[test]
+fn sensors_fetch_values() {
+    let expected = [
+        ("sensors.temperature.coretemp_coretemp_0.Package_id_0", 50.0),
+        ("sensors.temperature.coretemp_coretemp_0.Core_0", 45.5),
+        ("sensors.temperature.coretemp_coretemp_1.Package_id_0", 51.0),
+        ("sensors.temperature.coretemp_coretemp_1.Core_0", 45.5),
+        ("sensors.fan.nct6775.fan1", 1250.0),
+        ("sensors.fan.nct6775.fan2", 0.0),
+        ("sensors.temperature.nct6775.temp7", -1.0),
+        ("sensors.temperature.lm75.temp1", 38.25),
+    ]
+    .iter()
+    .map(|&(key, value)| (key.to_owned(), value.into()))
+    .collect::<HashMap<String, Value>>();
+    assert_eq!(
+        Sensors::new().hwmon_dir(hwmon_dir()).fetch_values(),
+        Ok(expected)
+    );
+}
+
+#[test]
+fn sensors_fetch_values_error() {
+    assert_eq!(
+        Sensors::new()
+            .hwmon_dir("/nonexistent/hwmon")
+            .fetch_values()
+            .unwrap_err()
+            .to_string(),
+        "read /nonexistent/hwmon failed: No such file or directory (os error 2)"
+    );
+}
+
+#[test]
+fn sensors_graphs() {
+    assert_eq!(
+        Sensors::graphs()
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec!["sensors.temperature.#", "sensors.fan.#"]
+    );
+}
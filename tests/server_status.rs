@@ -0,0 +1,254 @@
+#![cfg(feature = "http")]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::time::{Duration, Instant};
+
+use mackerel_plugin::{ApacheStatus, Context, NginxStatus, PhpFpmStatus, Value};
+
+fn mock_server(status: u16, body: &'static str) -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/status", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 {} Status\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+        .unwrap();
+    });
+    url
+}
+
+fn values(values: &[(&str, f64)]) -> HashMap<String, Value> {
+    values
+        .iter()
+        .map(|&(key, value)| (key.to_owned(), value.into()))
+        .collect()
+}
+
+const NGINX_STATUS: &str = "Active connections: 291
+server accepts handled requests
+ 16630948 16630948 31070465
+Reading: 6 Writing: 179 Waiting: 106
+";
+
+#[test]
+fn nginx_status_parse() {
+    assert_eq!(
+        NginxStatus::parse(NGINX_STATUS),
+        Ok(values(&[
+            ("nginx.connections.connections", 291.0),
+            ("nginx.requests.accepts", 16630948.0),
+            ("nginx.requests.handled", 16630948.0),
+            ("nginx.requests.requests", 31070465.0),
+            ("nginx.queue.reading", 6.0),
+            ("nginx.queue.writing", 179.0),
+            ("nginx.queue.waiting", 106.0),
+        ]))
+    );
+    assert_eq!(
+        NginxStatus::parse("<html>Not Found</html>")
+            .unwrap_err()
+            .to_string(),
+        "parse the nginx status failed: expected \"Active\" but got Some(\"<html>Not\")"
+    );
+    assert_eq!(
+        NginxStatus::parse("Active connections: 1\nserver accepts handled requests\n 1 1")
+            .unwrap_err()
+            .to_string(),
+        "parse the nginx status failed: expected a number but got None"
+    );
+}
+
+#[test]
+fn nginx_status_fetch_values() {
+    let url = mock_server(200, NGINX_STATUS);
+    let values = NginxStatus::new(url).fetch_values().unwrap();
+    assert_eq!(values["nginx.requests.requests"], Value::Float(31070465.0));
+
+    let url = mock_server(404, "Not Found");
+    let err = NginxStatus::new(url.clone()).fetch_values().unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with(&format!("GET {} failed: ", url)));
+}
+
+#[test]
+fn nginx_status_fetch_values_ctx() {
+    // the server accepting the connections but never responding
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/status", listener.local_addr().unwrap());
+    let started = Instant::now();
+    let ctx = Context::new().with_deadline(started + Duration::from_millis(200));
+    let err = NginxStatus::new(url.clone())
+        .fetch_values_ctx(&ctx)
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with(&format!("GET {} failed: ", url)));
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let err = NginxStatus::new(url.clone())
+        .fetch_values_ctx(&ctx)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("GET {} failed: fetch deadline exceeded", url)
+    );
+}
+
+#[test]
+fn apache_status_parse() {
+    assert_eq!(
+        ApacheStatus::parse(
+            "localhost
+ServerVersion: Apache/2.4.62 (Unix)
+Total Accesses: 1234
+Total kBytes: 567
+CPULoad: .0123
+Uptime: 3600
+BusyWorkers: 2
+IdleWorkers: 48
+Scoreboard: _W_K______....
+"
+        ),
+        Ok(values(&[
+            ("apache2.req.requests", 1234.0),
+            ("apache2.bytes.bytes_sent", 580608.0),
+            ("apache2.cpu.cpu_load", 0.0123),
+            ("apache2.workers.busy_workers", 2.0),
+            ("apache2.workers.idle_workers", 48.0),
+            ("apache2.scoreboard.waiting", 8.0),
+            ("apache2.scoreboard.starting", 0.0),
+            ("apache2.scoreboard.reading", 0.0),
+            ("apache2.scoreboard.sending", 1.0),
+            ("apache2.scoreboard.keepalive", 1.0),
+            ("apache2.scoreboard.dns", 0.0),
+            ("apache2.scoreboard.closing", 0.0),
+            ("apache2.scoreboard.logging", 0.0),
+            ("apache2.scoreboard.finishing", 0.0),
+            ("apache2.scoreboard.idle_cleanup", 0.0),
+            ("apache2.scoreboard.open", 4.0),
+        ]))
+    );
+    // without ExtendedStatus
+    assert_eq!(
+        ApacheStatus::parse("BusyWorkers: 1\nIdleWorkers: 9\n"),
+        Ok(values(&[
+            ("apache2.workers.busy_workers", 1.0),
+            ("apache2.workers.idle_workers", 9.0),
+        ]))
+    );
+    assert_eq!(
+        ApacheStatus::parse("<html>Forbidden</html>")
+            .unwrap_err()
+            .to_string(),
+        "parse the Apache status failed: no status found"
+    );
+    assert_eq!(
+        ApacheStatus::parse("BusyWorkers: many\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the Apache status failed: invalid BusyWorkers: \"many\""
+    );
+}
+
+#[test]
+fn apache_status_fetch_values() {
+    let url = mock_server(200, "BusyWorkers: 3\nIdleWorkers: 7\nScoreboard: WW_\n");
+    let values = ApacheStatus::new(url).fetch_values().unwrap();
+    assert_eq!(values["apache2.workers.busy_workers"], Value::Float(3.0));
+    assert_eq!(values["apache2.scoreboard.sending"], Value::Float(2.0));
+}
+
+#[test]
+fn php_fpm_status_parse() {
+    let expected = Ok(values(&[
+        ("php-fpm.processes.www.active_processes", 1.0),
+        ("php-fpm.processes.www.idle_processes", 4.0),
+        ("php-fpm.max_active_processes.www.max_active_processes", 3.0),
+        ("php-fpm.max_children_reached.www.max_children_reached", 0.0),
+        ("php-fpm.queue.www.listen_queue", 0.0),
+        ("php-fpm.queue.www.max_listen_queue", 2.0),
+        ("php-fpm.queue.www.listen_queue_len", 128.0),
+        ("php-fpm.requests.www.accepted_conn", 1234.0),
+        ("php-fpm.requests.www.slow_requests", 5.0),
+    ]));
+    assert_eq!(
+        PhpFpmStatus::parse(
+            "pool:                 www
+process manager:      dynamic
+start time:           16/Oct/2026:10:00:00 +0900
+start since:          3600
+accepted conn:        1234
+listen queue:         0
+max listen queue:     2
+listen queue len:     128
+idle processes:       4
+active processes:     1
+total processes:      5
+max active processes: 3
+max children reached: 0
+slow requests:        5
+
+************************
+pid:                  1001
+state:                Idle
+"
+        ),
+        expected
+    );
+    assert_eq!(
+        PhpFpmStatus::parse(
+            r#"{"pool":"www","process manager":"dynamic","start time":1791766800,"start since":3600,"accepted conn":1234,"listen queue":0,"max listen queue":2,"listen queue len":128,"idle processes":4,"active processes":1,"total processes":5,"max active processes":3,"max children reached":0,"slow requests":5,"processes":[{"pid":1001}]}"#
+        ),
+        expected
+    );
+    assert_eq!(
+        PhpFpmStatus::parse("File not found.\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the PHP-FPM status failed: no pool found"
+    );
+    assert_eq!(
+        PhpFpmStatus::parse("pool: www\nlisten queue: many\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the PHP-FPM status failed: invalid listen queue: \"many\""
+    );
+}
+
+#[test]
+fn php_fpm_status_fetch_values() {
+    let url = mock_server(200, r#"{"pool":"api.v1","active processes":2}"#);
+    assert_eq!(
+        PhpFpmStatus::new(url).fetch_values(),
+        Ok(values(&[(
+            "php-fpm.processes.api_v1.active_processes",
+            2.0
+        )]))
+    );
+}
+
+#[test]
+fn server_status_graphs() {
+    for (graphs, prefix) in [
+        (NginxStatus::graphs(), "nginx."),
+        (ApacheStatus::graphs(), "apache2."),
+        (PhpFpmStatus::graphs(), "php-fpm."),
+    ] {
+        for graph in graphs {
+            assert!(graph.name.starts_with(prefix), "{}", graph.name);
+        }
+    }
+    assert_eq!(ApacheStatus::graphs()[4].metrics.len(), 11);
+}
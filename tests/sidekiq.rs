@@ -0,0 +1,175 @@
+#![cfg(feature = "json")]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mackerel_plugin::{QueueBackend, QueueStats, Resque, Secret, Sidekiq};
+
+enum Reply {
+    Integer(i64),
+    Bulk(String),
+    Array(Vec<String>),
+}
+
+fn write_reply(stream: &mut impl Write, reply: Option<&Reply>) {
+    match reply {
+        None => write!(stream, "$-1\r\n"),
+        Some(Reply::Integer(value)) => write!(stream, ":{}\r\n", value),
+        Some(Reply::Bulk(value)) => write!(stream, "${}\r\n{}\r\n", value.len(), value),
+        Some(Reply::Array(values)) => {
+            write!(stream, "*{}\r\n", values.len()).unwrap();
+            for value in values {
+                write!(stream, "${}\r\n{}\r\n", value.len(), value).unwrap();
+            }
+            Ok(())
+        }
+    }
+    .unwrap();
+}
+
+/// Serves the replies by the commands joined by the spaces.
+fn mock_redis(replies: HashMap<&'static str, Reply>) -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 {
+            let len = line.trim_end()[1..].parse::<usize>().unwrap();
+            let mut args = Vec::new();
+            for _ in 0..len {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                let mut data = vec![0; line.trim_end()[1..].parse::<usize>().unwrap() + 2];
+                reader.read_exact(&mut data).unwrap();
+                args.push(String::from_utf8(data).unwrap().trim_end().to_owned());
+            }
+            let command = args.join(" ");
+            if command.starts_with("AUTH ") {
+                if command == "AUTH secret" {
+                    reader.get_mut().write_all(b"+OK\r\n").unwrap();
+                } else {
+                    reader
+                        .get_mut()
+                        .write_all(b"-WRONGPASS invalid password\r\n")
+                        .unwrap();
+                }
+            } else {
+                write_reply(reader.get_mut(), replies.get(command.as_str()));
+            }
+            line.clear();
+        }
+    });
+    addr
+}
+
+fn strings(values: &[&str]) -> Reply {
+    Reply::Array(values.iter().map(|value| value.to_string()).collect())
+}
+
+#[test]
+fn sidekiq_queues() {
+    let enqueued_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        - 30;
+    let addr = mock_redis(HashMap::from([
+        ("SMEMBERS app:queues", strings(&["default", "mailers"])),
+        ("SMEMBERS app:processes", strings(&["host:1:abc"])),
+        (
+            "HVALS app:host:1:abc:work",
+            strings(&[r#"{"queue":"default","payload":"{}","run_at":1700000000}"#]),
+        ),
+        (
+            "HGET app:host:1:abc info",
+            Reply::Bulk(r#"{"hostname":"host","queues":["default","mailers"]}"#.to_owned()),
+        ),
+        (
+            "ZRANGE app:retry 0 -1",
+            strings(&[
+                r#"{"queue":"default","class":"A"}"#,
+                r#"{"queue":"default","class":"B"}"#,
+                r#"{"queue":"mailers","class":"C"}"#,
+            ]),
+        ),
+        ("ZRANGE app:dead 0 -1", strings(&[r#"{"queue":"default"}"#])),
+        ("LLEN app:queue:default", Reply::Integer(3)),
+        (
+            "LINDEX app:queue:default -1",
+            Reply::Bulk(format!(
+                r#"{{"queue":"default","enqueued_at":{}}}"#,
+                enqueued_at * 1000
+            )),
+        ),
+        ("LLEN app:queue:mailers", Reply::Integer(0)),
+    ]));
+    let queues = Sidekiq::new(addr)
+        .namespace("app")
+        .password(Secret::new("secret"))
+        .queues()
+        .unwrap();
+    assert_eq!(queues.len(), 2);
+    let latency = queues[0].latency.unwrap();
+    assert!(latency >= Duration::from_secs(30) && latency < Duration::from_secs(40));
+    assert_eq!(
+        queues,
+        vec![
+            QueueStats::new("default", 3)
+                .in_flight(1)
+                .consumers(1)
+                .latency(latency)
+                .retries(2)
+                .dead(1),
+            QueueStats::new("mailers", 0)
+                .in_flight(0)
+                .consumers(1)
+                .latency(Duration::ZERO)
+                .retries(1)
+                .dead(0),
+        ]
+    );
+}
+
+#[test]
+fn sidekiq_queues_error() {
+    let addr = mock_redis(HashMap::new());
+    let err = Sidekiq::new(addr.clone())
+        .password(Secret::new("wrong"))
+        .queues()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("sidekiq {} failed: WRONGPASS invalid password", addr)
+    );
+}
+
+#[test]
+fn resque_queues() {
+    let addr = mock_redis(HashMap::from([
+        ("SMEMBERS resque:queues", strings(&["high", "low"])),
+        (
+            "SMEMBERS resque:workers",
+            strings(&["host:10:high,low", "host:11:*"]),
+        ),
+        (
+            "GET resque:worker:host:10:high,low",
+            Reply::Bulk(r#"{"queue":"high","run_at":"2024-01-01","payload":{}}"#.to_owned()),
+        ),
+        (
+            "LRANGE resque:failed 0 -1",
+            strings(&[r#"{"queue":"low","exception":"RuntimeError"}"#]),
+        ),
+        ("LLEN resque:queue:high", Reply::Integer(5)),
+        ("LLEN resque:queue:low", Reply::Integer(2)),
+    ]));
+    assert_eq!(
+        Resque::new(addr).queues().unwrap(),
+        vec![
+            QueueStats::new("high", 5).in_flight(1).consumers(2).dead(0),
+            QueueStats::new("low", 2).in_flight(0).consumers(2).dead(1),
+        ]
+    );
+}
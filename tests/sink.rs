@@ -0,0 +1,337 @@
+use rstest::rstest;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use mackerel_plugin::{
+    estimate_output_size, format_value, graph, Error, FileSink, Graph, JsonSink, LtsvSink,
+    MemoryStateStore, MetricSink, Plugin, TeeSink, TsvSink, Value,
+};
+
+fn write_values(sink: &mut dyn MetricSink, values: &[(&str, f64, i64)]) {
+    for &(name, value, time) in values {
+        assert_eq!(sink.write_value(name, value, time), Ok(()));
+    }
+    assert_eq!(sink.finish(), Ok(()));
+}
+
+#[rstest]
+#[case(vec![], "")]
+#[case(
+    vec![("dice.d6", 3.0, 1700000000), ("dice.d20", 17.5, 1700000000)],
+    "dice.d6\t3\t1700000000\ndice.d20\t17.5\t1700000000\n"
+)]
+fn tsv_sink(#[case] values: Vec<(&str, f64, i64)>, #[case] expected: &str) {
+    let mut out = Vec::new();
+    write_values(&mut TsvSink::new(&mut out), &values);
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[rstest]
+#[case(vec![], "[]\n")]
+#[case(
+    vec![("dice.d6", 3.0, 1700000000), ("dice.d20", 17.5, 1700000000)],
+    "[{\"name\":\"dice.d6\",\"value\":3,\"time\":1700000000},\
+     {\"name\":\"dice.d20\",\"value\":17.5,\"time\":1700000000}]\n"
+)]
+fn json_sink(#[case] values: Vec<(&str, f64, i64)>, #[case] expected: &str) {
+    let mut out = Vec::new();
+    write_values(&mut JsonSink::new(&mut out), &values);
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[rstest]
+#[case(vec![], "")]
+#[case(
+    vec![("dice.d6", 3.0, 1700000000), ("dice.d20", 17.5, 1700000000)],
+    "name:dice.d6\tvalue:3\ttime:1700000000\nname:dice.d20\tvalue:17.5\ttime:1700000000\n"
+)]
+fn ltsv_sink(#[case] values: Vec<(&str, f64, i64)>, #[case] expected: &str) {
+    let mut out = Vec::new();
+    write_values(&mut LtsvSink::new(&mut out), &values);
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[test]
+fn json_sink_runs() {
+    let mut out = Vec::new();
+    let mut sink = JsonSink::new(&mut out);
+    write_values(&mut sink, &[("dice.d6", 3.0, 1700000000)]);
+    write_values(&mut sink, &[("dice.d6", 4.0, 1700000060)]);
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "[{\"name\":\"dice.d6\",\"value\":3,\"time\":1700000000}]\n\
+         [{\"name\":\"dice.d6\",\"value\":4,\"time\":1700000060}]\n"
+    );
+}
+
+struct FailingSink {
+    writes: usize,
+}
+
+impl MetricSink for FailingSink {
+    fn write_value(&mut self, _: &str, _: f64, _: i64) -> Result<(), Error> {
+        self.writes += 1;
+        Err(Error::Other("connection refused".to_owned()))
+    }
+}
+
+#[test]
+fn tee_sink() {
+    let (mut primary, mut secondary) = (Vec::new(), Vec::new());
+    let mut failing = FailingSink { writes: 0 };
+    let mut sink = TeeSink::new(TsvSink::new(&mut primary))
+        .sink(&mut failing)
+        .sink(JsonSink::new(&mut secondary));
+    write_values(
+        &mut sink,
+        &[("dice.d6", 3.0, 1700000000), ("dice.d20", 17.0, 1700000000)],
+    );
+    assert_eq!(
+        sink.errors(),
+        vec![&Error::Other("connection refused".to_owned())]
+    );
+    write_values(&mut sink, &[("dice.d6", 4.0, 1700000060)]);
+    assert_eq!(sink.errors().len(), 1);
+    drop(sink);
+    assert_eq!(failing.writes, 2);
+    assert_eq!(
+        String::from_utf8(primary).unwrap(),
+        "dice.d6\t3\t1700000000\ndice.d20\t17\t1700000000\ndice.d6\t4\t1700000060\n"
+    );
+    assert_eq!(
+        String::from_utf8(secondary).unwrap(),
+        "[{\"name\":\"dice.d6\",\"value\":3,\"time\":1700000000},\
+         {\"name\":\"dice.d20\",\"value\":17,\"time\":1700000000}]\n\
+         [{\"name\":\"dice.d6\",\"value\":4,\"time\":1700000060}]\n"
+    );
+}
+
+fn file_sink_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-file-sink-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn file_names(sink: &FileSink) -> Vec<String> {
+    sink.files()
+        .unwrap()
+        .iter()
+        .map(|path| path.file_name().unwrap().to_str().unwrap().to_owned())
+        .collect()
+}
+
+#[test]
+fn file_sink_rotation() {
+    let dir = file_sink_dir("rotation");
+    let mut sink = FileSink::new(&dir, "mackerel-plugin-dice")
+        .max_size(40)
+        .max_age(Duration::from_secs(180))
+        .sync(true);
+    write_values(
+        &mut sink,
+        &[("dice.d6", 3.0, 1700000000), ("dice.d20", 17.0, 1700000000)],
+    );
+    write_values(&mut sink, &[("dice.d6", 4.0, 1700000060)]);
+    write_values(&mut sink, &[("dice.d6", 5.0, 1700000120)]);
+    assert_eq!(
+        file_names(&sink),
+        vec![
+            "mackerel-plugin-dice.1700000000.tsv",
+            "mackerel-plugin-dice.1700000060.tsv",
+        ]
+    );
+    drop(sink);
+
+    let mut sink = FileSink::new(&dir, "mackerel-plugin-dice").max_age(Duration::from_secs(180));
+    write_values(&mut sink, &[("dice.d6", 6.0, 1700000180)]);
+    write_values(&mut sink, &[("dice.d6", 1.0, 1700000240)]);
+    assert_eq!(
+        file_names(&sink),
+        vec![
+            "mackerel-plugin-dice.1700000000.tsv",
+            "mackerel-plugin-dice.1700000060.tsv",
+            "mackerel-plugin-dice.1700000240.tsv",
+        ]
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("mackerel-plugin-dice.1700000060.tsv")).unwrap(),
+        "dice.d6\t4\t1700000060\ndice.d6\t5\t1700000120\ndice.d6\t6\t1700000180\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn file_sink_rotation_same_time() {
+    let dir = file_sink_dir("rotation-same-time");
+    let mut sink = FileSink::new(&dir, "mackerel-plugin-dice").max_size(40);
+    write_values(
+        &mut sink,
+        &[
+            ("dice.d6", 1.0, 1700000000),
+            ("dice.d6", 2.0, 1700000000),
+            ("dice.d6", 3.0, 1700000000),
+            ("dice.d6", 4.0, 1700000000),
+            ("dice.d6", 5.0, 1700000000),
+        ],
+    );
+    drop(sink);
+    let mut sink = FileSink::new(&dir, "mackerel-plugin-dice").max_size(40);
+    write_values(
+        &mut sink,
+        &[("dice.d6", 6.0, 1700000000), ("dice.d6", 7.0, 1700000060)],
+    );
+    assert_eq!(
+        file_names(&sink),
+        vec![
+            "mackerel-plugin-dice.1700000000.tsv",
+            "mackerel-plugin-dice.1700000000.1.tsv",
+            "mackerel-plugin-dice.1700000000.2.tsv",
+            "mackerel-plugin-dice.1700000060.tsv",
+        ]
+    );
+    let contents = sink
+        .files()
+        .unwrap()
+        .iter()
+        .map(|path| std::fs::read_to_string(path).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        contents,
+        vec![
+            "dice.d6\t1\t1700000000\ndice.d6\t2\t1700000000\n",
+            "dice.d6\t3\t1700000000\ndice.d6\t4\t1700000000\n",
+            "dice.d6\t5\t1700000000\ndice.d6\t6\t1700000000\n",
+            "dice.d6\t7\t1700000060\n",
+        ]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+struct ManyValuesPlugin(usize);
+
+impl Plugin for ManyValuesPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok((0..self.0)
+            .map(|i| (format!("many.v{}", i), i as f64))
+            .collect())
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "many",
+            label: "Many",
+            unit: "integer",
+            metrics: [{ name: "*", label: "%1" }],
+        }]
+    }
+}
+
+/// Counts the values written between the flushes.
+#[derive(Default)]
+struct ChunkSink {
+    chunks: Vec<usize>,
+    count: usize,
+}
+
+impl MetricSink for ChunkSink {
+    fn write_value(&mut self, _: &str, _: f64, _: i64) -> Result<(), Error> {
+        self.count += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.chunks.push(std::mem::take(&mut self.count));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.flush()
+    }
+}
+
+#[test]
+fn plugin_output_flush_chunks() {
+    let mut sink = ChunkSink::default();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let plugin = ManyValuesPlugin(25000);
+    assert_eq!(
+        plugin.output_values_to(&mut sink, &MemoryStateStore::new(), &now),
+        Ok(())
+    );
+    assert_eq!(sink.chunks, vec![10000, 10000, 5000]);
+
+    let mut sink = ChunkSink::default();
+    let plugin = ManyValuesPlugin(20000);
+    assert_eq!(
+        plugin.output_values_to(&mut sink, &MemoryStateStore::new(), &now),
+        Ok(())
+    );
+    assert_eq!(sink.chunks, vec![10000, 10000]);
+}
+
+#[test]
+fn estimate_output_size_upper_bound() {
+    let values = HashMap::from([
+        ("dice.d6".to_owned(), Value::from(3.0)),
+        ("dice.huge".to_owned(), Value::from(1e30)),
+        ("dice.ratio".to_owned(), Value::from(1.0 / 3.0)),
+    ]);
+    let mut out = Vec::new();
+    let mut sink = TsvSink::new(&mut out);
+    for (name, value) in &values {
+        sink.write_value(&format!("game.{}", name), value.as_f64(), 1700000000)
+            .unwrap();
+    }
+    sink.finish().unwrap();
+    let size = estimate_output_size("game", &values);
+    assert!(out.len() <= size, "{} <= {}", out.len(), size);
+    assert_eq!(size, 59 + 61 + 62 + (31 - 24));
+}
+
+#[rstest]
+#[case(3.0, "3")]
+#[case(-0.25, "-0.25")]
+#[case(1234567.5, "1234567.5")]
+#[case(1e21, "1000000000000000000000")]
+#[case(1e-7, "0.0000001")]
+fn format_value_decimal(#[case] value: f64, #[case] expected: &str) {
+    let formatted = format_value(value);
+    assert_eq!(formatted, expected);
+    assert_eq!(formatted.parse::<f64>(), Ok(value));
+
+    let mut out = Vec::new();
+    write_values(
+        &mut TsvSink::new(&mut out),
+        &[("dice.d6", value, 1700000000)],
+    );
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        format!("dice.d6\t{}\t1700000000\n", expected)
+    );
+}
+
+#[test]
+fn sink_formatter() {
+    let mut out = Vec::new();
+    let mut sink = LtsvSink::new(&mut out).formatter(|value: f64| format!("{:.2}", value));
+    write_values(&mut sink, &[("dice.d6", 1.0 / 3.0, 1700000000)]);
+    drop(sink);
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "name:dice.d6\tvalue:0.33\ttime:1700000000\n"
+    );
+
+    let mut out = Vec::new();
+    let mut sink = JsonSink::new(&mut out).formatter(|value: f64| format!("{:.1}", value));
+    write_values(&mut sink, &[("dice.d6", 3.0, 1700000000)]);
+    drop(sink);
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "[{\"name\":\"dice.d6\",\"value\":3.0,\"time\":1700000000}]\n"
+    );
+}
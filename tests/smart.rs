@@ -0,0 +1,126 @@
+#![cfg(all(unix, feature = "smart"))]
+
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+use mackerel_plugin::{CheckStatus, SmartDisks, Value};
+
+fn fake_smartctl(name: &str, script: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-smart-test.{}.{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("smartctl");
+    std::fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+/// Prints the report of the device in tests/testdata.
+const REPORTS: &str = r#"[ "$1 $2 $3" = "--json --health --attributes" ] || exit 1
+cat "tests/testdata/smartctl_$(basename "$4").json"
+"#;
+
+#[test]
+fn smart_disks_fetch_values() {
+    let command = fake_smartctl("values", REPORTS);
+    let disks = SmartDisks::new()
+        .device("/dev/sda")
+        .device("/dev/nvme0n1")
+        .command(&command);
+    assert_eq!(
+        disks.fetch_values(),
+        Ok(HashMap::from([
+            (
+                "smart.reallocated_sectors.sda".to_owned(),
+                Value::Float(8.0)
+            ),
+            ("smart.pending_sectors.sda".to_owned(), Value::Float(2.0)),
+            ("smart.temperature.sda".to_owned(), Value::Float(36.0)),
+            ("smart.wear.sda".to_owned(), Value::Float(7.0)),
+            ("smart.media_errors.nvme0n1".to_owned(), Value::Float(0.0)),
+            ("smart.temperature.nvme0n1".to_owned(), Value::Float(41.0)),
+            ("smart.wear.nvme0n1".to_owned(), Value::Float(3.0)),
+        ]))
+    );
+}
+
+#[test]
+fn smart_disks_fetch_error() {
+    let command = fake_smartctl(
+        "error",
+        r#"echo '{"smartctl": {"exit_status": 2, "messages": [{"string": "/dev/sdz: No such device", "severity": "error"}]}}'
+exit 2
+"#,
+    );
+    let disks = SmartDisks::new().device("/dev/sdz").command(&command);
+    assert_eq!(
+        disks.fetch_values().map_err(|err| err.to_string()),
+        Err("smartctl /dev/sdz failed: /dev/sdz: No such device".to_owned())
+    );
+    let result = disks.check();
+    assert_eq!(result.status, CheckStatus::Unknown);
+}
+
+#[test]
+fn smart_disks_check() {
+    let command = fake_smartctl("check", REPORTS);
+    let result = SmartDisks::new()
+        .device("/dev/nvme0n1")
+        .command(&command)
+        .check();
+    assert_eq!(result.status, CheckStatus::Ok);
+    assert_eq!(result.message, "1 disks passed");
+
+    let result = SmartDisks::new()
+        .device("/dev/sda")
+        .device("/dev/nvme0n1")
+        .command(&command)
+        .check();
+    assert_eq!(result.status, CheckStatus::Warning);
+    assert_eq!(
+        result.message,
+        "sda: Airflow_Temperature_Cel failed in the past"
+    );
+
+    // the failing disk reported with the exit status of the bit 3
+    let command = fake_smartctl(
+        "failing",
+        r#"sed -e 's/"passed": true/"passed": false/' -e 's/"critical_warning": 0/"critical_warning": 4/' "tests/testdata/smartctl_$(basename "$4").json"
+exit 8
+"#,
+    );
+    let result = SmartDisks::new()
+        .device("/dev/sda")
+        .device("/dev/nvme0n1")
+        .command(&command)
+        .check();
+    assert_eq!(result.status, CheckStatus::Critical);
+    assert_eq!(
+        result.message,
+        "sda: the overall-health self-assessment failed, \
+         sda: Airflow_Temperature_Cel failed in the past, \
+         nvme0n1: the overall-health self-assessment failed, \
+         nvme0n1: the critical warning 0x04"
+    );
+}
+
+#[test]
+fn smart_disks_graphs() {
+    assert_eq!(
+        SmartDisks::graphs()
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec![
+            "smart.reallocated_sectors",
+            "smart.pending_sectors",
+            "smart.media_errors",
+            "smart.temperature",
+            "smart.wear",
+        ]
+    );
+}
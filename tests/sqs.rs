@@ -0,0 +1,143 @@
+#![cfg(feature = "sqs")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::mpsc;
+
+use mackerel_plugin::{Credentials, QueueBackend, QueueStats, Sqs};
+
+struct Request {
+    authorization: String,
+    body: String,
+}
+
+fn mock_server(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<Request>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for (status, response) in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let (mut authorization, mut length) = (String::new(), 0);
+            loop {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                match line.trim_end().split_once(": ") {
+                    Some((key, value)) if key.eq_ignore_ascii_case("authorization") => {
+                        authorization = value.to_owned()
+                    }
+                    Some((key, value)) if key.eq_ignore_ascii_case("content-length") => {
+                        length = value.parse().unwrap()
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {} Status\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response.len(),
+                response
+            )
+            .unwrap();
+            tx.send(Request {
+                authorization,
+                body: String::from_utf8(body).unwrap(),
+            })
+            .unwrap();
+        }
+    });
+    (url, rx)
+}
+
+fn sqs(url: String) -> Sqs {
+    let credentials = Credentials {
+        access_key_id: "AKIDEXAMPLE".to_owned(),
+        secret_access_key: "secret".to_owned(),
+        session_token: None,
+    };
+    Sqs::new("ap-northeast-1", credentials).endpoint(url)
+}
+
+#[test]
+fn sqs_queues() {
+    let (url, rx) = mock_server(vec![
+        (
+            200,
+            r#"<ListQueuesResponse>
+  <ListQueuesResult>
+    <QueueUrl>https://sqs.ap-northeast-1.amazonaws.com/123456789012/jobs</QueueUrl>
+    <NextToken>token/1</NextToken>
+  </ListQueuesResult>
+</ListQueuesResponse>"#,
+        ),
+        (
+            200,
+            r#"<ListQueuesResponse>
+  <ListQueuesResult>
+    <QueueUrl>https://sqs.ap-northeast-1.amazonaws.com/123456789012/jobs.fifo</QueueUrl>
+  </ListQueuesResult>
+</ListQueuesResponse>"#,
+        ),
+        (
+            200,
+            r#"<GetQueueAttributesResponse>
+  <GetQueueAttributesResult>
+    <Attribute><Name>ApproximateNumberOfMessages</Name><Value>12</Value></Attribute>
+    <Attribute><Name>ApproximateNumberOfMessagesNotVisible</Name><Value>3</Value></Attribute>
+  </GetQueueAttributesResult>
+</GetQueueAttributesResponse>"#,
+        ),
+        (
+            200,
+            r#"<GetQueueAttributesResponse>
+  <GetQueueAttributesResult>
+    <Attribute><Name>ApproximateNumberOfMessages</Name><Value>0</Value></Attribute>
+  </GetQueueAttributesResult>
+</GetQueueAttributesResponse>"#,
+        ),
+    ]);
+    assert_eq!(
+        sqs(url).queue_name_prefix("jobs").queues(),
+        Ok(vec![
+            QueueStats::new("jobs", 12).in_flight(3),
+            QueueStats::new("jobs.fifo", 0),
+        ])
+    );
+    let request = rx.recv().unwrap();
+    assert!(request
+        .authorization
+        .contains("/ap-northeast-1/sqs/aws4_request, "));
+    assert_eq!(
+        request.body,
+        "Action=ListQueues&Version=2012-11-05&MaxResults=1000&QueueNamePrefix=jobs"
+    );
+    assert!(rx.recv().unwrap().body.ends_with("&NextToken=token%2F1"));
+    assert_eq!(
+        rx.recv().unwrap().body.split('&').collect::<Vec<_>>(),
+        vec![
+            "Action=GetQueueAttributes",
+            "Version=2012-11-05",
+            "QueueUrl=https%3A%2F%2Fsqs.ap-northeast-1.amazonaws.com%2F123456789012%2Fjobs",
+            "AttributeName.1=ApproximateNumberOfMessages",
+            "AttributeName.2=ApproximateNumberOfMessagesNotVisible",
+        ]
+    );
+}
+
+#[test]
+fn sqs_queues_error() {
+    let (url, _rx) = mock_server(vec![(
+        403,
+        r#"<ErrorResponse><Error><Code>AccessDenied</Code></Error></ErrorResponse>"#,
+    )]);
+    assert_eq!(
+        sqs(url).queues().unwrap_err().to_string(),
+        "ListQueues failed: 403 AccessDenied"
+    );
+}
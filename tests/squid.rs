@@ -0,0 +1,60 @@
+use mackerel_plugin::{SquidStats, Value};
+
+const MGR_INFO: &str = "HTTP/1.1 200 OK\r
+Server: squid/4.10\r
+Content-Type: text/plain;charset=utf-8\r
+\r
+Squid Object Cache: Version 4.10
+Service Name: squid
+Start Time:\tMon, 01 Jan 2024 00:00:00 GMT
+Connection information for squid:
+\tNumber of clients accessing cache:\t3
+\tNumber of HTTP requests received:\t1234
+\tNumber of ICP messages received:\t0
+\tAverage HTTP requests per minute since start:\t12.3
+Cache information for squid:
+\tHits as % of all requests:\t5min: 20.5%, 60min: 18.5%
+\tHits as % of bytes sent:\t5min: 10.0%, 60min: 9.5%
+\tMemory hits as % of hit requests:\t5min: 50.0%, 60min: 48.0%
+\tDisk hits as % of hit requests:\t5min: 0.0%, 60min: 0.0%
+\tStorage Swap size:\t1024 KB
+\tStorage Mem size:\t512 KB
+Median Service Times (seconds)  5 min    60 min:
+\tHTTP Requests (All):   0.01235  0.01235
+";
+
+#[test]
+fn squid_stats_parse() {
+    let mut values = SquidStats::parse(MGR_INFO)
+        .unwrap()
+        .into_iter()
+        .collect::<Vec<_>>();
+    values.sort_by(|(a, _), (b, _)| a.cmp(b));
+    assert_eq!(
+        values,
+        [
+            ("squid.cache_hit_ratio.5min.byte_ratio", 10.0),
+            ("squid.cache_hit_ratio.5min.disk_ratio", 0.0),
+            ("squid.cache_hit_ratio.5min.memory_ratio", 50.0),
+            ("squid.cache_hit_ratio.5min.request_ratio", 20.5),
+            ("squid.clients.clients", 3.0),
+            ("squid.requests.requests", 1234.0),
+            ("squid.storage.mem_size", 512.0 * 1024.0),
+            ("squid.storage.swap_size", 1024.0 * 1024.0),
+        ]
+        .map(|(key, value)| (key.to_owned(), Value::from(value)))
+    );
+
+    assert_eq!(
+        SquidStats::parse("\tNumber of HTTP requests received:\tmany\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the squid info failed: invalid number of http requests received: \"many\""
+    );
+    assert_eq!(
+        SquidStats::parse("ERROR: Cannot connect to localhost:3128\n")
+            .unwrap_err()
+            .to_string(),
+        "parse the squid info failed: \"ERROR: Cannot connect to localhost:3128\""
+    );
+}
@@ -0,0 +1,49 @@
+use std::sync::Mutex;
+
+use mackerel_plugin::workdir;
+
+/// Serializes the tests depending on the environment variables.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+#[test]
+fn state_workdir() {
+    let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let tmpdir = std::env::var_os("TMPDIR");
+    std::env::set_var("MACKEREL_PLUGIN_WORKDIR", "/var/tmp/mackerel-agent");
+    assert_eq!(workdir(), std::path::Path::new("/var/tmp/mackerel-agent"));
+    std::env::set_var("MACKEREL_PLUGIN_WORKDIR", "");
+    std::env::set_var("TMPDIR", "/run/tmp");
+    assert_eq!(workdir(), std::path::Path::new("/run/tmp"));
+    std::env::remove_var("MACKEREL_PLUGIN_WORKDIR");
+    assert_eq!(workdir(), std::path::Path::new("/run/tmp"));
+    match tmpdir {
+        Some(tmpdir) => std::env::set_var("TMPDIR", tmpdir),
+        None => std::env::remove_var("TMPDIR"),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn state_file_fallback() {
+    use mackerel_plugin::{FileStateStore, StateStore};
+    use std::os::unix::fs::PermissionsExt;
+    let dir = {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::temp_dir().join(format!("mackerel-plugin-state-{}", std::process::id()))
+    };
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o500)).unwrap();
+    let key = dir.join("state");
+    let key = key.to_str().unwrap();
+    // the permission does not prevent the privileged users from writing
+    if std::fs::write(key, b"").is_err() {
+        assert_eq!(FileStateStore.load(key), Ok(None));
+        assert_eq!(FileStateStore.save(key, b"foo"), Ok(()));
+        assert_eq!(FileStateStore.load(key), Ok(Some(b"foo".to_vec())));
+    }
+    assert!(FileStateStore
+        .save("/mackerel-plugin-not-found/state", b"foo")
+        .is_err());
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
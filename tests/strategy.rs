@@ -0,0 +1,29 @@
+#![cfg(feature = "proptest")]
+
+use proptest::prelude::*;
+
+use mackerel_plugin::matches_metric;
+use mackerel_plugin::testing::strategy::{invalid_segment, metric_name, pattern_and_name, segment};
+
+proptest! {
+    #[test]
+    fn matches_metric_name(name in metric_name()) {
+        prop_assert!(matches_metric(&name, &name));
+    }
+
+    #[test]
+    fn matches_pattern((pattern, name) in pattern_and_name()) {
+        prop_assert!(matches_metric(&pattern, &name));
+    }
+
+    #[test]
+    fn matches_no_extra_segment((pattern, name) in pattern_and_name(), extra in segment()) {
+        prop_assert!(!matches_metric(&pattern, &(name.clone() + "." + &extra)));
+        prop_assert!(!matches_metric(&(pattern + "." + &extra), &name));
+    }
+
+    #[test]
+    fn matches_no_invalid_segment(name in metric_name(), segment in invalid_segment()) {
+        prop_assert!(!matches_metric(&(name.clone() + ".*"), &(name + "." + &segment)));
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use mackerel_plugin::*;
+
+fn assert_send_sync<T: Send + Sync + ?Sized>() {}
+
+#[test]
+fn types_send_sync() {
+    #[cfg(feature = "json")]
+    assert_send_sync::<Cache<'static>>();
+    assert_send_sync::<DefinitionsDiff>();
+    assert_send_sync::<Error>();
+    assert_send_sync::<FileStateStore>();
+    assert_send_sync::<Filter>();
+    assert_send_sync::<Graph>();
+    assert_send_sync::<MemoryStateStore>();
+    assert_send_sync::<Metric>();
+    assert_send_sync::<MetricMap>();
+    #[cfg(feature = "json")]
+    assert_send_sync::<Package>();
+    #[cfg(feature = "json")]
+    assert_send_sync::<PrefixMigration>();
+    assert_send_sync::<Rename>();
+    assert_send_sync::<Staleness>();
+    assert_send_sync::<SystemClock>();
+    assert_send_sync::<Transform>();
+    assert_send_sync::<Unit>();
+    assert_send_sync::<Value>();
+    assert_send_sync::<dyn SyncPlugin>();
+}
+
+struct ThreadPlugin {
+    seed: f64,
+}
+
+impl Plugin for ThreadPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::from([("thread.value".to_owned(), self.seed)]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "thread",
+            label: "Thread",
+            unit: "integer",
+            metrics: [
+                { name: "value", label: "value" },
+            ]
+        }]
+    }
+}
+
+#[test]
+fn sync_plugin_threads() {
+    let plugins: Vec<Arc<dyn SyncPlugin>> = (0..4)
+        .map(|i| Arc::new(ThreadPlugin { seed: i as f64 }) as Arc<dyn SyncPlugin>)
+        .collect();
+    let state = MemoryStateStore::new();
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    let outputs = std::thread::scope(|s| {
+        plugins
+            .iter()
+            .map(|plugin| {
+                let state = &state;
+                s.spawn(move || {
+                    let mut out = Cursor::new(Vec::new());
+                    plugin.output_values_with(&mut out, state, &now).unwrap();
+                    String::from_utf8(out.into_inner()).unwrap()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(
+        outputs,
+        (0..4)
+            .map(|i| format!("thread.value\t{}\t1700000000\n", i))
+            .collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,99 @@
+#![cfg(unix)]
+
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use mackerel_plugin::{Context, SystemdUnits, Value};
+
+fn fake_systemctl(name: &str, script: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "mackerel-plugin-systemd-test.{}.{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("systemctl");
+    std::fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[test]
+fn systemd_units_fetch_values() {
+    let command = fake_systemctl(
+        "values",
+        r#"[ "$*" = "show --property=ActiveState,NRestarts,MemoryCurrent,CPUUsageNSec,TasksCurrent -- nginx.service backup.timer" ] || exit 1
+cat <<EOF
+ActiveState=active
+NRestarts=2
+MemoryCurrent=10485760
+CPUUsageNSec=3000000000
+TasksCurrent=5
+
+ActiveState=failed
+MemoryCurrent=[not set]
+CPUUsageNSec=18446744073709551615
+TasksCurrent=[not set]
+EOF
+"#,
+    );
+    let units = SystemdUnits::new(["nginx.service", "backup.timer"]).command(&command);
+    assert_eq!(
+        units.fetch_values(),
+        Ok(HashMap::from([
+            ("systemd.active.nginx".to_owned(), Value::Bool(true)),
+            ("systemd.failed.nginx".to_owned(), Value::Bool(false)),
+            ("systemd.restarts.nginx".to_owned(), Value::Float(2.0)),
+            ("systemd.memory.nginx".to_owned(), Value::Float(10485760.0)),
+            ("systemd.cpu.nginx".to_owned(), Value::Float(5.0)),
+            ("systemd.tasks.nginx".to_owned(), Value::Float(5.0)),
+            ("systemd.active.backup_timer".to_owned(), Value::Bool(false)),
+            ("systemd.failed.backup_timer".to_owned(), Value::Bool(true)),
+        ]))
+    );
+}
+
+#[test]
+fn systemd_units_fetch_error() {
+    let command = fake_systemctl("error", "echo 'Failed to connect to bus' >&2\nexit 1\n");
+    let units = SystemdUnits::new(["nginx.service"]).command(&command);
+    assert_eq!(
+        units.fetch_values().map_err(|err| err.to_string()),
+        Err("systemctl show failed: Failed to connect to bus".to_owned())
+    );
+}
+
+#[test]
+fn systemd_units_fetch_values_ctx() {
+    let command = fake_systemctl("ctx", "exec sleep 10\n");
+    let units = SystemdUnits::new(["nginx.service"]).command(&command);
+    let started = Instant::now();
+    let ctx = Context::new().with_deadline(started + Duration::from_millis(100));
+    assert_eq!(
+        units.fetch_values_ctx(&ctx).map_err(|err| err.to_string()),
+        Err("systemctl show failed: fetch deadline exceeded".to_owned())
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn systemd_units_graphs() {
+    let graphs = SystemdUnits::graphs();
+    assert_eq!(
+        graphs
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec![
+            "systemd.active",
+            "systemd.failed",
+            "systemd.restarts",
+            "systemd.memory",
+            "systemd.cpu",
+            "systemd.tasks",
+        ]
+    );
+    assert!(graphs.iter().all(|graph| graph.metrics[0].name == "*"));
+}
@@ -0,0 +1,76 @@
+use rstest::rstest;
+
+use mackerel_plugin::{graph, GraphTemplate};
+
+fn template() -> GraphTemplate {
+    GraphTemplate::new(graph! {
+        name: "connections.#",
+        label: "{role} connections ({{shard}})",
+        unit: "integer",
+        metrics: [
+            { name: "active", label: "{role} active", stacked: true },
+            { name: "idle", label: "Idle on {host}", stacked: true },
+        ],
+    })
+}
+
+#[test]
+fn graph_template_instantiate() {
+    let template = template();
+    let graphs = [("shard1", "Primary", "db1"), ("shard2", "Replica", "db2")]
+        .iter()
+        .map(|&(shard, role, host)| {
+            template.instantiate(
+                &format!("mysql.{}", shard),
+                &[("role", role), ("host", host)],
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(graphs[0].name, "mysql.shard1.connections.#");
+    assert_eq!(graphs[0].label, "Primary connections ({shard})");
+    assert_eq!(graphs[0].metrics[0].label, "Primary active");
+    assert_eq!(graphs[0].metrics[1].label, "Idle on db1");
+    assert!(graphs[0].metrics[1].stacked);
+    assert_eq!(graphs[1].name, "mysql.shard2.connections.#");
+    assert_eq!(graphs[1].label, "Replica connections ({shard})");
+    assert_eq!(graphs[1].metrics[1].label, "Idle on db2");
+
+    let graph = template
+        .instantiate("", &[("role", "Primary"), ("host", "db1")])
+        .unwrap();
+    assert_eq!(graph.name, "connections.#");
+}
+
+#[rstest]
+#[case("mysql shard", &[("role", "Primary"), ("host", "db1")], "invalid graph name: mysql shard.connections.#")]
+#[case("mysql", &[("role", "Primary")], "unknown parameter {host} in label: Idle on {host}")]
+fn graph_template_instantiate_error(
+    #[case] prefix: &str,
+    #[case] params: &[(&str, &str)],
+    #[case] expected: &str,
+) {
+    assert_eq!(
+        template().instantiate(prefix, params),
+        Err(expected.to_owned())
+    );
+}
+
+#[rstest]
+#[case(
+    "{role connections",
+    "unclosed placeholder in label: {role connections"
+)]
+#[case("role} connections", "unmatched brace in label: role} connections")]
+fn graph_template_invalid_label(#[case] label: &str, #[case] expected: &str) {
+    let template = GraphTemplate::new(graph! {
+        name: "connections",
+        label: label,
+        unit: "integer",
+        metrics: [{ name: "active", label: "Active" }],
+    });
+    assert_eq!(
+        template.instantiate("mysql", &[("role", "Primary")]),
+        Err(expected.to_owned())
+    );
+}
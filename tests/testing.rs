@@ -0,0 +1,136 @@
+#![cfg(feature = "json")]
+
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use mackerel_plugin::testing::{assert_golden, assert_meta_golden, canonical_json, AgentSimulator};
+use mackerel_plugin::{graph, Graph, Plugin};
+
+struct CounterPlugin {
+    count: std::cell::Cell<f64>,
+}
+
+impl Plugin for CounterPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        self.count.set(self.count.get() + 300.0);
+        Ok(HashMap::from([(
+            "counter.value".to_owned(),
+            self.count.get(),
+        )]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "counter",
+            label: "Counter",
+            unit: "integer",
+            metrics: [
+                { name: "value", label: "value", diff: true },
+            ]
+        }]
+    }
+}
+
+#[test]
+fn agent_simulator_plugin() {
+    let plugin = CounterPlugin {
+        count: std::cell::Cell::new(0.0),
+    };
+    let mut agent = AgentSimulator::new(&plugin)
+        .at(std::time::UNIX_EPOCH + Duration::from_secs(1600000000))
+        .interval(Duration::from_secs(30));
+    assert_eq!(agent.run_values(), Ok(vec![]));
+    assert_eq!(
+        agent.run_values(),
+        Ok(vec![("counter.value".to_owned(), 600.0, 1600000030)])
+    );
+    assert_eq!(
+        agent.run_values(),
+        Ok(vec![("counter.value".to_owned(), 600.0, 1600000060)])
+    );
+    assert_eq!(
+        agent.run_meta(),
+        Ok(json!({
+            "graphs": {
+                "counter": {
+                    "label": "Counter",
+                    "unit": "integer",
+                    "metrics": [{ "name": "value", "label": "value", "stacked": false }],
+                },
+            },
+        }))
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn agent_simulator_command() {
+    use std::os::unix::fs::PermissionsExt;
+    let path = std::env::temp_dir().join(format!(
+        "mackerel-plugin-simulator-test-{}.sh",
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        r#"#!/bin/sh
+if [ -n "$MACKEREL_AGENT_PLUGIN_META" ]; then
+  echo '# mackerel-agent-plugin'
+  echo '{"graphs":{"foo":{"label":"Foo","unit":"integer","metrics":[{"name":"bar","label":"Bar"}]}}}'
+else
+  count=$(cat "$MACKEREL_PLUGIN_WORKDIR/count" 2>/dev/null || echo 0)
+  echo $((count + 1)) > "$MACKEREL_PLUGIN_WORKDIR/count"
+  printf 'foo.bar\t%d\t%d\n' "$count" 1700000000
+fi
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let mut agent = AgentSimulator::command(&path).interval(Duration::from_millis(10));
+    assert_eq!(
+        agent.run_values(),
+        Ok(vec![("foo.bar".to_owned(), 0.0, 1700000000)])
+    );
+    assert_eq!(
+        agent.run_values(),
+        Ok(vec![("foo.bar".to_owned(), 1.0, 1700000000)])
+    );
+    assert_eq!(
+        agent.run_meta().unwrap()["graphs"]["foo"]["label"],
+        json!("Foo")
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn canonical_json_sorts_keys() {
+    assert_eq!(
+        canonical_json(&json!({ "b": [{ "d": 1, "c": 2 }], "a": null })),
+        "{\n  \"a\": null,\n  \"b\": [\n    {\n      \"c\": 2,\n      \"d\": 1\n    }\n  ]\n}\n"
+    );
+}
+
+#[test]
+fn meta_golden() {
+    let plugin = CounterPlugin {
+        count: std::cell::Cell::new(0.0),
+    };
+    assert_meta_golden(&plugin, "tests/golden/counter_meta.json");
+}
+
+#[test]
+fn golden_mismatch() {
+    let path = std::env::temp_dir().join(format!(
+        "mackerel-plugin-golden-test-{}.txt",
+        std::process::id()
+    ));
+    std::fs::write(&path, "foo\nbar\nbaz\n").unwrap();
+    let err = std::panic::catch_unwind(|| assert_golden(&path, "foo\nqux\nbaz\n")).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    let message = err.downcast_ref::<String>().unwrap();
+    assert!(
+        message.ends_with("  foo\n- bar\n+ qux\n  baz\n"),
+        "{}",
+        message
+    );
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use mackerel_plugin::{
+    graph, metric_thresholds, Graph, MetricThreshold, Monitor, MonitorGenerator, Operator, Plugin,
+    Threshold,
+};
+
+struct DiskPlugin {}
+
+impl Plugin for DiskPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        Ok(HashMap::new())
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![
+            graph! {
+                name: "disk.#",
+                label: "Disk usage",
+                unit: "percentage",
+                metrics: [
+                    {
+                        name: "used",
+                        label: "Used",
+                        threshold: Threshold::above().warning(80.0).critical(90.0),
+                    },
+                    { name: "reserved", label: "Reserved" },
+                ]
+            },
+            graph! {
+                name: "inode",
+                label: "Inode",
+                unit: "integer",
+                metrics: [
+                    { name: "free", label: "Free", threshold: Threshold::below().critical(1000.0) },
+                ]
+            },
+        ]
+    }
+
+    fn metric_key_prefix(&self) -> String {
+        "fs".to_owned()
+    }
+}
+
+#[test]
+fn plugin_metric_thresholds() {
+    assert_eq!(
+        metric_thresholds(&DiskPlugin {}),
+        vec![
+            MetricThreshold {
+                metric: "custom.fs.disk.#.used".to_owned(),
+                threshold: Threshold {
+                    operator: Operator::Greater,
+                    warning: Some(80.0),
+                    critical: Some(90.0),
+                },
+            },
+            MetricThreshold {
+                metric: "custom.fs.inode.free".to_owned(),
+                threshold: Threshold {
+                    operator: Operator::Less,
+                    warning: None,
+                    critical: Some(1000.0),
+                },
+            },
+        ]
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn plugin_thresholds_json() {
+    let json: serde_json::Value =
+        serde_json::from_str(&mackerel_plugin::thresholds_json(&DiskPlugin {})).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "thresholds": [
+                { "metric": "custom.fs.disk.#.used", "operator": ">", "warning": 80.0, "critical": 90.0 },
+                { "metric": "custom.fs.inode.free", "operator": "<", "warning": null, "critical": 1000.0 },
+            ]
+        })
+    );
+}
+
+#[test]
+fn plugin_monitors() {
+    let monitors = MonitorGenerator::new()
+        .select("fs.disk.#.reserved")
+        .select("fs.unknown")
+        .duration(5)
+        .max_check_attempts(3)
+        .generate(&DiskPlugin {});
+    let monitor = |name: &str, metric: &str, threshold: Threshold| Monitor {
+        monitor_type: "host".to_owned(),
+        name: name.to_owned(),
+        metric: metric.to_owned(),
+        operator: threshold.operator,
+        warning: threshold.warning,
+        critical: threshold.critical,
+        duration: 5,
+        max_check_attempts: 3,
+    };
+    assert_eq!(
+        monitors,
+        vec![
+            monitor(
+                "Disk usage Used",
+                "custom.fs.disk.#.used",
+                Threshold::above().warning(80.0).critical(90.0)
+            ),
+            monitor(
+                "Disk usage Reserved",
+                "custom.fs.disk.#.reserved",
+                Threshold::above()
+            ),
+            monitor(
+                "Inode Free",
+                "custom.fs.inode.free",
+                Threshold::below().critical(1000.0)
+            ),
+        ]
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn plugin_monitors_json() {
+    let json: serde_json::Value = serde_json::from_str(
+        &MonitorGenerator::new()
+            .select("fs.inode")
+            .generate_json(&DiskPlugin {}),
+    )
+    .unwrap();
+    assert_eq!(
+        json["monitors"][1],
+        serde_json::json!({
+            "type": "host",
+            "name": "Inode Free",
+            "metric": "custom.fs.inode.free",
+            "operator": "<",
+            "warning": null,
+            "critical": 1000.0,
+            "duration": 1,
+            "maxCheckAttempts": 1,
+        })
+    );
+}
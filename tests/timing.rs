@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::Duration;
+
+use mackerel_plugin::{graph, section_durations, Graph, MemoryStateStore, Plugin};
+
+struct SectionPlugin {}
+
+impl Plugin for SectionPlugin {
+    fn fetch_metrics(&self) -> Result<HashMap<String, f64>, String> {
+        for _ in 0..2 {
+            let _t = self.time_section("db query");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let sections = section_durations();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].0, "db_query");
+        assert!(sections[0].1 >= Duration::from_millis(20));
+        Ok(HashMap::from([(
+            "db.query_ms".to_owned(),
+            sections[0].1.as_millis() as f64,
+        )]))
+    }
+
+    fn graph_definition(&self) -> Vec<Graph> {
+        vec![graph! {
+            name: "db",
+            label: "Database",
+            unit: "milliseconds",
+            metrics: [
+                { name: "query_ms", label: "Query" },
+            ]
+        }]
+    }
+
+    fn emit_self_metrics(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn plugin_time_section() {
+    let plugin = SectionPlugin {};
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1700000000);
+    for _ in 0..2 {
+        let mut out = Cursor::new(Vec::new());
+        assert_eq!(
+            plugin.output_values_with(&mut out, &MemoryStateStore::new(), &now),
+            Ok(())
+        );
+        let out = String::from_utf8(out.into_inner()).unwrap();
+        let value = |name: &str| -> f64 {
+            out.lines()
+                .find_map(|line| line.strip_prefix(&(name.to_owned() + "\t")))
+                .and_then(|line| line.split('\t').next()?.parse().ok())
+                .unwrap()
+        };
+        assert!(value("db.query_ms") >= 20.0);
+        assert!(value("mackerel_plugin.duration.db_query") >= 0.02);
+        assert!(value("mackerel_plugin.duration.db_query") < 1.0);
+        assert!(value("mackerel_plugin.duration.fetch") >= 0.02);
+    }
+    assert_eq!(section_durations(), vec![]);
+}
@@ -0,0 +1,133 @@
+#![cfg(feature = "json")]
+
+use std::io::Write;
+
+use mackerel_plugin::{UwsgiStats, Value};
+
+const STATS: &str = r#"{
+  "version": "2.0.28",
+  "listen_queue": 3,
+  "listen_queue_errors": 1,
+  "load": 0,
+  "workers": [
+    {"id": 1, "pid": 101, "requests": 120, "exceptions": 2, "status": "busy", "rss": 1048576, "vsz": 4194304},
+    {"id": 2, "pid": 102, "requests": 80, "exceptions": 0, "status": "idle", "rss": 2097152, "vsz": 4194304},
+    {"id": 3, "pid": 0, "requests": 0, "exceptions": 0, "status": "cheap", "rss": 0, "vsz": 0},
+    {"id": 4, "pid": 104, "requests": 5, "exceptions": 1, "status": "sig1", "rss": 0, "vsz": 0}
+  ]
+}"#;
+
+#[test]
+fn uwsgi_stats_parse() {
+    let values = UwsgiStats::new("app.api", "127.0.0.1:1717")
+        .parse(STATS)
+        .unwrap();
+    let mut keys = values.keys().map(|key| &key[..]).collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(
+        keys,
+        vec![
+            "uwsgi.memory.app_api.rss",
+            "uwsgi.memory.app_api.vsz",
+            "uwsgi.queue.app_api.listen_queue",
+            "uwsgi.queue.app_api.listen_queue_errors",
+            "uwsgi.requests.app_api.exceptions",
+            "uwsgi.requests.app_api.requests",
+            "uwsgi.worker_requests.app_api.1",
+            "uwsgi.worker_requests.app_api.2",
+            "uwsgi.worker_requests.app_api.3",
+            "uwsgi.worker_requests.app_api.4",
+            "uwsgi.workers.app_api.busy",
+            "uwsgi.workers.app_api.cheap",
+            "uwsgi.workers.app_api.idle",
+            "uwsgi.workers.app_api.pause",
+            "uwsgi.workers.app_api.sig",
+        ]
+    );
+    for (key, value) in [
+        ("uwsgi.workers.app_api.busy", 1.0),
+        ("uwsgi.workers.app_api.pause", 0.0),
+        ("uwsgi.workers.app_api.sig", 1.0),
+        ("uwsgi.requests.app_api.requests", 205.0),
+        ("uwsgi.requests.app_api.exceptions", 3.0),
+        ("uwsgi.memory.app_api.rss", 3145728.0),
+        ("uwsgi.queue.app_api.listen_queue", 3.0),
+        ("uwsgi.worker_requests.app_api.1", 120.0),
+    ] {
+        assert_eq!(values[key], Value::Float(value), "{}", key);
+    }
+}
+
+#[test]
+fn uwsgi_stats_parse_error() {
+    let stats = UwsgiStats::new("app", "127.0.0.1:1717");
+    assert!(stats
+        .parse("not json")
+        .unwrap_err()
+        .to_string()
+        .starts_with("parse the uWSGI stats failed: "));
+    assert_eq!(
+        stats.parse("{}").unwrap_err().to_string(),
+        "parse the uWSGI stats failed: no workers found"
+    );
+}
+
+#[test]
+fn uwsgi_stats_fetch_values() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.write_all(STATS.as_bytes()).unwrap();
+    });
+    let values = UwsgiStats::new("app", addr).fetch_values().unwrap();
+    assert_eq!(values["uwsgi.requests.app.requests"], Value::Float(205.0));
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let err = UwsgiStats::new("app", addr.clone())
+        .fetch_values()
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with(&format!("uWSGI stats {} failed: ", addr)));
+}
+
+#[cfg(unix)]
+#[test]
+fn uwsgi_stats_fetch_values_unix() {
+    let path = std::env::temp_dir().join(format!(
+        "mackerel-plugin-uwsgi-test.{}.sock",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.write_all(STATS.as_bytes()).unwrap();
+    });
+    let values = UwsgiStats::new("app", path.to_str().unwrap())
+        .fetch_values()
+        .unwrap();
+    assert_eq!(values["uwsgi.workers.app.idle"], Value::Float(1.0));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn uwsgi_stats_graphs() {
+    let graphs = UwsgiStats::graphs();
+    assert_eq!(
+        graphs
+            .iter()
+            .map(|graph| &graph.name[..])
+            .collect::<Vec<_>>(),
+        vec![
+            "uwsgi.workers.#",
+            "uwsgi.requests.#",
+            "uwsgi.queue.#",
+            "uwsgi.memory.#",
+            "uwsgi.worker_requests.#",
+        ]
+    );
+}
@@ -0,0 +1,28 @@
+use rstest::rstest;
+use std::time::Duration;
+
+use mackerel_plugin::Value;
+
+#[rstest]
+#[case(Value::from(1.5), Value::Float(1.5), 1.5)]
+#[case(Value::from(-3_i64), Value::Float(-3.0), -3.0)]
+#[case(Value::from(42_u32), Value::Float(42.0), 42.0)]
+#[case(Value::from(10_u64), Value::Float(10.0), 10.0)]
+#[case(Value::counter32(42), Value::Counter32(42), 42.0)]
+#[case(Value::counter(u64::MAX), Value::Counter(u64::MAX), u64::MAX as f64)]
+#[case(Value::from(true), Value::Bool(true), 1.0)]
+#[case(Value::from(false), Value::Bool(false), 0.0)]
+#[case(
+    Value::from(Duration::from_millis(1500)),
+    Value::Duration(Duration::from_millis(1500)),
+    1.5
+)]
+#[case(
+    Value::from((2.5, std::time::UNIX_EPOCH)),
+    Value::Observed(2.5, std::time::UNIX_EPOCH),
+    2.5
+)]
+fn value_as_f64(#[case] value: Value, #[case] expected: Value, #[case] expected_f64: f64) {
+    assert_eq!(value, expected);
+    assert_eq!(value.as_f64(), expected_f64);
+}
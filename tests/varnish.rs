@@ -0,0 +1,90 @@
+#![cfg(feature = "json")]
+
+use std::collections::HashMap;
+
+use mackerel_plugin::{Context, Value, VarnishStats};
+
+const VARNISHSTAT: &str = r#"{
+  "version": 1,
+  "timestamp": "2024-01-01T00:00:00",
+  "counters": {
+    "MAIN.client_req": {"description": "Good client requests received", "flag": "c", "format": "i", "value": 1000},
+    "MAIN.cache_hit": {"description": "Cache hits", "flag": "c", "format": "i", "value": 800},
+    "MAIN.cache_miss": {"description": "Cache misses", "flag": "c", "format": "i", "value": 200},
+    "MAIN.backend_req": {"description": "Backend requests made", "flag": "c", "format": "i", "value": 210},
+    "MAIN.backend_conn": {"description": "Backend conn. success", "flag": "c", "format": "i", "value": 50},
+    "MAIN.backend_fail": {"description": "Backend conn. failures", "flag": "c", "format": "i", "value": 1},
+    "MAIN.s_resp_hdrbytes": {"description": "Response header bytes", "flag": "c", "format": "B", "value": 300000},
+    "MAIN.s_resp_bodybytes": {"description": "Response body bytes", "flag": "c", "format": "B", "value": 9000000}
+  }
+}"#;
+
+#[test]
+fn varnish_stats_parse() {
+    let values = VarnishStats::parse(VARNISHSTAT).unwrap();
+    assert_eq!(values.len(), 8);
+    assert_eq!(values["varnish.requests.cache_hits"], Value::Counter(800));
+    assert_eq!(
+        values["varnish.transfer.body_bytes"],
+        Value::Counter(9000000)
+    );
+
+    // the counters at the top level before Varnish 6.5
+    let values = VarnishStats::parse(
+        r#"{"timestamp": "2020-01-01T00:00:00", "MAIN.client_req": {"value": 10}}"#,
+    )
+    .unwrap();
+    assert_eq!(values["varnish.requests.requests"], Value::Counter(10));
+
+    assert_eq!(
+        VarnishStats::parse("{}").unwrap_err().to_string(),
+        "parse the varnishstat output failed: no counters found"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn varnish_stats_fetch_values_ctx() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let command = std::env::temp_dir().join(format!(
+        "mackerel-plugin-varnishstat-test-{}.sh",
+        std::process::id()
+    ));
+    std::fs::write(
+        &command,
+        format!(
+            "#!/bin/sh\n[ \"$*\" = \"-j -n cache\" ] || exit 1\ncat <<'EOF'\n{}\nEOF\n",
+            VARNISHSTAT
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let stats = VarnishStats::new().command(&command).instance("cache");
+
+    // the hit ratio is not available on the first run
+    let values = stats.fetch_values().unwrap();
+    assert_eq!(values.len(), 8);
+
+    let ctx = Context::new().with_previous_values(
+        1700000000,
+        HashMap::from([
+            ("varnish.requests.cache_hits".to_owned(), 740.0),
+            ("varnish.requests.cache_misses".to_owned(), 180.0),
+        ]),
+    );
+    let values = stats.fetch_values_ctx(&ctx).unwrap();
+    assert_eq!(values["varnish.hit_ratio.hit_ratio"], Value::from(75.0));
+
+    // the counters are reset by restarting varnishd
+    let ctx = Context::new().with_previous_values(
+        1700000000,
+        HashMap::from([
+            ("varnish.requests.cache_hits".to_owned(), 1000.0),
+            ("varnish.requests.cache_misses".to_owned(), 180.0),
+        ]),
+    );
+    let values = stats.fetch_values_ctx(&ctx).unwrap();
+    assert!(!values.contains_key("varnish.hit_ratio.hit_ratio"));
+    std::fs::remove_file(&command).unwrap();
+}